@@ -5,3 +5,5 @@ mod head;
 pub use head::*;
 mod chart;
 pub use chart::*;
+mod spike;
+pub use spike::*;
@@ -0,0 +1,75 @@
+use log::tracing;
+use maud::{html, Markup};
+use metrics::prelude::{ProcessInfo, UnitStatus};
+use typed_builder::TypedBuilder;
+
+use crate::svg::round_to_len;
+
+const MAX_CMDLINE_LENGTH: usize = 80;
+
+#[derive(Debug, Default, Clone, TypedBuilder)]
+pub struct SpikeContext {
+    #[builder(setter(into))]
+    pub time: String,
+    pub cpu_usage: f64,
+    #[builder(default)]
+    pub processes: Vec<ProcessInfo>,
+    #[builder(default)]
+    pub units: Vec<UnitStatus>,
+}
+
+/// Top processes and systemd units captured at the time of the CPU spike
+#[tracing::instrument(level = "debug", skip(ctx), fields(time = ctx.time))]
+pub fn Spike(ctx: SpikeContext) -> Markup {
+    html! {
+        p { (format!("Captured at {} with a CPU usage of {}%.", ctx.time, round_to_len(ctx.cpu_usage, 2))) }
+        @if !ctx.processes.is_empty() {
+            table.spike {
+                thead {
+                    tr {
+                        th { "PID" }
+                        th { "Name" }
+                        th { "User" }
+                        th { "CPU" }
+                        th { "RSS" }
+                        th { "Command" }
+                    }
+                }
+                tbody {
+                    @for process in ctx.processes {
+                        tr {
+                            td { (process.pid) }
+                            td { (process.name) }
+                            td { (process.user) }
+                            td { (format!("{}%", round_to_len(process.cpu_percent as f64, 2))) }
+                            td { (format!("{}MiB", process.rss / 1024 / 1024)) }
+                            td.cmdline title=(process.cmdline.clone().unwrap_or_default()) {
+                                (process.cmdline.unwrap_or_default().chars().take(MAX_CMDLINE_LENGTH).collect::<String>())
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        @if !ctx.units.is_empty() {
+            table.spike {
+                thead {
+                    tr {
+                        th { "Unit" }
+                        th { "State" }
+                        th { "Sub state" }
+                    }
+                }
+                tbody {
+                    @for unit in ctx.units {
+                        tr class=(if unit.is_active() { "active" } else { "inactive" }) {
+                            td { (unit.name) }
+                            td { (unit.active_state) }
+                            td { (unit.sub_state) }
+                        }
+                    }
+                }
+            }
+        }
+    }
+}
@@ -8,7 +8,7 @@ use tokio::{
 };
 use typed_builder::TypedBuilder;
 
-use crate::{svg::values_to_polyline, ChartContext, ChartLine, ChartValue};
+use crate::{svg::values_to_polyline, ChartContext, ChartLine, ChartValue, SpikeContext};
 
 const ACTUALIZATION_INTERVAL: Duration = Duration::from_secs(120);
 
@@ -23,6 +23,8 @@ const DISKS_MEMORY_TITLE: &str = "Disks Memory Usage";
 pub struct ChartsData {
     pub last_updated_time: Instant,
     pub metrics: Vec<(&'static str, ChartContext)>,
+    #[builder(default)]
+    pub spike: Option<SpikeContext>,
 }
 
 impl Default for ChartsData {
@@ -30,6 +32,7 @@ impl Default for ChartsData {
         ChartsData {
             last_updated_time: Instant::now(),
             metrics: Vec::new(),
+            spike: None,
         }
     }
 }
@@ -219,9 +222,19 @@ impl From<Database> for ChartsData {
             ),
         ];
 
+        let spike = chart_data.get_cpu_spike().map(|(cpu_usage, snap)| {
+            SpikeContext::builder()
+                .time(snap.time.format("%d/%m/%Y %H:%M").to_string())
+                .cpu_usage(cpu_usage)
+                .processes(snap.processes.clone())
+                .units(snap.units.clone())
+                .build()
+        });
+
         ChartsData::builder()
             .last_updated_time(Instant::now())
             .metrics(chart_sections)
+            .spike(spike)
             .build()
     }
 }
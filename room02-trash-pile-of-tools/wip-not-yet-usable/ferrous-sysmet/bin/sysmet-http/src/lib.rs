@@ -17,6 +17,8 @@ pub use components::*;
 pub(crate) mod generator;
 pub(crate) mod macros;
 pub(crate) mod svg;
+#[cfg(test)]
+mod tests;
 
 use generator::ChartsData;
 
@@ -105,9 +107,9 @@ async fn home(
         .and_then(|ref t| humantime::parse_duration(t).ok());
     let refresh = time_from_now.refresh.is_some() && time_from_now.refresh.clone().unwrap() == "on";
 
-    let chart_sections = {
+    let (chart_sections, spike) = {
         let data = chart_data.read().await;
-        data.metrics.clone()
+        (data.metrics.clone(), data.spike.clone())
     };
 
     Base(
@@ -142,6 +144,12 @@ async fn home(
                     }
                 }
             }
+            @if let Some(spike) = spike {
+                section {
+                    h2 { "CPU Spike" }
+                    (Spike(spike))
+                }
+            }
             section {
                 a href=(SOURCE_URL) referer="none" target="_blank" { "Source code" }
                 span { " - Licensed under the AGPL v3.0." }
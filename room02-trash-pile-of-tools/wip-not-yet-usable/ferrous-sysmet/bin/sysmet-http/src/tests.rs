@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use axum::extract::{Extension, Query};
+use metrics::prelude::{ProcessInfo, UnitStatus};
+use tokio::sync::RwLock;
+
+use crate::{generator::ChartsData, home, HomeQuery, SpikeContext};
+
+fn charts_data(spike: Option<SpikeContext>) -> Extension<Arc<RwLock<ChartsData>>> {
+    Extension(Arc::new(RwLock::new(ChartsData {
+        spike,
+        ..Default::default()
+    })))
+}
+
+fn query() -> Query<HomeQuery> {
+    Query(HomeQuery {
+        t: None,
+        refresh: None,
+    })
+}
+
+#[tokio::test]
+async fn home_renders_the_spike_processes_and_units() {
+    let spike = SpikeContext::builder()
+        .time("2024-01-01 12:00:00")
+        .cpu_usage(97.456)
+        .processes(vec![ProcessInfo {
+            pid: 4242,
+            name: "cargo".to_string(),
+            cmdline: Some(format!("cargo build {}", "x".repeat(100))),
+            user: "root".to_string(),
+            cpu_percent: 180.5,
+            rss: 512 * 1024 * 1024,
+        }])
+        .units(vec![
+            UnitStatus {
+                name: "nginx.service".to_string(),
+                load_state: "loaded".to_string(),
+                active_state: "active".to_string(),
+                sub_state: "running".to_string(),
+            },
+            UnitStatus {
+                name: "backup.service".to_string(),
+                load_state: "loaded".to_string(),
+                active_state: "failed".to_string(),
+                sub_state: "failed".to_string(),
+            },
+        ])
+        .build();
+
+    let page = home(query(), charts_data(Some(spike))).await.into_string();
+
+    assert!(page.contains("<h2>CPU Spike</h2>"));
+    assert!(page.contains("Captured at 2024-01-01 12:00:00 with a CPU usage of 97.46%."));
+    assert!(page.contains("<td>4242</td><td>cargo</td><td>root</td><td>180.5%</td><td>512MiB</td>"));
+    // The full command line is in the title, the cell is truncated
+    assert!(page.contains(&format!("title=\"cargo build {}\"", "x".repeat(100))));
+    assert!(page.contains(&format!(">cargo build {}</td>", "x".repeat(68))));
+    assert!(page.contains(
+        "<tr class=\"active\"><td>nginx.service</td><td>active</td><td>running</td></tr>"
+    ));
+    assert!(page.contains(
+        "<tr class=\"inactive\"><td>backup.service</td><td>failed</td><td>failed</td></tr>"
+    ));
+}
+
+#[tokio::test]
+async fn home_renders_no_spike_section_without_a_spike() {
+    let page = home(query(), charts_data(None)).await.into_string();
+
+    assert!(page.contains("sysmet faster"));
+    assert!(!page.contains("CPU Spike"));
+    assert!(!page.contains("table class=\"spike\""));
+}
+
+#[tokio::test]
+async fn home_skips_the_empty_spike_tables() {
+    let spike = SpikeContext::builder()
+        .time("2024-01-01 12:00:00")
+        .cpu_usage(95.0)
+        .build();
+
+    let page = home(query(), charts_data(Some(spike))).await.into_string();
+
+    assert!(page.contains("<h2>CPU Spike</h2>"));
+    assert!(!page.contains("table class=\"spike\""));
+}
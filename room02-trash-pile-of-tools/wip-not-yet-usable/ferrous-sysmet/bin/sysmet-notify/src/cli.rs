@@ -1,10 +1,60 @@
-use std::time::Duration;
+use std::{str::FromStr, time::Duration};
 
-use clap::Parser;
+use clap::{
+    error::ErrorKind, parser::ValueSource, CommandFactory, FromArgMatches, Parser, ValueEnum,
+};
 use clap_verbosity_flag::Verbosity;
 use lettre::message::Mailbox;
 use log::{trace, tracing};
 
+use crate::{
+    notifiers::{ChannelKind, ChannelSpec},
+    template::Template,
+};
+
+pub const DEFAULT_SUBJECT_TEMPLATE: &str = "Warning threshold reached on {{hostname}}";
+pub const DEFAULT_BODY_TEMPLATE: &str =
+    "Thresholds crossed:\\n{{thresholds}}\\n\\n{{snapshot}}\\n{{processes}}\\n{{units}}";
+pub const DEFAULT_WEBHOOK_TEMPLATE: &str =
+    r#"{"hostname":"{{hostname}}","date":"{{date}}","subject":"{{subject}}","body":"{{body}}"}"#;
+
+/// Thresholds that can be routed to specific channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Rule {
+    Cpu,
+    Ram,
+    Swap,
+    Memory,
+    Disk,
+    AvgLoad,
+}
+
+/// Send a rule only to the listed channels, given as `RULE=CHANNEL[,CHANNEL]`
+#[derive(Debug, Clone)]
+pub struct Route {
+    pub rule: Rule,
+    pub channels: Vec<String>,
+}
+
+impl FromStr for Route {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (rule, channels) = value
+            .split_once('=')
+            .ok_or_else(|| format!("route `{value}` must be RULE=CHANNEL[,CHANNEL]"))?;
+
+        Ok(Self {
+            rule: Rule::from_str(rule.trim(), true)?,
+            channels: channels
+                .split(',')
+                .map(|channel| channel.trim().to_string())
+                .filter(|channel| !channel.is_empty())
+                .collect(),
+        })
+    }
+}
+
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 pub struct Cli {
@@ -68,7 +118,7 @@ pub struct Cli {
 		long = "from",
 		env = "MAIL_FROM",
 		value_parser = mailbox_try_from_str,
-		required_unless_present("dry_run"),
+		required_unless_present_any = ["dry_run", "channels"],
 		help = "Identity that will be used to send the mail"
 	)]
     pub from: Option<Mailbox>,
@@ -78,7 +128,7 @@ pub struct Cli {
 		env = "MAIL_CONTACTS",
 		value_delimiter = ',',
 		value_parser = mailbox_try_from_str,
-		required_unless_present("dry_run"),
+		required_unless_present_any = ["dry_run", "channels"],
 		help = "Contacts that will receive the mail",
 		action = clap::ArgAction::Append
 	)]
@@ -88,27 +138,27 @@ pub struct Cli {
         env = "MAIL_COOLDOWN",
         default_value = "1h",
 		value_parser = duration_try_from_str,
-        help = "Time to wait before sending a mail again"
+        help = "Time to wait before sending a notification again"
     )]
     pub cooldown: Duration,
     #[clap(
         long = "smtp-user",
         env = "SMTP_USER",
-        required_unless_present("dry_run"),
+        required_unless_present_any = ["dry_run", "channels"],
         help = "SMTP Username to authenticate with the Relay"
     )]
     pub smtp_user: Option<String>,
     #[clap(
         long = "smtp-pass",
         env = "SMTP_PASSWORD",
-        required_unless_present("dry_run"),
+        required_unless_present_any = ["dry_run", "channels"],
         help = "SMTP Password to authenticate with the Relay"
     )]
     pub smtp_password: Option<String>,
     #[clap(
         long = "smtp-relay",
         env = "SMTP_RELAY",
-        required_unless_present("dry_run"),
+        required_unless_present_any = ["dry_run", "channels"],
         help = "SMTP Relay that will be used to send the mail"
     )]
     pub smtp_relay: Option<String>,
@@ -120,11 +170,84 @@ pub struct Cli {
         help = "SMTP Relay port that will be used to connect to the relay"
     )]
     pub smtp_port: u16,
+    #[clap(
+        long = "top-processes",
+        env = "TOP_PROCESSES",
+        default_value = "5",
+        value_name = "NUMBER OF PROCESSES",
+        help = "Number of top CPU and memory consumers to include in the notification"
+    )]
+    pub top_processes: usize,
+    #[clap(
+        long = "units",
+        env = "SYSTEMD_UNITS",
+        value_delimiter = ',',
+        value_name = "SYSTEMD UNITS",
+        help = "Systemd units whose state is included in the notification"
+    )]
+    pub units: Vec<String>,
+    #[clap(
+        long = "channel",
+        env = "NOTIFY_CHANNELS",
+        value_name = "[NAME=]KIND[+URL]",
+        default_value = "smtp",
+        help = "Channel that will receive the notification (smtp, webhook, ntfy, gotify, matrix, discord), repeat it for several channels",
+        action = clap::ArgAction::Append
+    )]
+    pub channels: Vec<ChannelSpec>,
+    #[clap(
+        long = "route",
+        env = "NOTIFY_ROUTES",
+        value_name = "RULE=CHANNEL[,CHANNEL]",
+        value_delimiter = ';',
+        help = "Send a threshold only to some channels, unrouted thresholds are sent to every channel",
+        action = clap::ArgAction::Append
+    )]
+    pub routes: Vec<Route>,
+    #[clap(
+        long = "subject-template",
+        env = "NOTIFY_SUBJECT_TEMPLATE",
+        default_value = DEFAULT_SUBJECT_TEMPLATE,
+        help = "Subject of the notification, available placeholders are {{hostname}}, {{date}}, {{thresholds}}, {{snapshot}}, {{processes}} and {{units}}"
+    )]
+    pub subject_template: Template,
+    #[clap(
+        long = "body-template",
+        env = "NOTIFY_BODY_TEMPLATE",
+        default_value = DEFAULT_BODY_TEMPLATE,
+        help = "Body of the notification, available placeholders are {{hostname}}, {{date}}, {{thresholds}}, {{snapshot}}, {{processes}} and {{units}}"
+    )]
+    pub body_template: Template,
+    #[clap(
+        long = "webhook-template",
+        env = "WEBHOOK_TEMPLATE",
+        default_value = DEFAULT_WEBHOOK_TEMPLATE,
+        help = "JSON sent to webhook channels, {{subject}} and {{body}} are also available"
+    )]
+    pub webhook_template: Template,
+    #[clap(
+        long = "ntfy-token",
+        env = "NTFY_TOKEN",
+        help = "Access token of the ntfy server"
+    )]
+    pub ntfy_token: Option<String>,
+    #[clap(
+        long = "gotify-token",
+        env = "GOTIFY_TOKEN",
+        help = "Application token of the Gotify server"
+    )]
+    pub gotify_token: Option<String>,
+    #[clap(
+        long = "matrix-token",
+        env = "MATRIX_ACCESS_TOKEN",
+        help = "Access token of the Matrix account sending the messages"
+    )]
+    pub matrix_token: Option<String>,
     #[clap(
         long = "last-sent-path",
         env = "LAST_SENT_PATH",
         default_value = "/tmp/sysmet-notify-last-mail.txt",
-        help = "Timestamp of the last time a mail was sent"
+        help = "Timestamp of the last time a notification was sent"
     )]
     pub last_sent_instant: Option<String>,
     #[clap(
@@ -140,6 +263,62 @@ pub struct Cli {
     pub verbose: Verbosity,
 }
 
+impl Cli {
+    /// Parse the arguments then unescape `\n` in the templates given on the command line (and the defaults),
+    /// the ones read from the environment (or the env file) are kept as is
+    pub fn try_parse_args<I, T>(args: I) -> Result<Self, clap::Error>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<std::ffi::OsString> + Clone,
+    {
+        let matches = Self::command().try_get_matches_from(args)?;
+        let mut cli = Self::from_arg_matches(&matches)?;
+        for (id, template) in [
+            ("subject_template", &mut cli.subject_template),
+            ("body_template", &mut cli.body_template),
+            ("webhook_template", &mut cli.webhook_template),
+        ] {
+            if matches!(
+                matches.value_source(id),
+                Some(ValueSource::CommandLine | ValueSource::DefaultValue)
+            ) {
+                *template = template.unescaped();
+            }
+        }
+
+        // NOTE: `required_if_eq` only matches the raw value, a smtp channel can be named (E.g. `work=smtp`)
+        if !cli.dry_run
+            && cli
+                .channels
+                .iter()
+                .any(|channel| channel.kind == ChannelKind::Smtp)
+        {
+            let missing = [
+                ("--from", cli.from.is_none()),
+                ("--contacts", cli.contacts.is_empty()),
+                ("--smtp-user", cli.smtp_user.is_none()),
+                ("--smtp-pass", cli.smtp_password.is_none()),
+                ("--smtp-relay", cli.smtp_relay.is_none()),
+            ]
+            .into_iter()
+            .filter_map(|(arg, missing)| missing.then_some(arg))
+            .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                return Err(Self::command().error(
+                    ErrorKind::MissingRequiredArgument,
+                    format!("the smtp channels need {}", missing.join(", ")),
+                ));
+            }
+        }
+
+        Ok(cli)
+    }
+
+    pub fn parse_args() -> Self {
+        Self::try_parse_args(std::env::args_os()).unwrap_or_else(|e| e.exit())
+    }
+}
+
 #[tracing::instrument(level = "trace")]
 fn mailbox_try_from_str(value: &str) -> Result<Mailbox, lettre::address::AddressError> {
     let result = value.parse::<Mailbox>();
@@ -153,3 +332,74 @@ fn duration_try_from_str(value: &str) -> Result<Duration, humantime::DurationErr
     trace!(parsed_duration =? result);
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::notifiers::ChannelKind;
+
+    const SMTP_ARGS: [&str; 8] = [
+        "--from=alerts@example.org",
+        "--contacts=admin@example.org",
+        "--smtp-user",
+        "user",
+        "--smtp-pass",
+        "password",
+        "--smtp-relay",
+        "smtp.example.org",
+    ];
+
+    fn parse(args: &[&str]) -> Result<Cli, clap::Error> {
+        Cli::try_parse_args(["sysmet-notify"].iter().chain(args))
+    }
+
+    #[test]
+    fn channels_are_repeated_and_keep_the_commas_of_their_url() {
+        let cli = parse(&[
+            "--channel",
+            "webhook+https://example.org/hook?tags=cpu,ram",
+            "--channel",
+            "phone=ntfy+https://ntfy.sh/alerts",
+        ])
+        .unwrap();
+
+        assert_eq!(cli.channels.len(), 2);
+        assert_eq!(cli.channels[0].kind, ChannelKind::Webhook);
+        assert_eq!(
+            cli.channels[0].url.as_deref(),
+            Some("https://example.org/hook?tags=cpu,ram")
+        );
+        assert_eq!(cli.channels[1].name, "phone");
+    }
+
+    #[test]
+    fn smtp_needs_its_settings_unless_dry_run() {
+        // smtp is the default channel
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["--channel", "smtp"]).is_err());
+        assert!(parse(&["--channel", "mail"]).is_err());
+        let err = parse(&["--channel", "work=smtp"]).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::MissingRequiredArgument);
+        assert!(parse(&["--channel", "work=smtp", "--dry-run"]).is_ok());
+        assert!(parse(&[&["--channel=work=smtp"][..], &SMTP_ARGS].concat()).is_ok());
+        assert!(parse(&SMTP_ARGS).is_ok());
+        assert!(parse(&["--dry-run"]).is_ok());
+        assert!(parse(&["--channel", "ntfy+https://ntfy.sh/alerts"]).is_ok());
+    }
+
+    #[test]
+    fn only_the_templates_of_the_command_line_are_unescaped() {
+        let vars = [("hostname", "server")];
+        let cli = parse(&["--dry-run", "--subject-template", r"Alert\n{{hostname}}"]).unwrap();
+        assert_eq!(cli.subject_template.render(&vars), "Alert\nserver");
+        assert!(cli
+            .body_template
+            .render(&[])
+            .starts_with("Thresholds crossed:\n"));
+
+        std::env::set_var("NOTIFY_BODY_TEMPLATE", r#"{"text":"a\nb"}"#);
+        let cli = parse(&["--dry-run"]);
+        std::env::remove_var("NOTIFY_BODY_TEMPLATE");
+        assert_eq!(cli.unwrap().body_template.render(&[]), r#"{"text":"a\nb"}"#);
+    }
+}
@@ -5,10 +5,14 @@ use std::{
 
 use lettre::{message::Mailbox, Message};
 use log::tracing;
+use metrics::prelude::{ProcessInfo, UnitStatus};
 use rust_decimal::prelude::Decimal;
 
 use crate::{PercentSnapshot, Result};
 
+// NOTE: Keep the message readable, some command lines are huge
+const MAX_CMDLINE_LENGTH: usize = 120;
+
 #[tracing::instrument(level = "trace")]
 pub fn format_threshold_crossed_msg<T: Debug + Display, O: Debug + Display>(
     name: &str,
@@ -49,17 +53,60 @@ pub fn format_snapshot(snap: &PercentSnapshot) -> Result<String> {
     Ok(body)
 }
 
+#[tracing::instrument(level = "debug", skip(processes))]
+pub fn format_processes(processes: &[ProcessInfo]) -> Result<String> {
+    if processes.is_empty() {
+        return Ok(String::new());
+    }
+
+    let mut body = "Top processes:\n".to_string();
+    for process in processes {
+        body.push_str(&format!(
+            "- [{}] {} ({}) CPU {}% RSS {} MiB{}\n",
+            process.pid,
+            process.name,
+            process.user,
+            Decimal::from_str(&process.cpu_percent.to_string())?.round_dp(1),
+            process.rss / 1024 / 1024,
+            process
+                .cmdline
+                .as_ref()
+                .map(|cmdline| format!(
+                    ": {}",
+                    cmdline.chars().take(MAX_CMDLINE_LENGTH).collect::<String>()
+                ))
+                .unwrap_or_default()
+        ));
+    }
+
+    Ok(body)
+}
+
+#[tracing::instrument(level = "debug", skip(units))]
+pub fn format_units(units: &[UnitStatus]) -> String {
+    if units.is_empty() {
+        return String::new();
+    }
+
+    let mut body = "Systemd units:\n".to_string();
+    for unit in units {
+        body.push_str(&format!(
+            "- {} {} ({}, {})\n",
+            unit.name, unit.active_state, unit.sub_state, unit.load_state
+        ));
+    }
+
+    body
+}
+
 #[tracing::instrument]
 pub fn generate_mail(
-    server_ident: &str,
     from: Mailbox,
     contacts: Vec<Mailbox>,
+    subject: &str,
     body: &str,
 ) -> Result<Message> {
-    let email = Message::builder()
-        .date_now()
-        .from(from)
-        .subject(format!("Warning threshold reached on {server_ident}"));
+    let email = Message::builder().date_now().from(from).subject(subject);
     let email = contacts
         .into_iter()
         .fold(email, |email, contact| email.bcc(contact));
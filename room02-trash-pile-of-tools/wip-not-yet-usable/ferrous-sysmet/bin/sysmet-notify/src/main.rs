@@ -8,16 +8,20 @@ use std::{
     path::Path,
 };
 
-use clap::Parser;
 pub use color_eyre::Result;
-use lettre::{transport::smtp::authentication::Credentials, SmtpTransport, Transport};
-use log::{debug, error, info, trace};
+use log::{debug, error, info, trace, warn};
 use metrics::prelude::*;
 
-use crate::mail::{format_snapshot, format_threshold_crossed_msg, generate_mail};
+use crate::{
+    cli::{Route, Rule},
+    mail::{format_processes, format_snapshot, format_threshold_crossed_msg, format_units},
+    notifiers::{build_notifier, Notification},
+};
 
 mod cli;
 mod mail;
+mod notifiers;
+mod template;
 
 #[derive(Debug)]
 pub struct PercentSnapshot {
@@ -46,6 +50,25 @@ fn is_threshold_crossed(debug_msg: &str, threshold: Option<u32>, observered_valu
     is_threshold_crossed
 }
 
+/// A rule without route is sent to every channel
+fn is_routed_to(routes: &[Route], rule: Rule, channel: &str) -> bool {
+    let mut routes = routes.iter().filter(|route| route.rule == rule).peekable();
+    routes.peek().is_none() || routes.any(|route| route.channels.iter().any(|c| c == channel))
+}
+
+fn generate_notification(app: &cli::Cli, vars: Vec<(&'static str, String)>) -> Notification {
+    let borrowed_vars = vars
+        .iter()
+        .map(|(key, value)| (*key, value.as_str()))
+        .collect::<Vec<_>>();
+
+    Notification {
+        subject: app.subject_template.render(&borrowed_vars),
+        body: app.body_template.render(&borrowed_vars),
+        vars,
+    }
+}
+
 fn main() -> Result<()> {
     color_eyre::install()?;
 
@@ -65,7 +88,7 @@ fn main() -> Result<()> {
         env::setup_env();
     }
 
-    let app = cli::Cli::parse();
+    let app = cli::Cli::parse_args();
     if app.verbose.is_silent() {
         set_var("LOG_LEVEL", "SILENT");
     } else if let Some(level) = app.verbose.log_level() {
@@ -113,121 +136,125 @@ fn main() -> Result<()> {
 
     trace!(snapshot =? snapshot, "System snapshot taken at {pretty_formated_now}");
 
-    let cpu_threshold_crossed =
-        is_threshold_crossed("CPU threshold crossed", app.cpu_threshold, snapshot.cpu);
-    let ram_threshold_crossed =
-        is_threshold_crossed("RAM threshold crossed", app.ram_threshold, snapshot.ram);
-    let swap_threshold_crossed =
-        is_threshold_crossed("Swap threshold crossed", app.swap_threshold, snapshot.swap);
-    let memory_threshold_crossed = is_threshold_crossed(
-        "Memory threshold crossed",
-        app.memory_threshold,
-        snapshot.memory,
-    );
-    let disk_threshold_crossed =
-        is_threshold_crossed("Disk threshold crossed", app.disk_threshold, snapshot.disk);
-    let avg_load_threshold_crossed = is_threshold_crossed(
-        "Average Load threshold crossed",
-        app.avg_load_threshold,
-        snapshot.avg_load,
-    );
-    let mut body = "Thresholds crossed:\n".to_string();
-
-    let mut at_least_one_threshold_crossed = false;
-    if cpu_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg("CPU", app.cpu_threshold.unwrap(), snapshot.cpu)?),
-        );
-    }
-    if ram_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg("RAM", app.ram_threshold.unwrap(), snapshot.ram)?),
-        );
-    }
-    if swap_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg("Swap", app.swap_threshold.unwrap(), snapshot.swap)?),
-        );
-    }
-    if memory_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg(
-                "RAM & Swap",
-                app.memory_threshold.unwrap(),
-                snapshot.memory,
-            )?),
-        );
-    }
-    if disk_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg("Disk", app.disk_threshold.unwrap(), snapshot.disk)?),
-        );
-    }
-    if avg_load_threshold_crossed {
-        at_least_one_threshold_crossed = true;
-        body.push_str(
-            &(format_threshold_crossed_msg(
-                "Average Load",
-                app.avg_load_threshold.unwrap(),
-                snapshot.avg_load,
-            )?),
-        );
+    let thresholds = [
+        (Rule::Cpu, "CPU", app.cpu_threshold, snapshot.cpu),
+        (Rule::Ram, "RAM", app.ram_threshold, snapshot.ram),
+        (Rule::Swap, "Swap", app.swap_threshold, snapshot.swap),
+        (
+            Rule::Memory,
+            "RAM & Swap",
+            app.memory_threshold,
+            snapshot.memory,
+        ),
+        (Rule::Disk, "Disk", app.disk_threshold, snapshot.disk),
+        (
+            Rule::AvgLoad,
+            "Average Load",
+            app.avg_load_threshold,
+            snapshot.avg_load,
+        ),
+    ];
+    let mut crossed = Vec::new();
+    for (rule, name, threshold, observed_value) in thresholds {
+        if is_threshold_crossed(
+            &format!("{name} threshold crossed"),
+            threshold,
+            observed_value,
+        ) {
+            crossed.push((
+                rule,
+                format_threshold_crossed_msg(name, threshold.unwrap(), observed_value)?,
+            ));
+        }
     }
 
-    if !at_least_one_threshold_crossed {
+    if crossed.is_empty() {
         info!("Finishing early because no threshold have been crossed");
         return Ok(()); // Exit SUCCESS;
     } else {
         info!("At least one threshold crossed!");
     }
 
-    body.push_str("\n\n");
-    body.push_str(&format_snapshot(&snapshot)?);
-
-    debug!(body, "Body that will be sent");
+    let formated_snapshot = format_snapshot(&snapshot)?;
+    let formated_processes = match top_processes(app.top_processes) {
+        Ok(processes) => format_processes(&processes)?,
+        Err(e) => {
+            warn!(error =? e, "Failed to capture top processes");
+            String::new()
+        }
+    };
+    let formated_units = match units_status(&app.units) {
+        Ok(units) => format_units(&units),
+        Err(e) => {
+            warn!(error =? e, "Failed to capture systemd units");
+            String::new()
+        }
+    };
+    let mut at_least_one_sent = false;
+    for channel in &app.channels {
+        let routed = crossed
+            .iter()
+            .filter(|(rule, _)| is_routed_to(&app.routes, *rule, &channel.name))
+            .map(|(_, msg)| msg.as_str())
+            .collect::<String>();
+        if routed.is_empty() {
+            debug!(
+                channel = channel.name,
+                "No crossed threshold routed to channel"
+            );
+            continue;
+        }
 
-    if app.dry_run {
-        info!(
-            "Finishing early because there is no need to send a mail, the app is in dry-run mode"
+        let notification = generate_notification(
+            &app,
+            vec![
+                ("hostname", hostname.clone()),
+                ("date", pretty_formated_now.to_string()),
+                ("thresholds", routed),
+                ("snapshot", formated_snapshot.clone()),
+                ("processes", formated_processes.clone()),
+                ("units", formated_units.clone()),
+            ],
+        );
+        debug!(
+            channel = channel.name,
+            body = notification.body,
+            "Body that will be sent"
         );
-        return Ok(()); // Exit SUCCESS;
-    }
-
-    let smtp_relay = app.smtp_relay.unwrap();
-    let smtp_user = app.smtp_user.unwrap();
-    let smtp_password = app.smtp_password.unwrap();
-    let last_sent_instant = app.last_sent_instant.unwrap();
-
-    let email = generate_mail(
-        &hostname,
-        app.from.unwrap_or("user@example.org".parse()?),
-        app.contacts,
-        &body,
-    )?;
 
-    let mailer = SmtpTransport::relay(&smtp_relay)?
-        .port(app.smtp_port)
-        .credentials(Credentials::new(smtp_user, smtp_password))
-        .build();
+        if app.dry_run {
+            info!(
+                channel = channel.name,
+                "Skipping the channel because the app is in dry-run mode"
+            );
+            continue;
+        }
 
-    match mailer.send(&email) {
-        Ok(_) => {
-            info!("Mail sent successfully!");
+        let result = build_notifier(channel, &app).and_then(|notifier| {
+            notifier.send(&notification)?;
+            info!(channel = notifier.name(), "Notification sent successfully!");
+            Ok(())
+        });
+        match result {
+            Ok(_) => at_least_one_sent = true,
+            Err(e) => error!(
+                channel = channel.name,
+                error =? e,
+                "Failed to send notification because an error happened"
+            ),
+        }
+    }
 
-            let mut last_mail_instant = File::options()
+    if at_least_one_sent {
+        if let Some(last_sent_instant) = &app.last_sent_instant {
+            let mut last_notification_instant = File::options()
                 .write(true)
                 .create(true)
                 .truncate(true)
                 .open(last_sent_instant)?;
-            last_mail_instant.seek(SeekFrom::Start(0))?;
-            last_mail_instant.write_all(now.to_rfc3339().as_bytes())?;
+            last_notification_instant.seek(SeekFrom::Start(0))?;
+            last_notification_instant.write_all(now.to_rfc3339().as_bytes())?;
         }
-        Err(e) => error!(error =? e, "Failed to send mail because an error happened"),
     }
 
     Ok(())
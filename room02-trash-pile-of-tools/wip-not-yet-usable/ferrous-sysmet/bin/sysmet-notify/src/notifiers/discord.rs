@@ -0,0 +1,35 @@
+use log::tracing;
+use serde_json::json;
+
+use super::{http_agent, Notification, Notifier};
+use crate::Result;
+
+// SOURCE: https://discord.com/developers/docs/resources/webhook#execute-webhook
+const DISCORD_MAX_CONTENT_LENGTH: usize = 2000;
+
+/// Execute a Discord webhook, the url is the one given by Discord (`https://discord.com/api/webhooks/<id>/<token>`)
+#[derive(Debug)]
+pub struct DiscordNotifier {
+    pub(super) name: String,
+    pub(super) url: String,
+}
+
+impl Notifier for DiscordNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let content = format!("**{}**\n{}", notification.subject, notification.body)
+            .chars()
+            .take(DISCORD_MAX_CONTENT_LENGTH)
+            .collect::<String>();
+
+        http_agent()
+            .post(&self.url)
+            .send_json(json!({ "content": content }))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,36 @@
+use log::tracing;
+use serde_json::json;
+
+use super::{http_agent, Notification, Notifier};
+use crate::Result;
+
+// NOTE: High enough to trigger a notification on the Android client
+const GOTIFY_PRIORITY: u8 = 8;
+
+/// Push a message to a Gotify server, the url is the server root (e.g. `https://gotify.example.org`)
+#[derive(Debug)]
+pub struct GotifyNotifier {
+    pub(super) name: String,
+    pub(super) url: String,
+    pub(super) token: String,
+}
+
+impl Notifier for GotifyNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        http_agent()
+            .post(&format!("{}/message", self.url.trim_end_matches('/')))
+            .set("X-Gotify-Key", &self.token)
+            .send_json(json!({
+                "title": notification.subject,
+                "message": notification.body,
+                "priority": GOTIFY_PRIORITY,
+            }))?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,71 @@
+use color_eyre::eyre::eyre;
+use log::tracing;
+use serde_json::json;
+
+use super::{http_agent, Notification, Notifier};
+use crate::Result;
+
+/// Send a `m.text` message to a Matrix room, the url is `<homeserver>/<room id>`
+/// (e.g. `https://matrix.org/!abcdef:matrix.org`)
+#[derive(Debug)]
+pub struct MatrixNotifier {
+    name: String,
+    homeserver: String,
+    room_id: String,
+    token: String,
+}
+
+impl MatrixNotifier {
+    pub fn new(name: String, url: &str, token: String) -> Result<Self> {
+        let (homeserver, room_id) = url
+            .rsplit_once('/')
+            .filter(|(_, room_id)| room_id.starts_with('!'))
+            .ok_or_else(|| eyre!("Channel {name} url must be <homeserver>/<room id>"))?;
+
+        Ok(Self {
+            homeserver: homeserver.to_string(),
+            room_id: room_id.to_string(),
+            name,
+            token,
+        })
+    }
+}
+
+impl Notifier for MatrixNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        // NOTE: The transaction id only needs to be unique for this access token
+        let transaction_id = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let url = format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/sysmet-{transaction_id}",
+            self.homeserver,
+            percent_encode(&self.room_id),
+        );
+
+        http_agent()
+            .put(&url)
+            .set("Authorization", &format!("Bearer {}", self.token))
+            .send_json(json!({
+                "msgtype": "m.text",
+                "body": format!("{}\n\n{}", notification.subject, notification.body),
+            }))?;
+
+        Ok(())
+    }
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{byte:02X}"),
+        })
+        .collect()
+}
@@ -0,0 +1,172 @@
+use std::{fmt::Debug, str::FromStr, time::Duration};
+
+use color_eyre::eyre::eyre;
+use log::{trace, tracing};
+
+use crate::{cli::Cli, Result};
+
+mod discord;
+mod gotify;
+mod matrix;
+mod ntfy;
+mod smtp;
+#[cfg(test)]
+mod tests;
+mod webhook;
+
+// NOTE: Do not hang the cron job forever if a service does not answer
+const HTTP_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A rendered message, ready to be sent through any channel
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub subject: String,
+    pub body: String,
+    /// Variables used to render the subject and the body, channels may render their own templates with them
+    pub vars: Vec<(&'static str, String)>,
+}
+
+impl Notification {
+    pub fn vars(&self) -> Vec<(&str, &str)> {
+        self.vars
+            .iter()
+            .map(|(key, value)| (*key, value.as_str()))
+            .chain([
+                ("subject", self.subject.as_str()),
+                ("body", self.body.as_str()),
+            ])
+            .collect()
+    }
+}
+
+pub trait Notifier: Debug {
+    fn name(&self) -> &str;
+    fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelKind {
+    Smtp,
+    Webhook,
+    Ntfy,
+    Gotify,
+    Matrix,
+    Discord,
+}
+
+impl FromStr for ChannelKind {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        match value.to_lowercase().as_str() {
+            "smtp" | "mail" => Ok(Self::Smtp),
+            "webhook" => Ok(Self::Webhook),
+            "ntfy" => Ok(Self::Ntfy),
+            "gotify" => Ok(Self::Gotify),
+            "matrix" => Ok(Self::Matrix),
+            "discord" => Ok(Self::Discord),
+            other => Err(format!(
+                "unknown channel kind `{other}`, expected one of smtp, webhook, ntfy, gotify, matrix, discord"
+            )),
+        }
+    }
+}
+
+/// A channel given on the command line as `[NAME=]KIND[+URL]`
+/// e.g. `smtp`, `phone=ntfy+https://ntfy.sh/my-topic` or `discord+https://discord.com/api/webhooks/...`
+#[derive(Debug, Clone)]
+pub struct ChannelSpec {
+    pub name: String,
+    pub kind: ChannelKind,
+    pub url: Option<String>,
+}
+
+impl FromStr for ChannelSpec {
+    type Err = String;
+
+    fn from_str(value: &str) -> std::result::Result<Self, Self::Err> {
+        let (name, spec) = match value.split_once('=') {
+            Some((name, spec)) if !name.contains('+') => (Some(name.trim()), spec.trim()),
+            _ => (None, value.trim()),
+        };
+        let (kind, url) = match spec.split_once('+') {
+            Some((kind, url)) => (kind, Some(url.to_string())),
+            None => (spec, None),
+        };
+        let kind = kind.parse::<ChannelKind>()?;
+
+        if kind != ChannelKind::Smtp && url.is_none() {
+            return Err(format!("channel `{value}` needs an url (`{spec}+<URL>`)"));
+        }
+
+        Ok(Self {
+            name: name.unwrap_or(kind_name(kind)).to_string(),
+            kind,
+            url,
+        })
+    }
+}
+
+fn kind_name(kind: ChannelKind) -> &'static str {
+    match kind {
+        ChannelKind::Smtp => "smtp",
+        ChannelKind::Webhook => "webhook",
+        ChannelKind::Ntfy => "ntfy",
+        ChannelKind::Gotify => "gotify",
+        ChannelKind::Matrix => "matrix",
+        ChannelKind::Discord => "discord",
+    }
+}
+
+#[tracing::instrument(level = "debug", skip(app))]
+pub fn build_notifier(spec: &ChannelSpec, app: &Cli) -> Result<Box<dyn Notifier>> {
+    let name = spec.name.clone();
+    let url = || {
+        spec.url
+            .clone()
+            .ok_or_else(|| eyre!("Channel {} needs an url", spec.name))
+    };
+
+    let notifier: Box<dyn Notifier> = match spec.kind {
+        ChannelKind::Smtp => Box::new(smtp::SmtpNotifier::new(name, app)?),
+        ChannelKind::Webhook => Box::new(webhook::WebhookNotifier {
+            name,
+            url: url()?,
+            template: app.webhook_template.clone(),
+        }),
+        ChannelKind::Ntfy => Box::new(ntfy::NtfyNotifier {
+            name,
+            url: url()?,
+            token: app.ntfy_token.clone(),
+        }),
+        ChannelKind::Gotify => Box::new(gotify::GotifyNotifier {
+            name,
+            url: url()?,
+            token: app
+                .gotify_token
+                .clone()
+                .ok_or_else(|| eyre!("Channel {} needs --gotify-token", spec.name))?,
+        }),
+        ChannelKind::Matrix => Box::new(matrix::MatrixNotifier::new(
+            name,
+            &url()?,
+            app.matrix_token
+                .clone()
+                .ok_or_else(|| eyre!("Channel {} needs --matrix-token", spec.name))?,
+        )?),
+        ChannelKind::Discord => Box::new(discord::DiscordNotifier { name, url: url()? }),
+    };
+    trace!(notifier =? notifier, "Built notifier");
+
+    Ok(notifier)
+}
+
+pub(crate) fn http_agent() -> ureq::Agent {
+    ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build()
+}
+
+/// Escape a value so it can be embedded between quotes inside a JSON document
+pub(crate) fn json_escape(value: &str) -> String {
+    let quoted = serde_json::Value::String(value.to_string()).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
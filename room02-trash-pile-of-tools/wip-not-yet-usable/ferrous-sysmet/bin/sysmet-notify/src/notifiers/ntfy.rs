@@ -0,0 +1,32 @@
+use log::tracing;
+
+use super::{http_agent, Notification, Notifier};
+use crate::Result;
+
+/// Publish to a ntfy topic, the url is the full topic url (e.g. `https://ntfy.sh/my-topic`)
+#[derive(Debug)]
+pub struct NtfyNotifier {
+    pub(super) name: String,
+    pub(super) url: String,
+    pub(super) token: Option<String>,
+}
+
+impl Notifier for NtfyNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let mut request = http_agent()
+            .post(&self.url)
+            .set("Title", &notification.subject)
+            .set("Tags", "warning");
+        if let Some(token) = &self.token {
+            request = request.set("Authorization", &format!("Bearer {token}"));
+        }
+        request.send_string(&notification.body)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,79 @@
+use color_eyre::eyre::eyre;
+use lettre::{
+    message::Mailbox, transport::smtp::authentication::Credentials, SmtpTransport, Transport,
+};
+use log::tracing;
+
+use super::{Notification, Notifier};
+use crate::{cli::Cli, mail::generate_mail, Result};
+
+pub struct SmtpNotifier {
+    name: String,
+    from: Mailbox,
+    contacts: Vec<Mailbox>,
+    transport: SmtpTransport,
+}
+
+impl std::fmt::Debug for SmtpNotifier {
+    // NOTE: SmtpTransport does not implement Debug
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SmtpNotifier")
+            .field("name", &self.name)
+            .field("from", &self.from)
+            .field("contacts", &self.contacts)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SmtpNotifier {
+    pub fn new(name: String, app: &Cli) -> Result<Self> {
+        let missing = |arg: &str| eyre!("Channel {name} needs {arg}");
+        let smtp_relay = app
+            .smtp_relay
+            .as_ref()
+            .ok_or_else(|| missing("--smtp-relay"))?;
+        let smtp_user = app
+            .smtp_user
+            .clone()
+            .ok_or_else(|| missing("--smtp-user"))?;
+        let smtp_password = app
+            .smtp_password
+            .clone()
+            .ok_or_else(|| missing("--smtp-pass"))?;
+        let from = app.from.clone().ok_or_else(|| missing("--from"))?;
+        if app.contacts.is_empty() {
+            return Err(missing("--contacts"));
+        }
+
+        let transport = SmtpTransport::relay(smtp_relay)?
+            .port(app.smtp_port)
+            .credentials(Credentials::new(smtp_user, smtp_password))
+            .build();
+
+        Ok(Self {
+            from,
+            contacts: app.contacts.clone(),
+            transport,
+            name,
+        })
+    }
+}
+
+impl Notifier for SmtpNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let email = generate_mail(
+            self.from.clone(),
+            self.contacts.clone(),
+            &notification.subject,
+            &notification.body,
+        )?;
+        self.transport.send(&email)?;
+
+        Ok(())
+    }
+}
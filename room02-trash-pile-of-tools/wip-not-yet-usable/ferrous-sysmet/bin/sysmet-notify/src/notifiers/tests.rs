@@ -0,0 +1,138 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write},
+    net::TcpListener,
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+use super::{ntfy::NtfyNotifier, webhook::WebhookNotifier, Notification, Notifier};
+use crate::{cli::DEFAULT_WEBHOOK_TEMPLATE, template::Template};
+
+/// A request received by the local listener
+#[derive(Debug)]
+struct Received {
+    request_line: String,
+    headers: Vec<(String, String)>,
+    body: String,
+}
+
+impl Received {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+/// Answer one HTTP request with `status`, returns the url to send it to and the received request
+fn listen_once(status: u16) -> (String, Receiver<Received>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+        let mut headers = Vec::new();
+        loop {
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            match line.trim_end().split_once(':') {
+                Some((key, value)) => headers.push((key.to_string(), value.trim().to_string())),
+                None => break,
+            }
+        }
+        let length = headers
+            .iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case("content-length"))
+            .map_or(0, |(_, value)| value.parse().unwrap());
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        write!(
+            stream,
+            "HTTP/1.1 {status} Status\r\nContent-Length: 0\r\n\r\n"
+        )
+        .unwrap();
+        sender
+            .send(Received {
+                request_line: request_line.trim_end().to_string(),
+                headers,
+                body: String::from_utf8(body).unwrap(),
+            })
+            .unwrap();
+    });
+
+    (url, receiver)
+}
+
+fn notification() -> Notification {
+    Notification {
+        subject: "Warning threshold reached on server".to_string(),
+        body: "CPU usage is \"95%\"\nRAM usage is 80%".to_string(),
+        vars: vec![
+            ("hostname", "server".to_string()),
+            ("date", "01/01/2024 12:00".to_string()),
+        ],
+    }
+}
+
+#[test]
+fn webhook_posts_the_rendered_json() {
+    let (url, received) = listen_once(200);
+    let notifier = WebhookNotifier {
+        name: "webhook".to_string(),
+        url,
+        template: DEFAULT_WEBHOOK_TEMPLATE.parse::<Template>().unwrap(),
+    };
+
+    notifier.send(&notification()).unwrap();
+
+    let request = received.recv().unwrap();
+    assert_eq!(request.request_line, "POST /alerts HTTP/1.1");
+    assert_eq!(request.header("Content-Type"), Some("application/json"));
+    let payload = serde_json::from_str::<serde_json::Value>(&request.body).unwrap();
+    assert_eq!(payload["hostname"], "server");
+    assert_eq!(payload["date"], "01/01/2024 12:00");
+    assert_eq!(payload["subject"], "Warning threshold reached on server");
+    assert_eq!(payload["body"], "CPU usage is \"95%\"\nRAM usage is 80%");
+}
+
+#[test]
+fn ntfy_publishes_the_body_with_a_title() {
+    let (url, received) = listen_once(200);
+    let notifier = NtfyNotifier {
+        name: "ntfy".to_string(),
+        url,
+        token: Some("secret".to_string()),
+    };
+
+    notifier.send(&notification()).unwrap();
+
+    let request = received.recv().unwrap();
+    assert_eq!(request.request_line, "POST /alerts HTTP/1.1");
+    assert_eq!(
+        request.header("Title"),
+        Some("Warning threshold reached on server")
+    );
+    assert_eq!(request.header("Tags"), Some("warning"));
+    assert_eq!(request.header("Authorization"), Some("Bearer secret"));
+    assert_eq!(request.body, "CPU usage is \"95%\"\nRAM usage is 80%");
+}
+
+#[test]
+fn failed_deliveries_are_errors() {
+    let (url, received) = listen_once(500);
+    let notifier = NtfyNotifier {
+        name: "ntfy".to_string(),
+        url,
+        token: None,
+    };
+
+    assert!(notifier.send(&notification()).is_err());
+    assert_eq!(received.recv().unwrap().header("Authorization"), None);
+}
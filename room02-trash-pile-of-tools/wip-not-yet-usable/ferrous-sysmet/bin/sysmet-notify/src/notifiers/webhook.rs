@@ -0,0 +1,31 @@
+use log::{trace, tracing};
+
+use super::{http_agent, json_escape, Notification, Notifier};
+use crate::{template::Template, Result};
+
+/// POST a JSON document rendered from `--webhook-template` to any url
+#[derive(Debug)]
+pub struct WebhookNotifier {
+    pub(super) name: String,
+    pub(super) url: String,
+    pub(super) template: Template,
+}
+
+impl Notifier for WebhookNotifier {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    #[tracing::instrument(skip(self, notification), fields(channel = self.name))]
+    fn send(&self, notification: &Notification) -> Result<()> {
+        let payload = self.template.render_with(&notification.vars(), json_escape);
+        trace!(payload, "Webhook payload");
+
+        http_agent()
+            .post(&self.url)
+            .set("Content-Type", "application/json")
+            .send_string(&payload)?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,60 @@
+use std::{convert::Infallible, str::FromStr};
+
+use log::{trace, tracing};
+
+/// A message template shared by every notification channel.
+/// Placeholders are written `{{name}}`, unknown placeholders are kept as is.
+#[derive(Debug, Clone)]
+pub struct Template(String);
+
+impl FromStr for Template {
+    type Err = Infallible;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        Ok(Self(value.to_string()))
+    }
+}
+
+impl Template {
+    /// Replace the literal `\n` by newlines, to write multiline templates from the command line
+    pub fn unescaped(&self) -> Self {
+        Self(self.0.replace("\\n", "\n"))
+    }
+
+    #[tracing::instrument(level = "trace", skip(vars))]
+    pub fn render(&self, vars: &[(&str, &str)]) -> String {
+        self.render_with(vars, str::to_string)
+    }
+
+    /// Render the template, passing every substituted value through `escape` first
+    /// (e.g. to embed values inside a JSON document).
+    #[tracing::instrument(level = "trace", skip(vars, escape))]
+    pub fn render_with<E>(&self, vars: &[(&str, &str)], escape: E) -> String
+    where
+        E: Fn(&str) -> String,
+    {
+        let mut result = String::with_capacity(self.0.len());
+        let mut rest = self.0.as_str();
+
+        while let Some(start) = rest.find("{{") {
+            result.push_str(&rest[..start]);
+            let after_start = &rest[start + 2..];
+
+            let Some(end) = after_start.find("}}") else {
+                rest = &rest[start..];
+                break;
+            };
+
+            let name = after_start[..end].trim();
+            match vars.iter().find(|(key, _)| *key == name) {
+                Some((_, value)) => result.push_str(&escape(value)),
+                None => result.push_str(&rest[start..start + end + 4]),
+            }
+            rest = &after_start[end + 2..];
+        }
+        result.push_str(rest);
+
+        trace!(rendered = result);
+        result
+    }
+}
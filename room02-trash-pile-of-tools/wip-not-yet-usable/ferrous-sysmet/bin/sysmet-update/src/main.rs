@@ -1,14 +1,17 @@
 #![forbid(unsafe_code)]
 
-use std::env::set_var;
+use std::{env::set_var, path::PathBuf};
 
-use clap::{ArgAction, Parser};
+use clap::{ArgAction, Parser, Subcommand};
 pub(crate) use color_eyre::Result;
-use metrics::prelude::*;
+use log::info;
+use metrics::{import::read_go_database, prelude::*};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct Cli {
+    #[clap(subcommand)]
+    command: Option<Command>,
     #[clap(long, visible_alias = "db", value_name = "FILE")]
     database: String,
     #[clap(long, visible_alias = "gc", value_parser, value_name = "DAYS")]
@@ -17,6 +20,15 @@ struct Cli {
     ignored_networks: Vec<String>,
     #[clap(long, visible_alias = "gin", value_name = "GLOB")]
     glob_ignored_networks: Vec<String>, // TODO: Glob ignore
+    #[clap(
+        long,
+        visible_alias = "top",
+        value_name = "NUMBER OF PROCESSES",
+        default_value = "0"
+    )]
+    top_processes: usize,
+    #[clap(long, value_name = "SYSTEMD UNITS", value_delimiter = ',')]
+    units: Vec<String>,
     #[clap(short, long = "verbose", action = ArgAction::Count)]
     verbosity: u8,
     #[clap(long = "dry-run", action, default_value = "false")]
@@ -26,6 +38,18 @@ struct Cli {
     times: Option<u32>,
 }
 
+#[derive(Subcommand)]
+enum Command {
+    /// Import the snapshots of a database of the original Go sysmet instead of taking a snapshot
+    Import {
+        #[clap(value_name = "BOLT FILE")]
+        source: PathBuf,
+        /// Only import this top-level bucket
+        #[clap(long, value_name = "NAME")]
+        bucket: Option<String>,
+    },
+}
+
 fn main() -> Result<()> {
     color_eyre::install()?;
 
@@ -41,25 +65,23 @@ fn main() -> Result<()> {
     }
     log::setup_hierarchical_logger();
 
-    let (mut database, file, path) = Database::from_file_with_write(&app.database)?;
-    if let Some(times) = app.times {
-        for _ in 0..times {
-            database.take_snapshot(
-                app.ignored_networks
-                    .iter()
-                    .map(|n| n.as_ref())
-                    .collect::<Vec<&str>>()
-                    .as_ref(),
-            )?;
-        }
+    let (mut database, lock) = Database::from_file_with_write(&app.database)?;
+    if let Some(Command::Import { source, bucket }) = &app.command {
+        let (snapshots, report) = read_go_database(source, bucket.as_deref())?;
+        let added = database.merge_snapshots(snapshots);
+        info!(
+            "Imported {added} new snapshots ({} read) from {source:?}",
+            report.imported
+        );
     } else {
-        database.take_snapshot(
-            app.ignored_networks
-                .iter()
-                .map(|n| n.as_ref())
-                .collect::<Vec<&str>>()
-                .as_ref(),
-        )?;
+        let ignored_networks = app
+            .ignored_networks
+            .iter()
+            .map(|n| n.as_ref())
+            .collect::<Vec<&str>>();
+        for _ in 0..app.times.unwrap_or(1) {
+            database.take_snapshot(&ignored_networks, app.top_processes, &app.units)?;
+        }
     }
 
     if let Some(days_number) = app.cleanup_older {
@@ -67,9 +89,9 @@ fn main() -> Result<()> {
     }
 
     if app.dry_run {
-        database.close_file(&path)?;
+        database.close_file(lock)?;
     } else {
-        database.write_and_close_file(file, &path)?;
+        database.write_and_close_file(lock)?;
     }
 
     Ok(())
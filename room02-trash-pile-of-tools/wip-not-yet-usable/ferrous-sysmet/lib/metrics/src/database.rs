@@ -1,10 +1,9 @@
 use std::{
-    fs::{remove_file, File, OpenOptions},
-    io::{BufReader, BufWriter, Seek, SeekFrom, Write},
+    collections::HashSet,
+    fs::{remove_file, rename, File, OpenOptions},
+    io::{BufReader, BufWriter, ErrorKind, Write},
     path::{Path, PathBuf},
     str::FromStr,
-    thread::sleep,
-    time::{Duration, Instant},
 };
 
 use chrono::{DateTime, Utc};
@@ -14,9 +13,6 @@ use serde::{Deserialize, Serialize};
 
 use crate::{prelude::*, Result};
 
-const SLEEP_DURATION_BEFORE_RETRY_LOCK: Duration = Duration::from_millis(100);
-const LOCKFILE_TIMEOUT: Duration = Duration::from_secs(5);
-
 const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -34,70 +30,111 @@ impl Default for Database {
     }
 }
 
-impl Database {
-    fn str_to_pathbuf(path: &str) -> Result<PathBuf> {
-        let path = PathBuf::from_str(path).map_err(Error::InvalidPath)?;
-        Ok(path)
-    }
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LockKind {
+    /// Many readers can hold the lock at the same time
+    Shared,
+    /// Only one writer, no reader
+    Exclusive,
+}
 
+/// OS lock (`flock`) held on `<db>.lock`, released when dropped.
+/// NOTE: The lock is taken on a sibling file because the database itself is replaced on every write,
+/// the lockfile is never removed so a crashed process cannot leave a stale lock behind (the OS releases it).
+#[derive(Debug)]
+pub struct DatabaseLock {
+    path: PathBuf,
+    lockfile: File,
+}
+
+impl DatabaseLock {
     #[tracing::instrument(level = "trace")]
-    fn lock(options: OpenOptions, path: &PathBuf) -> Result<File> {
-        let lockfile = PathBuf::from_str(&format!("{}.lock", path.to_str().unwrap()))
-            .map_err(Error::InvalidPath)?;
-        let instant = Instant::now();
-        while lockfile.exists() {
-            if instant.elapsed() > LOCKFILE_TIMEOUT {
-                return Err(Error::LockFileTimeout(path.clone()));
-            }
-            sleep(SLEEP_DURATION_BEFORE_RETRY_LOCK);
+    fn acquire(path: &Path, kind: LockKind) -> Result<Self> {
+        let lockfile_path = Database::sibling_path(path, "lock")?;
+        let lockfile = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&lockfile_path)
+            .map_err(Error::FailedToOpenFile)?;
+
+        // NOTE: Block until the lock is free, a writer only holds it while it updates the database
+        // and the OS releases it if the process dies
+        match kind {
+            LockKind::Shared => lockfile.lock_shared(),
+            LockKind::Exclusive => lockfile.lock(),
         }
+        .map_err(Error::FailedToLockFile)?;
+        debug!("Acquired {:?} lock on {:?}", kind, &lockfile_path);
 
-        {
-            // Create lockfile and drop immediately the handle
-            File::create(&lockfile).map_err(Error::FailedToOpenFile)?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            lockfile,
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for DatabaseLock {
+    fn drop(&mut self) {
+        if let Err(e) = self.lockfile.unlock() {
+            warn!("Failed to release the lock on {:?}: {e}", self.path);
         }
-        debug!("Created lockfile {:?}", &lockfile);
-        let file = options.open(path).map_err(Error::FailedToOpenFile)?;
-        let file_size = file
-            .metadata()
-            .map_err(Error::FailedToGetFileMetadata)?
-            .len();
-        debug!(
-            "Opened {:?} for reading and writing, file size is {}",
-            path, file_size,
-        );
+    }
+}
 
-        Ok(file)
+impl Database {
+    fn str_to_pathbuf(path: &str) -> Result<PathBuf> {
+        let path = PathBuf::from_str(path).map_err(Error::InvalidPath)?;
+        Ok(path)
+    }
+
+    fn sibling_path(path: &Path, extension: &str) -> Result<PathBuf> {
+        PathBuf::from_str(&format!("{}.{extension}", path.to_str().unwrap()))
+            .map_err(Error::InvalidPath)
     }
 
     #[tracing::instrument(level = "trace")]
-    fn unlock(path: &Path) -> Result<()> {
-        if path.exists() {
-            let lockfile = PathBuf::from_str(&format!("{}.lock", path.to_str().unwrap()))
-                .map_err(Error::InvalidPath)?;
-            remove_file(lockfile).map_err(Error::FailedToRemoveFile)?;
+    fn open(path: &Path) -> Result<Option<File>> {
+        match File::open(path) {
+            Ok(file) => {
+                let file_size = file
+                    .metadata()
+                    .map_err(Error::FailedToGetFileMetadata)?
+                    .len();
+                debug!("Opened {:?} for reading, file size is {}", path, file_size);
+                Ok(Some(file))
+            }
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(Error::FailedToOpenFile(e)),
         }
-
-        Ok(())
     }
 
     #[tracing::instrument(level = "debug")]
-    fn load_database(file: &File) -> Result<Self> {
-        let file_size = file
-            .metadata()
-            .map_err(Error::FailedToGetFileMetadata)?
-            .len();
-
-        let mut result = if file_size == 0 {
-            Database::default()
-        } else {
-            let mut reader = BufReader::new(file);
-            let database = ciborium::de::from_reader::<Database, _>(&mut reader)?;
-            tracing::debug!(
-                "Deserialized database with {} snapshots",
-                database.snapshots.len()
-            );
-            database
+    fn load_database(file: Option<&File>) -> Result<Self> {
+        let file_size = match file {
+            Some(file) => file
+                .metadata()
+                .map_err(Error::FailedToGetFileMetadata)?
+                .len(),
+            None => 0,
+        };
+
+        let mut result = match file {
+            Some(file) if file_size > 0 => {
+                let mut reader = BufReader::new(file);
+                let database = ciborium::de::from_reader::<Database, _>(&mut reader)?;
+                tracing::debug!(
+                    "Deserialized database with {} snapshots",
+                    database.snapshots.len()
+                );
+                database
+            }
+            _ => Database::default(),
         };
 
         debug!("Loaded database with version {}", result.version);
@@ -116,58 +153,81 @@ impl Database {
         Ok(result)
     }
 
-    #[tracing::instrument(level = "debug")]
-    fn write_self_to_file(&self, file: &File) -> Result<()> {
-        let mut writer = BufWriter::new(file);
-        debug!(
-            "File size before write is {}",
-            file.metadata()
-                .map_err(Error::FailedToGetFileMetadata)?
-                .len()
-        );
-        ciborium::ser::into_writer(&self, &mut writer)?;
-        writer.flush().map_err(Error::FailedToWriteFile)?;
+    /// Write to `<db>.tmp` then rename it over the database,
+    /// so a killed process never leaves a half-written database behind.
+    /// NOTE: Must be called while holding an exclusive lock
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn write_self_atomically(&self, path: &Path) -> Result<()> {
+        let tmp_path = Self::sibling_path(path, "tmp")?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&tmp_path)
+            .map_err(Error::FailedToOpenFile)?;
+
+        {
+            let mut writer = BufWriter::new(&file);
+            ciborium::ser::into_writer(&self, &mut writer)?;
+            writer.flush().map_err(Error::FailedToWriteFile)?;
+        }
+        file.sync_all().map_err(Error::FailedToWriteFile)?;
         debug!(
-            "File size after write is {}",
+            "Temporary file size after write is {}",
             file.metadata()
                 .map_err(Error::FailedToGetFileMetadata)?
                 .len()
         );
+
+        rename(&tmp_path, path).map_err(Error::FailedToRenameFile)?;
+        // NOTE: Persist the rename itself
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            if let Ok(dir) = File::open(parent) {
+                dir.sync_all().ok();
+            }
+        }
+        debug!("Replaced {:?} with {:?}", path, tmp_path);
+
+        Ok(())
+    }
+
+    /// Remove a temporary file left behind by a killed writer.
+    /// NOTE: Must be called while holding an exclusive lock
+    #[tracing::instrument(level = "trace")]
+    fn remove_stale_tmp(path: &Path) -> Result<()> {
+        let tmp_path = Self::sibling_path(path, "tmp")?;
+        if tmp_path.exists() {
+            warn!("Removing {:?} left by an interrupted write", tmp_path);
+            remove_file(tmp_path).map_err(Error::FailedToRemoveFile)?;
+        }
+
         Ok(())
     }
 
+    /// Load the database with a shared lock, readers do not block each other
     #[tracing::instrument]
     pub fn from_file(ipath: &str) -> Result<Self> {
         let path = Self::str_to_pathbuf(ipath)?;
 
-        let mut options = OpenOptions::new();
-        options.read(true);
-
-        let file = Self::lock(options, &path)?;
-        let result = Self::load_database(&file)?;
-        Self::unlock(&path)?;
+        let lock = DatabaseLock::acquire(&path, LockKind::Shared)?;
+        let file = Self::open(&path)?;
+        let result = Self::load_database(file.as_ref())?;
+        drop(lock);
 
         Ok(result)
     }
 
+    /// Load the database and keep an exclusive lock until it is written or closed
     #[tracing::instrument]
-    pub fn from_file_with_write(ipath: &str) -> Result<(Self, File, PathBuf)> {
+    pub fn from_file_with_write(ipath: &str) -> Result<(Self, DatabaseLock)> {
         let path = Self::str_to_pathbuf(ipath)?;
 
-        let mut options = OpenOptions::new();
-        options.read(true);
-        options.write(true);
-        options.create(true);
-
-        let mut file = Self::lock(options, &path)?;
-        let result = Self::load_database(&file)?;
+        let lock = DatabaseLock::acquire(&path, LockKind::Exclusive)?;
+        Self::remove_stale_tmp(&path)?;
+        let file = Self::open(&path)?;
+        let result = Self::load_database(file.as_ref())?;
 
-        // NOTE: We need to reset the file pointer to the beginning of the file to overwrite
-        // SOURCE: https://doc.rust-lang.org/std/fs/struct.OpenOptions.html#method.append
-        file.seek(SeekFrom::Start(0))
-            .map_err(Error::FailedToSetFileCursor)?;
-
-        Ok((result, file, path))
+        Ok((result, lock))
     }
 
     #[tracing::instrument(skip(self))]
@@ -178,48 +238,46 @@ impl Database {
         );
         let path = Self::str_to_pathbuf(path)?;
 
-        let mut options = OpenOptions::new();
-        options.write(true);
-        options.truncate(true);
-        options.create(true);
-
-        let file = Self::lock(options, &path)?;
-        debug!(
-            "Number of snapshot that will be written {}",
-            self.snapshots.len()
-        );
-        self.write_self_to_file(&file)?;
-        Self::unlock(&path)?;
+        let lock = DatabaseLock::acquire(&path, LockKind::Exclusive)?;
+        self.write_self_atomically(&path)?;
+        drop(lock);
 
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub fn write_and_close_file(&self, file: File, path: &PathBuf) -> Result<()> {
+    pub fn write_and_close_file(&self, lock: DatabaseLock) -> Result<()> {
         debug!(
             "Number of snapshot that will be written {}",
             self.snapshots.len()
         );
-        self.write_self_to_file(&file)?;
-        Self::unlock(path)?;
+        self.write_self_atomically(lock.path())?;
+        drop(lock);
 
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub fn close_file(&self, path: &PathBuf) -> Result<()> {
+    pub fn close_file(&self, lock: DatabaseLock) -> Result<()> {
         debug!(
             "Number of snapshot that would have been written {}",
             self.snapshots.len()
         );
-        Self::unlock(path)?;
+        drop(lock);
 
         Ok(())
     }
 
     #[tracing::instrument(skip(self))]
-    pub fn take_snapshot(&mut self, networks_to_ignore: &[&str]) -> Result<()> {
-        self.snapshots.push(SnapShot::new(networks_to_ignore)?);
+    pub fn take_snapshot(
+        &mut self,
+        networks_to_ignore: &[&str],
+        top_processes: usize,
+        units: &[String],
+    ) -> Result<()> {
+        let mut snapshot = SnapShot::new(networks_to_ignore)?;
+        snapshot.capture_details(top_processes, units);
+        self.snapshots.push(snapshot);
         debug!(
             "Number of snapshots after appending {}",
             self.snapshots.len()
@@ -228,6 +286,28 @@ impl Database {
         Ok(())
     }
 
+    /// Add snapshots taken elsewhere, the ones with a time already in the database are ignored
+    #[tracing::instrument(skip(self, snapshots))]
+    pub fn merge_snapshots(&mut self, snapshots: Vec<SnapShot>) -> usize {
+        let before = self.snapshots.len();
+        let mut times = self
+            .snapshots
+            .iter()
+            .map(|snap| snap.time)
+            .collect::<HashSet<_>>();
+        for snapshot in snapshots {
+            // NOTE: Also ignore the duplicates of the merged snapshots
+            if times.insert(snapshot.time) {
+                self.snapshots.push(snapshot);
+            }
+        }
+        self.snapshots.sort_by_key(|snap| snap.time);
+
+        let added = self.snapshots.len() - before;
+        debug!("Merged {} snapshots", added);
+        added
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn remove_older(&mut self, older_than_days: i64) -> Result<()> {
         let oldest_date = Utc::now()
@@ -259,6 +339,21 @@ impl Database {
         result
     }
 
+    /// Snapshot with the highest CPU usage among the ones with processes or units captured
+    #[tracing::instrument(skip(self))]
+    pub fn get_cpu_spike(&self) -> Option<(f64, &SnapShot)> {
+        let result = self
+            .get_cpu_usage()
+            .into_iter()
+            .zip(self.snapshots.iter())
+            .filter(|(_, snap)| snap.has_details())
+            .map(|((usage, _), snap)| (usage, snap))
+            .max_by(|(a, _), (b, _)| a.total_cmp(b));
+
+        debug!(cpu_spike = ?result.as_ref().map(|(usage, snap)| (usage, snap.time)));
+        result
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn get_ram_usage(&self) -> Vec<((f64, f64), DateTime<Utc>)> {
         let result = self
@@ -346,3 +441,122 @@ impl Database {
         result
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs, process, sync::mpsc, thread, time::Duration};
+
+    use super::*;
+
+    /// How long a blocked reader is given to prove it waits for the lock
+    const BLOCKED: Duration = Duration::from_millis(200);
+    const UNBLOCKED: Duration = Duration::from_secs(5);
+
+    /// A folder in the temporary folder, removed when dropped
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new(name: &str) -> Self {
+            let path = env::temp_dir().join(format!("sysmet-{}-{name}", process::id()));
+            fs::create_dir_all(&path).unwrap();
+            Self(path)
+        }
+
+        fn database(&self) -> String {
+            self.0.join("sysmet.db").to_str().unwrap().to_string()
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    fn database(version: &str) -> Database {
+        Database {
+            version: version.to_string(),
+            snapshots: Vec::new(),
+        }
+    }
+
+    /// Load the database in another thread, the version read is sent once the lock is acquired
+    fn read_version_in_thread(path: &str) -> mpsc::Receiver<std::result::Result<String, String>> {
+        let (sender, receiver) = mpsc::channel();
+        let path = path.to_string();
+        thread::spawn(move || {
+            let result = Database::from_file(&path)
+                .map(|db| db.version)
+                .map_err(|e| e.to_string());
+            let _ = sender.send(result);
+        });
+        receiver
+    }
+
+    #[test]
+    fn readers_share_the_lock() {
+        let dir = TempDir::new("readers");
+        let path = dir.database();
+        database("0.0.1").write_to_file(&path).unwrap();
+
+        let lock = DatabaseLock::acquire(Path::new(&path), LockKind::Shared).unwrap();
+        let version = read_version_in_thread(&path).recv_timeout(UNBLOCKED);
+        assert_eq!(version, Ok(Ok("0.0.1".to_string())));
+        drop(lock);
+    }
+
+    #[test]
+    fn a_writer_excludes_the_readers() {
+        let dir = TempDir::new("writer");
+        let path = dir.database();
+        database("0.0.1").write_to_file(&path).unwrap();
+
+        let (_, lock) = Database::from_file_with_write(&path).unwrap();
+        let reader = read_version_in_thread(&path);
+        assert_eq!(
+            reader.recv_timeout(BLOCKED),
+            Err(mpsc::RecvTimeoutError::Timeout)
+        );
+
+        // The reader gets the database written by the writer
+        database(CRATE_VERSION).write_and_close_file(lock).unwrap();
+        assert_eq!(
+            reader.recv_timeout(UNBLOCKED),
+            Ok(Ok(CRATE_VERSION.to_string()))
+        );
+    }
+
+    #[test]
+    fn leftover_lock_and_temporary_files_are_recovered() {
+        let dir = TempDir::new("leftovers");
+        let path = dir.database();
+        database("0.0.1").write_to_file(&path).unwrap();
+        let tmp_path = format!("{path}.tmp");
+        fs::write(format!("{path}.lock"), "left by a killed process").unwrap();
+        fs::write(&tmp_path, "half-written database").unwrap();
+
+        assert_eq!(Database::from_file(&path).unwrap().version, "0.0.1");
+
+        let (db, lock) = Database::from_file_with_write(&path).unwrap();
+        assert!(!Path::new(&tmp_path).exists());
+        db.write_and_close_file(lock).unwrap();
+        assert_eq!(Database::from_file(&path).unwrap().version, "0.0.1");
+        assert!(!Path::new(&tmp_path).exists());
+    }
+
+    #[test]
+    fn a_failed_write_keeps_the_database() {
+        let dir = TempDir::new("failed-write");
+        let path = dir.database();
+        database("0.0.1").write_to_file(&path).unwrap();
+        let before = fs::read(&path).unwrap();
+
+        // The temporary file cannot be created, so the database is never replaced
+        fs::create_dir(format!("{path}.tmp")).unwrap();
+        let result = database(CRATE_VERSION).write_to_file(&path);
+
+        assert!(matches!(result, Err(Error::FailedToOpenFile(_))));
+        assert_eq!(fs::read(&path).unwrap(), before);
+        assert_eq!(Database::from_file(&path).unwrap().version, "0.0.1");
+    }
+}
@@ -31,14 +31,26 @@ pub enum Error {
     #[error("Failed to write to file: {0}")]
     FailedToWriteFile(std::io::Error),
     #[cfg(feature = "database")]
-    #[error("Failed to set file cursor: {0}")]
-    FailedToSetFileCursor(std::io::Error),
+    #[error("Failed to rename file: {0}")]
+    FailedToRenameFile(std::io::Error),
+    #[cfg(feature = "database")]
+    #[error("Failed to lock file: {0}")]
+    FailedToLockFile(std::io::Error),
     #[cfg(feature = "database")]
     #[error("Failed to remove file: {0}")]
     FailedToRemoveFile(std::io::Error),
-    #[cfg(feature = "database")]
-    #[error("Timeout while trying to lock {0:?}")]
-    LockFileTimeout(std::path::PathBuf),
+    // Import
+    #[cfg(feature = "import")]
+    #[error("Invalid bolt database: {0}")]
+    InvalidBoltDatabase(String),
+    #[cfg(feature = "import")]
+    #[error("Invalid snapshot at key {key:?}: {reason}")]
+    InvalidGoSnapshot { key: Vec<u8>, reason: &'static str },
+    // Systemd
+    #[error("Failed to run systemctl: {0}")]
+    Systemctl(std::io::Error),
+    #[error("systemctl failed: {0}")]
+    SystemctlFailed(String),
     // Chrono
     #[error("Oldest date is too big to big calculated")]
     OldestDateOverflow,
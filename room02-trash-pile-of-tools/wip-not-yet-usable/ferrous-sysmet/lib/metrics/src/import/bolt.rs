@@ -0,0 +1,201 @@
+//! Minimal read-only reader of bbolt/BoltDB files, only what is needed to walk buckets.
+//! SOURCE: https://github.com/etcd-io/bbolt/blob/main/internal/common/page.go
+
+use std::{fs, path::Path};
+
+use log::{debug, trace, tracing};
+
+use crate::{errors::Error, Result};
+
+const MAGIC: u32 = 0xED0C_DAED;
+const PAGE_HEADER_SIZE: usize = 16;
+const ELEMENT_SIZE: usize = 16;
+const BUCKET_HEADER_SIZE: usize = 16;
+
+const BRANCH_PAGE_FLAG: u16 = 0x01;
+const LEAF_PAGE_FLAG: u16 = 0x02;
+const META_PAGE_FLAG: u16 = 0x04;
+const BUCKET_LEAF_FLAG: u32 = 0x01;
+
+// NOTE: Do not loop forever on a corrupted file
+const MAX_DEPTH: usize = 64;
+
+#[derive(Debug)]
+pub struct Entry<'a> {
+    pub key: &'a [u8],
+    pub value: &'a [u8],
+    pub is_bucket: bool,
+}
+
+pub struct BoltFile {
+    data: Vec<u8>,
+    page_size: usize,
+    root: u64,
+}
+
+impl BoltFile {
+    #[tracing::instrument(level = "debug")]
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = fs::read(path).map_err(Error::FailedToOpenFile)?;
+
+        // NOTE: Use the meta page with the highest transaction id, the other one may be half-written
+        let first = read_meta(&data, 0);
+        let page_size = first.map_or(4096, |meta| meta.page_size);
+        let second = read_meta(&data, page_size);
+        let meta = match (first, second) {
+            (Some(a), Some(b)) => Some(if a.txid >= b.txid { a } else { b }),
+            (a, b) => a.or(b),
+        }
+        .ok_or_else(|| Error::InvalidBoltDatabase("no valid meta page".to_string()))?;
+        debug!(
+            page_size = meta.page_size,
+            root = meta.root,
+            txid = meta.txid,
+            "Opened bolt database"
+        );
+
+        Ok(Self {
+            data,
+            page_size: meta.page_size,
+            root: meta.root,
+        })
+    }
+
+    /// Entries of the top-level bucket list (every entry is a bucket)
+    pub fn root_entries(&self) -> Result<Vec<Entry<'_>>> {
+        let mut entries = Vec::new();
+        self.walk(self.page(self.root)?, &mut entries, 0)?;
+        Ok(entries)
+    }
+
+    /// Entries of a bucket given its value in the parent bucket
+    pub fn bucket_entries<'a>(&'a self, bucket_value: &'a [u8]) -> Result<Vec<Entry<'a>>> {
+        let invalid = || Error::InvalidBoltDatabase("truncated bucket header".to_string());
+        let root = read_u64(bucket_value, 0).ok_or_else(invalid)?;
+
+        let mut entries = Vec::new();
+        if root == 0 {
+            // NOTE: Small buckets are stored inline, their page directly follows the header
+            let page = bucket_value.get(BUCKET_HEADER_SIZE..).ok_or_else(invalid)?;
+            self.walk(page, &mut entries, 0)?;
+        } else {
+            self.walk(self.page(root)?, &mut entries, 0)?;
+        }
+        Ok(entries)
+    }
+
+    fn page(&self, id: u64) -> Result<&[u8]> {
+        let out_of_bounds = || Error::InvalidBoltDatabase(format!("page {id} is out of bounds"));
+        let start = usize::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_mul(self.page_size))
+            .filter(|start| {
+                start
+                    .checked_add(PAGE_HEADER_SIZE)
+                    .is_some_and(|end| end <= self.data.len())
+            })
+            .ok_or_else(out_of_bounds)?;
+        // NOTE: Overflow pages directly follow their page so the element positions can go past the page size
+        self.data.get(start..).ok_or_else(out_of_bounds)
+    }
+
+    fn walk<'a>(
+        &'a self,
+        page: &'a [u8],
+        entries: &mut Vec<Entry<'a>>,
+        depth: usize,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH {
+            return Err(Error::InvalidBoltDatabase("tree is too deep".to_string()));
+        }
+        let invalid = || Error::InvalidBoltDatabase("truncated page".to_string());
+
+        let flags = read_u16(page, 8).ok_or_else(invalid)?;
+        let count = read_u16(page, 10).ok_or_else(invalid)? as usize;
+        trace!(flags, count, depth, "Walking page");
+
+        for idx in 0..count {
+            let element = PAGE_HEADER_SIZE + idx * ELEMENT_SIZE;
+            if flags & BRANCH_PAGE_FLAG != 0 {
+                let child = read_u64(page, element + 8).ok_or_else(invalid)?;
+                self.walk(self.page(child)?, entries, depth + 1)?;
+            } else if flags & LEAF_PAGE_FLAG != 0 {
+                let element_flags = read_u32(page, element).ok_or_else(invalid)?;
+                let pos = read_u32(page, element + 4).ok_or_else(invalid)? as usize;
+                let key_size = read_u32(page, element + 8).ok_or_else(invalid)? as usize;
+                let value_size = read_u32(page, element + 12).ok_or_else(invalid)? as usize;
+
+                // NOTE: The sizes come from the file, a corrupted one must not overflow the offsets
+                let key_start = element.checked_add(pos).ok_or_else(invalid)?;
+                let value_start = key_start.checked_add(key_size).ok_or_else(invalid)?;
+                let value_end = value_start.checked_add(value_size).ok_or_else(invalid)?;
+
+                entries.push(Entry {
+                    key: page.get(key_start..value_start).ok_or_else(invalid)?,
+                    value: page.get(value_start..value_end).ok_or_else(invalid)?,
+                    is_bucket: element_flags & BUCKET_LEAF_FLAG != 0,
+                });
+            } else {
+                return Err(Error::InvalidBoltDatabase(format!(
+                    "unexpected page flags {flags:#x}"
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Meta {
+    page_size: usize,
+    root: u64,
+    txid: u64,
+}
+
+fn read_meta(data: &[u8], offset: usize) -> Option<Meta> {
+    let page = data.get(offset..)?;
+    if read_u16(page, 8)? & META_PAGE_FLAG == 0 {
+        return None;
+    }
+
+    let meta = PAGE_HEADER_SIZE;
+    if read_u32(page, meta)? != MAGIC {
+        return None;
+    }
+    // NOTE: The checksum covers every field before it
+    let checksum_offset = meta + 56;
+    if fnv64a(page.get(meta..checksum_offset)?) != read_u64(page, checksum_offset)? {
+        return None;
+    }
+
+    Some(Meta {
+        page_size: read_u32(page, meta + 8)? as usize,
+        root: read_u64(page, meta + 16)?,
+        txid: read_u64(page, meta + 48)?,
+    })
+}
+
+fn fnv64a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset.checked_add(2)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset.checked_add(4)?)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset.checked_add(8)?)?.try_into().ok()?,
+    ))
+}
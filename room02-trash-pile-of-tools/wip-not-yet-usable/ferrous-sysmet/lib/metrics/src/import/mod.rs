@@ -0,0 +1,295 @@
+//! Import of databases written by the original Go sysmet (https://github.com/diamondburned/sysmet).
+//! They are bbolt files where each snapshot is stored under its big endian unix time in nanoseconds,
+//! values are the Go `Snapshot` struct of gopsutil stats encoded in MessagePack (maps keyed by the Go field names).
+//! NOTE: A value that cannot be read as a snapshot fails the import, nothing is imported partially.
+//! REVIEW: The layout follows the Go sources, it is not checked against a database written by Go sysmet yet
+
+use std::{collections::HashMap, path::Path, time::Duration};
+
+use chrono::{DateTime, TimeZone, Utc};
+use log::{debug, info, tracing};
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{errors::Error, psutil::LoadAvg, snapshot::SnapShot, Result};
+
+mod bolt;
+
+use bolt::{BoltFile, Entry};
+
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub imported: usize,
+}
+
+/// Read every snapshot of a Go sysmet database, `bucket` restricts the import to one top-level bucket
+#[tracing::instrument]
+pub fn read_go_database(
+    path: &Path,
+    bucket: Option<&str>,
+) -> Result<(Vec<SnapShot>, ImportReport)> {
+    let file = BoltFile::open(path)?;
+    let mut snapshots = Vec::new();
+    let mut report = ImportReport::default();
+
+    for entry in file.root_entries()? {
+        let name = String::from_utf8_lossy(entry.key);
+        if bucket.is_some_and(|bucket| bucket != name) {
+            debug!("Skipping bucket {name}");
+            continue;
+        }
+        info!("Importing bucket {name}");
+        read_bucket(&file, &entry, &mut snapshots, &mut report)?;
+    }
+    snapshots.sort_by_key(|snap| snap.time);
+
+    Ok((snapshots, report))
+}
+
+fn read_bucket(
+    file: &BoltFile,
+    bucket: &Entry<'_>,
+    snapshots: &mut Vec<SnapShot>,
+    report: &mut ImportReport,
+) -> Result<()> {
+    for entry in file.bucket_entries(bucket.value)? {
+        if entry.is_bucket {
+            // NOTE: Snapshots may be grouped in nested buckets
+            read_bucket(file, &entry, snapshots, report)?;
+            continue;
+        }
+
+        let invalid = |reason| Error::InvalidGoSnapshot {
+            key: entry.key.to_vec(),
+            reason,
+        };
+        let time = to_time(entry.key).ok_or_else(|| invalid("not a unix time in nanoseconds"))?;
+        let value = rmp_serde::from_slice::<GoSnapshot>(entry.value).map_err(|err| {
+            debug!("Failed to decode the snapshot: {err}");
+            invalid("not a MessagePack snapshot")
+        })?;
+        let snapshot = to_snapshot(time, value).ok_or_else(|| invalid("invalid stats"))?;
+        snapshots.push(snapshot);
+        report.imported += 1;
+    }
+
+    Ok(())
+}
+
+/// The oldest snapshot time accepted, a time before it is not in nanoseconds
+const OLDEST_SNAPSHOT: i64 = 1_000_000_000_000_000_000;
+
+/// `Snapshot` of Go sysmet
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GoSnapshot {
+    #[serde(rename = "CPUs", default)]
+    cpus: Vec<GoCpuTimes>,
+    #[serde(default)]
+    memory: GoVirtualMemory,
+    #[serde(default)]
+    swap: GoSwapMemory,
+    #[serde(default)]
+    network: Vec<GoNetIoCounters>,
+    #[serde(default)]
+    disks: Vec<GoDiskUsage>,
+    #[serde(default)]
+    temperatures: Vec<GoTemperature>,
+}
+
+/// `cpu.TimesStat` of gopsutil, in seconds
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoCpuTimes {
+    user: f64,
+    system: f64,
+    idle: f64,
+    nice: f64,
+    iowait: f64,
+    irq: f64,
+    softirq: f64,
+    steal: f64,
+    guest: f64,
+    guest_nice: f64,
+}
+
+/// `mem.VirtualMemoryStat` of gopsutil
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoVirtualMemory {
+    total: u64,
+    available: u64,
+    used: u64,
+    used_percent: f64,
+    free: u64,
+    active: u64,
+    inactive: u64,
+    buffers: u64,
+    cached: u64,
+    shared: u64,
+    slab: u64,
+}
+
+/// `mem.SwapMemoryStat` of gopsutil
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoSwapMemory {
+    total: u64,
+    used: u64,
+    free: u64,
+    used_percent: f64,
+    sin: u64,
+    sout: u64,
+}
+
+/// `net.IOCountersStat` of gopsutil
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoNetIoCounters {
+    bytes_sent: u64,
+    bytes_recv: u64,
+    packets_sent: u64,
+    packets_recv: u64,
+    errin: u64,
+    errout: u64,
+    dropin: u64,
+    dropout: u64,
+}
+
+/// `disk.UsageStat` of gopsutil, the size usage of a partition (Go sysmet has no I/O counters)
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoDiskUsage {
+    path: String,
+    used_percent: f64,
+}
+
+/// `host.TemperatureStat` of gopsutil, in celsius
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "PascalCase", default)]
+struct GoTemperature {
+    sensor_key: String,
+    temperature: f64,
+    high: f64,
+    critical: f64,
+}
+
+/// Serialized like serde does for `std::time::Duration`
+fn duration(secs: f64) -> serde_json::Value {
+    let duration = Duration::try_from_secs_f64(secs).unwrap_or_default();
+    json!({ "secs": duration.as_secs(), "nanos": duration.subsec_nanos() })
+}
+
+/// The key of a snapshot is its unix time in nanoseconds, big endian
+fn to_time(key: &[u8]) -> Option<DateTime<Utc>> {
+    let nanos = i64::from_be_bytes(key.try_into().ok()?);
+    (nanos >= OLDEST_SNAPSHOT).then(|| Utc.timestamp_nanos(nanos))
+}
+
+#[tracing::instrument(level = "trace", skip(value))]
+fn to_snapshot(time: DateTime<Utc>, value: GoSnapshot) -> Option<SnapShot> {
+    // NOTE: The psutil stats have private fields, they are built from their serde representation
+    let cpus = value
+        .cpus
+        .iter()
+        .map(|cpu| {
+            serde_json::from_value(json!({
+                "user": duration(cpu.user),
+                "system": duration(cpu.system),
+                "idle": duration(cpu.idle),
+                "nice": duration(cpu.nice),
+                "iowait": duration(cpu.iowait),
+                "irq": duration(cpu.irq),
+                "softirq": duration(cpu.softirq),
+                "steal": duration(cpu.steal),
+                "guest": duration(cpu.guest),
+                "guest_nice": duration(cpu.guest_nice),
+            }))
+            .ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let networks = value
+        .network
+        .iter()
+        .map(|net| {
+            serde_json::from_value(json!({
+                "bytes_sent": net.bytes_sent,
+                "bytes_recv": net.bytes_recv,
+                "packets_sent": net.packets_sent,
+                "packets_recv": net.packets_recv,
+                "err_in": net.errin,
+                "err_out": net.errout,
+                "drop_in": net.dropin,
+                "drop_out": net.dropout,
+            }))
+            .ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let disks_memory = value
+        .disks
+        .iter()
+        .map(|disk| (disk.path.clone(), disk.used_percent as f32))
+        .collect::<HashMap<_, _>>();
+
+    let temps = value
+        .temperatures
+        .iter()
+        .map(|temp| {
+            let celsius = |value: f64| (value > 0.0).then(|| json!({ "celsius": value }));
+            serde_json::from_value(json!({
+                "unit": temp.sensor_key,
+                "label": temp.sensor_key,
+                "current": { "celsius": temp.temperature },
+                "max": celsius(temp.high),
+                "crit": celsius(temp.critical),
+            }))
+            .ok()
+        })
+        .collect::<Option<Vec<_>>>()?;
+
+    let memory = &value.memory;
+    let swap = &value.swap;
+    let snapshot = SnapShot {
+        cpus,
+        memory: serde_json::from_value(json!({
+            "total": memory.total,
+            "available": memory.available,
+            "used": memory.used,
+            "free": memory.free,
+            "percent": memory.used_percent,
+            "active": memory.active,
+            "inactive": memory.inactive,
+            "buffers": memory.buffers,
+            "cached": memory.cached,
+            "shared": memory.shared,
+            "slab": memory.slab,
+        }))
+        .ok()?,
+        swap: serde_json::from_value(json!({
+            "total": swap.total,
+            "used": swap.used,
+            "free": swap.free,
+            "percent": swap.used_percent,
+            "swapped_in": swap.sin,
+            "swapped_out": swap.sout,
+        }))
+        .ok()?,
+        networks,
+        disks_io: HashMap::new(),
+        disks_memory,
+        temps,
+        // NOTE: Go sysmet does not record the load average
+        load_avgs: LoadAvg {
+            one: 0.0,
+            five: 0.0,
+            fifteen: 0.0,
+        },
+        time,
+        processes: Vec::new(),
+        units: Vec::new(),
+    };
+
+    Some(snapshot)
+}
@@ -1,20 +1,26 @@
 #[cfg(feature = "database")]
 pub mod database;
+#[cfg(feature = "import")]
+pub mod import;
 #[cfg(feature = "thresholds")]
 pub mod thresholds;
 
 pub mod errors;
+pub mod processes;
 pub mod psutil;
 pub mod snapshot;
+pub mod units;
 
 pub mod prelude {
     #[cfg(feature = "database")]
-    pub use super::database::Database;
+    pub use super::database::{Database, DatabaseLock};
     #[cfg(feature = "thresholds")]
     pub use super::thresholds::*;
 
     pub use super::errors::Error;
+    pub use super::processes::{top_processes, ProcessInfo};
     pub use super::snapshot::SnapShot;
+    pub use super::units::{units_status, UnitStatus};
 
     pub fn get_hostname() -> String {
         ::psutil::host::info().hostname().to_string()
@@ -0,0 +1,142 @@
+use std::{collections::HashMap, fs, thread::sleep, time::Duration};
+
+use ::psutil::process::{os::linux::ProcessExt, processes, Process};
+use log::{debug, trace, tracing};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::Result;
+
+// NOTE: Same interval as the global CPU usage, process CPU usage must be calculated on an interval
+const PROCESS_CPU_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: Option<String>,
+    pub user: String,
+    /// Percentage of one CPU, may be above 100% for multi-threaded processes
+    pub cpu_percent: f32,
+    /// Resident set size in bytes
+    pub rss: u64,
+}
+
+/// Processes using the most CPU and the most memory, `count` of each (merged when the same process is in both)
+#[tracing::instrument(level = "debug")]
+pub fn top_processes(count: usize) -> Result<Vec<ProcessInfo>> {
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    // NOTE: Processes may exit while we are reading them, just ignore them
+    let mut all = processes()?
+        .into_iter()
+        .filter_map(|process| process.ok())
+        .collect::<Vec<Process>>();
+    for process in all.iter_mut() {
+        process.cpu_percent().ok();
+    }
+    sleep(PROCESS_CPU_INTERVAL);
+
+    let users = read_users();
+    let infos = all
+        .iter_mut()
+        .filter_map(|process| {
+            Some(ProcessInfo {
+                pid: process.pid(),
+                cpu_percent: process.cpu_percent().ok()?,
+                rss: process.memory_info().ok()?.rss(),
+                name: process.name().ok()?,
+                cmdline: process.cmdline().ok().flatten(),
+                user: process
+                    .procfs_status()
+                    .ok()
+                    .map(|status| {
+                        let uid = status.uid[0];
+                        users.get(&uid).cloned().unwrap_or_else(|| uid.to_string())
+                    })
+                    .unwrap_or_default(),
+            })
+        })
+        .collect::<Vec<_>>();
+    trace!(processes_count = infos.len());
+
+    let result = merge_top(infos, count);
+    debug!(top_processes = ?result);
+
+    Ok(result)
+}
+
+/// Keep the `count` processes using the most CPU and the `count` using the most memory,
+/// sorted by CPU usage, a process in both is only kept once
+fn merge_top(mut infos: Vec<ProcessInfo>, count: usize) -> Vec<ProcessInfo> {
+    infos.sort_by_key(|p| std::cmp::Reverse(p.rss));
+    let top_rss = infos.iter().take(count).map(|p| p.pid).collect::<Vec<_>>();
+    infos.sort_by(|a, b| b.cpu_percent.total_cmp(&a.cpu_percent));
+
+    infos
+        .into_iter()
+        .enumerate()
+        .filter(|(idx, process)| *idx < count || top_rss.contains(&process.pid))
+        .map(|(_, process)| process)
+        .collect()
+}
+
+/// Map uids to user names from `/etc/passwd`
+fn read_users() -> HashMap<u32, String> {
+    fs::read_to_string("/etc/passwd")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse::<u32>().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, cpu_percent: f32, rss: u64) -> ProcessInfo {
+        ProcessInfo {
+            pid,
+            name: format!("process-{pid}"),
+            cmdline: None,
+            user: "root".to_string(),
+            cpu_percent,
+            rss,
+        }
+    }
+
+    fn pids(processes: &[ProcessInfo]) -> Vec<u32> {
+        processes.iter().map(|process| process.pid).collect()
+    }
+
+    #[test]
+    fn top_cpu_and_memory_consumers_are_merged() {
+        let infos = vec![
+            process(1, 1.0, 100),
+            process(2, 90.0, 10),
+            process(3, 50.0, 500),
+            process(4, 0.0, 900),
+            process(5, 20.0, 50),
+        ];
+
+        // Sorted by CPU usage, the memory consumers come after the CPU ones
+        assert_eq!(pids(&merge_top(infos.clone(), 2)), [2, 3, 4]);
+        assert_eq!(pids(&merge_top(infos.clone(), 1)), [2, 4]);
+        assert!(merge_top(infos.clone(), 0).is_empty());
+        assert_eq!(pids(&merge_top(infos, 10)), [2, 3, 5, 1, 4]);
+    }
+
+    #[test]
+    fn a_process_in_both_tops_is_kept_once() {
+        let infos = vec![process(1, 90.0, 900), process(2, 50.0, 10)];
+        assert_eq!(pids(&merge_top(infos, 1)), [1]);
+    }
+}
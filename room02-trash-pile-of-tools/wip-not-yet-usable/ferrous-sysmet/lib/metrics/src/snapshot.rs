@@ -8,11 +8,11 @@ use ::psutil::{
     sensors::{temperatures, TemperatureSensor},
 };
 use chrono::{DateTime, Utc};
-use log::{debug, tracing};
+use log::{debug, tracing, warn};
 #[cfg(feature = "serde")]
 use serde::{Deserialize, Serialize};
 
-use crate::Result;
+use crate::{processes::ProcessInfo, units::UnitStatus, Result};
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
@@ -26,6 +26,12 @@ pub struct SnapShot {
     pub temps: Vec<TemperatureSensor>,
     pub load_avgs: crate::psutil::LoadAvg,
     pub time: DateTime<Utc>,
+    /// Top consumers, only captured when asked for
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub processes: Vec<ProcessInfo>,
+    /// Selected systemd units, only captured when asked for
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub units: Vec<UnitStatus>,
 }
 
 impl SnapShot {
@@ -61,6 +67,8 @@ impl SnapShot {
                 .collect::<std::result::Result<Vec<TemperatureSensor>, _>>()?,
             load_avgs: crate::psutil::LoadAvg::new()?,
             time: Utc::now(),
+            processes: Vec::new(),
+            units: Vec::new(),
         };
 
         log::trace!("Snapshot taken with data\n{:#?}", result);
@@ -68,6 +76,24 @@ impl SnapShot {
         Ok(result)
     }
 
+    /// Capture the `top_processes` biggest consumers and the state of `units`
+    /// NOTE: Failing to capture them should not prevent the snapshot from being saved
+    #[tracing::instrument(skip(self))]
+    pub fn capture_details(&mut self, top_processes: usize, units: &[String]) {
+        match crate::processes::top_processes(top_processes) {
+            Ok(processes) => self.processes = processes,
+            Err(e) => warn!("Failed to capture top processes: {e}"),
+        }
+        match crate::units::units_status(units) {
+            Ok(units) => self.units = units,
+            Err(e) => warn!("Failed to capture systemd units: {e}"),
+        }
+    }
+
+    pub fn has_details(&self) -> bool {
+        !self.processes.is_empty() || !self.units.is_empty()
+    }
+
     #[tracing::instrument(skip(self))]
     pub fn get_cpu_count(&self) -> usize {
         self.cpus.len()
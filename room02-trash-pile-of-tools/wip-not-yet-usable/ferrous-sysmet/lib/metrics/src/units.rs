@@ -0,0 +1,168 @@
+use std::{collections::HashMap, process::Command};
+
+use log::{debug, tracing};
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::{errors::Error, Result};
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct UnitStatus {
+    pub name: String,
+    /// e.g. `loaded` or `not-found`
+    pub load_state: String,
+    /// e.g. `active`, `inactive` or `failed`
+    pub active_state: String,
+    /// e.g. `running` or `dead`
+    pub sub_state: String,
+}
+
+impl UnitStatus {
+    pub fn is_active(&self) -> bool {
+        self.active_state == "active"
+    }
+}
+
+/// State of the given systemd units as reported by `systemctl show`
+#[tracing::instrument(level = "debug")]
+pub fn units_status(units: &[String]) -> Result<Vec<UnitStatus>> {
+    if units.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let output = Command::new("systemctl")
+        .args([
+            "show",
+            "--property=Id,Names,LoadState,ActiveState,SubState",
+            "--",
+        ])
+        .args(units)
+        .output()
+        .map_err(Error::Systemctl)?;
+    if !output.status.success() {
+        return Err(Error::SystemctlFailed(
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+
+    let result = parse_units_status(&String::from_utf8_lossy(&output.stdout), units);
+    debug!(units_status = ?result);
+
+    Ok(result)
+}
+
+/// Find the block of properties of each unit in the output of `systemctl show`.
+/// NOTE: The blocks are separated by an empty line, they are matched by the unit names
+/// because their order is not documented, a unit without a block is reported as `not-found`
+fn parse_units_status(stdout: &str, units: &[String]) -> Vec<UnitStatus> {
+    let blocks = stdout
+        .split("\n\n")
+        .filter(|block| !block.trim().is_empty())
+        .map(|block| {
+            block
+                .lines()
+                .filter_map(|line| line.split_once('='))
+                .collect::<HashMap<_, _>>()
+        })
+        .collect::<Vec<_>>();
+
+    units
+        .iter()
+        .map(|unit| {
+            // NOTE: systemctl adds `.service` to the names without a suffix
+            let service = format!("{unit}.service");
+            let block = blocks.iter().find(|block| {
+                block
+                    .get("Id")
+                    .into_iter()
+                    .chain(block.get("Names"))
+                    .flat_map(|names| names.split_whitespace())
+                    .any(|name| name == unit || (!unit.contains('.') && name == service))
+            });
+
+            match block {
+                Some(block) => {
+                    let property =
+                        |key: &str| block.get(key).copied().unwrap_or_default().to_string();
+                    UnitStatus {
+                        name: block
+                            .get("Id")
+                            .map_or_else(|| unit.clone(), |id| id.to_string()),
+                        load_state: property("LoadState"),
+                        active_state: property("ActiveState"),
+                        sub_state: property("SubState"),
+                    }
+                }
+                None => UnitStatus {
+                    name: unit.clone(),
+                    load_state: "not-found".to_string(),
+                    active_state: "inactive".to_string(),
+                    sub_state: "dead".to_string(),
+                },
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHOW: &str = "\
+Id=nginx.service
+Names=nginx.service
+LoadState=loaded
+ActiveState=active
+SubState=running
+
+Id=dbus.service
+Names=dbus.service messagebus.service
+LoadState=loaded
+ActiveState=failed
+SubState=failed
+";
+
+    fn units(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn blocks_are_matched_by_the_unit_names() {
+        // NOTE: Not in the order of the blocks, with an alias and a name without suffix
+        let status = parse_units_status(
+            SHOW,
+            &units(&["messagebus.service", "nginx", "missing.timer"]),
+        );
+
+        let states = status
+            .iter()
+            .map(|unit| {
+                (
+                    unit.name.as_str(),
+                    unit.load_state.as_str(),
+                    unit.active_state.as_str(),
+                    unit.sub_state.as_str(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            states,
+            [
+                ("dbus.service", "loaded", "failed", "failed"),
+                ("nginx.service", "loaded", "active", "running"),
+                ("missing.timer", "not-found", "inactive", "dead"),
+            ]
+        );
+        assert!(!status[0].is_active());
+        assert!(status[1].is_active());
+    }
+
+    #[test]
+    fn an_empty_output_reports_every_unit_as_not_found() {
+        let status = parse_units_status("", &units(&["nginx"]));
+        assert_eq!(status.len(), 1);
+        assert_eq!(status[0].name, "nginx");
+        assert_eq!(status[0].load_state, "not-found");
+    }
+}
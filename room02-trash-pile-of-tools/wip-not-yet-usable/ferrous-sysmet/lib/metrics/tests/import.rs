@@ -0,0 +1,256 @@
+//! Round trip of a Go sysmet database through the import and the ferrous database.
+//! The bolt file is written by hand with the layout of bbolt, its values are encoded like the Go
+//! MessagePack encoder does for gopsutil structs (maps keyed by the Go field names).
+//! REVIEW: Replace it with a database written by Go sysmet once one is available
+#![cfg(feature = "import")]
+
+use std::{env, fs, path::PathBuf, process};
+
+use chrono::{TimeZone, Utc};
+use metrics::{errors::Error, import::read_go_database, prelude::*};
+use serde_json::json;
+
+const PAGE_SIZE: usize = 4096;
+const NANOS: i64 = 1_000_000_000;
+
+/// A file in the temporary folder, removed when dropped
+struct TempFile(PathBuf);
+
+impl TempFile {
+    fn new(name: &str) -> Self {
+        Self(env::temp_dir().join(format!("sysmet-{}-{name}", process::id())))
+    }
+
+    fn path(&self) -> &str {
+        self.0.to_str().unwrap()
+    }
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+        let _ = fs::remove_file(self.0.with_extension("lock"));
+    }
+}
+
+fn fnv64a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+fn page_header(id: u64, flags: u16, count: usize) -> Vec<u8> {
+    let mut page = Vec::new();
+    page.extend(id.to_le_bytes());
+    page.extend(flags.to_le_bytes());
+    page.extend((count as u16).to_le_bytes());
+    page.extend(0_u32.to_le_bytes());
+    page
+}
+
+/// A leaf page, `(is_bucket, key, value)` for each element
+fn leaf(id: u64, elements: &[(bool, Vec<u8>, Vec<u8>)]) -> Vec<u8> {
+    let mut page = page_header(id, 0x02, elements.len());
+    let mut data: Vec<u8> = Vec::new();
+    for (idx, (is_bucket, key, value)) in elements.iter().enumerate() {
+        // NOTE: The position is relative to the element
+        let pos = (elements.len() - idx) * 16 + data.len();
+        page.extend(u32::from(*is_bucket).to_le_bytes());
+        page.extend((pos as u32).to_le_bytes());
+        page.extend((key.len() as u32).to_le_bytes());
+        page.extend((value.len() as u32).to_le_bytes());
+        data.extend(key);
+        data.extend(value);
+    }
+    page.extend(data);
+    page
+}
+
+fn meta(id: u64, txid: u64, root: u64) -> Vec<u8> {
+    let mut meta = Vec::new();
+    meta.extend(0xED0C_DAED_u32.to_le_bytes());
+    meta.extend(2_u32.to_le_bytes());
+    meta.extend((PAGE_SIZE as u32).to_le_bytes());
+    meta.extend(0_u32.to_le_bytes());
+    // Root bucket then freelist page, page count and transaction id
+    meta.extend(root.to_le_bytes());
+    meta.extend(0_u64.to_le_bytes());
+    meta.extend(2_u64.to_le_bytes());
+    meta.extend(4_u64.to_le_bytes());
+    meta.extend(txid.to_le_bytes());
+    meta.extend(fnv64a(&meta).to_le_bytes());
+
+    let mut page = page_header(id, 0x04, 0);
+    page.extend(meta);
+    page
+}
+
+/// A bolt database with one inline bucket of snapshots keyed by their big endian unix time in nanoseconds
+fn go_database(bucket: &str, snapshots: &[(i64, Vec<u8>)]) -> Vec<u8> {
+    go_database_with_root(bucket, snapshots, 3)
+}
+
+fn go_database_with_root(bucket: &str, snapshots: &[(i64, Vec<u8>)], root: u64) -> Vec<u8> {
+    let elements = snapshots
+        .iter()
+        .map(|(time, value)| (false, time.to_be_bytes().to_vec(), value.clone()))
+        .collect::<Vec<_>>();
+    let mut bucket_value = vec![0; 16];
+    bucket_value.extend(leaf(0, &elements));
+    let root_page = leaf(3, &[(true, bucket.as_bytes().to_vec(), bucket_value)]);
+
+    let mut data = Vec::new();
+    for page in [
+        meta(0, 0, root),
+        meta(1, 1, root),
+        page_header(2, 0x10, 0),
+        root_page,
+    ] {
+        // NOTE: A page larger than the page size overflows on the next ones
+        data.extend(page);
+        data.resize(data.len().div_ceil(PAGE_SIZE) * PAGE_SIZE, 0);
+    }
+    data
+}
+
+fn go_snapshot(user: f64, total: u64) -> Vec<u8> {
+    let snapshot = json!({
+        "CPUs": [{ "CPU": "cpu0", "User": user, "System": 2.5, "Idle": 100.0 }],
+        "Memory": { "Total": total, "Available": total / 2, "Used": total / 2, "UsedPercent": 50.0 },
+        "Swap": { "Total": 1024, "Used": 0, "Free": 1024, "UsedPercent": 0.0 },
+        "Network": [{ "Name": "eth0", "BytesSent": 10, "BytesRecv": 20 }],
+        "Disks": [{ "Path": "/", "Fstype": "ext4", "Total": 4096, "UsedPercent": 42.5 }],
+        "Temperatures": [{ "SensorKey": "coretemp", "Temperature": 42.0, "High": 80.0 }],
+    });
+    rmp_serde::to_vec(&snapshot).unwrap()
+}
+
+#[test]
+fn go_snapshots_round_trip_through_the_database() {
+    let source = TempFile::new("go.db");
+    let target = TempFile::new("database");
+    fs::write(
+        &source.0,
+        go_database(
+            "sysmet-v1",
+            &[
+                (1_600_000_000 * NANOS, go_snapshot(10.0, 8192)),
+                (1_600_000_060 * NANOS, go_snapshot(20.0, 16384)),
+            ],
+        ),
+    )
+    .unwrap();
+
+    let (snapshots, report) = read_go_database(&source.0, None).unwrap();
+    assert_eq!(report.imported, 2);
+
+    let mut database = Database::default();
+    assert_eq!(database.merge_snapshots(snapshots.clone()), 2);
+    // Importing twice does not duplicate the snapshots
+    assert_eq!(database.merge_snapshots(snapshots), 0);
+    database.write_to_file(target.path()).unwrap();
+
+    let snapshots = Database::from_file(target.path()).unwrap().snapshots;
+    assert_eq!(snapshots.len(), 2);
+    let first = &snapshots[0];
+    assert_eq!(
+        first.time,
+        Utc.timestamp_opt(1_600_000_000, 0).unwrap()
+    );
+    assert_eq!(
+        snapshots[1].time,
+        Utc.timestamp_opt(1_600_000_060, 0).unwrap()
+    );
+    assert_eq!(first.cpus.len(), 1);
+    assert_eq!(first.cpus[0].user().as_secs(), 10);
+    assert_eq!(first.memory.total(), 8192);
+    assert_eq!(snapshots[1].memory.total(), 16384);
+    assert_eq!(first.swap.total(), 1024);
+    assert_eq!(first.networks[0].bytes_recv(), 20);
+    assert_eq!(first.disks_memory["/"], 42.5);
+    assert!(first.disks_io.is_empty());
+    assert_eq!(first.temps[0].unit(), "coretemp");
+    assert_eq!(first.temps[0].high().map(|t| t.celsius()), Some(80.0));
+}
+
+#[test]
+fn invalid_go_snapshots_fail_the_import() {
+    let source = TempFile::new("invalid-go.db");
+    fs::write(
+        &source.0,
+        go_database(
+            "sysmet-v1",
+            &[
+                (1_600_000_000 * NANOS, go_snapshot(10.0, 8192)),
+                (1_600_000_060 * NANOS, b"not a snapshot".to_vec()),
+            ],
+        ),
+    )
+    .unwrap();
+
+    let error = read_go_database(&source.0, None).unwrap_err();
+    assert!(
+        matches!(&error, Error::InvalidGoSnapshot { key, .. } if *key == (1_600_000_060 * NANOS).to_be_bytes()),
+        "{error}"
+    );
+}
+
+#[test]
+fn imports_one_bucket() {
+    let source = TempFile::new("bucket-go.db");
+    fs::write(
+        &source.0,
+        go_database(
+            "sysmet-v1",
+            &[(1_600_000_000 * NANOS, go_snapshot(10.0, 8192))],
+        ),
+    )
+    .unwrap();
+
+    let (snapshots, _) = read_go_database(&source.0, Some("other")).unwrap();
+    assert!(snapshots.is_empty());
+    let (snapshots, _) = read_go_database(&source.0, Some("sysmet-v1")).unwrap();
+    assert_eq!(snapshots.len(), 1);
+}
+
+#[test]
+fn keys_not_in_nanoseconds_fail_the_import() {
+    let source = TempFile::new("seconds-go.db");
+    fs::write(
+        &source.0,
+        go_database("sysmet-v1", &[(1_600_000_000, go_snapshot(10.0, 8192))]),
+    )
+    .unwrap();
+
+    let error = read_go_database(&source.0, None).unwrap_err();
+    assert!(
+        matches!(&error, Error::InvalidGoSnapshot { key, .. } if *key == 1_600_000_000_i64.to_be_bytes()),
+        "{error}"
+    );
+}
+
+#[test]
+fn corrupted_files_fail_the_import() {
+    let source = TempFile::new("corrupted-go.db");
+    let snapshots = [(1_600_000_000 * NANOS, go_snapshot(10.0, 8192))];
+
+    // The root page is past the end of the file
+    fs::write(
+        &source.0,
+        go_database_with_root("sysmet-v1", &snapshots, u64::MAX / PAGE_SIZE as u64),
+    )
+    .unwrap();
+    let error = read_go_database(&source.0, None).unwrap_err();
+    assert!(matches!(error, Error::InvalidBoltDatabase(_)), "{error}");
+
+    // The position and the size of the bucket element point past the end of the page
+    let element = 3 * PAGE_SIZE + 16;
+    for (offset, value) in [(4, u32::MAX), (8, u32::MAX), (12, u32::MAX)] {
+        let mut data = go_database("sysmet-v1", &snapshots);
+        data[element + offset..element + offset + 4].copy_from_slice(&value.to_le_bytes());
+        fs::write(&source.0, data).unwrap();
+
+        let error = read_go_database(&source.0, None).unwrap_err();
+        assert!(matches!(error, Error::InvalidBoltDatabase(_)), "{error}");
+    }
+}
@@ -0,0 +1,101 @@
+use gitlab::api::{
+    self,
+    projects::{
+        jobs::{Job as JobEndpoint, JobTrace, Jobs},
+        pipelines::{Pipeline as PipelineEndpoint, Pipelines},
+    },
+    Query,
+};
+use log_utils::{debug, tracing};
+use serde::Deserialize;
+
+use crate::{client::GitlabClient, Result};
+
+/// How many of the latest pipelines and jobs are listed
+const HISTORY_LIMIT: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct Pipeline {
+    pub id: u64,
+    pub status: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Job {
+    pub id: u64,
+    pub status: String,
+}
+
+/// Get the latest pipelines of a project
+#[tracing::instrument]
+pub fn get_pipelines<C: GitlabClient>(client: &C, project_id: u32) -> Result<Vec<Pipeline>> {
+    let result = api::paged(
+        Pipelines::builder().project(project_id as u64).build()?,
+        api::Pagination::Limit(HISTORY_LIMIT),
+    )
+    .query(client)?;
+
+    debug!(pipelines=?result);
+
+    Ok(result)
+}
+
+#[tracing::instrument]
+pub fn get_pipeline<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    pipeline_id: u64,
+) -> Result<Pipeline> {
+    let result = PipelineEndpoint::builder()
+        .project(project_id as u64)
+        .pipeline(pipeline_id)
+        .build()?
+        .query(client)?;
+
+    debug!(pipeline=?result);
+
+    Ok(result)
+}
+
+/// Get the latest jobs of a project
+#[tracing::instrument]
+pub fn get_jobs<C: GitlabClient>(client: &C, project_id: u32) -> Result<Vec<Job>> {
+    let result = api::paged(
+        Jobs::builder().project(project_id as u64).build()?,
+        api::Pagination::Limit(HISTORY_LIMIT),
+    )
+    .query(client)?;
+
+    debug!(jobs=?result);
+
+    Ok(result)
+}
+
+#[tracing::instrument]
+pub fn get_job<C: GitlabClient>(client: &C, project_id: u32, job_id: u64) -> Result<Job> {
+    let result = JobEndpoint::builder()
+        .project(project_id as u64)
+        .job(job_id)
+        .build()?
+        .query(client)?;
+
+    debug!(job=?result);
+
+    Ok(result)
+}
+
+/// Get the log of a job
+#[tracing::instrument]
+pub fn get_job_trace<C: GitlabClient>(client: &C, project_id: u32, job_id: u64) -> Result<Vec<u8>> {
+    let result = api::raw(
+        JobTrace::builder()
+            .project(project_id as u64)
+            .job(job_id)
+            .build()?,
+    )
+    .query(client)?;
+
+    debug!("Got {} bytes of logs", result.len());
+
+    Ok(result)
+}
@@ -0,0 +1,8 @@
+use std::fmt::Debug;
+
+use gitlab::api::Client;
+
+/// Any client able to query the Gitlab API (E.g. `gitlab::Gitlab`, or a scripted mock in the tests)
+pub trait GitlabClient: Client + Debug + Send + Sync + 'static {}
+
+impl<C> GitlabClient for C where C: Client + Debug + Send + Sync + 'static {}
@@ -0,0 +1,101 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    path::PathBuf,
+};
+
+use log_utils::{debug, tracing};
+
+use crate::project_env::Variable;
+
+/// Placeholder of a variable missing in an environment
+const UNSET: &str = "(unset)";
+
+/// Keep each variable on a single line so the views can be grepped
+fn single_line(value: &str) -> String {
+    value.replace('\n', "\\n")
+}
+
+/// The name of a file for an environment scope
+/// NOTE: A scope can contain slashes (E.g. `review/*`), escaped like the branches
+fn file_name(scope: &str) -> String {
+    scope.replace('/', "%2F")
+}
+
+/// Group the variables by environment scope then by key
+fn by_environment(variables: &[Variable]) -> BTreeMap<&str, BTreeMap<&str, &str>> {
+    variables.iter().fold(BTreeMap::new(), |mut acc, var| {
+        acc.entry(var.environment_scope.as_str())
+            .or_insert_with(BTreeMap::new)
+            .insert(var.key.as_str(), var.value.as_str());
+        acc
+    })
+}
+
+/// Generate a diff of each pair of environments of a project as a list of (`<envA>..<envB>.diff`, diff)
+#[tracing::instrument(level = "debug", skip(variables))]
+pub fn compare_envs(variables: &[Variable]) -> Vec<(String, String)> {
+    let envs = by_environment(variables);
+
+    let mut results = Vec::new();
+    for (env_a, vars_a) in envs.iter() {
+        for (env_b, vars_b) in envs.iter().filter(|(env_b, _)| *env_b != env_a) {
+            let keys = vars_a.keys().chain(vars_b.keys()).collect::<BTreeSet<_>>();
+            let mut diff = vec![format!("--- {env_a}"), format!("+++ {env_b}")];
+            for key in keys {
+                match (vars_a.get(key), vars_b.get(key)) {
+                    (Some(a), Some(b)) if a == b => diff.push(format!(" {key}={}", single_line(a))),
+                    (a, b) => {
+                        if let Some(a) = a {
+                            diff.push(format!("-{key}={}", single_line(a)));
+                        }
+                        if let Some(b) = b {
+                            diff.push(format!("+{key}={}", single_line(b)));
+                        }
+                    }
+                }
+            }
+            results.push((
+                format!("{}..{}.diff", file_name(env_a), file_name(env_b)),
+                diff.join("\n") + "\n",
+            ));
+        }
+    }
+
+    debug!("Generated {} diffs", results.len());
+    results
+}
+
+/// Generate a file per variable key listing its value in each environment of each project
+/// as a list of (`<KEY>`, lines of `<project>\t<env>\t<value>`)
+#[tracing::instrument(level = "debug", skip(projects))]
+pub fn matrix(projects: &[(PathBuf, Vec<Variable>)]) -> Vec<(String, String)> {
+    let projects = projects
+        .iter()
+        .map(|(path, variables)| (path, by_environment(variables)))
+        .collect::<Vec<_>>();
+    let keys = projects
+        .iter()
+        .flat_map(|(_, envs)| envs.values().flat_map(|vars| vars.keys()))
+        .collect::<BTreeSet<_>>();
+
+    let results = keys
+        .into_iter()
+        .map(|key| {
+            let lines = projects
+                .iter()
+                .flat_map(|(path, envs)| {
+                    envs.iter().map(move |(env, vars)| {
+                        let value = vars
+                            .get(key)
+                            .map_or_else(|| UNSET.to_string(), |v| single_line(v));
+                        format!("{}\t{env}\t{value}\n", path.display())
+                    })
+                })
+                .collect::<String>();
+            (key.to_string(), lines)
+        })
+        .collect::<Vec<_>>();
+
+    debug!("Generated {} matrix files", results.len());
+    results
+}
@@ -5,3 +5,28 @@ pub use libc::*;
 
 /// The time to live of the filesystem responses (E.g. `getattr`)
 pub const TTL: Duration = Duration::from_millis(1000);
+/// The longest time the pipelines and jobs are kept before being fetched again
+pub const CI_REFRESH_TTL: Duration = Duration::from_secs(10);
+
+/// The root folder of the views with a diff of each pair of environments of each project
+pub const COMPARE_FOLDER: &str = ".compare";
+/// The root folder of the views with each variable across the projects of each group
+pub const MATRIX_FOLDER: &str = ".matrix";
+
+/// The file with the instance variables at the root of the filesystem
+pub const INSTANCE_FILE: &str = ".instance";
+/// The suffix of the files with the variables of a group for an environment scope (E.g. `production.group`)
+pub const GROUP_FILE_SUFFIX: &str = ".group";
+/// The suffix of the files with the variables applying to an environment of a project (E.g. `production.effective`)
+pub const EFFECTIVE_FILE_SUFFIX: &str = ".effective";
+/// The folder of a project with the deleted environments, they are deleted from Gitlab once removed from it
+/// NOTE: It is only kept in memory, the trashed environments are back in their project after a remount
+pub const TRASH_FOLDER: &str = ".trash";
+/// The namespace of the extended attributes of the variables (E.g. `user.API_KEY.masked`)
+pub const XATTR_PREFIX: &str = "user.";
+/// The folders of a project with its repository at each branch, its latest pipelines and jobs
+pub const REPOSITORY_FOLDER: &str = "repository";
+pub const PIPELINES_FOLDER: &str = "pipelines";
+pub const JOBS_FOLDER: &str = "jobs";
+/// Prefixes the files of the environments named after a folder of their project (E.g. `%jobs`)
+pub const ESCAPE_PREFIX: char = '%';
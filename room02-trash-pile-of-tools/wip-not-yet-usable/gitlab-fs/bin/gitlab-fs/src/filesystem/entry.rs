@@ -3,6 +3,7 @@ use std::path::PathBuf;
 use std::time::SystemTime;
 
 use super::types::*;
+use crate::project_env::{Variable, VariablesOwner};
 
 /// A folder (group of Projects) in Gitlab
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -11,6 +12,8 @@ pub struct FSFolder {
     pub path: PathBuf,
     /// All the filesystem inodes of the childs of the folder
     pub inos: Vec<Ino>,
+    /// The group variables files have been added to the folder
+    pub loaded: bool,
 }
 impl From<FSFolder> for FSEntry {
     fn from(folder: FSFolder) -> Self {
@@ -45,6 +48,29 @@ pub struct FSFile {
     pub last_update: SystemTime,
     /// The last time the file was accessed
     pub last_access: SystemTime,
+    /// The content was edited since it was last written to Gitlab
+    pub dirty: bool,
+    /// The file is generated (E.g. in a view) and cannot be edited
+    pub read_only: bool,
+    /// The variables the file is generated from, used for their attributes
+    pub variables: Vec<Variable>,
+    /// Where the variables are defined, `None` if their attributes cannot be edited
+    pub owner: Option<VariablesOwner>,
+    /// The content to fetch from Gitlab on first access, `None` once it is loaded
+    pub lazy: Option<LazyContent>,
+}
+
+/// The content of a file fetched on first access because it can be large
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LazyContent {
+    /// A file of a project repository at a ref
+    Blob {
+        external_id: u32,
+        ref_: String,
+        path: String,
+    },
+    /// The log of a job
+    JobTrace { external_id: u32, job_id: u64 },
 }
 impl From<FSFile> for FSEntry {
     fn from(file: FSFile) -> Self {
@@ -52,12 +78,69 @@ impl From<FSFile> for FSEntry {
     }
 }
 
+/// What a view is generated from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ViewKind {
+    /// Compare each pair of environments of a project
+    Compare {
+        external_id: u32,
+    },
+    /// Compare each variable across all the projects (with their path) of a group
+    Matrix {
+        projects: Vec<(u32, PathBuf)>,
+    },
+    /// The branches of a project repository
+    Branches {
+        external_id: u32,
+    },
+    /// A folder of a project repository at a ref (the root folder has an empty path)
+    Tree {
+        external_id: u32,
+        ref_: String,
+        path: String,
+    },
+    /// The latest pipelines of a project
+    Pipelines {
+        external_id: u32,
+    },
+    Pipeline {
+        external_id: u32,
+        pipeline_id: u64,
+    },
+    /// The latest jobs of a project
+    Jobs {
+        external_id: u32,
+    },
+    Job {
+        external_id: u32,
+        job_id: u64,
+    },
+}
+
+/// A read-only folder generated on demand (E.g. `./.compare/folder1/project` or `./folder1/project/jobs`)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FSView {
+    /// The path of the view in the filesystem
+    pub path: PathBuf,
+    /// All the filesystem inodes of the childs of the view (sub views and generated files)
+    pub inos: Vec<Ino>,
+    pub kind: ViewKind,
+    /// The generated files have been added to the view
+    pub loaded: bool,
+}
+impl From<FSView> for FSEntry {
+    fn from(view: FSView) -> Self {
+        Self::View(view)
+    }
+}
+
 /// Any possible Gitlab entry (Folder, Project, File)
 #[derive(Debug, Clone, Eq)]
 pub enum FSEntry {
     Folder(FSFolder),
     Project(FSProject),
     File(FSFile),
+    View(FSView),
     /// Represent the root folder that contains each of the folder (the mountpoint)
     Root {
         inos: Vec<Ino>,
@@ -69,7 +152,8 @@ impl FSEntry {
         match self {
             entry @ (FSEntry::File(FSFile { path, .. })
             | FSEntry::Folder(FSFolder { path, .. })
-            | FSEntry::Project(FSProject { path, .. })) => Some((path, entry)),
+            | FSEntry::Project(FSProject { path, .. })
+            | FSEntry::View(FSView { path, .. })) => Some((path, entry)),
             _ => None,
         }
     }
@@ -77,10 +161,29 @@ impl FSEntry {
         match self {
             entry @ (FSEntry::Folder(FSFolder { inos, .. })
             | FSEntry::Project(FSProject { inos, .. })
+            | FSEntry::View(FSView { inos, .. })
             | FSEntry::Root { inos }) => Some((inos, entry)),
             _ => None,
         }
     }
+    pub fn get_mut_path(&mut self) -> Option<&mut PathBuf> {
+        match self {
+            FSEntry::File(FSFile { path, .. })
+            | FSEntry::Folder(FSFolder { path, .. })
+            | FSEntry::Project(FSProject { path, .. })
+            | FSEntry::View(FSView { path, .. }) => Some(path),
+            _ => None,
+        }
+    }
+    pub fn get_mut_children_inos(&mut self) -> Option<&mut Vec<Ino>> {
+        match self {
+            FSEntry::Folder(FSFolder { inos, .. })
+            | FSEntry::Project(FSProject { inos, .. })
+            | FSEntry::View(FSView { inos, .. })
+            | FSEntry::Root { inos } => Some(inos),
+            _ => None,
+        }
+    }
 }
 
 /// Allow to compare entries by their path if possible
@@ -91,16 +194,22 @@ impl PartialEq for FSEntry {
             (
                 Self::Folder(FSFolder { path: l_path, .. })
                 | Self::Project(FSProject { path: l_path, .. })
-                | Self::File(FSFile { path: l_path, .. }),
+                | Self::File(FSFile { path: l_path, .. })
+                | Self::View(FSView { path: l_path, .. }),
                 Self::Folder(FSFolder { path: r_path, .. })
                 | Self::Project(FSProject { path: r_path, .. })
-                | Self::File(FSFile { path: r_path, .. }),
+                | Self::File(FSFile { path: r_path, .. })
+                | Self::View(FSView { path: r_path, .. }),
             ) => l_path == r_path,
             // If one of the object is a root then it is not equal to any other object
-            (Self::Root { .. }, Self::File { .. } | Self::Folder { .. } | Self::Project { .. })
-            | (Self::File { .. } | Self::Folder { .. } | Self::Project { .. }, Self::Root { .. }) => {
-                false
-            }
+            (
+                Self::Root { .. },
+                Self::File { .. } | Self::Folder { .. } | Self::Project { .. } | Self::View { .. },
+            )
+            | (
+                Self::File { .. } | Self::Folder { .. } | Self::Project { .. } | Self::View { .. },
+                Self::Root { .. },
+            ) => false,
             // If both objects are root then they are equal (it should not happen, the root is unique)
             (Self::Root { .. }, Self::Root { .. }) => {
                 unreachable!("The root must be unique in the FileSystem")
@@ -118,22 +227,26 @@ impl PartialOrd for FSEntry {
             (
                 Self::Folder(FSFolder { path: l_path, .. })
                 | Self::Project(FSProject { path: l_path, .. })
-                | Self::File(FSFile { path: l_path, .. }),
+                | Self::File(FSFile { path: l_path, .. })
+                | Self::View(FSView { path: l_path, .. }),
                 Self::Folder(FSFolder { path: r_path, .. })
                 | Self::Project(FSProject { path: r_path, .. })
-                | Self::File(FSFile { path: r_path, .. }),
+                | Self::File(FSFile { path: r_path, .. })
+                | Self::View(FSView { path: r_path, .. }),
             ) => l_path
                 .components()
                 .count()
                 .partial_cmp(&r_path.components().count()),
             // If the first object is a root then it is always less than any other object (except another root)
-            (Self::Root { .. }, Self::File { .. } | Self::Folder { .. } | Self::Project { .. }) => {
-                Some(Ordering::Less)
-            }
+            (
+                Self::Root { .. },
+                Self::File { .. } | Self::Folder { .. } | Self::Project { .. } | Self::View { .. },
+            ) => Some(Ordering::Less),
             // If the second object is a root then it is always greater than any other object (except another root)
-            (Self::File { .. } | Self::Folder { .. } | Self::Project { .. }, Self::Root { .. }) => {
-                Some(Ordering::Greater)
-            }
+            (
+                Self::File { .. } | Self::Folder { .. } | Self::Project { .. } | Self::View { .. },
+                Self::Root { .. },
+            ) => Some(Ordering::Greater),
             // If both objects are root then they are equal (it should not happen, the root is unique)
             (Self::Root { .. }, Self::Root { .. }) => {
                 unreachable!("The root must be unique in the FileSystem")
@@ -156,6 +269,8 @@ pub trait FSEntries {
     fn get_mut_projects(&mut self) -> Vec<&mut FSProject>;
     /// Get a mutable reference to the files in a `FSEntry::Project` else return an empty vector
     fn get_mut_files(&mut self) -> Vec<&mut FSFile>;
+    /// Get a mutable reference to the views in a `FSEntry::Folder` or `FSEntry::View` else return an empty vector
+    fn get_mut_views(&mut self) -> Vec<&mut FSView>;
     /// Get the reversed index of an entry (TLDR; the last entry has index 0)
     fn get_reversed_idx(&self, idx: usize) -> usize;
 }
@@ -185,6 +300,14 @@ impl FSEntries for Vec<FSEntry> {
             })
             .collect()
     }
+    fn get_mut_views(&mut self) -> Vec<&mut FSView> {
+        self.iter_mut()
+            .filter_map(|e| match e {
+                FSEntry::View(v) => Some(v),
+                _ => None,
+            })
+            .collect()
+    }
     fn get_reversed_idx(&self, idx: usize) -> usize {
         self.len() - idx - 1
     }
@@ -3,8 +3,9 @@ use std::path::PathBuf;
 use log_utils::{info, trace, tracing};
 
 use super::{
-    entry::{FSEntries, FSFolder, FSProject},
-    FSEntry, GitlabFS,
+    entry::{FSEntries, FSFolder, FSProject, FSView},
+    inodes::idx_to_ino,
+    FSEntry,
 };
 
 /// Generate the folders from flat project list of Gitlab
@@ -32,6 +33,7 @@ pub fn generate_folders_from_projects(projects: &[FSProject]) -> Vec<FSFolder> {
         .map(|p| FSFolder {
             path: PathBuf::from(p),
             inos: Vec::new(),
+            loaded: false,
         })
         .collect::<Vec<_>>()
 }
@@ -46,13 +48,14 @@ pub fn generate_ino_from_fs_entries(fs_entries: &[FSEntry]) -> Vec<FSEntry> {
 
     for (idx, fs_entry) in fs_entries.iter().enumerate() {
         // We convert the index to an inode
-        let fs_entry_ino = GitlabFS::idx_to_ino(fs_entries.get_reversed_idx(idx));
+        let fs_entry_ino = idx_to_ino(fs_entries.get_reversed_idx(idx));
 
-        // If the entry is a project or a folder then we add the inode to the root or the parent folder
-        if let FSEntry::Project(FSProject { path, .. }) | FSEntry::Folder(FSFolder { path, .. }) =
-            fs_entry
+        // If the entry is a project, a folder or a view then we add the inode to the root or the parent folder
+        if let FSEntry::Project(FSProject { path, .. })
+        | FSEntry::Folder(FSFolder { path, .. })
+        | FSEntry::View(FSView { path, .. }) = fs_entry
         {
-            trace!(path =? path, "Computing childrens of projects and folders");
+            trace!(path =? path, "Computing childrens of projects, folders and views");
 
             // If the path has only one component (E.g "/") then it is the root
             if path.components().count() == 1 {
@@ -70,12 +73,20 @@ pub fn generate_ino_from_fs_entries(fs_entries: &[FSEntry]) -> Vec<FSEntry> {
                     None => unreachable!("No root entry found"),
                 }
             } else {
-                // Else we add the inode to the parent folder
-                for folder in results.get_mut_folders() {
-                    if folder.path.eq(path.parent().unwrap()) {
-                        folder.inos.push(fs_entry_ino);
-                        break;
-                    }
+                // Else we add the inode to the parent folder (or the parent view for nested groups)
+                let parent = path.parent().unwrap();
+                if let Some(folder) = results
+                    .get_mut_folders()
+                    .into_iter()
+                    .find(|f| f.path == parent)
+                {
+                    folder.inos.push(fs_entry_ino);
+                } else if let Some(view) = results
+                    .get_mut_views()
+                    .into_iter()
+                    .find(|v| v.path == parent)
+                {
+                    view.inos.push(fs_entry_ino);
                 }
             }
         }
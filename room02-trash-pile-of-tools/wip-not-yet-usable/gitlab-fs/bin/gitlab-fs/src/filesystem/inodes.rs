@@ -0,0 +1,298 @@
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use log_utils::{debug, trace};
+
+use super::{entry::*, types::*};
+
+/// The inode of the root folder (the mountpoint)
+pub const ROOT_INO: Ino = 1;
+
+/// Convert an index in the filesystem to an inode
+pub fn idx_to_ino(idx: usize) -> Ino {
+    (idx + 1) as Ino
+}
+
+/// Convert an inode to an index in the filesystem
+pub fn ino_to_idx(ino: Ino) -> usize {
+    (ino - 1) as usize
+}
+
+/// An entry of the filesystem with its position in the tree
+#[derive(Debug, Clone)]
+struct Inode {
+    entry: FSEntry,
+    /// The inode of the parent, `None` for the root
+    parent: Option<Ino>,
+    /// When the childs must be fetched again from Gitlab, `None` if they were never loaded or never expire
+    expires_at: Option<SystemTime>,
+}
+
+/// The table of all the filesystem entries indexed by inode
+/// NOTE: A removed entry leaves an empty slot so the inodes known by the kernel never point to another entry
+#[derive(Debug, Default)]
+pub struct InodeTable {
+    inodes: Vec<Option<Inode>>,
+}
+
+impl InodeTable {
+    /// Create the table from entries whose childs inodes are their index in the list (E.g. from `generate_ino_from_fs_entries`)
+    pub fn from_entries(entries: Vec<FSEntry>) -> Self {
+        let mut parents = vec![None; entries.len()];
+        for (idx, entry) in entries.iter().enumerate() {
+            if let Some((inos, _)) = entry.get_children_inos() {
+                for ino in inos {
+                    if let Some(parent) = parents.get_mut(ino_to_idx(*ino)) {
+                        *parent = Some(idx_to_ino(idx));
+                    }
+                }
+            }
+        }
+
+        Self {
+            inodes: entries
+                .into_iter()
+                .zip(parents)
+                .map(|(entry, parent)| {
+                    Some(Inode {
+                        entry,
+                        parent,
+                        expires_at: None,
+                    })
+                })
+                .collect(),
+        }
+    }
+
+    fn inode(&self, ino: Ino) -> Option<&Inode> {
+        self.inodes.get(ino_to_idx(ino))?.as_ref()
+    }
+
+    fn inode_mut(&mut self, ino: Ino) -> Option<&mut Inode> {
+        self.inodes.get_mut(ino_to_idx(ino))?.as_mut()
+    }
+
+    pub fn get(&self, ino: Ino) -> Option<&FSEntry> {
+        self.inode(ino).map(|inode| &inode.entry)
+    }
+
+    pub fn get_mut(&mut self, ino: Ino) -> Option<&mut FSEntry> {
+        self.inode_mut(ino).map(|inode| &mut inode.entry)
+    }
+
+    pub fn parent(&self, ino: Ino) -> Option<Ino> {
+        self.inode(ino)?.parent
+    }
+
+    /// Iterate on all the entries with their inode
+    pub fn iter(&self) -> impl Iterator<Item = (Ino, &FSEntry)> {
+        self.inodes
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, inode)| inode.as_ref().map(|inode| (idx_to_ino(idx), &inode.entry)))
+    }
+
+    /// The inodes of the childs of a folder, a project, a view or the root
+    pub fn children(&self, ino: Ino) -> Vec<Ino> {
+        self.get(ino)
+            .and_then(|entry| entry.get_children_inos())
+            .map(|(inos, _)| inos.clone())
+            .unwrap_or_default()
+    }
+
+    /// Find a child by its file name
+    pub fn find_child(&self, parent: Ino, name: &OsStr) -> Option<Ino> {
+        self.children(parent).into_iter().find(|ino| {
+            self.get(*ino)
+                .and_then(|entry| entry.get_path())
+                .and_then(|(path, _)| path.file_name())
+                .is_some_and(|file_name| file_name == name)
+        })
+    }
+
+    /// Add an entry under a parent and return its inode
+    pub fn insert(&mut self, parent: Ino, entry: FSEntry) -> Ino {
+        let ino = idx_to_ino(self.inodes.len());
+        trace!(ino, parent, path =? entry.get_path().map(|(p, _)| p), "Inserting entry");
+        self.inodes.push(Some(Inode {
+            entry,
+            parent: Some(parent),
+            expires_at: None,
+        }));
+        if let Some(inos) = self
+            .get_mut(parent)
+            .and_then(FSEntry::get_mut_children_inos)
+        {
+            inos.push(ino);
+        }
+        ino
+    }
+
+    /// Remove an entry and all its childs
+    pub fn remove(&mut self, ino: Ino) -> Option<FSEntry> {
+        for child in self.children(ino) {
+            self.remove(child);
+        }
+
+        let inode = self.inodes.get_mut(ino_to_idx(ino))?.take()?;
+        trace!(ino, path =? inode.entry.get_path().map(|(p, _)| p), "Removed entry");
+        if let Some(inos) = inode
+            .parent
+            .and_then(|parent| self.get_mut(parent))
+            .and_then(FSEntry::get_mut_children_inos)
+        {
+            inos.retain(|child| *child != ino);
+        }
+        Some(inode.entry)
+    }
+
+    /// Move an entry (and its childs) under a new parent with a new name
+    pub fn rename(&mut self, ino: Ino, new_parent: Ino, name: &OsStr) -> FSResult<()> {
+        let parent_path = match self.get(new_parent).ok_or(libc::ENOENT)? {
+            FSEntry::Root { .. } => PathBuf::new(),
+            entry => entry.get_path().ok_or(libc::ENOTDIR)?.0.clone(),
+        };
+        let old_path = self
+            .get(ino)
+            .and_then(|entry| entry.get_path())
+            .map(|(path, _)| path.clone())
+            .ok_or(libc::ENOENT)?;
+        let new_path = parent_path.join(name);
+        debug!(?old_path, ?new_path, "Renaming entry");
+
+        // Detach the entry from its old parent
+        let old_parent = self.parent(ino);
+        if let Some(inos) = old_parent
+            .and_then(|parent| self.get_mut(parent))
+            .and_then(FSEntry::get_mut_children_inos)
+        {
+            inos.retain(|child| *child != ino);
+        }
+        if let Some(inos) = self
+            .get_mut(new_parent)
+            .and_then(FSEntry::get_mut_children_inos)
+        {
+            inos.push(ino);
+        }
+        if let Some(inode) = self.inode_mut(ino) {
+            inode.parent = Some(new_parent);
+        }
+
+        self.replace_path_prefix(ino, &old_path, &new_path);
+        Ok(())
+    }
+
+    fn replace_path_prefix(&mut self, ino: Ino, old_prefix: &Path, new_prefix: &Path) {
+        if let Some(path) = self.get_mut(ino).and_then(FSEntry::get_mut_path) {
+            if let Ok(rest) = path.strip_prefix(old_prefix) {
+                *path = new_prefix.join(rest);
+            }
+        }
+        for child in self.children(ino) {
+            self.replace_path_prefix(child, old_prefix, new_prefix);
+        }
+    }
+
+    /// Replace the generated childs (files and views) of an entry, keeping the inodes of the ones with the same path.
+    /// The edited files not yet written to Gitlab and the static childs (folders and projects) are kept as is.
+    pub fn replace_children(&mut self, parent: Ino, entries: Vec<FSEntry>) {
+        let mut outdated = self.children(parent);
+        outdated.retain(|ino| {
+            matches!(
+                self.get(*ino),
+                Some(FSEntry::File(FSFile { dirty: false, .. }) | FSEntry::View(_))
+            )
+        });
+
+        for entry in entries {
+            let existing = entry.get_path().and_then(|(path, _)| {
+                self.children(parent).into_iter().find(|ino| {
+                    self.get(*ino)
+                        .and_then(|e| e.get_path())
+                        .is_some_and(|(p, _)| p == path)
+                })
+            });
+
+            match (existing.and_then(|ino| self.get_mut(ino)), entry) {
+                (Some(FSEntry::File(file)), FSEntry::File(new_file)) => {
+                    if !file.dirty {
+                        if file.content != new_file.content || file.lazy != new_file.lazy {
+                            file.last_update = new_file.last_update;
+                        }
+                        file.content = new_file.content;
+                        file.variables = new_file.variables;
+                        file.owner = new_file.owner;
+                        file.read_only = new_file.read_only;
+                        file.lazy = new_file.lazy;
+                    }
+                }
+                // NOTE: A sub view is refreshed on its own when it expires
+                (Some(FSEntry::View(_)), FSEntry::View(_)) => (),
+                (Some(_), entry) => {
+                    // The entry changed of kind, it gets a new inode
+                    if let Some(ino) = existing {
+                        self.remove(ino);
+                    }
+                    self.insert(parent, entry);
+                    continue;
+                }
+                (None, entry) => {
+                    self.insert(parent, entry);
+                    continue;
+                }
+            }
+            if let Some(ino) = existing {
+                outdated.retain(|outdated_ino| *outdated_ino != ino);
+            }
+        }
+
+        for ino in outdated {
+            self.remove(ino);
+        }
+    }
+
+    /// Set when the childs of an entry must be fetched again
+    pub fn set_expiration(&mut self, ino: Ino, expires_at: Option<SystemTime>) {
+        if let Some(inode) = self.inode_mut(ino) {
+            inode.expires_at = expires_at;
+        }
+    }
+
+    pub fn is_expired(&self, ino: Ino) -> bool {
+        self.inode(ino)
+            .and_then(|inode| inode.expires_at)
+            .is_some_and(|expires_at| expires_at <= SystemTime::now())
+    }
+
+    /// Expire the loaded entries matching a predicate and all their loaded childs
+    pub fn expire_where<P>(&mut self, predicate: P) -> usize
+    where
+        P: Fn(&FSEntry) -> bool,
+    {
+        let matching = self
+            .iter()
+            .filter(|(_, entry)| predicate(entry))
+            .map(|(ino, _)| ino)
+            .collect::<Vec<_>>();
+        matching.into_iter().map(|ino| self.expire(ino)).sum()
+    }
+
+    fn expire(&mut self, ino: Ino) -> usize {
+        let mut expired = 0;
+        if let Some(inode) = self.inode_mut(ino) {
+            if inode.expires_at.is_some() {
+                inode.expires_at = Some(SystemTime::UNIX_EPOCH);
+                expired += 1;
+            }
+        }
+        expired
+            + self
+                .children(ino)
+                .into_iter()
+                .map(|child| self.expire(child))
+                .sum::<usize>()
+    }
+}
@@ -2,63 +2,197 @@
 
 use std::{
     ffi::OsStr,
-    path::PathBuf,
+    path::{Path, PathBuf},
     sync::Arc,
-    time::{SystemTime, UNIX_EPOCH},
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
 use fuser::{FileAttr, FileType, Filesystem};
 use gitlab::Gitlab;
 use if_chain::if_chain;
-use log_utils::{debug, info, trace, tracing, warn};
+use log_utils::{debug, error, info, trace, tracing, warn};
 use parking_lot::RwLock;
 
-use crate::project::Project;
+use crate::{
+    client::GitlabClient,
+    project::Project,
+    project_env::{Variable, VariablesOwner, ATTRIBUTES},
+};
 
 mod constants;
 mod entry;
 mod folders;
+mod inodes;
 mod types;
+mod views;
+
+#[cfg(test)]
+mod tests;
 
 use constants::*;
 use entry::*;
+use inodes::*;
 use types::*;
 
+/// The name of the file of an environment, escaped when it would collide with a folder of the project
+/// or look escaped (E.g. `jobs` is `%jobs`, `%a` is `%%a`)
+pub(crate) fn env_file_name(environment_scope: &str) -> String {
+    if [REPOSITORY_FOLDER, PIPELINES_FOLDER, JOBS_FOLDER].contains(&environment_scope)
+        || environment_scope.starts_with(ESCAPE_PREFIX)
+    {
+        format!("{ESCAPE_PREFIX}{environment_scope}")
+    } else {
+        environment_scope.to_string()
+    }
+}
+
+/// The environment scope of an environment file, see [`env_file_name`]
+pub(crate) fn env_scope(file_name: &str) -> &str {
+    file_name.strip_prefix(ESCAPE_PREFIX).unwrap_or(file_name)
+}
+
 #[derive(Debug)]
-pub struct GitlabFS {
+pub struct GitlabFS<C = Gitlab> {
     /// The Gitlab client
-    client: Arc<Gitlab>,
+    client: Arc<C>,
     /// The virtual filesystem state
-    vfs: Arc<RwLock<Vec<FSEntry>>>,
+    vfs: Arc<RwLock<InodeTable>>,
     /// The Gitlab query to filter projects
     query: String,
+    /// Only log the changes to the variables instead of writing them to Gitlab
+    dry_run: bool,
+    /// The instance variables, inherited by every project
+    instance_variables: Arc<RwLock<Vec<Variable>>>,
+    /// How long the childs of a project, a group or a view are kept before being fetched again
+    refresh_ttl: Duration,
+    /// Create a Gitlab environment along with each new environment file
+    create_environments: bool,
+}
+
+/// A handle to expire the cached entries of a project from another thread (E.g. the webhook listener)
+#[derive(Debug, Clone)]
+pub struct Invalidator {
+    vfs: Arc<RwLock<InodeTable>>,
+}
+
+/// How a project is identified in an event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProjectRef {
+    Id(u64),
+    /// The path with its namespace (E.g. `folder1/folder2/project`)
+    Path(String),
+}
+
+impl Invalidator {
+    /// Expire a project and the views generated from it, they are fetched again on their next access
+    #[tracing::instrument(level = "debug", skip(self))]
+    pub fn invalidate_project(&self, project: &ProjectRef) {
+        let mut vfs = self.vfs.write();
+        let external_id = vfs.iter().find_map(|(_, entry)| match entry {
+            FSEntry::Project(FSProject {
+                external_id, path, ..
+            }) => match project {
+                ProjectRef::Id(id) if *id == *external_id as u64 => Some(*external_id),
+                ProjectRef::Path(project_path) if Path::new(project_path) == path => {
+                    Some(*external_id)
+                }
+                _ => None,
+            },
+            _ => None,
+        });
+        let Some(external_id) = external_id else {
+            debug!("The project is not mounted");
+            return;
+        };
+
+        let expired = vfs.expire_where(|entry| match entry {
+            FSEntry::Project(FSProject {
+                external_id: id, ..
+            })
+            | FSEntry::View(FSView {
+                kind: ViewKind::Compare { external_id: id },
+                ..
+            }) => *id == external_id,
+            FSEntry::View(FSView {
+                kind: ViewKind::Matrix { projects },
+                ..
+            }) => projects.iter().any(|(id, _)| *id == external_id),
+            _ => false,
+        });
+        info!(external_id, "Invalidated {expired} entries");
+    }
 }
 
 impl GitlabFS {
     /// Create a new GitlabFS filesystem
-    pub fn new(host: String, token: String, query: String) -> Self {
+    pub fn new(
+        host: String,
+        token: String,
+        query: String,
+        dry_run: bool,
+        refresh_ttl: Duration,
+        create_environments: bool,
+    ) -> Self {
+        Self::with_client(
+            Gitlab::new(host, token).expect("Failed to initialize Gitlab client"),
+            query,
+            dry_run,
+            refresh_ttl,
+            create_environments,
+        )
+    }
+}
+
+impl<C: GitlabClient> GitlabFS<C> {
+    /// Create a new GitlabFS filesystem querying Gitlab through any client
+    pub fn with_client(
+        client: C,
+        query: String,
+        dry_run: bool,
+        refresh_ttl: Duration,
+        create_environments: bool,
+    ) -> Self {
         Self {
-            client: Arc::new(Gitlab::new(host, token).expect("Failed to initialize Gitlab client")),
-            vfs: Arc::new(RwLock::new(Vec::default())),
+            client: Arc::new(client),
+            vfs: Arc::new(RwLock::new(InodeTable::default())),
             query,
+            dry_run,
+            instance_variables: Arc::new(RwLock::new(Vec::default())),
+            refresh_ttl,
+            create_environments,
         }
     }
 
-    /// Convert an index in the filesystem to an inode
-    fn idx_to_ino(idx: usize) -> Ino {
-        (idx + 1) as Ino
+    /// Get a handle to invalidate the entries of the filesystem once it is mounted
+    pub fn invalidator(&self) -> Invalidator {
+        Invalidator {
+            vfs: self.vfs.clone(),
+        }
     }
 
-    /// Convert an inode to an index in the filesystem
-    fn ino_to_idx(ino: Ino) -> usize {
-        (ino - 1) as usize
+    /// How long the childs of an entry are kept, the CI status changes more often than the variables
+    fn refresh_ttl(&self, entry: &FSEntry) -> Duration {
+        match entry {
+            FSEntry::View(FSView {
+                kind:
+                    ViewKind::Pipelines { .. }
+                    | ViewKind::Pipeline { .. }
+                    | ViewKind::Jobs { .. }
+                    | ViewKind::Job { .. },
+                ..
+            }) => self.refresh_ttl.min(CI_REFRESH_TTL),
+            _ => self.refresh_ttl,
+        }
     }
 
     /// Convert a filesystem entry to a FUSE FileAttr response
     fn entry_as_fileattr(request: &fuser::Request<'_>, entry: &FSEntry, ino: u64) -> FileAttr {
         // It can only be a folder or a file
         match entry {
-            FSEntry::Folder { .. } | FSEntry::Project { .. } | FSEntry::Root { .. } => FileAttr {
+            FSEntry::Folder { .. }
+            | FSEntry::Project { .. }
+            | FSEntry::View { .. }
+            | FSEntry::Root { .. } => FileAttr {
                 ino,
                 // A folder standard size is 4096 bytes but it does not matter
                 size: 4096,
@@ -69,7 +203,12 @@ impl GitlabFS {
                 crtime: UNIX_EPOCH,
                 mtime: UNIX_EPOCH,
                 kind: FileType::Directory,
-                perm: 0o755,
+                // Views are generated, nothing can be created inside them
+                perm: if matches!(entry, FSEntry::View { .. }) {
+                    0o555
+                } else {
+                    0o755
+                },
                 nlink: 0,
                 // We copy the user id and group so he always have access
                 uid: request.uid(),
@@ -84,6 +223,7 @@ impl GitlabFS {
                 content,
                 last_update,
                 last_access,
+                read_only,
                 ..
             }) => {
                 let file_len = content.len() as u64;
@@ -96,7 +236,7 @@ impl GitlabFS {
                     crtime: UNIX_EPOCH,
                     mtime: *last_update,
                     kind: FileType::RegularFile,
-                    perm: 0o644,
+                    perm: if *read_only { 0o444 } else { 0o644 },
                     nlink: 0,
                     // We copy the user id and group so he always have access
                     uid: request.uid(),
@@ -138,44 +278,634 @@ impl GitlabFS {
             "Generated project parent folders list of {} folder",
             folders.len()
         );
+        let (view_folders, views) = views::generate_views_from_projects(&folders, &projects);
         let root = FSEntry::Root { inos: Vec::new() };
 
         let results = [
             vec![root],
             folders.into_iter().map(FSEntry::from).collect::<Vec<_>>(),
             projects.into_iter().map(FSEntry::from).collect::<Vec<_>>(),
+            view_folders
+                .into_iter()
+                .map(FSEntry::from)
+                .collect::<Vec<_>>(),
+            views.into_iter().map(FSEntry::from).collect::<Vec<_>>(),
         ]
         .concat();
 
         let results = folders::generate_ino_from_fs_entries(&results);
         debug!(filesystem_state =? results, "Filesystem generated from projects");
 
-        // REVIEW: Env variables are loaded on access to each project directory
+        // REVIEW: Env variables and views are loaded on access to each project or view directory
 
         Ok(results)
     }
 
-    // WIP: Add entry to the filesystem
-    /*fn add_entry(&mut self, entry: FSEntry) -> Ino {
+    /// Generate the files and sub views of a view
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn generate_view_entries(&self, path: &Path, kind: &ViewKind) -> FSResult<Vec<FSEntry>> {
+        let gitlab_err = |err: eyre::Report| {
+            error!(?path, "Failed to generate the view: {err}");
+            EIO
+        };
+        let file = |name: &str, content: Vec<u8>| {
+            FSEntry::File(Self::new_generated_file(path.join(name), content))
+        };
+        let view = |name: &str, kind: ViewKind| {
+            FSEntry::View(FSView {
+                path: path.join(name),
+                inos: Vec::new(),
+                kind,
+                loaded: false,
+            })
+        };
+
+        let entries = match kind {
+            ViewKind::Compare { external_id } => {
+                let variables =
+                    crate::project_env::get_project_env(self.client.as_ref(), *external_id)
+                        .map_err(gitlab_err)?;
+                crate::compare::compare_envs(&variables)
+                    .into_iter()
+                    .map(|(name, content)| file(&name, content.into_bytes()))
+                    .collect()
+            }
+            ViewKind::Matrix { projects } => {
+                // NOTE: A project that cannot be fetched is only missing from the matrix
+                let variables = projects
+                    .iter()
+                    .filter_map(|(external_id, path)| {
+                        crate::project_env::get_project_env(self.client.as_ref(), *external_id)
+                            .map_err(|err| {
+                                warn!(external_id, "Skipping project from the matrix: {err}")
+                            })
+                            .ok()
+                            .map(|variables| (path.clone(), variables))
+                    })
+                    .collect::<Vec<_>>();
+                crate::compare::matrix(&variables)
+                    .into_iter()
+                    .map(|(name, content)| file(&name, content.into_bytes()))
+                    .collect()
+            }
+            ViewKind::Branches { external_id } => {
+                crate::repository::get_branches(self.client.as_ref(), *external_id)
+                    .map_err(gitlab_err)?
+                    .into_iter()
+                    .map(|branch| {
+                        // NOTE: A branch name can contain slashes (E.g. `feature/login`)
+                        view(
+                            &branch.name.replace('/', "%2F"),
+                            ViewKind::Tree {
+                                external_id: *external_id,
+                                ref_: branch.name,
+                                path: String::new(),
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            ViewKind::Tree {
+                external_id,
+                ref_,
+                path: tree_path,
+            } => crate::repository::get_tree(self.client.as_ref(), *external_id, ref_, tree_path)
+                .map_err(gitlab_err)?
+                .into_iter()
+                .filter_map(|entry| match entry.kind.as_str() {
+                    "tree" => Some(view(
+                        &entry.name,
+                        ViewKind::Tree {
+                            external_id: *external_id,
+                            ref_: ref_.clone(),
+                            path: entry.path,
+                        },
+                    )),
+                    "blob" => Some(FSEntry::File(FSFile {
+                        lazy: Some(LazyContent::Blob {
+                            external_id: *external_id,
+                            ref_: ref_.clone(),
+                            path: entry.path,
+                        }),
+                        ..Self::new_generated_file(path.join(&entry.name), Vec::new())
+                    })),
+                    // Submodules are not browsable
+                    _ => None,
+                })
+                .collect(),
+            ViewKind::Pipelines { external_id } => {
+                crate::ci::get_pipelines(self.client.as_ref(), *external_id)
+                    .map_err(gitlab_err)?
+                    .into_iter()
+                    .map(|pipeline| {
+                        view(
+                            &pipeline.id.to_string(),
+                            ViewKind::Pipeline {
+                                external_id: *external_id,
+                                pipeline_id: pipeline.id,
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            ViewKind::Pipeline {
+                external_id,
+                pipeline_id,
+            } => {
+                let pipeline =
+                    crate::ci::get_pipeline(self.client.as_ref(), *external_id, *pipeline_id)
+                        .map_err(gitlab_err)?;
+                vec![file(
+                    "status",
+                    format!("{}\n", pipeline.status).into_bytes(),
+                )]
+            }
+            ViewKind::Jobs { external_id } => {
+                crate::ci::get_jobs(self.client.as_ref(), *external_id)
+                    .map_err(gitlab_err)?
+                    .into_iter()
+                    .map(|job| {
+                        view(
+                            &job.id.to_string(),
+                            ViewKind::Job {
+                                external_id: *external_id,
+                                job_id: job.id,
+                            },
+                        )
+                    })
+                    .collect()
+            }
+            ViewKind::Job {
+                external_id,
+                job_id,
+            } => {
+                let job = crate::ci::get_job(self.client.as_ref(), *external_id, *job_id)
+                    .map_err(gitlab_err)?;
+                vec![
+                    file("status", format!("{}\n", job.status).into_bytes()),
+                    FSEntry::File(FSFile {
+                        lazy: Some(LazyContent::JobTrace {
+                            external_id: *external_id,
+                            job_id: *job_id,
+                        }),
+                        ..Self::new_generated_file(path.join("trace.log"), Vec::new())
+                    }),
+                ]
+            }
+        };
+
+        Ok(entries)
+    }
+
+    /// Fetch the content of a file loaded on first access
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn load_content(&self, ino: Ino) -> FSResult<()> {
+        let lazy = match self.vfs.read().get(ino) {
+            Some(FSEntry::File(FSFile { lazy, .. })) => lazy.clone(),
+            Some(_) => None,
+            None => return Err(ENOENT),
+        };
+        let Some(lazy) = lazy else {
+            return Ok(());
+        };
+
+        info!(?lazy, "Loading file content");
+        let content = match &lazy {
+            LazyContent::Blob {
+                external_id,
+                ref_,
+                path,
+            } => crate::repository::get_file(self.client.as_ref(), *external_id, ref_, path),
+            LazyContent::JobTrace {
+                external_id,
+                job_id,
+            } => crate::ci::get_job_trace(self.client.as_ref(), *external_id, *job_id),
+        }
+        .map_err(|err| {
+            error!(?lazy, "Failed to load the file: {err}");
+            EIO
+        })?;
+
+        if let Some(FSEntry::File(file)) = self.vfs.write().get_mut(ino) {
+            file.content = content;
+            file.last_update = SystemTime::now();
+            file.lazy = None;
+        }
+
+        Ok(())
+    }
+
+    /// Create a file generated from Gitlab
+    fn new_file(
+        path: PathBuf,
+        variables: Vec<Variable>,
+        owner: Option<VariablesOwner>,
+        read_only: bool,
+    ) -> FSFile {
+        FSFile {
+            path,
+            content: crate::project_env::env_file(&variables).into_bytes(),
+            last_update: SystemTime::now(),
+            last_access: SystemTime::now(),
+            dirty: false,
+            read_only,
+            variables,
+            owner,
+            lazy: None,
+        }
+    }
+
+    /// Create a read-only file that is not made of variables
+    fn new_generated_file(path: PathBuf, content: Vec<u8>) -> FSFile {
+        FSFile {
+            content,
+            ..Self::new_file(path, Vec::new(), None, true)
+        }
+    }
+
+    /// Generate the env files of a project, the effective env files
+    /// merging the instance, groups and project variables and the repository and CI views
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn generate_project_entries(&self, external_id: u32, path: &Path) -> Vec<FSEntry> {
+        let project_variables = crate::project_env::variables_to_env(
+            crate::project_env::get_project_env(self.client.as_ref(), external_id),
+        );
+
+        // The parent groups from the top level one to the closest one
+        let mut groups = path
+            .ancestors()
+            .skip(1)
+            .filter(|p| p.components().count() > 0)
+            .collect::<Vec<_>>();
+        groups.reverse();
+        let groups_variables = groups
+            .iter()
+            .map(|group| {
+                let group = group.to_string_lossy();
+                crate::inherited_env::get_group_env(self.client.as_ref(), &group).unwrap_or_else(
+                    |err| {
+                        warn!(%group, "Failed to get the group variables: {err}");
+                        Vec::new()
+                    },
+                )
+            })
+            .collect::<Vec<_>>();
+        let instance_variables = self.instance_variables.read().clone();
+        let all_project_variables = project_variables
+            .iter()
+            .flat_map(|(_, variables)| variables.clone())
+            .collect::<Vec<_>>();
+
+        let mut layers = vec![instance_variables.as_slice()];
+        layers.extend(groups_variables.iter().map(Vec::as_slice));
+        layers.push(all_project_variables.as_slice());
+
+        let mut entries = Vec::new();
+        for (env, variables) in project_variables {
+            let effective = crate::inherited_env::effective_variables(&layers, &env);
+            let file_name = env_file_name(&env);
+            entries.push(FSEntry::File(Self::new_file(
+                path.join(format!("{file_name}{EFFECTIVE_FILE_SUFFIX}")),
+                effective,
+                None,
+                true,
+            )));
+            entries.push(FSEntry::File(Self::new_file(
+                path.join(file_name),
+                variables,
+                Some(VariablesOwner::Project(external_id)),
+                false,
+            )));
+        }
+
+        // NOTE: Their content is only fetched when they are accessed
+        let views = [
+            (REPOSITORY_FOLDER, ViewKind::Branches { external_id }),
+            (PIPELINES_FOLDER, ViewKind::Pipelines { external_id }),
+            (JOBS_FOLDER, ViewKind::Jobs { external_id }),
+        ];
+        entries.extend(views.into_iter().map(|(name, kind)| {
+            FSEntry::View(FSView {
+                path: path.join(name),
+                inos: Vec::new(),
+                kind,
+                loaded: false,
+            })
+        }));
+
+        entries
+    }
+
+    /// Add the childs of a project (its environments and views), of a group (its variables)
+    /// or of a view (its generated files and sub views) the first time they are needed
+    /// and fetch them again once they expired
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn load_children(&self, ino: Ino) -> FSResult<()> {
+        // NOTE: Clone the entry so the vfs is not locked while querying Gitlab
+        let (entry, expired) = {
+            let vfs = self.vfs.read();
+            (vfs.get(ino).cloned(), vfs.is_expired(ino))
+        };
+        let entries = match entry.clone() {
+            // If there is no inodes, we havent got the env variables of the project
+            Some(FSEntry::Project(FSProject {
+                external_id,
+                path,
+                inos,
+            })) if inos.is_empty() || expired => {
+                info!(external_id, ?path, "Listing project");
+                // NOTE: The trashed environments are still on Gitlab until they are removed from the trash
+                let trashed = {
+                    let vfs = self.vfs.read();
+                    vfs.find_child(ino, OsStr::new(TRASH_FOLDER))
+                        .map(|trash| vfs.children(trash))
+                        .unwrap_or_default()
+                        .into_iter()
+                        .filter_map(|trashed| {
+                            vfs.get(trashed)
+                                .and_then(|e| e.get_path())
+                                .and_then(|(p, _)| p.file_name())
+                                .map(|name| name.to_string_lossy().to_string())
+                        })
+                        .collect::<Vec<_>>()
+                };
+                self.generate_project_entries(external_id, &path)
+                    .into_iter()
+                    .filter(|entry| {
+                        let name = entry
+                            .get_path()
+                            .and_then(|(p, _)| p.file_name())
+                            .map(|name| name.to_string_lossy().to_string())
+                            .unwrap_or_default();
+                        let env = name.strip_suffix(EFFECTIVE_FILE_SUFFIX).unwrap_or(&name);
+                        !trashed.iter().any(|trashed| trashed == env)
+                    })
+                    .collect()
+            }
+            Some(FSEntry::Folder(FSFolder { path, loaded, .. })) if !loaded || expired => {
+                info!(?path, "Listing group variables");
+                let group = path.to_string_lossy().to_string();
+                let group_variables = crate::project_env::variables_to_env(
+                    crate::inherited_env::get_group_env(self.client.as_ref(), &group),
+                );
+                // NOTE: The content is read only, only the attributes of the variables can be edited
+                group_variables
+                    .into_iter()
+                    .map(|(env, variables)| {
+                        FSEntry::File(Self::new_file(
+                            path.join(format!("{env}{GROUP_FILE_SUFFIX}")),
+                            variables,
+                            Some(VariablesOwner::Group(group.clone())),
+                            true,
+                        ))
+                    })
+                    .collect()
+            }
+            Some(FSEntry::View(FSView {
+                path, kind, loaded, ..
+            })) if !loaded || expired => {
+                info!(?path, "Generating view");
+                self.generate_view_entries(&path, &kind)?
+            }
+            Some(_) => return Ok(()),
+            None => return Err(ENOENT),
+        };
+
         let mut vfs = self.vfs.write();
-        let idx = vfs.len();
-        vfs.push(entry);
-        return Self::idx_to_ino(idx);
-    }*/
+        vfs.replace_children(ino, entries);
+        if let Some(
+            FSEntry::Folder(FSFolder { loaded, .. }) | FSEntry::View(FSView { loaded, .. }),
+        ) = vfs.get_mut(ino)
+        {
+            *loaded = true;
+        }
+        if let Some(entry) = entry {
+            vfs.set_expiration(ino, Some(SystemTime::now() + self.refresh_ttl(&entry)));
+        }
+
+        Ok(())
+    }
+
+    /// Parse an extended attribute name (E.g. `user.API_KEY.masked`) as (key, attribute)
+    fn parse_xattr_name(name: &OsStr) -> Option<(&str, &str)> {
+        let (key, attribute) = name
+            .to_str()?
+            .strip_prefix(XATTR_PREFIX)?
+            .rsplit_once('.')?;
+        ATTRIBUTES.contains(&attribute).then_some((key, attribute))
+    }
+
+    /// Reply the size of an extended attribute when asked for (size of 0), else its value
+    fn reply_xattr(reply: fuser::ReplyXattr, size: u32, data: &[u8]) {
+        if size == 0 {
+            reply.size(data.len() as u32);
+        } else if data.len() > size as usize {
+            reply.error(ERANGE);
+        } else {
+            reply.data(data);
+        }
+    }
+
+    /// Find the project of an environment file from its parent folder:
+    /// (project id, project inode, whether the file is in the trash)
+    fn env_location(vfs: &InodeTable, parent: Ino) -> Option<(u32, Ino, bool)> {
+        match vfs.get(parent)? {
+            FSEntry::Project(FSProject { external_id, .. }) => Some((*external_id, parent, false)),
+            FSEntry::Folder(FSFolder { path, .. })
+                if path.file_name() == Some(OsStr::new(TRASH_FOLDER)) =>
+            {
+                let project = vfs.parent(parent)?;
+                match vfs.get(project)? {
+                    FSEntry::Project(FSProject { external_id, .. }) => {
+                        Some((*external_id, project, true))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Get the trash folder of a project, creating it if needed
+    /// NOTE: The trash only lives in memory, it is lost on remount while its variables stay on Gitlab
+    fn trash_folder(vfs: &mut InodeTable, project: Ino) -> Ino {
+        if let Some(trash) = vfs.find_child(project, OsStr::new(TRASH_FOLDER)) {
+            return trash;
+        }
+        let path = vfs
+            .get(project)
+            .and_then(|e| e.get_path())
+            .map(|(path, _)| path.join(TRASH_FOLDER))
+            .unwrap_or_else(|| PathBuf::from(TRASH_FOLDER));
+        // NOTE: It is not a group, there is no variable to load
+        vfs.insert(
+            project,
+            FSEntry::Folder(FSFolder {
+                path,
+                inos: Vec::new(),
+                loaded: true,
+            }),
+        )
+    }
+
+    /// Move the variables of an environment scope to another scope on Gitlab
+    fn move_scope(&self, project_id: u32, from: &str, to: &str) -> FSResult<()> {
+        if self.dry_run {
+            info!(
+                project_id,
+                "Dry run: would move the variables of {from} to {to}"
+            );
+            return Ok(());
+        }
+        crate::project_env::move_env_scope(self.client.as_ref(), project_id, from, to).map_err(
+            |err| {
+                error!(
+                    project_id,
+                    "Failed to move the variables of {from} to {to}: {err}"
+                );
+                EIO
+            },
+        )
+    }
+
+    /// Delete the variables of an environment scope on Gitlab
+    fn delete_scope(&self, project_id: u32, environment_scope: &str) -> FSResult<()> {
+        if self.dry_run {
+            info!(
+                project_id,
+                "Dry run: would delete the variables of {environment_scope}"
+            );
+            return Ok(());
+        }
+        crate::project_env::delete_env_scope(self.client.as_ref(), project_id, environment_scope)
+            .map_err(|err| {
+                error!(
+                    project_id,
+                    "Failed to delete the variables of {environment_scope}: {err}"
+                );
+                EIO
+            })
+    }
+
+    /// Write the content of an edited environment file to the Gitlab variables of its scope
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn write_back(&self, ino: Ino) -> FSResult<()> {
+        let (path, content) = {
+            let vfs = self.vfs.read();
+            match vfs.get(ino) {
+                Some(FSEntry::File(FSFile { dirty: false, .. })) => return Ok(()),
+                Some(FSEntry::File(FSFile { path, content, .. })) => {
+                    (path.clone(), content.clone())
+                }
+                _ => return Err(ENOENT),
+            }
+        };
+
+        // The file is named after its environment scope and lives in its project folder
+        let environment_scope = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .map(env_scope)
+            .ok_or(EINVAL)?;
+        let project_id = {
+            let vfs = self.vfs.read();
+            match vfs.parent(ino).and_then(|parent| vfs.get(parent)) {
+                Some(FSEntry::Project(FSProject { external_id, .. })) => *external_id,
+                _ => return Err(ENOENT),
+            }
+        };
+
+        let content = String::from_utf8(content).map_err(|_err| EINVAL)?;
+        let wanted = crate::project_env::env_to_variables(&content).map_err(|err| {
+            warn!(?path, "Invalid environment file: {err}");
+            EINVAL
+        })?;
+        let current = crate::project_env::get_project_env(self.client.as_ref(), project_id)
+            .map_err(|err| {
+                error!(project_id, "Failed to get the project variables: {err}");
+                EIO
+            })?;
+        let changes = crate::project_env::diff_env(&current, environment_scope, &wanted);
+
+        let mut variables = None;
+        if changes.is_empty() {
+            debug!(?path, "No change to write");
+        } else if self.dry_run {
+            for change in &changes {
+                info!(project_id, environment_scope, "Dry run: would {change}");
+            }
+        } else {
+            crate::project_env::apply_env_changes(
+                self.client.as_ref(),
+                project_id,
+                environment_scope,
+                &changes,
+            )
+            .map_err(|err| {
+                error!(
+                    project_id,
+                    environment_scope, "Failed to write the variables: {err}"
+                );
+                EIO
+            })?;
+
+            // The attributes of the variables are read from the file, they must include the new ones
+            match crate::project_env::get_project_env(self.client.as_ref(), project_id) {
+                Ok(current) => {
+                    variables = Some(
+                        current
+                            .into_iter()
+                            .filter(|var| var.environment_scope == environment_scope)
+                            .collect::<Vec<_>>(),
+                    );
+                }
+                Err(err) => {
+                    warn!(project_id, "Failed to refresh the project variables: {err}");
+                }
+            }
+        }
+
+        if let Some(FSEntry::File(file)) = self.vfs.write().get_mut(ino) {
+            file.dirty = false;
+            if let Some(variables) = variables {
+                file.variables = variables;
+            }
+        }
+
+        Ok(())
+    }
 }
 
-impl Filesystem for GitlabFS {
+impl<C: GitlabClient> Filesystem for GitlabFS<C> {
     /// Initialise the filesystem to its default state
     #[tracing::instrument(skip(self, req, config))]
     fn init(&mut self, req: &fuser::Request<'_>, config: &mut fuser::KernelConfig) -> FSResult<()> {
         trace!(request =? req, config =? config, "init FS");
 
-        let projects =
-            crate::project::get_projects(&self.client, &self.query).map_err(|_err| EAGAIN)?;
-        let fs_entries = Self::generate_fs_entries_from_projects(projects)?;
+        let projects = crate::project::get_projects(self.client.as_ref(), &self.query)
+            .map_err(|_err| EAGAIN)?;
+        let mut inodes =
+            InodeTable::from_entries(Self::generate_fs_entries_from_projects(projects)?);
+
+        // NOTE: Only administrators can read the instance variables
+        match crate::inherited_env::get_instance_env(self.client.as_ref()) {
+            Ok(variables) => {
+                inodes.insert(
+                    ROOT_INO,
+                    FSEntry::File(Self::new_file(
+                        PathBuf::from(INSTANCE_FILE),
+                        variables.clone(),
+                        None,
+                        true,
+                    )),
+                );
+                *self.instance_variables.write() = variables;
+            }
+            Err(err) => info!("Instance variables are not available: {err}"),
+        }
 
         let mut vfs = self.vfs.write();
-        *vfs = fs_entries;
+        *vfs = inodes;
 
         Ok(())
     }
@@ -190,7 +920,7 @@ impl Filesystem for GitlabFS {
     #[tracing::instrument(level = "debug", skip(self, req, reply))]
     fn getattr(&mut self, req: &fuser::Request<'_>, ino: u64, reply: fuser::ReplyAttr) {
         trace!(request =? req, "getattr of ino {ino}");
-        if let Some(fs_entry) = { self.vfs.read().get(Self::ino_to_idx(ino)) } {
+        if let Some(fs_entry) = { self.vfs.read().get(ino) } {
             reply.attr(&TTL, &Self::entry_as_fileattr(req, fs_entry, ino));
         } else {
             reply.error(ENOENT);
@@ -215,68 +945,21 @@ impl Filesystem for GitlabFS {
     ) {
         trace!(request =? req, "readdir of ino {ino}");
         let mut err = None;
-        let mut self_idx = Self::ino_to_idx(ino);
 
-        {
-            // Here we borrow vfs as write till the end of the block
-            let mut vfs = self.vfs.write();
-            // TODO: Add a method on the filesystem to add new files to it
-            let mut next_idx: usize = vfs.len();
-            let mut file_buffer = Vec::new();
-            let mut self_entry = {
-                if let Some(entry) = vfs.get_mut(self_idx) {
-                    entry
-                } else {
-                    debug!("Entry not found {ino}");
-                    reply.error(ENOENT);
-                    return;
-                }
-            };
-
-            if_chain! {
-                // Only if the entry is a project
-                if let FSEntry::Project(FSProject {
-                    external_id, path, inos,
-                }) = self_entry;
-                if inos.is_empty(); // If there is no inodes, we havent got the env variables of the project
-                then {
-                    let path = path.clone();
-                    info!(external_id, ?path, "Listing project");
-                    let project_variables = crate::project_env::variables_to_env(
-                        crate::project_env::get_project_env(&self.client, *external_id),
-                    );
-                    for (env, env_file) in project_variables {
-                        file_buffer.push(FSEntry::File(FSFile {
-                            path: path.join(env),
-                            content: env_file.as_bytes().to_vec(),
-                            last_update: SystemTime::now(),
-                            last_access: SystemTime::now(),
-                        }));
-                        // TODO: Move this to a "add" method on the filesystem
-                        inos.push(Self::idx_to_ino(next_idx));
-                        next_idx += 1;
-                    }
-                }
-            }
-
-            for file in file_buffer {
-                vfs.push(file);
-            }
-
-            // Here the writable borrow to vfs is dropped
+        // Load the environments of a project or the files of a view before listing them
+        if let Err(load_err) = self.load_children(ino) {
+            debug!("Failed to load the childs of {ino}");
+            reply.error(load_err);
+            return;
         }
 
         // Here we borrow vfs as read till the end of the function
         let vfs = self.vfs.read();
         // Only if the entry is a Folder, a Project or the Root
-        if let Some((inos, self_entry)) = { vfs.get(self_idx).and_then(|e| e.get_children_inos()) }
-        {
+        if let Some((inos, self_entry)) = { vfs.get(ino).and_then(|e| e.get_children_inos()) } {
             // We iterate on the children
             for (idx, child_ino) in inos.iter().enumerate().skip(offset as usize) {
-                if let Some((path, entry)) = {
-                    vfs.get(Self::ino_to_idx(*child_ino))
-                        .and_then(|e| e.get_path())
-                } {
+                if let Some((path, entry)) = { vfs.get(*child_ino).and_then(|e| e.get_path()) } {
                     let attrs = Self::entry_as_fileattr(req, entry, *child_ino);
                     if_chain! {
                         if let Some(name) = path.file_name();
@@ -315,32 +998,27 @@ impl Filesystem for GitlabFS {
     ) {
         trace!(request =? req, parent = parent_ino, "loopup of name {:?}", name);
 
-        let vfs = self.vfs.read();
-
-        if_chain! {
-            if let Some(target_file_name) = name.to_str();
-            if let Some((parent_inos, _))
-                = { vfs.get(Self::ino_to_idx(parent_ino)).and_then(|e| e.get_children_inos()) };
-            then {
-                for child_ino in parent_inos.iter() {
-                    if_chain! {
-                        if let Some(
-                            (path, fs_entry),
-                        ) = { vfs.get(Self::ino_to_idx(*child_ino)).and_then(|e| e.get_path()) };
-                        if let Some(found_file_name) = path.file_name();
-                        if found_file_name == target_file_name;
-                        then {
-                            let attrs = Self::entry_as_fileattr(req, fs_entry, *child_ino);
-                            debug!(folder =? attrs, "Lookup folder");
-                            reply.entry(&TTL,  &attrs, 0);
-                            return;
-                        }
-                    }
-                }
-            }
+        // A path can be accessed directly without listing its parent first (E.g. `cat .compare/project/a..b.diff`)
+        if let Err(err) = self.load_children(parent_ino) {
+            reply.error(err);
+            return;
         }
 
-        reply.error(ENOENT);
+        let found_ino = self.vfs.read().find_child(parent_ino, name);
+        let Some(child_ino) = found_ino else {
+            reply.error(ENOENT);
+            return;
+        };
+
+        // NOTE: A lazy file is empty until it is opened, its content is read with direct IO
+        let vfs = self.vfs.read();
+        if let Some(fs_entry) = vfs.get(child_ino) {
+            let attrs = Self::entry_as_fileattr(req, fs_entry, child_ino);
+            debug!(folder =? attrs, "Lookup folder");
+            reply.entry(&TTL, &attrs, 0);
+        } else {
+            reply.error(ENOENT);
+        }
     }
 
     /// The read function in a FUSE (Filesystem in Userspace) implementation\
@@ -366,12 +1044,17 @@ impl Filesystem for GitlabFS {
     ) {
         trace!(request =? req, "read of file at ino {ino}");
 
+        if let Err(err) = self.load_content(ino) {
+            reply.error(err);
+            return;
+        }
+
         let mut vfs = self.vfs.write();
         if let Some(FSEntry::File(FSFile {
             content,
             last_access,
             ..
-        })) = vfs.get_mut(Self::ino_to_idx(ino))
+        })) = vfs.get_mut(ino)
         {
             *last_access = SystemTime::now();
             if let Some(end_offset) = offset.checked_add_unsigned(size as u64) {
@@ -387,16 +1070,26 @@ impl Filesystem for GitlabFS {
         }
     }
 
+    /// Fetch the content of a lazy file, it is read with direct IO since its size was unknown until now
     #[tracing::instrument(level = "debug", skip(self, req, reply))]
     fn open(&mut self, req: &fuser::Request<'_>, ino: u64, _flags: i32, reply: fuser::ReplyOpen) {
         trace!(request =? req, "open file at ino {ino}");
-        info!("Open do nothing because the file access is not handled manually");
 
-        let vfs = self.vfs.read();
-        if let Some(FSEntry::File(FSFile { .. })) = vfs.get(Self::ino_to_idx(ino)) {
-            reply.opened(0, 0);
+        let lazy = match self.vfs.read().get(ino) {
+            Some(FSEntry::File(FSFile { lazy, .. })) => lazy.is_some(),
+            _ => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+        if lazy {
+            if let Err(err) = self.load_content(ino) {
+                reply.error(err);
+                return;
+            }
+            reply.opened(0, fuser::consts::FOPEN_DIRECT_IO);
         } else {
-            reply.error(ENOENT);
+            reply.opened(0, 0);
         }
     }
 
@@ -417,10 +1110,16 @@ impl Filesystem for GitlabFS {
 
         let mut vfs = self.vfs.write();
         if let Some(FSEntry::File(FSFile {
+            read_only: true, ..
+        })) = vfs.get(ino)
+        {
+            reply.error(EACCES);
+        } else if let Some(FSEntry::File(FSFile {
             content,
             last_update,
+            dirty,
             ..
-        })) = vfs.get_mut(Self::ino_to_idx(ino))
+        })) = vfs.get_mut(ino)
         {
             info!(content =? content.clone(), "Writing {} bytes at {offset}", data.len());
 
@@ -431,6 +1130,7 @@ impl Filesystem for GitlabFS {
             content[(offset as usize)..max_len].copy_from_slice(data);
 
             *last_update = SystemTime::now();
+            *dirty = true;
             reply.written(data.len() as u32);
         } else {
             reply.error(ENOENT);
@@ -451,13 +1151,19 @@ impl Filesystem for GitlabFS {
         warn!(request =? req, "create file under parent ino {parent}");
 
         let mut vfs = self.vfs.write();
-        let child_ino = Self::idx_to_ino(vfs.len());
+        // E.g. the folders of the project
+        if vfs.find_child(parent, name).is_some() {
+            reply.error(EEXIST);
+            return;
+        }
         let mut child_path = PathBuf::new();
-        if let Some(FSEntry::Project(FSProject { inos, path, .. })) =
-            vfs.get_mut(Self::ino_to_idx(parent))
+        let owner;
+        if let Some(FSEntry::Project(FSProject {
+            path, external_id, ..
+        })) = vfs.get(parent)
         {
             child_path.push(path);
-            inos.push(child_ino);
+            owner = VariablesOwner::Project(*external_id);
         } else {
             reply.error(ENOENT);
             return;
@@ -470,8 +1176,30 @@ impl Filesystem for GitlabFS {
             content: Vec::new(),
             last_update: SystemTime::now(),
             last_access: SystemTime::now(),
+            dirty: true,
+            read_only: false,
+            variables: Vec::new(),
+            owner: Some(owner.clone()),
+            lazy: None,
         });
-        vfs.push(entry.clone());
+        let child_ino = vfs.insert(parent, entry.clone());
+        drop(vfs);
+
+        // NOTE: The environment may already exist on Gitlab, its variables are still created on the first write
+        if let (true, VariablesOwner::Project(project_id), Some(name)) =
+            (self.create_environments, &owner, name.to_str())
+        {
+            if self.dry_run {
+                info!(project_id, "Dry run: would create the environment {name}");
+            } else if let Err(err) = crate::project_env::create_environment(
+                self.client.as_ref(),
+                *project_id,
+                env_scope(name),
+            ) {
+                warn!(project_id, "Failed to create the environment {name}: {err}");
+            }
+        }
+
         reply.created(
             &TTL,
             &Self::entry_as_fileattr(req, &entry, child_ino),
@@ -481,6 +1209,281 @@ impl Filesystem for GitlabFS {
         );
     }
 
+    /// Rename an environment, moving its variables to the new scope on Gitlab,
+    /// move it to the trash of its project or restore it from the trash
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn rename(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        new_parent: u64,
+        new_name: &OsStr,
+        _flags: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        trace!(request =? req, "rename {name:?} of parent ino {parent} to {new_name:?} of {new_parent}");
+
+        let (ino, local_only, from, to) = {
+            let vfs = self.vfs.read();
+            let Some(ino) = vfs.find_child(parent, name) else {
+                reply.error(ENOENT);
+                return;
+            };
+            // NOTE: Do not merge two environments, only a file not yet written can be replaced
+            let replaced = vfs
+                .find_child(new_parent, new_name)
+                .filter(|target| *target != ino);
+            match replaced.and_then(|target| vfs.get(target)) {
+                Some(FSEntry::File(FSFile {
+                    dirty, variables, ..
+                })) if *dirty && variables.is_empty() => {}
+                Some(_) => {
+                    reply.error(EEXIST);
+                    return;
+                }
+                None => {}
+            }
+            // Only the environment files can be moved
+            let local_only = match vfs.get(ino) {
+                Some(FSEntry::File(FSFile {
+                    owner: Some(VariablesOwner::Project(_)),
+                    dirty,
+                    variables,
+                    ..
+                })) => *dirty && variables.is_empty(),
+                _ => {
+                    reply.error(EACCES);
+                    return;
+                }
+            };
+            match (
+                Self::env_location(&vfs, parent),
+                Self::env_location(&vfs, new_parent),
+            ) {
+                (Some(from), Some(to)) if from.0 == to.0 => (ino, local_only, from, to),
+                // The variables can be copied to another project, but not moved
+                (Some(_), Some(_)) => {
+                    reply.error(EXDEV);
+                    return;
+                }
+                _ => {
+                    reply.error(EACCES);
+                    return;
+                }
+            }
+        };
+        let (project_id, project_ino, from_trash) = from;
+        let (_, _, to_trash) = to;
+        let (Some(scope), Some(new_scope)) = (name.to_str(), new_name.to_str()) else {
+            reply.error(EINVAL);
+            return;
+        };
+
+        // A trashed environment keeps its name, it is only renamed once restored
+        if to_trash && scope != new_scope {
+            reply.error(EACCES);
+            return;
+        }
+        // NOTE: A file created but not yet written (E.g. by an editor) has no variable to move,
+        // its content replaces the variables of its new scope on the next flush
+        if !local_only && !to_trash && scope != new_scope {
+            if let Err(err) = self.move_scope(project_id, env_scope(scope), env_scope(new_scope)) {
+                reply.error(err);
+                return;
+            }
+        }
+
+        let mut vfs = self.vfs.write();
+        let new_parent = if to_trash {
+            Self::trash_folder(&mut vfs, project_ino)
+        } else {
+            new_parent
+        };
+        if let Some(replaced) = vfs.find_child(new_parent, new_name) {
+            vfs.remove(replaced);
+        }
+        // The effective variables are generated again on the next refresh of the project
+        if !from_trash {
+            let effective = format!("{scope}{EFFECTIVE_FILE_SUFFIX}");
+            if let Some(effective) = vfs.find_child(parent, OsStr::new(&effective)) {
+                vfs.remove(effective);
+            }
+        }
+        if let Err(err) = vfs.rename(ino, new_parent, new_name) {
+            reply.error(err);
+            return;
+        }
+
+        if let Some(FSEntry::File(FSFile {
+            read_only,
+            dirty,
+            variables,
+            ..
+        })) = vfs.get_mut(ino)
+        {
+            // A trashed environment cannot be edited
+            *read_only = to_trash;
+            *dirty = *dirty && !to_trash;
+            for variable in variables.iter_mut() {
+                variable.environment_scope = env_scope(new_scope).to_string();
+            }
+        }
+        reply.ok();
+    }
+
+    /// Move an environment to the trash of its project, or delete its variables from Gitlab when it is in the trash
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn unlink(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: fuser::ReplyEmpty,
+    ) {
+        trace!(request =? req, "unlink {name:?} of parent ino {parent}");
+
+        let (ino, local_only, location) = {
+            let vfs = self.vfs.read();
+            let Some(ino) = vfs.find_child(parent, name) else {
+                reply.error(ENOENT);
+                return;
+            };
+            let local_only = match vfs.get(ino) {
+                Some(FSEntry::File(FSFile {
+                    owner: Some(VariablesOwner::Project(_)),
+                    dirty,
+                    variables,
+                    ..
+                })) => *dirty && variables.is_empty(),
+                Some(FSEntry::File(_)) => {
+                    reply.error(EACCES);
+                    return;
+                }
+                _ => {
+                    reply.error(EISDIR);
+                    return;
+                }
+            };
+            (ino, local_only, Self::env_location(&vfs, parent))
+        };
+
+        match location {
+            // Nothing was written to Gitlab yet
+            Some(_) if local_only => {
+                self.vfs.write().remove(ino);
+                reply.ok();
+            }
+            Some((project_id, _, true)) => {
+                let Some(scope) = name.to_str() else {
+                    reply.error(EINVAL);
+                    return;
+                };
+                match self.delete_scope(project_id, env_scope(scope)) {
+                    Ok(()) => {
+                        self.vfs.write().remove(ino);
+                        reply.ok();
+                    }
+                    Err(err) => reply.error(err),
+                }
+            }
+            Some((_, project_ino, false)) => {
+                let mut vfs = self.vfs.write();
+                let trash = Self::trash_folder(&mut vfs, project_ino);
+                if let Some(replaced) = vfs.find_child(trash, name) {
+                    vfs.remove(replaced);
+                }
+                let effective = format!("{}{EFFECTIVE_FILE_SUFFIX}", name.to_string_lossy());
+                if let Some(effective) = vfs.find_child(parent, OsStr::new(&effective)) {
+                    vfs.remove(effective);
+                }
+                if let Err(err) = vfs.rename(ino, trash, name) {
+                    reply.error(err);
+                    return;
+                }
+                if let Some(FSEntry::File(FSFile {
+                    read_only, dirty, ..
+                })) = vfs.get_mut(ino)
+                {
+                    *read_only = true;
+                    *dirty = false;
+                }
+                info!(?name, "Moved the environment to the trash");
+                reply.ok();
+            }
+            None => reply.error(EACCES),
+        }
+    }
+
+    /// Folders cannot be created: the environments are files, and an environment scope only
+    /// exists on Gitlab through its variables (`touch project/review` creates an empty one)
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn mkdir(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        _mode: u32,
+        _umask: u32,
+        reply: fuser::ReplyEntry,
+    ) {
+        trace!(request =? req, "mkdir {name:?} under parent ino {parent}");
+
+        reply.error(EPERM);
+    }
+
+    /// Empty the trash of a project, deleting the variables of each trashed environment from Gitlab
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn rmdir(
+        &mut self,
+        req: &fuser::Request<'_>,
+        parent: u64,
+        name: &OsStr,
+        reply: fuser::ReplyEmpty,
+    ) {
+        trace!(request =? req, "rmdir {name:?} of parent ino {parent}");
+
+        let (trash, project_id) = {
+            let vfs = self.vfs.read();
+            match (vfs.find_child(parent, name), vfs.get(parent)) {
+                (Some(trash), Some(FSEntry::Project(FSProject { external_id, .. })))
+                    if name == TRASH_FOLDER =>
+                {
+                    (trash, *external_id)
+                }
+                (None, _) => {
+                    reply.error(ENOENT);
+                    return;
+                }
+                _ => {
+                    reply.error(EACCES);
+                    return;
+                }
+            }
+        };
+
+        let trashed = self.vfs.read().children(trash);
+        for ino in trashed {
+            let scope = self
+                .vfs
+                .read()
+                .get(ino)
+                .and_then(|e| e.get_path())
+                .and_then(|(path, _)| path.file_name())
+                .map(|name| name.to_string_lossy().to_string());
+            if let Some(scope) = scope {
+                if let Err(err) = self.delete_scope(project_id, env_scope(&scope)) {
+                    reply.error(err);
+                    return;
+                }
+            }
+            self.vfs.write().remove(ino);
+        }
+
+        self.vfs.write().remove(trash);
+        reply.ok();
+    }
+
     #[tracing::instrument(level = "debug", skip(self, req, reply))]
     fn fsync(
         &mut self,
@@ -492,12 +1495,27 @@ impl Filesystem for GitlabFS {
     ) {
         trace!(request =? req, "fsync file at ino {ino}");
 
-        let vfs = self.vfs.read();
-        if let Some(FSEntry::File(FSFile { .. })) = vfs.get(Self::ino_to_idx(ino)) {
-            // TODO: Time to write content online
-            reply.ok();
-        } else {
-            reply.error(ENOENT);
+        match self.write_back(ino) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
+        }
+    }
+
+    /// Called on each close of a file descriptor, editors do not always call `fsync` when saving
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn flush(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        _fh: u64,
+        _lock_owner: u64,
+        reply: fuser::ReplyEmpty,
+    ) {
+        trace!(request =? req, "flush file at ino {ino}");
+
+        match self.write_back(ino) {
+            Ok(()) => reply.ok(),
+            Err(err) => reply.error(err),
         }
     }
 
@@ -509,7 +1527,7 @@ impl Filesystem for GitlabFS {
             _mode: Option<u32>,
             _uid: Option<u32>,
             _gid: Option<u32>,
-            _size: Option<u64>,
+            size: Option<u64>,
             atime: Option<fuser::TimeOrNow>,
             mtime: Option<fuser::TimeOrNow>,
             _ctime: Option<SystemTime>,
@@ -523,7 +1541,19 @@ impl Filesystem for GitlabFS {
         trace!(request =? req, "setattr file at ino {ino}");
 
         let mut vfs = self.vfs.write();
-        if let Some(FSEntry::File(FSFile { last_update, last_access, .. })) = vfs.get_mut(Self::ino_to_idx(ino)) {
+        if let Some(FSEntry::File(FSFile { content, last_update, last_access, dirty, read_only, .. })) = vfs.get_mut(ino) {
+            if *read_only && size.is_some() {
+                reply.error(EACCES);
+                return;
+            }
+
+            // NOTE: Truncate the file when it is opened to be overwritten (E.g. `echo "A=b" > dev`)
+            if let Some(size) = size {
+                content.resize(size as usize, 0);
+                *last_update = SystemTime::now();
+                *dirty = true;
+            }
+
             match atime {
                 Some(fuser::TimeOrNow::Now) => {
                     *last_access = SystemTime::now();
@@ -548,6 +1578,135 @@ impl Filesystem for GitlabFS {
             return;
         }
 
-        reply.attr(&TTL, &Self::entry_as_fileattr(&req, vfs.get(Self::ino_to_idx(ino)).unwrap(), ino))
+        reply.attr(&TTL, &Self::entry_as_fileattr(&req, vfs.get(ino).unwrap(), ino))
+    }
+
+    /// List the attributes of each variable of a file (E.g. `getfattr -d project/production`)
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn listxattr(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        size: u32,
+        reply: fuser::ReplyXattr,
+    ) {
+        trace!(request =? req, "listxattr of ino {ino}");
+
+        let vfs = self.vfs.read();
+        let names = match vfs.get(ino) {
+            Some(FSEntry::File(FSFile { variables, .. })) => variables
+                .iter()
+                .flat_map(|var| {
+                    ATTRIBUTES
+                        .iter()
+                        .map(move |attribute| format!("{XATTR_PREFIX}{}.{attribute}\0", var.key))
+                })
+                .collect::<String>(),
+            Some(_) => String::new(),
+            None => {
+                reply.error(ENOENT);
+                return;
+            }
+        };
+
+        Self::reply_xattr(reply, size, names.as_bytes());
+    }
+
+    #[tracing::instrument(level = "debug", skip(self, req, reply))]
+    fn getxattr(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        size: u32,
+        reply: fuser::ReplyXattr,
+    ) {
+        trace!(request =? req, "getxattr {name:?} of ino {ino}");
+
+        let vfs = self.vfs.read();
+        let value = if_chain! {
+            if let Some(FSEntry::File(FSFile { variables, .. })) = vfs.get(ino);
+            if let Some((key, attribute)) = Self::parse_xattr_name(name);
+            if let Some(variable) = variables.iter().find(|var| var.key == key);
+            then {
+                variable.attribute(attribute)
+            } else {
+                None
+            }
+        };
+
+        match value {
+            Some(value) => Self::reply_xattr(reply, size, value.as_bytes()),
+            None => reply.error(ENODATA),
+        }
+    }
+
+    /// Edit an attribute of a variable (E.g. `setfattr -n user.API_KEY.masked -v true project/production`)
+    #[tracing::instrument(level = "debug", skip(self, req, value, reply))]
+    fn setxattr(
+        &mut self,
+        req: &fuser::Request<'_>,
+        ino: u64,
+        name: &OsStr,
+        value: &[u8],
+        _flags: i32,
+        _position: u32,
+        reply: fuser::ReplyEmpty,
+    ) {
+        trace!(request =? req, "setxattr {name:?} of ino {ino}");
+
+        let Some((key, attribute)) = Self::parse_xattr_name(name) else {
+            reply.error(ENOTSUP);
+            return;
+        };
+        let (owner, mut variable) = {
+            let vfs = self.vfs.read();
+            match vfs.get(ino) {
+                Some(FSEntry::File(FSFile {
+                    owner: Some(owner),
+                    variables,
+                    ..
+                })) => match variables.iter().find(|var| var.key == key) {
+                    Some(variable) => (owner.clone(), variable.clone()),
+                    None => {
+                        reply.error(ENODATA);
+                        return;
+                    }
+                },
+                // Generated files (E.g. the effective variables) cannot be edited
+                Some(_) => {
+                    reply.error(EACCES);
+                    return;
+                }
+                None => {
+                    reply.error(ENOENT);
+                    return;
+                }
+            }
+        };
+
+        let value = String::from_utf8_lossy(value);
+        if let Err(err) = variable.set_attribute(attribute, &value) {
+            warn!("Invalid attribute: {err}");
+            reply.error(EINVAL);
+            return;
+        }
+
+        if self.dry_run {
+            info!(?owner, key, "Dry run: would set {attribute} to {value}");
+        } else if let Err(err) =
+            crate::project_env::update_variable_attributes(self.client.as_ref(), &owner, &variable)
+        {
+            error!(?owner, key, "Failed to update the variable: {err}");
+            reply.error(EIO);
+            return;
+        }
+
+        if let Some(FSEntry::File(FSFile { variables, .. })) = self.vfs.write().get_mut(ino) {
+            if let Some(var) = variables.iter_mut().find(|var| var.key == key) {
+                *var = variable;
+            }
+        }
+        reply.ok();
     }
 }
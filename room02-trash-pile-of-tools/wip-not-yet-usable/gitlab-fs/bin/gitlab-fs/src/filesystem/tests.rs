@@ -0,0 +1,457 @@
+//! Tests of the filesystem state without a mount, the mount tests are in `crate::test::filesystem`
+
+use std::{
+    ffi::OsStr,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime},
+};
+
+use http::Method;
+use serde_json::json;
+
+use super::*;
+use crate::test::client::MockGitlab;
+
+/// Two projects of a group, the first one with two environments
+fn mock_gitlab() -> MockGitlab {
+    let client = MockGitlab::new();
+    client
+        .respond(
+            Method::GET,
+            "projects",
+            json!([
+                { "id": 1, "name": "api", "path_with_namespace": "group/api" },
+                { "id": 2, "name": "web", "path_with_namespace": "group/web" },
+            ]),
+        )
+        .respond(
+            Method::GET,
+            "projects/1/variables",
+            json!([
+                { "key": "A", "value": "1", "environment_scope": "production" },
+                { "key": "A", "value": "0", "environment_scope": "staging" },
+            ]),
+        )
+        .respond(Method::GET, "projects/2/variables", json!([]));
+    client
+}
+
+/// The filesystem as it is once mounted, before any project is listed
+fn gitlab_fs(client: MockGitlab) -> GitlabFS<MockGitlab> {
+    let fs = GitlabFS::with_client(client, String::new(), false, Duration::from_secs(60), false);
+    let projects = crate::project::get_projects(fs.client.as_ref(), "").unwrap();
+    *fs.vfs.write() = InodeTable::from_entries(
+        GitlabFS::<MockGitlab>::generate_fs_entries_from_projects(projects).unwrap(),
+    );
+    fs
+}
+
+fn folder(path: &str) -> FSEntry {
+    FSEntry::Folder(FSFolder {
+        path: PathBuf::from(path),
+        inos: Vec::new(),
+        loaded: true,
+    })
+}
+
+fn file(path: &str, content: &str) -> FSEntry {
+    FSEntry::File(GitlabFS::<MockGitlab>::new_generated_file(
+        PathBuf::from(path),
+        content.as_bytes().to_vec(),
+    ))
+}
+
+/// The inode of an entry by its path
+fn ino(vfs: &InodeTable, path: &str) -> Ino {
+    vfs.iter()
+        .find(|(_, entry)| entry.get_path().is_some_and(|(p, _)| p == Path::new(path)))
+        .map(|(ino, _)| ino)
+        .unwrap_or_else(|| panic!("No entry at {path}"))
+}
+
+fn path(vfs: &InodeTable, ino: Ino) -> Option<PathBuf> {
+    vfs.get(ino)
+        .and_then(|entry| entry.get_path())
+        .map(|(path, _)| path.clone())
+}
+
+fn content(vfs: &InodeTable, ino: Ino) -> String {
+    match vfs.get(ino) {
+        Some(FSEntry::File(FSFile { content, .. })) => String::from_utf8(content.clone()).unwrap(),
+        entry => panic!("Not a file: {entry:?}"),
+    }
+}
+
+#[test]
+fn removed_inodes_never_resolve_to_another_entry() {
+    let mut vfs = InodeTable::from_entries(vec![FSEntry::Root { inos: Vec::new() }]);
+    let group = vfs.insert(ROOT_INO, folder("group"));
+    let a = vfs.insert(group, file("group/a", "A=1"));
+    let b = vfs.insert(group, file("group/b", "B=2"));
+
+    assert_eq!(
+        vfs.remove(a)
+            .and_then(|e| e.get_path().map(|(p, _)| p.clone())),
+        Some(PathBuf::from("group/a"))
+    );
+    assert!(vfs.get(a).is_none());
+    assert_eq!(vfs.parent(a), None);
+    assert_eq!(vfs.children(group), [b]);
+    assert_eq!(vfs.find_child(group, OsStr::new("a")), None);
+
+    // A new entry gets a new inode, the removed one stays empty
+    let c = vfs.insert(group, file("group/a", "A=2"));
+    assert_ne!(c, a);
+    assert!(vfs.get(a).is_none());
+    assert_eq!(vfs.find_child(group, OsStr::new("a")), Some(c));
+
+    // The childs are removed along with their parent
+    vfs.remove(group);
+    assert!(vfs.get(group).is_none());
+    assert!(vfs.get(b).is_none());
+    assert!(vfs.get(c).is_none());
+    assert!(vfs.children(ROOT_INO).is_empty());
+    assert_eq!(vfs.iter().count(), 1);
+}
+
+#[test]
+fn rename_reparents_the_entry_and_its_childs() {
+    let mut vfs = InodeTable::from_entries(vec![FSEntry::Root { inos: Vec::new() }]);
+    let a = vfs.insert(ROOT_INO, folder("a"));
+    let b = vfs.insert(ROOT_INO, folder("b"));
+    let sub = vfs.insert(a, folder("a/sub"));
+    let nested = vfs.insert(sub, file("a/sub/production", "A=1"));
+
+    vfs.rename(sub, b, OsStr::new("renamed")).unwrap();
+
+    assert_eq!(vfs.parent(sub), Some(b));
+    assert!(vfs.children(a).is_empty());
+    assert_eq!(vfs.children(b), [sub]);
+    assert_eq!(vfs.find_child(b, OsStr::new("renamed")), Some(sub));
+    assert_eq!(path(&vfs, sub), Some(PathBuf::from("b/renamed")));
+    assert_eq!(
+        path(&vfs, nested),
+        Some(PathBuf::from("b/renamed/production"))
+    );
+    assert_eq!(vfs.parent(nested), Some(sub));
+
+    // Moving to the root drops the parent prefix
+    vfs.rename(nested, ROOT_INO, OsStr::new("top")).unwrap();
+    assert_eq!(vfs.parent(nested), Some(ROOT_INO));
+    assert_eq!(path(&vfs, nested), Some(PathBuf::from("top")));
+    assert!(vfs.children(sub).is_empty());
+
+    assert_eq!(vfs.rename(sub, 42, OsStr::new("x")), Err(ENOENT));
+    assert_eq!(vfs.parent(sub), Some(b));
+}
+
+#[test]
+fn only_loaded_entries_expire() {
+    let mut vfs = InodeTable::from_entries(vec![FSEntry::Root { inos: Vec::new() }]);
+    let group = vfs.insert(ROOT_INO, folder("group"));
+    let loaded = vfs.insert(group, folder("group/loaded"));
+    let never_loaded = vfs.insert(group, folder("group/never-loaded"));
+    assert!(!vfs.is_expired(loaded));
+
+    vfs.set_expiration(loaded, Some(SystemTime::now() + Duration::from_secs(60)));
+    assert!(!vfs.is_expired(loaded));
+
+    assert_eq!(
+        vfs.expire_where(|entry| entry
+            .get_path()
+            .is_some_and(|(p, _)| p == Path::new("group"))),
+        1
+    );
+    assert!(vfs.is_expired(loaded));
+    assert!(!vfs.is_expired(never_loaded));
+    assert!(!vfs.is_expired(group));
+}
+
+#[test]
+fn expired_projects_are_listed_again() {
+    let client = mock_gitlab();
+    let fs = gitlab_fs(client.clone());
+    let project = ino(&fs.vfs.read(), "group/api");
+
+    fs.load_children(project).unwrap();
+    let production = ino(&fs.vfs.read(), "group/api/production");
+    assert_eq!(content(&fs.vfs.read(), production), "A=1");
+    assert!(!fs.vfs.read().is_expired(project));
+
+    client.respond(
+        Method::GET,
+        "projects/1/variables",
+        json!([{ "key": "A", "value": "2", "environment_scope": "production" }]),
+    );
+
+    // Still cached until it expires
+    fs.load_children(project).unwrap();
+    assert_eq!(content(&fs.vfs.read(), production), "A=1");
+
+    fs.vfs
+        .write()
+        .set_expiration(project, Some(SystemTime::UNIX_EPOCH));
+    fs.load_children(project).unwrap();
+
+    let vfs = fs.vfs.read();
+    // The file keeps its inode, the removed environment is gone
+    assert_eq!(ino(&vfs, "group/api/production"), production);
+    assert_eq!(content(&vfs, production), "A=2");
+    assert_eq!(vfs.find_child(project, OsStr::new("staging")), None);
+    assert!(!vfs.is_expired(project));
+}
+
+#[test]
+fn invalidate_project_by_id_and_by_path() {
+    let fs = gitlab_fs(mock_gitlab());
+    let invalidator = fs.invalidator();
+    let (api, web, compare) = {
+        let vfs = fs.vfs.read();
+        (
+            ino(&vfs, "group/api"),
+            ino(&vfs, "group/web"),
+            ino(&vfs, ".compare/group/api"),
+        )
+    };
+    for ino in [api, web, compare] {
+        fs.load_children(ino).unwrap();
+    }
+    let expired = |ino| fs.vfs.read().is_expired(ino);
+
+    invalidator.invalidate_project(&ProjectRef::Id(1));
+    assert!(expired(api));
+    assert!(expired(compare));
+    assert!(!expired(web));
+
+    for ino in [api, compare] {
+        fs.load_children(ino).unwrap();
+    }
+    assert!(!expired(api));
+
+    invalidator.invalidate_project(&ProjectRef::Path("group/api".to_string()));
+    assert!(expired(api));
+    assert!(expired(compare));
+    assert!(!expired(web));
+
+    // An unknown project does not expire anything
+    fs.load_children(api).unwrap();
+    invalidator.invalidate_project(&ProjectRef::Id(3));
+    invalidator.invalidate_project(&ProjectRef::Path("group".to_string()));
+    assert!(!expired(api));
+    assert!(!expired(web));
+}
+
+/// Edit a file as the kernel does before it is flushed
+fn edit(fs: &GitlabFS<MockGitlab>, ino: Ino, content: &str) {
+    match fs.vfs.write().get_mut(ino) {
+        Some(FSEntry::File(file)) => {
+            file.content = content.as_bytes().to_vec();
+            file.dirty = true;
+        }
+        entry => panic!("Not a file: {entry:?}"),
+    }
+}
+
+fn is_dirty(fs: &GitlabFS<MockGitlab>, ino: Ino) -> bool {
+    matches!(
+        fs.vfs.read().get(ino),
+        Some(FSEntry::File(FSFile { dirty: true, .. }))
+    )
+}
+
+/// The names of the childs of an entry, sorted
+fn names(vfs: &InodeTable, parent: Ino) -> Vec<String> {
+    let mut names = vfs
+        .children(parent)
+        .into_iter()
+        .filter_map(|ino| path(vfs, ino))
+        .filter_map(|path| path.file_name().map(|n| n.to_string_lossy().to_string()))
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+fn write_back_applies_the_edited_environment() {
+    let client = mock_gitlab();
+    let fs = gitlab_fs(client.clone());
+    let project = ino(&fs.vfs.read(), "group/api");
+    fs.load_children(project).unwrap();
+    let production = ino(&fs.vfs.read(), "group/api/production");
+
+    edit(&fs, production, "A=2\nC=3\n");
+    fs.write_back(production).unwrap();
+
+    let updated = client.requests(Method::PUT);
+    assert_eq!(updated.len(), 1);
+    assert_eq!(updated[0].endpoint, "projects/1/variables/A");
+    assert!(updated[0].body.contains("value=2"));
+    let created = client.requests(Method::POST);
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].endpoint, "projects/1/variables");
+    assert!(created[0].body.contains("key=C"));
+    assert!(created[0].body.contains("environment_scope=production"));
+    assert!(client.requests(Method::DELETE).is_empty());
+    assert!(!is_dirty(&fs, production));
+
+    // A file that was not edited is not written again
+    fs.write_back(production).unwrap();
+    assert_eq!(client.requests(Method::PUT).len(), 1);
+    assert_eq!(client.requests(Method::POST).len(), 1);
+}
+
+#[test]
+fn write_back_rejects_invalid_files_and_only_logs_in_dry_run() {
+    let client = mock_gitlab();
+    let mut fs = gitlab_fs(client.clone());
+    let project = ino(&fs.vfs.read(), "group/api");
+    fs.load_children(project).unwrap();
+    let production = ino(&fs.vfs.read(), "group/api/production");
+
+    edit(&fs, production, "A=1\nnot a variable");
+    assert_eq!(fs.write_back(production), Err(EINVAL));
+    assert!(is_dirty(&fs, production));
+
+    fs.dry_run = true;
+    edit(&fs, production, "A=2");
+    fs.write_back(production).unwrap();
+    assert!(!is_dirty(&fs, production));
+
+    assert!(client.requests(Method::POST).is_empty());
+    assert!(client.requests(Method::PUT).is_empty());
+    assert!(client.requests(Method::DELETE).is_empty());
+}
+
+#[test]
+fn env_location_finds_the_project_of_environments_and_trashed_ones() {
+    let fs = gitlab_fs(mock_gitlab());
+    let mut vfs = fs.vfs.write();
+    let project = ino(&vfs, "group/api");
+
+    assert_eq!(
+        GitlabFS::<MockGitlab>::env_location(&vfs, project),
+        Some((1, project, false))
+    );
+
+    let trash = GitlabFS::<MockGitlab>::trash_folder(&mut vfs, project);
+    assert_eq!(
+        GitlabFS::<MockGitlab>::trash_folder(&mut vfs, project),
+        trash
+    );
+    assert_eq!(path(&vfs, trash), Some(PathBuf::from("group/api/.trash")));
+    assert_eq!(
+        GitlabFS::<MockGitlab>::env_location(&vfs, trash),
+        Some((1, project, true))
+    );
+
+    // Neither a group, a view nor a missing entry hold environments
+    for parent in [
+        ino(&vfs, "group"),
+        ino(&vfs, ".compare/group/api"),
+        ROOT_INO,
+        1000,
+    ] {
+        assert_eq!(GitlabFS::<MockGitlab>::env_location(&vfs, parent), None);
+    }
+}
+
+#[test]
+fn views_are_generated_from_gitlab() {
+    let client = mock_gitlab();
+    client
+        .respond(
+            Method::GET,
+            "projects/1/jobs",
+            json!([{ "id": 7, "status": "failed" }]),
+        )
+        .respond(
+            Method::GET,
+            "projects/1/jobs/7",
+            json!({ "id": 7, "status": "failed" }),
+        )
+        .fail(Method::GET, "projects/1/pipelines");
+    let fs = gitlab_fs(client.clone());
+    let project = ino(&fs.vfs.read(), "group/api");
+    fs.load_children(project).unwrap();
+
+    let compare = fs
+        .generate_view_entries(
+            Path::new(".compare/group/api"),
+            &ViewKind::Compare { external_id: 1 },
+        )
+        .unwrap();
+    let compare = compare
+        .iter()
+        .map(|entry| match entry {
+            FSEntry::File(file) => (
+                file.path.clone(),
+                String::from_utf8(file.content.clone()).unwrap(),
+                file.read_only,
+            ),
+            entry => panic!("Not a file: {entry:?}"),
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        compare,
+        [
+            (
+                PathBuf::from(".compare/group/api/production..staging.diff"),
+                "--- production\n+++ staging\n-A=1\n+A=0\n".to_string(),
+                true,
+            ),
+            (
+                PathBuf::from(".compare/group/api/staging..production.diff"),
+                "--- staging\n+++ production\n-A=0\n+A=1\n".to_string(),
+                true,
+            ),
+        ]
+    );
+
+    let matrix = ino(&fs.vfs.read(), ".matrix/group");
+    fs.load_children(matrix).unwrap();
+    {
+        let vfs = fs.vfs.read();
+        assert_eq!(names(&vfs, matrix), ["A"]);
+        assert_eq!(
+            content(&vfs, ino(&vfs, ".matrix/group/A")),
+            "group/api\tproduction\t1\ngroup/api\tstaging\t0\n"
+        );
+    }
+
+    let jobs = ino(&fs.vfs.read(), "group/api/jobs");
+    fs.load_children(jobs).unwrap();
+    let job = ino(&fs.vfs.read(), "group/api/jobs/7");
+    fs.load_children(job).unwrap();
+    {
+        let vfs = fs.vfs.read();
+        assert_eq!(names(&vfs, job), ["status", "trace.log"]);
+        assert_eq!(
+            content(&vfs, ino(&vfs, "group/api/jobs/7/status")),
+            "failed\n"
+        );
+        // The log is only fetched when it is read
+        assert!(matches!(
+            vfs.get(ino(&vfs, "group/api/jobs/7/trace.log")),
+            Some(FSEntry::File(FSFile {
+                lazy: Some(LazyContent::JobTrace {
+                    external_id: 1,
+                    job_id: 7
+                }),
+                ..
+            }))
+        ));
+    }
+    assert!(!client
+        .requests(Method::GET)
+        .iter()
+        .any(|request| request.endpoint.ends_with("/trace")));
+
+    // A view that cannot be generated stays empty and is generated again on the next access
+    let pipelines = ino(&fs.vfs.read(), "group/api/pipelines");
+    assert_eq!(fs.load_children(pipelines), Err(EIO));
+    assert!(matches!(
+        fs.vfs.read().get(pipelines),
+        Some(FSEntry::View(FSView { loaded: false, .. }))
+    ));
+    assert!(fs.vfs.read().children(pipelines).is_empty());
+}
@@ -0,0 +1,61 @@
+use std::path::PathBuf;
+
+use log_utils::{info, tracing};
+
+use super::{
+    constants::{COMPARE_FOLDER, MATRIX_FOLDER},
+    entry::{FSFolder, FSProject, FSView, ViewKind},
+};
+
+/// Generate the views mirroring the folders and projects under the `.compare` and `.matrix` folders
+#[tracing::instrument(level = "debug", skip_all)]
+pub fn generate_views_from_projects(
+    folders: &[FSFolder],
+    projects: &[FSProject],
+) -> (Vec<FSFolder>, Vec<FSView>) {
+    let compare = PathBuf::from(COMPARE_FOLDER);
+    let matrix = PathBuf::from(MATRIX_FOLDER);
+    // NOTE: The view folders are not groups, there is no variable to load
+    let folder = |path: PathBuf| FSFolder {
+        path,
+        inos: Vec::new(),
+        loaded: true,
+    };
+
+    // The projects are compared in a copy of the folders arborescence
+    let view_folders = [folder(compare.clone()), folder(matrix.clone())]
+        .into_iter()
+        .chain(folders.iter().map(|f| folder(compare.join(&f.path))))
+        .collect::<Vec<_>>();
+
+    let compare_views = projects.iter().map(|p| FSView {
+        path: compare.join(&p.path),
+        inos: Vec::new(),
+        kind: ViewKind::Compare {
+            external_id: p.external_id,
+        },
+        loaded: false,
+    });
+    // A group matrix includes the projects of its sub groups
+    let matrix_views = folders.iter().map(|f| FSView {
+        path: matrix.join(&f.path),
+        inos: Vec::new(),
+        kind: ViewKind::Matrix {
+            projects: projects
+                .iter()
+                .filter(|p| p.path.starts_with(&f.path))
+                .map(|p| (p.external_id, p.path.clone()))
+                .collect(),
+        },
+        loaded: false,
+    });
+    let views = compare_views.chain(matrix_views).collect::<Vec<_>>();
+
+    info!(
+        "Generated {} views in {} folders",
+        views.len(),
+        view_folders.len()
+    );
+
+    (view_folders, views)
+}
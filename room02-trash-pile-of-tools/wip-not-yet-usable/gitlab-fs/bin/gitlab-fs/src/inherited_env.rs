@@ -0,0 +1,63 @@
+use std::collections::BTreeMap;
+
+use gitlab::api::{
+    self, admin::ci::variables::InstanceVariables, groups::variables::GroupVariables, Query,
+};
+use log_utils::{debug, tracing};
+
+use crate::{client::GitlabClient, project_env::Variable, Result};
+
+/// Get the variables of a group (not including its parent groups)
+#[tracing::instrument]
+pub fn get_group_env<C: GitlabClient>(client: &C, group_path: &str) -> Result<Vec<Variable>> {
+    let result = api::paged(
+        GroupVariables::builder().group(group_path).build()?,
+        api::Pagination::All,
+    )
+    .query(client)?;
+
+    debug!(variables=?result);
+
+    Ok(result)
+}
+
+/// Get the instance variables (only available with an administrator token)
+#[tracing::instrument]
+pub fn get_instance_env<C: GitlabClient>(client: &C) -> Result<Vec<Variable>> {
+    let result =
+        api::paged(InstanceVariables::builder().build()?, api::Pagination::All).query(client)?;
+
+    debug!(variables=?result);
+
+    Ok(result)
+}
+
+/// Whether a variable scope applies to an environment (E.g. `review/*` applies to `review/feature`)
+pub fn scope_matches(scope: &str, environment: &str) -> bool {
+    match scope.strip_suffix('*') {
+        Some(prefix) => environment.starts_with(prefix),
+        None => scope == environment,
+    }
+}
+
+/// Merge the variables applying to an environment, each layer overrides the previous ones
+/// (E.g. instance → group → sub group → project)
+/// NOTE: Inside a layer the most specific scope wins, so `production` overrides `*`
+#[tracing::instrument(level = "debug", skip(layers))]
+pub fn effective_variables(layers: &[&[Variable]], environment: &str) -> Vec<Variable> {
+    let mut effective = BTreeMap::new();
+
+    for layer in layers {
+        let mut matching = layer
+            .iter()
+            .filter(|var| scope_matches(&var.environment_scope, environment))
+            .collect::<Vec<_>>();
+        // Apply the wildcards first so the exact scopes override them
+        matching.sort_by_key(|var| (var.environment_scope != "*", var.environment_scope.len()));
+        for var in matching {
+            effective.insert(var.key.clone(), var.clone());
+        }
+    }
+
+    effective.into_values().collect()
+}
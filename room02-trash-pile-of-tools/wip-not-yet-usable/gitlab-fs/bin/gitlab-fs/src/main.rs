@@ -1,13 +1,21 @@
-use std::{env::set_var, fs::create_dir_all, path::Path};
+use std::{env::set_var, fs::create_dir_all, path::Path, time::Duration};
 
 use clap::Parser;
 use eyre::Result;
 use fuser::MountOption;
 use log_utils::info;
 
+mod ci;
+mod client;
+mod compare;
 mod filesystem;
+mod inherited_env;
 mod project;
 mod project_env;
+mod repository;
+#[cfg(test)]
+mod test;
+mod webhook;
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
@@ -22,6 +30,21 @@ struct Cli {
     /// -v for info ; -vv for debug ; -vvv for trace (shortcut to set LOG_LEVEL)
     #[clap(short, long = "verbose", action = clap::ArgAction::Count)]
     verbosity: u8,
+    /// Only log the changes made to the variables instead of writing them to Gitlab
+    #[clap(long, env)]
+    dry_run: bool,
+    /// Create a Gitlab environment for each new environment file
+    #[clap(long, env)]
+    create_environments: bool,
+    /// How many seconds the projects, groups and views are kept before being fetched again
+    #[clap(long, env, default_value_t = 60)]
+    refresh_ttl: u64,
+    /// Listen for Gitlab webhooks on this address (E.g. `0.0.0.0:8080`) to refresh the projects as soon as they change
+    #[clap(long, env, requires = "webhook_secret")]
+    webhook_listen: Option<String>,
+    /// The secret token configured on the Gitlab webhooks (required to listen for them)
+    #[clap(long, env)]
+    webhook_secret: Option<String>,
     /// The Gitlab query to filter projects
     #[clap(default_value = "")]
     query: String,
@@ -43,7 +66,20 @@ fn main() -> Result<()> {
     // Setup the logger (tracing)
     log_utils::setup_simple_logger();
 
-    let fs = filesystem::GitlabFS::new(app.gitlab_host, app.gitlab_access_token, app.query);
+    let fs = filesystem::GitlabFS::new(
+        app.gitlab_host,
+        app.gitlab_access_token,
+        app.query,
+        app.dry_run,
+        Duration::from_secs(app.refresh_ttl),
+        app.create_environments,
+    );
+
+    // The listener runs as long as the process
+    // NOTE: clap requires the secret with the listener
+    if let (Some(address), Some(secret)) = (&app.webhook_listen, &app.webhook_secret) {
+        webhook::listen(address, secret.clone(), fs.invalidator())?;
+    }
 
     // Mount the filesystem with a custom name "gitlabfs" as Read and Write
     let fuse_args = [MountOption::FSName("gitlabfs".to_string()), MountOption::RW];
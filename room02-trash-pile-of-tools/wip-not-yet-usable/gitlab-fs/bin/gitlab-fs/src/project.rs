@@ -1,11 +1,8 @@
-use gitlab::{
-    api::{self, projects::Projects, Query},
-    Gitlab,
-};
+use gitlab::api::{self, projects::Projects, Query};
 use log_utils::{debug, tracing};
 use serde::Deserialize;
 
-use crate::Result;
+use crate::{client::GitlabClient, Result};
 
 #[derive(Debug, Deserialize)]
 pub struct Project {
@@ -17,7 +14,7 @@ pub struct Project {
 
 // Get all projects matching a query
 #[tracing::instrument]
-pub fn get_projects(client: &Gitlab, query: &str) -> Result<Vec<Project>> {
+pub fn get_projects<C: GitlabClient>(client: &C, query: &str) -> Result<Vec<Project>> {
     let result = api::paged(
         Projects::builder()
             .search(query)
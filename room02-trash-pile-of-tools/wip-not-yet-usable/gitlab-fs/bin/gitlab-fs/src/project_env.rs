@@ -1,24 +1,61 @@
-use std::{collections::HashMap, fmt::Display};
+use std::{borrow::Cow, collections::HashMap, fmt::Display};
 
-use gitlab::{
-    api::{self, projects::variables::ProjectVariables, Query},
-    Gitlab,
+use eyre::eyre;
+use gitlab::api::{
+    self,
+    groups::variables::UpdateGroupVariable,
+    projects::environments::CreateEnvironment,
+    projects::variables::{
+        CreateProjectVariable, DeleteProjectVariable, ProjectVariableFilter, ProjectVariableType,
+        ProjectVariables, UpdateProjectVariable,
+    },
+    Query,
 };
-use log_utils::{debug, tracing};
+use log_utils::{debug, error, info, tracing};
 use serde::Deserialize;
 
-use crate::Result;
+use crate::{client::GitlabClient, Result};
 
-#[derive(Debug, Deserialize)]
+/// The attributes of a variable exposed as extended attributes (E.g. `user.API_KEY.masked`)
+pub const ATTRIBUTES: [&str; 4] = ["protected", "masked", "raw", "variable_type"];
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
 pub struct Variable {
     pub key: String,
     pub value: String,
+    // NOTE: Instance variables do not have an environment scope, they apply to all environments
+    #[serde(default = "default_environment_scope")]
     pub environment_scope: String,
+    #[serde(default)]
+    pub protected: bool,
+    #[serde(default)]
+    pub masked: bool,
+    #[serde(default)]
+    pub raw: bool,
+    /// Either `env_var` or `file`
+    #[serde(default = "default_variable_type")]
+    pub variable_type: String,
+}
+
+fn default_environment_scope() -> String {
+    "*".to_string()
+}
+
+fn default_variable_type() -> String {
+    "env_var".to_string()
+}
+
+/// Where a variable is defined in Gitlab
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariablesOwner {
+    Project(u32),
+    /// A group by its full path
+    Group(String),
 }
 
 /// Get the project env variables
 #[tracing::instrument]
-pub fn get_project_env(client: &Gitlab, project_id: u32) -> Result<Vec<Variable>> {
+pub fn get_project_env<C: GitlabClient>(client: &C, project_id: u32) -> Result<Vec<Variable>> {
     let result = api::paged(
         ProjectVariables::builder()
             .project(project_id as u64)
@@ -32,35 +69,427 @@ pub fn get_project_env(client: &Gitlab, project_id: u32) -> Result<Vec<Variable>
     Ok(result)
 }
 
-/// Map the env variables to a list of (env_name, env_file)
+/// Map the env variables to a list of (env_name, variables of the env)
 #[tracing::instrument]
-pub fn variables_to_env(variables: Result<Vec<Variable>>) -> Vec<(String, String)> {
+pub fn variables_to_env(variables: Result<Vec<Variable>>) -> Vec<(String, Vec<Variable>)> {
     variables.map_or_else(
         |_| Vec::new(),
         |vars| {
-            let envs = vars.iter().fold(
-                HashMap::new() as HashMap<String, Vec<String>>,
+            let envs = vars.into_iter().fold(
+                HashMap::new() as HashMap<String, Vec<Variable>>,
                 |mut acc, var| {
-                    if let Some(env) = acc.get_mut(&var.environment_scope) {
-                        env.push(var.to_string());
-                    } else {
-                        acc.insert(var.environment_scope.clone(), vec![var.to_string()]);
-                    }
+                    acc.entry(var.environment_scope.clone())
+                        .or_default()
+                        .push(var);
                     acc
                 },
             );
-            envs.into_iter()
-                .map(|(k, mut v)| {
-                    v.sort();
-                    (k, v.join("\n"))
-                })
-                .collect::<Vec<_>>()
+            envs.into_iter().collect::<Vec<_>>()
         },
     )
 }
 
+/// Generate the content of an env file (`KEY=value` lines sorted by key)
+/// NOTE: Multiline values are quoted so that each variable stays on one line
+pub fn env_file(variables: &[Variable]) -> String {
+    let mut lines = variables
+        .iter()
+        .map(|var| var.to_string())
+        .collect::<Vec<_>>();
+    lines.sort();
+    lines.join("\n")
+}
+
 impl Display for Variable {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}={}", self.key, self.value)
+        write!(f, "{}={}", self.key, quote_value(&self.value))
+    }
+}
+
+/// Quote a value that could not be read back as is from an env file (E.g. `"line 1\nline 2"`)
+fn quote_value(value: &str) -> Cow<'_, str> {
+    if !value.contains(['\n', '\r']) && !value.starts_with('"') {
+        return Cow::Borrowed(value);
+    }
+
+    let mut quoted = String::with_capacity(value.len() + 2);
+    quoted.push('"');
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+
+    Cow::Owned(quoted)
+}
+
+/// Read a value quoted by `quote_value`, from after its opening quote
+/// NOTE: The quoted value may also span several lines when edited by hand
+fn unquote_value<'a>(
+    key: &str,
+    mut line: &'a str,
+    lines: &mut impl Iterator<Item = &'a str>,
+) -> Result<String> {
+    let mut value = String::new();
+    loop {
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '"' if chars.as_str().trim().is_empty() => return Ok(value),
+                '"' => return Err(eyre!("Unexpected text after the quoted value of {key}")),
+                '\\' => match chars.next() {
+                    Some('n') => value.push('\n'),
+                    Some('r') => value.push('\r'),
+                    Some(c @ ('\\' | '"')) => value.push(c),
+                    Some(c) => {
+                        value.push('\\');
+                        value.push(c);
+                    }
+                    None => value.push('\\'),
+                },
+                c => value.push(c),
+            }
+        }
+
+        line = lines
+            .next()
+            .ok_or_else(|| eyre!("Unterminated quoted value of {key}"))?;
+        value.push('\n');
+    }
+}
+
+impl Variable {
+    /// Get an attribute of the variable as text
+    pub fn attribute(&self, name: &str) -> Option<String> {
+        match name {
+            "protected" => Some(self.protected.to_string()),
+            "masked" => Some(self.masked.to_string()),
+            "raw" => Some(self.raw.to_string()),
+            "variable_type" => Some(self.variable_type.clone()),
+            _ => None,
+        }
+    }
+
+    /// Set an attribute of the variable from text
+    pub fn set_attribute(&mut self, name: &str, value: &str) -> Result<()> {
+        let as_bool = || {
+            value
+                .trim()
+                .parse::<bool>()
+                .map_err(|_err| eyre!("Invalid value for {name}, expected true or false: {value}"))
+        };
+        match name {
+            "protected" => self.protected = as_bool()?,
+            "masked" => self.masked = as_bool()?,
+            "raw" => self.raw = as_bool()?,
+            "variable_type" => match value.trim() {
+                variable_type @ ("env_var" | "file") => {
+                    self.variable_type = variable_type.to_string();
+                }
+                _ => {
+                    return Err(eyre!(
+                        "Invalid variable type, expected env_var or file: {value}"
+                    ))
+                }
+            },
+            _ => return Err(eyre!("Unknown attribute {name}")),
+        }
+        Ok(())
+    }
+
+    fn project_variable_type(&self) -> ProjectVariableType {
+        if self.variable_type == "file" {
+            ProjectVariableType::File
+        } else {
+            ProjectVariableType::EnvVar
+        }
+    }
+}
+
+/// Write all the attributes of a variable to Gitlab
+#[tracing::instrument(skip(client))]
+pub fn update_variable_attributes<C: GitlabClient>(
+    client: &C,
+    owner: &VariablesOwner,
+    variable: &Variable,
+) -> Result<()> {
+    let filter = ProjectVariableFilter::builder()
+        .environment_scope(variable.environment_scope.as_str())
+        .build()?;
+
+    info!(?owner, key = variable.key, "Updating variable attributes");
+    match owner {
+        VariablesOwner::Project(project_id) => api::ignore(
+            UpdateProjectVariable::builder()
+                .project(*project_id as u64)
+                .key(variable.key.as_str())
+                .value(variable.value.as_str())
+                .variable_type(variable.project_variable_type())
+                .protected(variable.protected)
+                .masked(variable.masked)
+                .raw(variable.raw)
+                .filter(filter)
+                .build()?,
+        )
+        .query(client)?,
+        VariablesOwner::Group(group_path) => api::ignore(
+            UpdateGroupVariable::builder()
+                .group(group_path.as_str())
+                .key(variable.key.as_str())
+                .value(variable.value.as_str())
+                .variable_type(variable.project_variable_type())
+                .protected(variable.protected)
+                .masked(variable.masked)
+                .raw(variable.raw)
+                .filter(filter)
+                .build()?,
+        )
+        .query(client)?,
+    }
+
+    Ok(())
+}
+
+/// A change to apply on the variables of an environment scope
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VariableChange {
+    Create { key: String, value: String },
+    Update { key: String, value: String },
+    Delete { key: String },
+}
+
+impl Display for VariableChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Create { key, .. } => write!(f, "create {key}"),
+            Self::Update { key, .. } => write!(f, "update {key}"),
+            Self::Delete { key } => write!(f, "delete {key}"),
+        }
     }
 }
+
+/// Parse an env file generated by `env_file` back to a list of (key, value)
+/// NOTE: Values starting with a quote are unquoted, the other ones are kept as is
+#[tracing::instrument(level = "debug", skip(content))]
+pub fn env_to_variables(content: &str) -> Result<Vec<(String, String)>> {
+    let is_key =
+        |key: &str| !key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+
+    let mut variables: Vec<(String, String)> = Vec::new();
+    let mut lines = content.lines();
+    while let Some(line) = lines.next() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((key, value)) = line.split_once('=').filter(|(key, _)| is_key(key)) else {
+            return Err(eyre!("Invalid line, expected KEY=value: {line}"));
+        };
+        let value = match value.strip_prefix('"') {
+            Some(quoted) => unquote_value(key, quoted, &mut lines)?,
+            None => value.to_string(),
+        };
+        variables.push((key.to_string(), value));
+    }
+
+    // Keys are unique in an environment scope
+    let mut keys = variables.iter().map(|(key, _)| key).collect::<Vec<_>>();
+    keys.sort();
+    if let Some(key) = keys.windows(2).find(|w| w[0] == w[1]).map(|w| w[0]) {
+        return Err(eyre!("Duplicated variable {key}"));
+    }
+
+    Ok(variables)
+}
+
+/// Compute the changes needed to go from the current variables of a scope to the wanted ones
+pub fn diff_env(
+    current: &[Variable],
+    environment_scope: &str,
+    wanted: &[(String, String)],
+) -> Vec<VariableChange> {
+    let current = current
+        .iter()
+        .filter(|var| var.environment_scope == environment_scope)
+        .map(|var| (var.key.as_str(), var.value.as_str()))
+        .collect::<HashMap<_, _>>();
+
+    let mut changes = wanted
+        .iter()
+        .filter_map(|(key, value)| match current.get(key.as_str()) {
+            None => Some(VariableChange::Create {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(current_value) if current_value != value => Some(VariableChange::Update {
+                key: key.clone(),
+                value: value.clone(),
+            }),
+            Some(_) => None,
+        })
+        .collect::<Vec<_>>();
+
+    let mut deleted = current
+        .keys()
+        .filter(|key| !wanted.iter().any(|(wanted_key, _)| wanted_key == *key))
+        .collect::<Vec<_>>();
+    deleted.sort();
+    changes.extend(deleted.into_iter().map(|key| VariableChange::Delete {
+        key: key.to_string(),
+    }));
+
+    changes
+}
+
+/// Apply the changes on the variables of an environment scope of a project
+#[tracing::instrument(skip(client))]
+pub fn apply_env_changes<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    environment_scope: &str,
+    changes: &[VariableChange],
+) -> Result<()> {
+    let filter = || {
+        ProjectVariableFilter::builder()
+            .environment_scope(environment_scope)
+            .build()
+    };
+
+    for change in changes {
+        info!(project_id, environment_scope, "Applying {change}");
+        match change {
+            VariableChange::Create { key, value } => api::ignore(
+                CreateProjectVariable::builder()
+                    .project(project_id as u64)
+                    .key(key.as_str())
+                    .value(value.as_str())
+                    .environment_scope(environment_scope)
+                    .build()?,
+            )
+            .query(client)?,
+            VariableChange::Update { key, value } => api::ignore(
+                UpdateProjectVariable::builder()
+                    .project(project_id as u64)
+                    .key(key.as_str())
+                    .value(value.as_str())
+                    .filter(filter()?)
+                    .build()?,
+            )
+            .query(client)?,
+            VariableChange::Delete { key } => api::ignore(
+                DeleteProjectVariable::builder()
+                    .project(project_id as u64)
+                    .key(key.as_str())
+                    .filter(filter()?)
+                    .build()?,
+            )
+            .query(client)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Move all the variables of an environment scope of a project to another scope
+/// NOTE: The variables already moved are moved back if one fails, Gitlab has no transaction
+#[tracing::instrument(skip(client))]
+pub fn move_env_scope<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    let current = get_project_env(client, project_id)?;
+    // NOTE: Do not merge two scopes, a variable could be overwritten
+    if current.iter().any(|var| var.environment_scope == to) {
+        return Err(eyre!("The environment scope {to} already has variables"));
+    }
+
+    let moved = current
+        .iter()
+        .filter(|var| var.environment_scope == from)
+        .collect::<Vec<_>>();
+    for (index, variable) in moved.iter().enumerate() {
+        info!(
+            project_id,
+            key = variable.key,
+            "Moving variable from {from} to {to}"
+        );
+        if let Err(err) = move_variable(client, project_id, variable, from, to) {
+            for variable in moved[..index].iter().rev() {
+                info!(
+                    project_id,
+                    key = variable.key,
+                    "Moving variable back from {to} to {from}"
+                );
+                if let Err(rollback_err) = move_variable(client, project_id, variable, to, from) {
+                    error!(
+                        project_id,
+                        key = variable.key,
+                        "Failed to move the variable back to {from}: {rollback_err}"
+                    );
+                }
+            }
+            return Err(err);
+        }
+    }
+
+    Ok(())
+}
+
+/// Move a variable of a project from an environment scope to another one
+fn move_variable<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    variable: &Variable,
+    from: &str,
+    to: &str,
+) -> Result<()> {
+    api::ignore(
+        UpdateProjectVariable::builder()
+            .project(project_id as u64)
+            .key(variable.key.as_str())
+            .value(variable.value.as_str())
+            .environment_scope(to)
+            .filter(
+                ProjectVariableFilter::builder()
+                    .environment_scope(from)
+                    .build()?,
+            )
+            .build()?,
+    )
+    .query(client)?;
+
+    Ok(())
+}
+
+/// Delete all the variables of an environment scope of a project
+#[tracing::instrument(skip(client))]
+pub fn delete_env_scope<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    environment_scope: &str,
+) -> Result<()> {
+    let current = get_project_env(client, project_id)?;
+    let changes = diff_env(&current, environment_scope, &[]);
+    apply_env_changes(client, project_id, environment_scope, &changes)
+}
+
+/// Create a Gitlab environment (used by the deployments) in a project
+#[tracing::instrument(skip(client))]
+pub fn create_environment<C: GitlabClient>(client: &C, project_id: u32, name: &str) -> Result<()> {
+    info!(project_id, "Creating environment {name}");
+    api::ignore(
+        CreateEnvironment::builder()
+            .project(project_id as u64)
+            .name(name)
+            .build()?,
+    )
+    .query(client)?;
+
+    Ok(())
+}
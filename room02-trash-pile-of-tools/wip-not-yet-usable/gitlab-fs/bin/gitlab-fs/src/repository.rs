@@ -0,0 +1,79 @@
+use gitlab::api::{
+    self,
+    projects::repository::{branches::Branches, files::FileRaw, Tree},
+    Query,
+};
+use log_utils::{debug, tracing};
+use serde::Deserialize;
+
+use crate::{client::GitlabClient, Result};
+
+#[derive(Debug, Deserialize)]
+pub struct Branch {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TreeEntry {
+    pub name: String,
+    pub path: String,
+    /// Either `tree` (a folder), `blob` (a file) or `commit` (a submodule)
+    #[serde(rename = "type")]
+    pub kind: String,
+}
+
+/// Get the branches of a project repository
+#[tracing::instrument]
+pub fn get_branches<C: GitlabClient>(client: &C, project_id: u32) -> Result<Vec<Branch>> {
+    let result = api::paged(
+        Branches::builder().project(project_id as u64).build()?,
+        api::Pagination::All,
+    )
+    .query(client)?;
+
+    debug!(branches=?result);
+
+    Ok(result)
+}
+
+/// Get the content of a folder of a project repository at a ref
+#[tracing::instrument]
+pub fn get_tree<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    ref_: &str,
+    path: &str,
+) -> Result<Vec<TreeEntry>> {
+    let mut tree = Tree::builder();
+    tree.project(project_id as u64).ref_(ref_);
+    if !path.is_empty() {
+        tree.path(path);
+    }
+    let result = api::paged(tree.build()?, api::Pagination::All).query(client)?;
+
+    debug!(tree=?result);
+
+    Ok(result)
+}
+
+/// Get the raw content of a file of a project repository at a ref
+#[tracing::instrument]
+pub fn get_file<C: GitlabClient>(
+    client: &C,
+    project_id: u32,
+    ref_: &str,
+    path: &str,
+) -> Result<Vec<u8>> {
+    let result = api::raw(
+        FileRaw::builder()
+            .project(project_id as u64)
+            .file_path(path)
+            .ref_(ref_)
+            .build()?,
+    )
+    .query(client)?;
+
+    debug!("Got {} bytes", result.len());
+
+    Ok(result)
+}
@@ -0,0 +1,8 @@
+//! Offline tests of the filesystem, mounted against a scripted Gitlab client
+
+pub mod client;
+
+mod compare;
+mod filesystem;
+mod project_env;
+mod webhook;
@@ -0,0 +1,138 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use bytes::Bytes;
+use gitlab::api::{ApiError, Client, RestClient};
+use http::{request::Builder as RequestBuilder, Method, Response, StatusCode};
+use serde::Serialize;
+use thiserror::Error;
+use url::Url;
+
+const API_PREFIX: &str = "/api/v4/";
+
+/// The scripted bodies by method and endpoint
+type Responses = HashMap<(Method, String), Vec<u8>>;
+
+#[derive(Debug, Error)]
+#[error("mock gitlab client error")]
+pub struct MockError;
+
+/// A request received by the mock, the endpoint is without the API prefix and the query
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecordedRequest {
+    pub method: Method,
+    pub endpoint: String,
+    pub body: String,
+}
+
+/// A Gitlab client answering with scripted responses and recording every request.
+/// Unscripted reads answer `404 Not Found`, unscripted writes succeed with an empty object.
+#[derive(Debug, Clone, Default)]
+pub struct MockGitlab {
+    responses: Arc<Mutex<Responses>>,
+    failures: Arc<Mutex<HashSet<(Method, String)>>>,
+    requests: Arc<Mutex<Vec<RecordedRequest>>>,
+}
+
+impl MockGitlab {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answer a request on an endpoint (E.g. `projects/1/variables`) with a JSON body
+    pub fn respond<T: Serialize>(&self, method: Method, endpoint: &str, body: T) -> &Self {
+        let body = serde_json::to_vec(&body).expect("Invalid mock response");
+        self.respond_raw(method, endpoint, body)
+    }
+
+    /// Answer a request on an endpoint with a raw body (E.g. a file of a repository)
+    pub fn respond_raw(&self, method: Method, endpoint: &str, body: impl Into<Vec<u8>>) -> &Self {
+        self.responses
+            .lock()
+            .unwrap()
+            .insert((method, endpoint.to_string()), body.into());
+        self
+    }
+
+    /// Answer a request on an endpoint with `500 Internal Server Error`
+    pub fn fail(&self, method: Method, endpoint: &str) -> &Self {
+        self.failures
+            .lock()
+            .unwrap()
+            .insert((method, endpoint.to_string()));
+        self
+    }
+
+    /// The requests received with a method, in order
+    pub fn requests(&self, method: Method) -> Vec<RecordedRequest> {
+        self.requests
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|request| request.method == method)
+            .cloned()
+            .collect()
+    }
+}
+
+impl RestClient for MockGitlab {
+    type Error = MockError;
+
+    fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+        Ok(Url::parse(&format!(
+            "https://gitlab.test{API_PREFIX}{endpoint}"
+        ))?)
+    }
+}
+
+impl Client for MockGitlab {
+    fn rest(
+        &self,
+        request: RequestBuilder,
+        body: Vec<u8>,
+    ) -> Result<Response<Bytes>, ApiError<Self::Error>> {
+        let request = request.body(body).expect("Invalid request");
+        let method = request.method().clone();
+        let endpoint = request
+            .uri()
+            .path()
+            .trim_start_matches(API_PREFIX)
+            .to_string();
+        self.requests.lock().unwrap().push(RecordedRequest {
+            method: method.clone(),
+            endpoint: endpoint.clone(),
+            body: String::from_utf8_lossy(request.body()).to_string(),
+        });
+
+        let failed = self
+            .failures
+            .lock()
+            .unwrap()
+            .contains(&(method.clone(), endpoint.clone()));
+        let scripted = self
+            .responses
+            .lock()
+            .unwrap()
+            .get(&(method.clone(), endpoint))
+            .cloned();
+        let (status, body) = match scripted {
+            _ if failed => (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                br#"{"message":"500 Internal Server Error"}"#.to_vec(),
+            ),
+            Some(body) => (StatusCode::OK, body),
+            None if method == Method::GET => (
+                StatusCode::NOT_FOUND,
+                br#"{"message":"404 Not Found"}"#.to_vec(),
+            ),
+            None => (StatusCode::OK, b"{}".to_vec()),
+        };
+
+        Ok(Response::builder()
+            .status(status)
+            .body(Bytes::from(body))
+            .expect("Invalid response"))
+    }
+}
@@ -0,0 +1,120 @@
+use std::path::PathBuf;
+
+use crate::{
+    compare::{compare_envs, matrix},
+    project_env::Variable,
+};
+
+fn variable(scope: &str, key: &str, value: &str) -> Variable {
+    Variable {
+        key: key.to_string(),
+        value: value.to_string(),
+        environment_scope: scope.to_string(),
+        protected: false,
+        masked: false,
+        raw: false,
+        variable_type: "env_var".to_string(),
+    }
+}
+
+#[test]
+fn each_pair_of_environments_is_compared() {
+    let variables = [
+        variable("production", "HOST", "example.org"),
+        variable("production", "DEBUG", "false"),
+        variable("production", "CERT", "line 1\nline 2"),
+        variable("staging", "HOST", "example.org"),
+        variable("staging", "DEBUG", "true"),
+        variable("staging", "SEED", "42"),
+    ];
+
+    let diffs = compare_envs(&variables);
+
+    assert_eq!(
+        diffs,
+        [
+            (
+                "production..staging.diff".to_string(),
+                "--- production\n+++ staging\n-CERT=line 1\\nline 2\n-DEBUG=false\n+DEBUG=true\n HOST=example.org\n+SEED=42\n"
+                    .to_string()
+            ),
+            (
+                "staging..production.diff".to_string(),
+                "--- staging\n+++ production\n+CERT=line 1\\nline 2\n-DEBUG=true\n+DEBUG=false\n HOST=example.org\n-SEED=42\n"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn a_single_environment_has_nothing_to_compare() {
+    assert!(compare_envs(&[variable("production", "HOST", "example.org")]).is_empty());
+    assert!(compare_envs(&[]).is_empty());
+}
+
+#[test]
+fn scopes_with_slashes_are_escaped() {
+    let variables = [
+        variable("review/*", "HOST", "review.example.org"),
+        variable("production", "HOST", "example.org"),
+    ];
+
+    let names = compare_envs(&variables)
+        .into_iter()
+        .map(|(name, diff)| {
+            assert!(diff.starts_with("--- "));
+            name
+        })
+        .collect::<Vec<_>>();
+
+    assert_eq!(
+        names,
+        ["production..review%2F*.diff", "review%2F*..production.diff"]
+    );
+    // The content keeps the real scope
+    let diffs = compare_envs(&variables);
+    assert!(diffs[1].1.starts_with("--- review/*\n+++ production\n"));
+}
+
+#[test]
+fn the_matrix_lists_each_key_in_each_environment_of_each_project() {
+    let projects = [
+        (
+            PathBuf::from("group/api"),
+            vec![
+                variable("production", "HOST", "api.example.org"),
+                variable("staging", "HOST", "api.staging.example.org"),
+                variable("staging", "DEBUG", "true"),
+            ],
+        ),
+        (
+            PathBuf::from("group/web"),
+            vec![variable("*", "HOST", "line 1\nline 2")],
+        ),
+    ];
+
+    let files = matrix(&projects);
+
+    assert_eq!(
+        files,
+        [
+            (
+                "DEBUG".to_string(),
+                "group/api\tproduction\t(unset)\ngroup/api\tstaging\ttrue\ngroup/web\t*\t(unset)\n"
+                    .to_string()
+            ),
+            (
+                "HOST".to_string(),
+                "group/api\tproduction\tapi.example.org\ngroup/api\tstaging\tapi.staging.example.org\ngroup/web\t*\tline 1\\nline 2\n"
+                    .to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn the_matrix_of_no_variables_is_empty() {
+    assert!(matrix(&[]).is_empty());
+    assert!(matrix(&[(PathBuf::from("group/api"), Vec::new())]).is_empty());
+}
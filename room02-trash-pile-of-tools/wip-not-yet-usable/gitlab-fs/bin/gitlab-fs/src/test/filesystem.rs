@@ -0,0 +1,253 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    path::Path,
+    time::Duration,
+};
+
+use fuser::{BackgroundSession, MountOption};
+use http::Method;
+use serde_json::json;
+use tempfile::TempDir;
+
+use super::client::MockGitlab;
+use crate::filesystem::{env_file_name, env_scope, GitlabFS};
+
+// NOTE: The mount tests need the fuse kernel module, run them with `cargo test -- --ignored`
+// The state of the filesystem is tested without a mount in `crate::filesystem::tests`
+
+/// A group with one project, with two environments
+fn mock_gitlab() -> MockGitlab {
+    let client = MockGitlab::new();
+    client
+        .respond(
+            Method::GET,
+            "projects",
+            json!([{ "id": 1, "name": "api", "path_with_namespace": "group/api" }]),
+        )
+        .respond(
+            Method::GET,
+            "projects/1/variables",
+            json!([
+                { "key": "A", "value": "1", "environment_scope": "production" },
+                { "key": "B", "value": "2", "environment_scope": "production" },
+                { "key": "A", "value": "0", "environment_scope": "staging" },
+            ]),
+        )
+        .respond(
+            Method::GET,
+            "groups/group/variables",
+            json!([{ "key": "REGION", "value": "eu", "environment_scope": "*" }]),
+        );
+    client
+}
+
+/// Mount the filesystem in a temporary folder, it is unmounted when the session is dropped
+fn mount(client: MockGitlab) -> (TempDir, BackgroundSession) {
+    let mountpoint = tempfile::tempdir().expect("Failed to create the mountpoint");
+    let fs = GitlabFS::with_client(client, String::new(), false, Duration::from_secs(60), false);
+    let session = fuser::spawn_mount2(
+        fs,
+        mountpoint.path(),
+        &[MountOption::FSName("gitlabfs".to_string()), MountOption::RW],
+    )
+    .expect("Failed to mount the filesystem");
+    (mountpoint, session)
+}
+
+fn list(path: &Path) -> Vec<String> {
+    let mut names = fs::read_dir(path)
+        .expect("Failed to list the folder")
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn readdir_lists_groups_projects_and_environments() {
+    let (mountpoint, _session) = mount(mock_gitlab());
+
+    assert_eq!(list(mountpoint.path()), [".compare", ".matrix", "group"]);
+    assert_eq!(list(&mountpoint.path().join("group")), ["*.group", "api"]);
+    assert_eq!(
+        list(&mountpoint.path().join("group/api")),
+        [
+            "jobs",
+            "pipelines",
+            "production",
+            "production.effective",
+            "repository",
+            "staging",
+            "staging.effective",
+        ]
+    );
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn lookup_finds_files_without_listing_their_parent() {
+    let (mountpoint, _session) = mount(mock_gitlab());
+
+    let metadata = fs::metadata(mountpoint.path().join("group/api/production")).unwrap();
+    assert!(metadata.is_file());
+    assert_eq!(metadata.len(), "A=1\nB=2".len() as u64);
+
+    let metadata = fs::metadata(mountpoint.path().join("group/api/repository")).unwrap();
+    assert!(metadata.is_dir());
+
+    let missing = fs::metadata(mountpoint.path().join("group/api/review"));
+    assert_eq!(missing.unwrap_err().kind(), std::io::ErrorKind::NotFound);
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn read_env_and_effective_files() {
+    let (mountpoint, _session) = mount(mock_gitlab());
+
+    let production = fs::read_to_string(mountpoint.path().join("group/api/production")).unwrap();
+    assert_eq!(production, "A=1\nB=2");
+
+    let effective =
+        fs::read_to_string(mountpoint.path().join("group/api/staging.effective")).unwrap();
+    assert_eq!(effective, "A=0\nREGION=eu");
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn write_and_fsync_update_the_variables() {
+    let client = mock_gitlab();
+    let (mountpoint, _session) = mount(client.clone());
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .open(mountpoint.path().join("group/api/production"))
+        .unwrap();
+    file.write_all(b"A=1\nC=3\n").unwrap();
+    file.sync_all().unwrap();
+    drop(file);
+
+    let created = client.requests(Method::POST);
+    assert_eq!(created.len(), 1);
+    assert_eq!(created[0].endpoint, "projects/1/variables");
+    assert!(created[0].body.contains("key=C"));
+    assert!(created[0].body.contains("environment_scope=production"));
+
+    let deleted = client.requests(Method::DELETE);
+    assert_eq!(deleted.len(), 1);
+    assert_eq!(deleted[0].endpoint, "projects/1/variables/B");
+    assert!(client.requests(Method::PUT).is_empty());
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn read_only_files_cannot_be_written() {
+    let client = mock_gitlab();
+    let (mountpoint, _session) = mount(client.clone());
+
+    let written = fs::write(
+        mountpoint.path().join("group/api/production.effective"),
+        "A=2",
+    );
+    assert!(written.is_err());
+    assert!(client.requests(Method::POST).is_empty());
+    assert!(client.requests(Method::PUT).is_empty());
+}
+
+#[test]
+fn environments_named_after_a_folder_are_escaped() {
+    for scope in [
+        "production",
+        "jobs",
+        "repository",
+        "pipelines",
+        "%jobs",
+        "%",
+        "jobs%",
+    ] {
+        assert_eq!(env_scope(&env_file_name(scope)), scope);
+    }
+    assert_eq!(env_file_name("jobs"), "%jobs");
+    assert_eq!(env_file_name("%jobs"), "%%jobs");
+    assert_eq!(env_file_name("production"), "production");
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn environments_do_not_collide_with_the_folders_of_the_project() {
+    let client = mock_gitlab();
+    client.respond(
+        Method::GET,
+        "projects/1/variables",
+        json!([{ "key": "A", "value": "1", "environment_scope": "jobs" }]),
+    );
+    let (mountpoint, _session) = mount(client.clone());
+
+    assert!(mountpoint.path().join("group/api/jobs").is_dir());
+    let jobs = fs::read_to_string(mountpoint.path().join("group/api/%jobs")).unwrap();
+    assert_eq!(jobs, "A=1");
+
+    // The folders cannot be replaced by an environment
+    let renamed = fs::rename(
+        mountpoint.path().join("group/api/%jobs"),
+        mountpoint.path().join("group/api/pipelines"),
+    );
+    assert!(renamed.is_err());
+    assert!(client.requests(Method::POST).is_empty());
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn lookup_does_not_fetch_the_content_of_files() {
+    let client = mock_gitlab();
+    client
+        .respond(
+            Method::GET,
+            "projects/1/repository/branches",
+            json!([{ "name": "main" }]),
+        )
+        .respond(
+            Method::GET,
+            "projects/1/repository/tree",
+            json!([{ "name": "README.md", "path": "README.md", "type": "blob" }]),
+        )
+        .respond_raw(
+            Method::GET,
+            "projects/1/repository/files/README.md/raw",
+            "# API\n",
+        );
+    let (mountpoint, _session) = mount(client.clone());
+    let readme = mountpoint
+        .path()
+        .join("group/api/repository/main/README.md");
+    let fetched = || {
+        client
+            .requests(Method::GET)
+            .iter()
+            .filter(|request| request.endpoint.ends_with("/raw"))
+            .count()
+    };
+
+    assert!(fs::metadata(&readme).unwrap().is_file());
+    assert_eq!(fetched(), 0);
+
+    assert_eq!(fs::read_to_string(&readme).unwrap(), "# API\n");
+    assert_eq!(fetched(), 1);
+}
+
+#[test]
+#[ignore = "needs /dev/fuse"]
+fn folders_cannot_be_created() {
+    let client = mock_gitlab();
+    let (mountpoint, _session) = mount(client.clone());
+
+    let created = fs::create_dir(mountpoint.path().join("group/api/review"));
+    assert_eq!(
+        created.unwrap_err().kind(),
+        std::io::ErrorKind::PermissionDenied
+    );
+    assert!(!mountpoint.path().join("group/api/review").exists());
+    assert!(client.requests(Method::POST).is_empty());
+}
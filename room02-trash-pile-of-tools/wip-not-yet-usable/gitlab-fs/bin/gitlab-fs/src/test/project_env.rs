@@ -0,0 +1,100 @@
+use http::Method;
+use serde_json::json;
+
+use super::client::MockGitlab;
+use crate::project_env::{env_file, env_to_variables, move_env_scope, Variable};
+
+fn variable(key: &str, value: &str) -> Variable {
+    Variable {
+        key: key.to_string(),
+        value: value.to_string(),
+        environment_scope: "production".to_string(),
+        protected: false,
+        masked: false,
+        raw: false,
+        variable_type: "env_var".to_string(),
+    }
+}
+
+#[test]
+fn env_files_round_trip() {
+    let variables = [
+        variable("A", "1"),
+        variable("CERT", "-----BEGIN-----\nB=2\n-----END-----\n"),
+        variable("EMPTY", ""),
+        variable("QUOTED", "\"quoted\""),
+        variable("WINDOWS", "line 1\r\nline 2"),
+        variable("PATH", "C:\\bin\\n"),
+    ];
+
+    let content = env_file(&variables);
+    assert_eq!(content.lines().count(), variables.len());
+
+    let mut expected = variables
+        .iter()
+        .map(|var| (var.key.clone(), var.value.clone()))
+        .collect::<Vec<_>>();
+    expected.sort();
+    assert_eq!(env_to_variables(&content).unwrap(), expected);
+}
+
+#[test]
+fn multiline_values_are_quoted() {
+    let content = env_file(&[variable("CERT", "B=2\nC=\"3\"")]);
+    assert_eq!(content, r#"CERT="B=2\nC=\"3\"""#);
+}
+
+#[test]
+fn quoted_values_can_span_several_lines() {
+    let variables = env_to_variables("CERT=\"line 1\nB=2\"\nC=3\n").unwrap();
+    assert_eq!(
+        variables,
+        [
+            ("CERT".to_string(), "line 1\nB=2".to_string()),
+            ("C".to_string(), "3".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn invalid_env_files_are_rejected() {
+    assert!(env_to_variables("A=1\nnot a variable").is_err());
+    assert!(env_to_variables("A=\"unterminated\nB=2").is_err());
+    assert!(env_to_variables("A=\"quoted\" trailing").is_err());
+    assert!(env_to_variables("A=1\nA=2").is_err());
+}
+
+#[test]
+fn moving_a_scope_moves_back_the_variables_on_failure() {
+    let client = MockGitlab::new();
+    client
+        .respond(
+            Method::GET,
+            "projects/1/variables",
+            json!([
+                { "key": "A", "value": "1", "environment_scope": "staging" },
+                { "key": "B", "value": "2", "environment_scope": "staging" },
+            ]),
+        )
+        .fail(Method::PUT, "projects/1/variables/B");
+
+    assert!(move_env_scope(&client, 1, "staging", "preprod").is_err());
+
+    let updated = client.requests(Method::PUT);
+    let endpoints = updated
+        .iter()
+        .map(|request| request.endpoint.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        endpoints,
+        [
+            "projects/1/variables/A",
+            "projects/1/variables/B",
+            "projects/1/variables/A"
+        ]
+    );
+    assert!(updated[2].body.contains("environment_scope=staging"));
+    assert!(updated[2]
+        .body
+        .contains("filter%5Benvironment_scope%5D=preprod"));
+}
@@ -0,0 +1,10 @@
+use crate::webhook::is_valid_token;
+
+#[test]
+fn webhooks_need_the_secret_token() {
+    assert!(is_valid_token(Some("secret"), "secret"));
+    assert!(!is_valid_token(Some("secre"), "secret"));
+    assert!(!is_valid_token(Some("secret!"), "secret"));
+    assert!(!is_valid_token(Some(""), "secret"));
+    assert!(!is_valid_token(None, "secret"));
+}
@@ -0,0 +1,81 @@
+use std::thread::{self, JoinHandle};
+
+use eyre::eyre;
+use gitlab::webhooks::WebHook;
+use log_utils::{debug, info, tracing, warn};
+use subtle::ConstantTimeEq;
+use tiny_http::{Method, Response, Server};
+
+use crate::{
+    filesystem::{Invalidator, ProjectRef},
+    Result,
+};
+
+/// The header Gitlab sends with the secret token of the webhook
+const TOKEN_HEADER: &str = "X-Gitlab-Token";
+
+/// Check the token of a webhook against the secret, in constant time to not leak the secret
+pub(crate) fn is_valid_token(token: Option<&str>, secret: &str) -> bool {
+    token.is_some_and(|token| bool::from(token.as_bytes().ct_eq(secret.as_bytes())))
+}
+
+/// Get the project affected by a Gitlab event
+fn hook_project(hook: &WebHook) -> ProjectRef {
+    match hook {
+        WebHook::Push(hook) => ProjectRef::Id(hook.project_id.value()),
+        WebHook::Build(hook) => ProjectRef::Id(hook.project_id.value()),
+        WebHook::Pipeline(hook) => ProjectRef::Id(hook.project.id.value()),
+        WebHook::Note(hook) => ProjectRef::Id(hook.project_id.value()),
+        WebHook::Issue(hook) => ProjectRef::Path(hook.project.path_with_namespace.clone()),
+        WebHook::MergeRequest(hook) => ProjectRef::Path(hook.project.path_with_namespace.clone()),
+        WebHook::WikiPage(hook) => ProjectRef::Path(hook.project.path_with_namespace.clone()),
+    }
+}
+
+/// Listen for Gitlab webhooks in the background and invalidate the projects they are about,
+/// the webhooks must send the secret token
+#[tracing::instrument(skip(secret, invalidator))]
+pub fn listen(address: &str, secret: String, invalidator: Invalidator) -> Result<JoinHandle<()>> {
+    // NOTE: Without a secret, anyone able to reach the listener could make the filesystem fetch again
+    if secret.is_empty() {
+        return Err(eyre!("The webhook secret token cannot be empty"));
+    }
+    let server =
+        Server::http(address).map_err(|err| eyre!("Failed to listen on {address}: {err}"))?;
+    info!("Listening for Gitlab webhooks on {address}");
+
+    Ok(thread::spawn(move || {
+        for mut request in server.incoming_requests() {
+            if request.method() != &Method::Post {
+                let _ = request.respond(Response::empty(405));
+                continue;
+            }
+
+            let token = request
+                .headers()
+                .iter()
+                .find(|header| header.field.equiv(TOKEN_HEADER))
+                .map(|header| header.value.as_str().to_string());
+            if !is_valid_token(token.as_deref(), &secret) {
+                warn!("Rejected a webhook with an invalid token");
+                let _ = request.respond(Response::empty(401));
+                continue;
+            }
+
+            let hook = serde_json::from_reader::<_, WebHook>(request.as_reader());
+            let status = match hook {
+                Ok(hook) => {
+                    let project = hook_project(&hook);
+                    debug!(?project, "Received a webhook");
+                    invalidator.invalidate_project(&project);
+                    200
+                }
+                Err(err) => {
+                    warn!("Failed to parse the webhook: {err}");
+                    400
+                }
+            };
+            let _ = request.respond(Response::empty(status));
+        }
+    }))
+}
@@ -69,6 +69,7 @@ mod sudo;
 
 pub mod endpoint_prelude;
 
+pub mod admin;
 pub mod common;
 pub mod deploy_keys;
 pub mod groups;
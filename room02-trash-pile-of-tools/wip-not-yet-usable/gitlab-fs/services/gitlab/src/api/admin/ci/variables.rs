@@ -0,0 +1,15 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Instance variable API endpoints.
+//!
+//! These endpoints are used for querying the variables shared by every project of the instance.
+
+mod variables;
+
+pub use self::variables::InstanceVariables;
+pub use self::variables::InstanceVariablesBuilder;
+pub use self::variables::InstanceVariablesBuilderError;
@@ -22,6 +22,7 @@ pub mod projects;
 mod share;
 pub mod subgroups;
 mod unshare;
+pub mod variables;
 
 pub use create::BranchProtection;
 pub use create::CreateGroup;
@@ -0,0 +1,20 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Group variable API endpoints.
+//!
+//! These endpoints are used for querying and modifying a group's variables.
+
+mod update;
+mod variables;
+
+pub use self::update::UpdateGroupVariable;
+pub use self::update::UpdateGroupVariableBuilder;
+pub use self::update::UpdateGroupVariableBuilderError;
+
+pub use self::variables::GroupVariables;
+pub use self::variables::GroupVariablesBuilder;
+pub use self::variables::GroupVariablesBuilderError;
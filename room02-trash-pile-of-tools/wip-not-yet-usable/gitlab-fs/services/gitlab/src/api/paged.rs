@@ -8,6 +8,7 @@ mod link_header;
 mod pagination;
 
 mod all_at_once;
+mod concurrent;
 mod lazy;
 
 /// A trait to indicate that an endpoint is pageable.
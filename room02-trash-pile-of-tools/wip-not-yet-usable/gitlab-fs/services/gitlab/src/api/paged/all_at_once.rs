@@ -7,6 +7,7 @@
 use std::sync::{Arc, Mutex};
 
 use async_trait::async_trait;
+use futures_util::TryStreamExt;
 use http::{header, Request};
 use serde::de::DeserializeOwned;
 
@@ -20,6 +21,7 @@ use crate::api::{
 pub struct Paged<E> {
     pub(in crate::api::paged) endpoint: E,
     pub(in crate::api::paged) pagination: Pagination,
+    pub(in crate::api::paged) parallelism: usize,
 }
 
 /// Collect data from a paged endpoint.
@@ -27,6 +29,20 @@ pub fn paged<E>(endpoint: E, pagination: Pagination) -> Paged<E> {
     Paged {
         endpoint,
         pagination,
+        parallelism: 1,
+    }
+}
+
+impl<E> Paged<E> {
+    /// Fetch up to `parallelism` pages at the same time with asynchronous queries.
+    ///
+    /// The number of pages is read from the `X-Total-Pages` header of the first page and the
+    /// results are still returned in order. Endpoints using keyset pagination and responses
+    /// without the header (e.g., `/projects` beyond 10000 results) are fetched one page after the
+    /// other. Synchronous queries are not affected.
+    pub fn concurrent(mut self, parallelism: usize) -> Self {
+        self.parallelism = parallelism.max(1);
+        self
     }
 }
 
@@ -51,6 +67,10 @@ where
     C: AsyncClient + Sync,
 {
     async fn query_async(&self, client: &C) -> Result<Vec<T>, ApiError<C::Error>> {
+        if self.parallelism > 1 {
+            return self.iter_async(client).try_collect().await;
+        }
+
         let url = {
             let mut url = client.rest_endpoint(&self.endpoint.endpoint())?;
             self.endpoint.parameters().add_to_url(&mut url);
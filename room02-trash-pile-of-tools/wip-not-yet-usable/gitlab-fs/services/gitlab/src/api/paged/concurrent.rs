@@ -0,0 +1,351 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use futures_util::future::{self, Either};
+use futures_util::stream::{self, Stream, StreamExt};
+use serde::de::DeserializeOwned;
+use url::Url;
+
+use crate::api::paged::lazy::LazilyPagedState;
+use crate::api::{ApiError, AsyncClient, Endpoint, LazilyPagedIter, Pageable, Paged, Pagination};
+
+/// The header with the number of pages of the results.
+///
+/// GitLab omits it when counting the results would be too expensive.
+const TOTAL_PAGES_HEADER: &str = "X-Total-Pages";
+
+/// The first page of the results.
+struct FirstPage<T> {
+    results: Vec<T>,
+    next_url: Option<Url>,
+    total_pages: Option<u64>,
+}
+
+async fn first_page<E, C, T>(
+    paged: &Paged<E>,
+    client: &C,
+) -> Result<FirstPage<T>, ApiError<C::Error>>
+where
+    E: Endpoint + Pageable + Sync,
+    T: DeserializeOwned,
+    C: AsyncClient + Sync,
+{
+    let state = LazilyPagedState::new(paged);
+    let url = state.page_number_url(client, 1)?;
+    let (req, data) = state.build_request::<C>(url)?;
+    let rsp = client.rest_async(req, data).await?;
+
+    let total_pages = rsp
+        .headers()
+        .get(TOTAL_PAGES_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.parse().ok());
+    let (results, next_url) = state.parse_response::<C, T>(rsp)?;
+
+    Ok(FirstPage {
+        results,
+        next_url,
+        total_pages,
+    })
+}
+
+async fn page<E, C, T>(
+    paged: &Paged<E>,
+    client: &C,
+    number: u64,
+) -> Result<Vec<T>, ApiError<C::Error>>
+where
+    E: Endpoint + Pageable + Sync,
+    T: DeserializeOwned,
+    C: AsyncClient + Sync,
+{
+    let state = LazilyPagedState::new(paged);
+    let url = state.page_number_url(client, number)?;
+    let (req, data) = state.build_request::<C>(url)?;
+    let rsp = client.rest_async(req, data).await?;
+    state
+        .parse_response::<C, T>(rsp)
+        .map(|(results, _)| results)
+}
+
+/// The number of the last page to fetch for a pagination.
+fn last_page(pagination: Pagination, total_pages: u64) -> u64 {
+    match pagination {
+        Pagination::All => total_pages,
+        Pagination::Limit(limit) => {
+            let per_page = pagination.page_limit();
+            let needed = limit.div_ceil(per_page);
+            total_pages.min(needed as u64)
+        },
+    }
+}
+
+/// Stream the results of a paginated endpoint, fetching the pages after the first one
+/// concurrently when the first response tells how many there are.
+pub(in crate::api::paged) fn stream<'a, E, C, T>(
+    paged: &'a Paged<E>,
+    client: &'a C,
+) -> impl Stream<Item = Result<T, ApiError<C::Error>>> + 'a
+where
+    E: Endpoint + Pageable + Sync,
+    T: DeserializeOwned + 'static,
+    C: AsyncClient + Sync,
+{
+    // The pages of keyset pagination can only be found through the previous one.
+    if paged.endpoint.use_keyset_pagination() {
+        return Either::Left(LazilyPagedIter::new(paged, client).into_stream());
+    }
+
+    let pages = stream::once(first_page(paged, client)).flat_map(move |first| match first {
+        Err(err) => Either::Left(stream::once(future::ready(Err(err)))),
+        Ok(FirstPage {
+            results,
+            next_url,
+            total_pages: None,
+        }) => {
+            let mut iter = LazilyPagedIter::new(paged, client);
+            iter.resume(results, next_url);
+            Either::Right(Either::Left(iter.into_stream()))
+        },
+        Ok(FirstPage {
+            results,
+            total_pages: Some(total_pages),
+            ..
+        }) => {
+            let next_pages = stream::iter(2..=last_page(paged.pagination, total_pages))
+                .map(move |number| page(paged, client, number))
+                .buffered(paged.parallelism)
+                .flat_map(|page| match page {
+                    Ok(results) => Either::Left(stream::iter(results.into_iter().map(Ok))),
+                    Err(err) => Either::Right(stream::once(future::ready(Err(err)))),
+                });
+            Either::Right(Either::Right(
+                stream::iter(results.into_iter().map(Ok)).chain(next_pages),
+            ))
+        },
+    });
+    Either::Right(pages)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use async_trait::async_trait;
+    use bytes::Bytes;
+    use futures_util::TryStreamExt;
+    use http::request::Builder as RequestBuilder;
+    use http::{Response, StatusCode};
+    use serde::{Deserialize, Serialize};
+    use serde_json::json;
+    use url::Url;
+
+    use crate::api::endpoint_prelude::*;
+    use crate::api::paged::concurrent::last_page;
+    use crate::api::{self, ApiError, AsyncClient, AsyncQuery, Pagination, RestClient};
+    use crate::test::client::{ExpectedUrl, PagedTestClient, SingleTestClient};
+
+    #[derive(Debug, Default)]
+    struct Dummy {
+        with_keyset: bool,
+    }
+
+    impl Endpoint for Dummy {
+        fn method(&self) -> Method {
+            Method::GET
+        }
+
+        fn endpoint(&self) -> Cow<'static, str> {
+            "paged_dummy".into()
+        }
+    }
+
+    impl Pageable for Dummy {
+        fn use_keyset_pagination(&self) -> bool {
+            self.with_keyset
+        }
+    }
+
+    #[derive(Debug, Deserialize, Serialize)]
+    struct DummyResult {
+        value: u16,
+    }
+
+    fn paged_client() -> PagedTestClient<DummyResult> {
+        let endpoint = ExpectedUrl::builder()
+            .endpoint("paged_dummy")
+            .paginated(true)
+            .build()
+            .unwrap();
+        PagedTestClient::new_raw(endpoint, (0..1000).map(|value| DummyResult { value }))
+    }
+
+    /// A client counting how many requests are in flight at the same time.
+    struct CountingClient {
+        client: PagedTestClient<DummyResult>,
+        requests: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    impl CountingClient {
+        fn new(client: PagedTestClient<DummyResult>) -> Self {
+            Self {
+                client,
+                requests: AtomicUsize::new(0),
+                in_flight: AtomicUsize::new(0),
+                max_in_flight: AtomicUsize::new(0),
+            }
+        }
+    }
+
+    impl RestClient for CountingClient {
+        type Error = <PagedTestClient<DummyResult> as RestClient>::Error;
+
+        fn rest_endpoint(&self, endpoint: &str) -> Result<Url, ApiError<Self::Error>> {
+            self.client.rest_endpoint(endpoint)
+        }
+    }
+
+    #[async_trait]
+    impl AsyncClient for CountingClient {
+        async fn rest_async(
+            &self,
+            request: RequestBuilder,
+            body: Vec<u8>,
+        ) -> Result<Response<Bytes>, ApiError<Self::Error>> {
+            self.requests.fetch_add(1, Ordering::SeqCst);
+            let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+            // Let the other requests start.
+            tokio::task::yield_now().await;
+            let rsp = self.client.rest_async(request, body).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+            rsp
+        }
+    }
+
+    #[test]
+    fn test_last_page() {
+        assert_eq!(last_page(Pagination::All, 10), 10);
+        assert_eq!(last_page(Pagination::Limit(25), 10), 1);
+        assert_eq!(last_page(Pagination::Limit(150), 10), 2);
+        assert_eq!(last_page(Pagination::Limit(2000), 10), 10);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_pagination_all() {
+        let client = CountingClient::new(paged_client());
+        let query = Dummy::default();
+
+        let res: Vec<DummyResult> = api::paged(query, Pagination::All)
+            .concurrent(4)
+            .query_async(&client)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1000);
+        for (i, value) in res.iter().enumerate() {
+            assert_eq!(value.value, i as u16);
+        }
+        assert_eq!(client.requests.load(Ordering::SeqCst), 10);
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 4);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_pagination_limit() {
+        let client = CountingClient::new(paged_client());
+        let query = Dummy::default();
+
+        let res: Vec<DummyResult> = api::paged(query, Pagination::Limit(250))
+            .concurrent(4)
+            .query_async(&client)
+            .await
+            .unwrap();
+        // Like sequential queries, whole pages are returned.
+        assert_eq!(res.len(), 300);
+        for (i, value) in res.iter().enumerate() {
+            assert_eq!(value.value, i as u16);
+        }
+        assert_eq!(client.requests.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_pagination_without_totals() {
+        let client = CountingClient::new(paged_client().without_totals());
+        let query = Dummy::default();
+
+        let res: Vec<DummyResult> = api::paged(query, Pagination::All)
+            .concurrent(4)
+            .query_async(&client)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1000);
+        for (i, value) in res.iter().enumerate() {
+            assert_eq!(value.value, i as u16);
+        }
+        // The pages are fetched one after the other, until an empty one.
+        assert_eq!(client.requests.load(Ordering::SeqCst), 11);
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_keyset_pagination() {
+        let client = CountingClient::new(paged_client());
+        let query = Dummy { with_keyset: true };
+
+        let res: Vec<DummyResult> = api::paged(query, Pagination::All)
+            .concurrent(4)
+            .query_async(&client)
+            .await
+            .unwrap();
+        assert_eq!(res.len(), 1000);
+        for (i, value) in res.iter().enumerate() {
+            assert_eq!(value.value, i as u16);
+        }
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_iter_async() {
+        let client = CountingClient::new(paged_client());
+        let query = api::paged(Dummy::default(), Pagination::All).concurrent(3);
+
+        let res: Vec<DummyResult> = query.iter_async(&client).try_collect().await.unwrap();
+        assert_eq!(res.len(), 1000);
+        for (i, value) in res.iter().enumerate() {
+            assert_eq!(value.value, i as u16);
+        }
+        assert_eq!(client.max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_gitlab_error_detection() {
+        let endpoint = ExpectedUrl::builder()
+            .endpoint("paged_dummy")
+            .add_query_params(&[("page", "1"), ("per_page", "100")])
+            .status(StatusCode::NOT_FOUND)
+            .build()
+            .unwrap();
+        let client = SingleTestClient::new_json(
+            endpoint,
+            &json!({
+                "message": "dummy error message",
+            }),
+        );
+        let endpoint = Dummy::default();
+
+        let res: Result<Vec<DummyResult>, _> = api::paged(endpoint, Pagination::All)
+            .concurrent(4)
+            .query_async(&client)
+            .await;
+        let err = res.unwrap_err();
+        if let ApiError::Gitlab { msg } = err {
+            assert_eq!(msg, "dummy error message");
+        } else {
+            panic!("unexpected error: {}", err);
+        }
+    }
+}
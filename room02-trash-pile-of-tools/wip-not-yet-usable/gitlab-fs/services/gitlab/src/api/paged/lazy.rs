@@ -8,6 +8,7 @@ use std::sync::RwLock;
 
 use async_trait::async_trait;
 use bytes::Bytes;
+use futures_util::future::Either;
 use futures_util::Stream;
 use http::request::Builder as RequestBuilder;
 use http::{header, Request, Response};
@@ -15,11 +16,14 @@ use query::AsyncQuery;
 use serde::de::DeserializeOwned;
 use url::Url;
 
-use crate::api::paged::link_header;
+use crate::api::paged::{concurrent, link_header};
 use crate::api::{
     query, ApiError, AsyncClient, Client, Endpoint, Pageable, Paged, Query, RestClient,
 };
 
+/// The results of a page and the URL of the next one.
+type PageResults<T> = (Vec<T>, Option<Url>);
+
 impl<E> Paged<E>
 where
     E: Endpoint,
@@ -36,6 +40,8 @@ where
     E: Endpoint + Pageable + Sync,
 {
     /// Create a stream over the results of paginated results for with a client.
+    ///
+    /// Pages are fetched concurrently when requested with `Paged::concurrent`.
     pub fn iter_async<'a, C, T>(
         &'a self,
         client: &'a C,
@@ -44,10 +50,11 @@ where
         T: DeserializeOwned + 'static,
         C: AsyncClient + Sync,
     {
-        let iter = LazilyPagedIter::new(self, client);
-        futures_util::stream::unfold(iter, |mut iter| async move {
-            iter.next_async().await.map(|item| (item, iter))
-        })
+        if self.parallelism > 1 {
+            Either::Left(concurrent::stream(self, client))
+        } else {
+            Either::Right(LazilyPagedIter::new(self, client).into_stream())
+        }
     }
 }
 
@@ -110,7 +117,7 @@ struct PageState {
     next_page: Page,
 }
 
-struct LazilyPagedState<'a, E> {
+pub(in crate::api::paged) struct LazilyPagedState<'a, E> {
     paged: &'a Paged<E>,
     page_state: RwLock<PageState>,
 }
@@ -119,7 +126,7 @@ impl<'a, E> LazilyPagedState<'a, E>
 where
     E: Pageable,
 {
-    fn new(paged: &'a Paged<E>) -> Self {
+    pub(in crate::api::paged) fn new(paged: &'a Paged<E>) -> Self {
         let next_page = if paged.endpoint.use_keyset_pagination() {
             Page::Keyset(KeysetPage::First)
         } else {
@@ -177,26 +184,48 @@ where
         let url = if let Some(next_url) = next_page.next_url() {
             next_url.clone()
         } else {
-            let mut url = client.rest_endpoint(&self.paged.endpoint.endpoint())?;
-            self.paged.endpoint.parameters().add_to_url(&mut url);
+            self.first_url(client, next_page)?
+        };
 
-            let per_page = self.paged.pagination.page_limit();
-            let per_page_str = per_page.to_string();
+        Ok(Some(url))
+    }
 
-            {
-                let mut pairs = url.query_pairs_mut();
-                pairs.append_pair("per_page", &per_page_str);
+    /// The URL of a page by its number.
+    pub(in crate::api::paged) fn page_number_url<C>(
+        &self,
+        client: &C,
+        number: u64,
+    ) -> Result<Url, ApiError<C::Error>>
+    where
+        C: RestClient,
+    {
+        self.first_url(client, &Page::Number(number))
+    }
 
-                next_page.apply_to(&mut pairs);
-            }
+    fn first_url<C>(&self, client: &C, page: &Page) -> Result<Url, ApiError<C::Error>>
+    where
+        C: RestClient,
+    {
+        let mut url = client.rest_endpoint(&self.paged.endpoint.endpoint())?;
+        self.paged.endpoint.parameters().add_to_url(&mut url);
 
-            url
-        };
+        let per_page = self.paged.pagination.page_limit();
+        let per_page_str = per_page.to_string();
 
-        Ok(Some(url))
+        {
+            let mut pairs = url.query_pairs_mut();
+            pairs.append_pair("per_page", &per_page_str);
+
+            page.apply_to(&mut pairs);
+        }
+
+        Ok(url)
     }
 
-    fn build_request<C>(&self, url: Url) -> Result<(RequestBuilder, Vec<u8>), ApiError<C::Error>>
+    pub(in crate::api::paged) fn build_request<C>(
+        &self,
+        url: Url,
+    ) -> Result<(RequestBuilder, Vec<u8>), ApiError<C::Error>>
     where
         C: RestClient,
     {
@@ -214,6 +243,22 @@ where
     }
 
     fn process_response<C, T>(&self, rsp: Response<Bytes>) -> Result<Vec<T>, ApiError<C::Error>>
+    where
+        E: Pageable,
+        T: DeserializeOwned,
+        C: RestClient,
+    {
+        let (page, next_url) = self.parse_response::<C, T>(rsp)?;
+        self.next_page(page.len(), next_url);
+
+        Ok(page)
+    }
+
+    /// Get the results of a page and the URL of the next one (with keyset pagination).
+    pub(in crate::api::paged) fn parse_response<C, T>(
+        &self,
+        rsp: Response<Bytes>,
+    ) -> Result<PageResults<T>, ApiError<C::Error>>
     where
         E: Pageable,
         T: DeserializeOwned,
@@ -237,9 +282,8 @@ where
         }
 
         let page = serde_json::from_value::<Vec<T>>(v).map_err(ApiError::data_type::<Vec<T>>)?;
-        self.next_page(page.len(), next_url);
 
-        Ok(page)
+        Ok((page, next_url))
     }
 }
 
@@ -301,7 +345,7 @@ where
     E: Endpoint,
     E: Pageable,
 {
-    fn new(paged: &'a Paged<E>, client: &'a C) -> Self {
+    pub(in crate::api::paged) fn new(paged: &'a Paged<E>, client: &'a C) -> Self {
         let state = LazilyPagedState::new(paged);
 
         Self {
@@ -310,6 +354,15 @@ where
             current_page: Vec::new(),
         }
     }
+
+    /// Continue after a page which was fetched without the iterator.
+    pub(in crate::api::paged) fn resume(&mut self, page: Vec<T>, next_url: Option<Url>) {
+        self.state.next_page(page.len(), next_url);
+        self.current_page = page;
+
+        // Reverse the page order so that `.pop()` works.
+        self.current_page.reverse();
+    }
 }
 
 impl<'a, E, C, T> Iterator for LazilyPagedIter<'a, E, C, T>
@@ -344,6 +397,14 @@ where
     T: DeserializeOwned + 'static,
     C: AsyncClient + Sync,
 {
+    pub(in crate::api::paged) fn into_stream(
+        self,
+    ) -> impl Stream<Item = Result<T, ApiError<C::Error>>> + 'a {
+        futures_util::stream::unfold(self, |mut iter| async move {
+            iter.next_async().await.map(|item| (item, iter))
+        })
+    }
+
     async fn next_async(&mut self) -> Option<Result<T, ApiError<C::Error>>> {
         if self.current_page.is_empty() {
             self.current_page = match self.state.query_async(self.client).await {
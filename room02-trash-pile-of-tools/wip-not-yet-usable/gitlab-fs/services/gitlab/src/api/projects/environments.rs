@@ -6,13 +6,18 @@
 
 //! Project environments API endpoints.
 //!
-//! These endpoints are used for querying environments.
+//! These endpoints are used for querying and creating environments.
 
+mod create;
 mod environment;
 mod environments;
 
 pub use self::environments::EnvironmentState;
 
+pub use self::create::CreateEnvironment;
+pub use self::create::CreateEnvironmentBuilder;
+pub use self::create::CreateEnvironmentBuilderError;
+
 pub use self::environment::Environment;
 pub use self::environment::EnvironmentBuilder;
 pub use self::environment::EnvironmentBuilderError;
@@ -0,0 +1,158 @@
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use derive_builder::Builder;
+
+use crate::api::common::NameOrId;
+use crate::api::endpoint_prelude::*;
+
+/// Create an environment within a project.
+#[derive(Debug, Builder, Clone)]
+#[builder(setter(strip_option))]
+pub struct CreateEnvironment<'a> {
+    /// The project to create the environment within.
+    #[builder(setter(into))]
+    project: NameOrId<'a>,
+    /// The name of the environment.
+    #[builder(setter(into))]
+    name: Cow<'a, str>,
+
+    /// The URL of the deployed environment.
+    #[builder(setter(into), default)]
+    external_url: Option<Cow<'a, str>>,
+    /// The tier of the environment (e.g. `production` or `staging`).
+    #[builder(setter(into), default)]
+    tier: Option<Cow<'a, str>>,
+}
+
+impl<'a> CreateEnvironment<'a> {
+    /// Create a builder for the endpoint.
+    pub fn builder() -> CreateEnvironmentBuilder<'a> {
+        CreateEnvironmentBuilder::default()
+    }
+}
+
+impl<'a> Endpoint for CreateEnvironment<'a> {
+    fn method(&self) -> Method {
+        Method::POST
+    }
+
+    fn endpoint(&self) -> Cow<'static, str> {
+        format!("projects/{}/environments", self.project).into()
+    }
+
+    fn body(&self) -> Result<Option<(&'static str, Vec<u8>)>, BodyError> {
+        let mut params = FormParams::default();
+
+        params
+            .push("name", &self.name)
+            .push_opt("external_url", self.external_url.as_ref())
+            .push_opt("tier", self.tier.as_ref());
+
+        params.into_body()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Method;
+
+    use crate::api::projects::environments::{CreateEnvironment, CreateEnvironmentBuilderError};
+    use crate::api::{self, Query};
+    use crate::test::client::{ExpectedUrl, SingleTestClient};
+
+    #[test]
+    fn project_and_name_are_necessary() {
+        let err = CreateEnvironment::builder().build().unwrap_err();
+        crate::test::assert_missing_field!(err, CreateEnvironmentBuilderError, "project");
+    }
+
+    #[test]
+    fn project_is_necessary() {
+        let err = CreateEnvironment::builder()
+            .name("production")
+            .build()
+            .unwrap_err();
+        crate::test::assert_missing_field!(err, CreateEnvironmentBuilderError, "project");
+    }
+
+    #[test]
+    fn name_is_necessary() {
+        let err = CreateEnvironment::builder().project(1).build().unwrap_err();
+        crate::test::assert_missing_field!(err, CreateEnvironmentBuilderError, "name");
+    }
+
+    #[test]
+    fn project_and_name_are_sufficient() {
+        CreateEnvironment::builder()
+            .project(1)
+            .name("production")
+            .build()
+            .unwrap();
+    }
+
+    #[test]
+    fn endpoint() {
+        let endpoint = ExpectedUrl::builder()
+            .method(Method::POST)
+            .endpoint("projects/simple%2Fproject/environments")
+            .content_type("application/x-www-form-urlencoded")
+            .body_str("name=production")
+            .build()
+            .unwrap();
+        let client = SingleTestClient::new_raw(endpoint, "");
+
+        let endpoint = CreateEnvironment::builder()
+            .project("simple/project")
+            .name("production")
+            .build()
+            .unwrap();
+        api::ignore(endpoint).query(&client).unwrap();
+    }
+
+    #[test]
+    fn endpoint_external_url() {
+        let endpoint = ExpectedUrl::builder()
+            .method(Method::POST)
+            .endpoint("projects/simple%2Fproject/environments")
+            .content_type("application/x-www-form-urlencoded")
+            .body_str(concat!(
+                "name=production",
+                "&external_url=https%3A%2F%2Fexample.com",
+            ))
+            .build()
+            .unwrap();
+        let client = SingleTestClient::new_raw(endpoint, "");
+
+        let endpoint = CreateEnvironment::builder()
+            .project("simple/project")
+            .name("production")
+            .external_url("https://example.com")
+            .build()
+            .unwrap();
+        api::ignore(endpoint).query(&client).unwrap();
+    }
+
+    #[test]
+    fn endpoint_tier() {
+        let endpoint = ExpectedUrl::builder()
+            .method(Method::POST)
+            .endpoint("projects/simple%2Fproject/environments")
+            .content_type("application/x-www-form-urlencoded")
+            .body_str(concat!("name=review", "&tier=development"))
+            .build()
+            .unwrap();
+        let client = SingleTestClient::new_raw(endpoint, "");
+
+        let endpoint = CreateEnvironment::builder()
+            .project("simple/project")
+            .name("review")
+            .tier("development")
+            .build()
+            .unwrap();
+        api::ignore(endpoint).query(&client).unwrap();
+    }
+}
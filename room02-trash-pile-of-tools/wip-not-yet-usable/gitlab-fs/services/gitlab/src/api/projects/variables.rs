@@ -9,6 +9,7 @@
 //! These endpoints are used for querying a project's variables.
 
 mod create;
+mod delete;
 mod update;
 mod variable;
 mod variables;
@@ -18,6 +19,10 @@ pub use self::create::CreateProjectVariableBuilder;
 pub use self::create::CreateProjectVariableBuilderError;
 pub use self::create::ProjectVariableType;
 
+pub use self::delete::DeleteProjectVariable;
+pub use self::delete::DeleteProjectVariableBuilder;
+pub use self::delete::DeleteProjectVariableBuilderError;
+
 pub use self::update::UpdateProjectVariable;
 pub use self::update::UpdateProjectVariableBuilder;
 pub use self::update::UpdateProjectVariableBuilderError;
@@ -8,15 +8,23 @@
 //!
 //! This module provides a `Client` implementation which can wrap other `ApiClient` instances in
 //! order to retry requests with an exponential backoff. Only service errors (those in the `5xx`
-//! range) are retried and all others are passed through as final statuses.
+//! range) and rate limited requests (`429 Too Many Requests`) are retried and all others are
+//! passed through as final statuses.
+//!
+//! The rate limit headers sent by GitLab are honoured: a rate limited request is retried after
+//! the delay given by its `Retry-After` header and, once `RateLimit-Remaining` reaches zero, the
+//! next requests wait for `RateLimit-Reset` instead of being rejected.
 
 use std::error::Error as StdError;
-use std::iter;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use async_trait::async_trait;
 use bytes::Bytes;
-use http::Response;
+use chrono::DateTime;
+use http::{HeaderMap, Response, StatusCode};
 use url::Url;
 
 use derive_builder::Builder;
@@ -24,7 +32,13 @@ use thiserror::Error;
 
 use crate::api;
 
+const RETRY_AFTER: &str = "Retry-After";
+const RATE_LIMIT_REMAINING: &str = "RateLimit-Remaining";
+const RATE_LIMIT_RESET: &str = "RateLimit-Reset";
+
 /// Parameters for retrying queries with an exponential backoff.
+///
+/// Clones share their rate limit state so that every client using them is paced together.
 #[derive(Debug, Builder, Clone)]
 pub struct Backoff {
     /// The maximum number of times to backoff.
@@ -42,6 +56,21 @@ pub struct Backoff {
     /// Defaults to `2.0`.
     #[builder(default = "2.0")]
     scale: f64,
+    /// Whether to honour the `Retry-After` and `RateLimit-*` headers.
+    ///
+    /// When disabled, rate limited requests are still retried, but with the exponential backoff.
+    ///
+    /// Defaults to `true`.
+    #[builder(default = "true")]
+    rate_limit: bool,
+    /// The longest time to wait for a rate limit given by the headers.
+    ///
+    /// Defaults to 5 minutes.
+    #[builder(default = "Duration::from_secs(300)")]
+    max_wait: Duration,
+    /// When the rate limit was exhausted, the time at which it resets.
+    #[builder(setter(skip))]
+    paused_until: Arc<Mutex<Option<SystemTime>>>,
 }
 
 fn should_backoff<E>(err: &api::ApiError<E>) -> bool
@@ -49,48 +78,171 @@ where
     E: StdError + Send + Sync + 'static,
 {
     if let api::ApiError::GitlabService { status, .. } = err {
-        status.is_server_error()
+        should_retry(*status)
     } else {
         false
     }
 }
 
+fn should_retry(status: StatusCode) -> bool {
+    status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS
+}
+
+fn header_str<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name)?.to_str().ok().map(str::trim)
+}
+
+fn header_u64(headers: &HeaderMap, name: &str) -> Option<u64> {
+    header_str(headers, name)?.parse().ok()
+}
+
+/// The time at which the rate limit resets (sent as a Unix timestamp).
+fn rate_limit_reset(headers: &HeaderMap) -> Option<SystemTime> {
+    header_u64(headers, RATE_LIMIT_RESET).map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// The delay requested by a `Retry-After` header, either in seconds or as an HTTP date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = header_str(headers, RETRY_AFTER)?;
+    if let Ok(secs) = value.parse() {
+        return Some(Duration::from_secs(secs));
+    }
+    let date = DateTime::parse_from_rfc2822(value).ok()?;
+    let date = UNIX_EPOCH + Duration::from_secs(date.timestamp().max(0) as u64);
+    Some(until(date))
+}
+
+fn until(time: SystemTime) -> Duration {
+    time.duration_since(SystemTime::now()).unwrap_or_default()
+}
+
+/// The outcome of a single attempt.
+enum Attempt<T> {
+    /// The result is final.
+    Done(T),
+    /// The request must be sent again after a delay.
+    Retry(Duration),
+}
+
+type RetryResult<E> = Result<Response<Bytes>, api::ApiError<Error<E>>>;
+
 impl Backoff {
     /// Create a builder for retry backoff parameters.
     pub fn builder() -> BackoffBuilder {
         BackoffBuilder::default()
     }
 
-    fn retry<F, E>(&self, mut tryf: F) -> Result<Response<Bytes>, api::ApiError<Error<E>>>
+    /// How long to wait before sending a request because the rate limit is exhausted.
+    fn pause(&self) -> Option<Duration> {
+        if !self.rate_limit {
+            return None;
+        }
+        let paused_until = (*self.paused_until.lock().unwrap())?;
+        let pause = until(paused_until);
+        if pause.is_zero() {
+            None
+        } else {
+            Some(pause.min(self.max_wait))
+        }
+    }
+
+    /// Remember when the next requests must wait for the rate limit to reset.
+    fn record_rate_limit(&self, headers: &HeaderMap) {
+        if !self.rate_limit {
+            return;
+        }
+        if let Some(remaining) = header_u64(headers, RATE_LIMIT_REMAINING) {
+            let mut paused_until = self.paused_until.lock().unwrap();
+            *paused_until = if remaining == 0 {
+                rate_limit_reset(headers)
+            } else {
+                None
+            };
+        }
+    }
+
+    /// The delay before retrying a rate limited request.
+    fn rate_limit_delay(&self, headers: &HeaderMap) -> Option<Duration> {
+        if !self.rate_limit {
+            return None;
+        }
+        retry_after(headers)
+            .or_else(|| rate_limit_reset(headers).map(until))
+            .map(|delay| delay.min(self.max_wait))
+    }
+
+    fn next_timeout(&self, timeout: &mut Duration) -> Duration {
+        let current = *timeout;
+        *timeout = timeout.mul_f64(self.scale);
+        current
+    }
+
+    fn attempt<E>(
+        &self,
+        result: Result<Response<Bytes>, api::ApiError<E>>,
+        timeout: &mut Duration,
+    ) -> Attempt<RetryResult<E>>
+    where
+        E: StdError + Send + Sync + 'static,
+    {
+        match result {
+            Ok(rsp) => {
+                self.record_rate_limit(rsp.headers());
+                if rsp.status() == StatusCode::TOO_MANY_REQUESTS {
+                    let delay = self.rate_limit_delay(rsp.headers());
+                    Attempt::Retry(delay.unwrap_or_else(|| self.next_timeout(timeout)))
+                } else if should_retry(rsp.status()) {
+                    Attempt::Retry(self.next_timeout(timeout))
+                } else {
+                    Attempt::Done(Ok(rsp))
+                }
+            },
+            Err(err) => {
+                if should_backoff(&err) {
+                    Attempt::Retry(self.next_timeout(timeout))
+                } else {
+                    Attempt::Done(Err(err.map_client(Error::inner)))
+                }
+            },
+        }
+    }
+
+    fn retry<F, E>(&self, mut tryf: F) -> RetryResult<E>
     where
         F: FnMut() -> Result<Response<Bytes>, api::ApiError<E>>,
         E: StdError + Send + Sync + 'static,
     {
-        iter::repeat(())
-            .take(self.limit)
-            .scan(self.init, |timeout, _| match tryf() {
-                Ok(rsp) => {
-                    if rsp.status().is_server_error() {
-                        thread::sleep(*timeout);
-                        *timeout = timeout.mul_f64(self.scale);
-                        Some(None)
-                    } else {
-                        Some(Some(Ok(rsp)))
-                    }
-                },
-                Err(err) => {
-                    if should_backoff(&err) {
-                        thread::sleep(*timeout);
-                        *timeout = timeout.mul_f64(self.scale);
-                        Some(None)
-                    } else {
-                        Some(Some(Err(err.map_client(Error::inner))))
-                    }
-                },
-            })
-            .flatten()
-            .next()
-            .unwrap_or_else(|| Err(api::ApiError::client(Error::backoff())))
+        let mut timeout = self.init;
+        for _ in 0..self.limit {
+            if let Some(pause) = self.pause() {
+                thread::sleep(pause);
+            }
+            match self.attempt(tryf(), &mut timeout) {
+                Attempt::Done(result) => return result,
+                Attempt::Retry(delay) => thread::sleep(delay),
+            }
+        }
+        Err(api::ApiError::client(Error::backoff()))
+    }
+
+    /// Same as `retry`, but waits with the timer of the `tokio` runtime.
+    async fn retry_async<F, Fut, E>(&self, mut tryf: F) -> RetryResult<E>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<Response<Bytes>, api::ApiError<E>>>,
+        E: StdError + Send + Sync + 'static,
+    {
+        let mut timeout = self.init;
+        for _ in 0..self.limit {
+            if let Some(pause) = self.pause() {
+                tokio::time::sleep(pause).await;
+            }
+            match self.attempt(tryf().await, &mut timeout) {
+                Attempt::Done(result) => return result,
+                Attempt::Retry(delay) => tokio::time::sleep(delay).await,
+            }
+        }
+        Err(api::ApiError::client(Error::backoff()))
     }
 }
 
@@ -164,6 +316,30 @@ where
     }
 }
 
+/// Copy a request so that it can be sent again.
+fn clone_request(request: &http::request::Builder) -> http::request::Builder {
+    let mut builder = http::request::Request::builder();
+    if let Some(method) = request.method_ref() {
+        builder = builder.method(method);
+    }
+    if let Some(uri) = request.uri_ref() {
+        builder = builder.uri(uri);
+    }
+    // https://github.com/hyperium/http/pull/495
+    // if let Some(version) = request.version_ref() {
+    //     builder = builder.version(version);
+    // }
+    if let Some(headers) = request.headers_ref() {
+        for (key, value) in headers.iter() {
+            builder = builder.header(key, value);
+        }
+    }
+    // Ignore extensions for now. Can be handled once this is released:
+    // https://github.com/hyperium/http/pull/497
+
+    builder
+}
+
 impl<C> api::Client for Client<C>
 where
     C: api::Client,
@@ -173,40 +349,43 @@ where
         request: http::request::Builder,
         body: Vec<u8>,
     ) -> Result<Response<Bytes>, api::ApiError<Self::Error>> {
-        self.backoff.retry(|| {
-            let mut builder = http::request::Request::builder();
-            if let Some(method) = request.method_ref() {
-                builder = builder.method(method);
-            }
-            if let Some(uri) = request.uri_ref() {
-                builder = builder.uri(uri);
-            }
-            // https://github.com/hyperium/http/pull/495
-            // if let Some(version) = request.version_ref() {
-            //     builder = builder.version(version);
-            // }
-            if let Some(headers) = request.headers_ref() {
-                for (key, value) in headers.iter() {
-                    builder = builder.header(key, value);
-                }
-            }
-            // Ignore extensions for now. Can be handled once this is released:
-            // https://github.com/hyperium/http/pull/497
+        self.backoff
+            .retry(|| self.client.rest(clone_request(&request), body.clone()))
+    }
+}
 
-            self.client.rest(builder, body.clone())
-        })
+/// The delays are waited with the timer of the `tokio` runtime, which must be enabled.
+#[async_trait]
+impl<C> api::AsyncClient for Client<C>
+where
+    C: api::AsyncClient + Sync,
+{
+    async fn rest_async(
+        &self,
+        request: http::request::Builder,
+        body: Vec<u8>,
+    ) -> Result<Response<Bytes>, api::ApiError<Self::Error>> {
+        self.backoff
+            .retry_async(|| {
+                self.client
+                    .rest_async(clone_request(&request), body.clone())
+            })
+            .await
     }
 }
 
 #[cfg(test)]
 mod test {
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    use bytes::Bytes;
     use http::{Response, StatusCode};
     use serde::Deserialize;
     use serde_json::json;
     use thiserror::Error;
 
     use crate::api::endpoint_prelude::*;
-    use crate::api::{self, retry, ApiError, Query};
+    use crate::api::{self, retry, ApiError, AsyncQuery, Query};
     use crate::test::client::{ExpectedUrl, SingleTestClient};
 
     #[derive(Debug, Error)]
@@ -329,6 +508,170 @@ mod test {
         }
     }
 
+    fn rate_limited(retry_after: &str) -> Response<Bytes> {
+        let body: &'static [u8] = b"";
+        Response::builder()
+            .status(StatusCode::TOO_MANY_REQUESTS)
+            .header("Retry-After", retry_after)
+            .body(body.into())
+            .unwrap()
+    }
+
+    fn rate_limit_headers(remaining: u64, reset: SystemTime) -> Response<Bytes> {
+        let body: &'static [u8] = b"";
+        let reset = reset.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        Response::builder()
+            .status(StatusCode::OK)
+            .header("RateLimit-Remaining", remaining.to_string())
+            .header("RateLimit-Reset", reset.to_string())
+            .body(body.into())
+            .unwrap()
+    }
+
+    #[test]
+    fn backoff_rate_limited_retry_after() {
+        // The exponential backoff would wait for a minute.
+        let backoff = retry::Backoff::builder()
+            .init(Duration::from_secs(60))
+            .build()
+            .unwrap();
+        let mut call_count = 0;
+        let body: &'static [u8] = b"";
+        let start = Instant::now();
+        backoff
+            .retry::<_, BogusError>(|| {
+                call_count += 1;
+                if call_count == 1 {
+                    Ok(rate_limited("0"))
+                } else {
+                    Ok(Response::builder()
+                        .status(StatusCode::OK)
+                        .body(body.into())
+                        .unwrap())
+                }
+            })
+            .unwrap();
+        assert_eq!(call_count, 2);
+        assert!(start.elapsed() < Duration::from_secs(60));
+    }
+
+    #[test]
+    fn backoff_rate_limited_without_headers() {
+        let backoff = retry::Backoff::builder()
+            .init(Duration::from_millis(1))
+            .limit(3)
+            .build()
+            .unwrap();
+        let mut call_count = 0;
+        let err = backoff
+            .retry::<_, BogusError>(|| {
+                call_count += 1;
+                Err(api::ApiError::GitlabService {
+                    status: StatusCode::TOO_MANY_REQUESTS,
+                    data: Vec::default(),
+                })
+            })
+            .unwrap_err();
+        assert_eq!(call_count, 3);
+        if let api::ApiError::Client {
+            source: retry::Error::Backoff {},
+        } = err
+        {
+        } else {
+            panic!("unexpected error: {}", err);
+        }
+    }
+
+    #[test]
+    fn backoff_rate_limit_delay() {
+        let backoff = retry::Backoff::builder()
+            .max_wait(Duration::from_secs(30))
+            .build()
+            .unwrap();
+
+        let rsp = rate_limited("10");
+        assert_eq!(
+            backoff.rate_limit_delay(rsp.headers()),
+            Some(Duration::from_secs(10)),
+        );
+
+        // The delay is capped.
+        let rsp = rate_limited("3600");
+        assert_eq!(
+            backoff.rate_limit_delay(rsp.headers()),
+            Some(Duration::from_secs(30)),
+        );
+
+        // A date in the past does not wait.
+        let rsp = rate_limited("Sun, 06 Nov 1994 08:49:37 GMT");
+        assert_eq!(
+            backoff.rate_limit_delay(rsp.headers()),
+            Some(Duration::ZERO)
+        );
+
+        // Without `Retry-After`, wait for the reset.
+        let rsp = rate_limit_headers(0, SystemTime::now() + Duration::from_secs(20));
+        let delay = backoff.rate_limit_delay(rsp.headers()).unwrap();
+        assert!(delay > Duration::from_secs(10) && delay <= Duration::from_secs(20));
+    }
+
+    #[test]
+    fn backoff_rate_limit_disabled() {
+        let backoff = retry::Backoff::builder().rate_limit(false).build().unwrap();
+
+        let rsp = rate_limited("10");
+        assert_eq!(backoff.rate_limit_delay(rsp.headers()), None);
+
+        let rsp = rate_limit_headers(0, SystemTime::now() + Duration::from_secs(20));
+        backoff.record_rate_limit(rsp.headers());
+        assert_eq!(backoff.pause(), None);
+    }
+
+    #[test]
+    fn backoff_rate_limit_pause() {
+        let backoff = retry::Backoff::default();
+        assert_eq!(backoff.pause(), None);
+
+        let rsp = rate_limit_headers(5, SystemTime::now() + Duration::from_secs(20));
+        backoff.record_rate_limit(rsp.headers());
+        assert_eq!(backoff.pause(), None);
+
+        // The pause is shared between the clones.
+        let clone = backoff.clone();
+        let rsp = rate_limit_headers(0, SystemTime::now() + Duration::from_secs(20));
+        clone.record_rate_limit(rsp.headers());
+        let pause = backoff.pause().unwrap();
+        assert!(pause > Duration::from_secs(10) && pause <= Duration::from_secs(20));
+
+        // The reset is already over.
+        let rsp = rate_limit_headers(0, SystemTime::now() - Duration::from_secs(20));
+        backoff.record_rate_limit(rsp.headers());
+        assert_eq!(backoff.pause(), None);
+    }
+
+    #[tokio::test]
+    async fn backoff_async_second_success() {
+        let backoff = retry::Backoff::default();
+        let mut call_count = 0;
+        let body: &'static [u8] = b"";
+        backoff
+            .retry_async::<_, _, BogusError>(|| {
+                call_count += 1;
+                let rsp = if call_count == 1 {
+                    rate_limited("0")
+                } else {
+                    Response::builder()
+                        .status(StatusCode::OK)
+                        .body(body.into())
+                        .unwrap()
+                };
+                async { Ok(rsp) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(call_count, 2);
+    }
+
     struct Dummy;
 
     impl Endpoint for Dummy {
@@ -409,4 +752,20 @@ mod test {
             panic!("unexpected error: {}", err);
         }
     }
+
+    #[tokio::test]
+    async fn retry_client_async_ok() {
+        let endpoint = ExpectedUrl::builder().endpoint("dummy").build().unwrap();
+        let client = SingleTestClient::new_json(
+            endpoint,
+            &json!({
+                "value": 0,
+            }),
+        );
+        let backoff = retry::Backoff::default();
+        let client = retry::Client::new(client, backoff);
+
+        let res: DummyResult = Dummy.query_async(&client).await.unwrap();
+        assert_eq!(res.value, 0);
+    }
 }
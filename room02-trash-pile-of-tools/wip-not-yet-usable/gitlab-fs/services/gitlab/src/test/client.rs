@@ -234,6 +234,7 @@ impl Page {
 pub struct PagedTestClient<T> {
     expected: ExpectedUrl,
     data: Vec<T>,
+    totals: bool,
 }
 
 const KEYSET_QUERY_PARAM: &str = "__test_keyset";
@@ -247,8 +248,15 @@ impl<T> PagedTestClient<T> {
         Self {
             expected,
             data: data.into_iter().collect(),
+            totals: true,
         }
     }
+
+    /// Omit the `X-Total-Pages` header like GitLab does for large collections.
+    pub fn without_totals(mut self) -> Self {
+        self.totals = false;
+        self
+    }
 }
 
 impl<T> RestClient for PagedTestClient<T> {
@@ -357,6 +365,9 @@ where
             } else {
                 response
             }
+        } else if self.totals {
+            let total_pages = self.data.len().div_ceil(per_page);
+            response.header("X-Total-Pages", total_pages.to_string())
         } else {
             response
         };
@@ -21,6 +21,12 @@ pub struct ServerArgs {
         parse(try_from_str = parse_port_range)
     )]
     pub port_range: (u32, u32),
+    /// The secret shared with the clients to sign the probes
+    #[clap(long, env = "PORTMOLE_SECRET", parse(try_from_str = parse_secret))]
+    pub secret: String,
+    /// Accept the clients asking to be dialed back (reverse mode) on this port
+    #[clap(long)]
+    pub control_port: Option<u32>,
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: usize,
 }
@@ -39,10 +45,93 @@ pub struct ClientArgs {
     pub server: String,
     #[clap(short, long, default_value = "500")]
     pub timeout: u64,
+    /// How many ports are probed at the same time
+    #[clap(
+        short,
+        long,
+        default_value = "256",
+        parse(try_from_str = parse_concurrency),
+        conflicts_with = "reverse"
+    )]
+    pub concurrency: usize,
+    /// Send at most this many packets per second (E.g. to stay under the radar of an IDS)
+    #[clap(short, long, value_name = "pps", conflicts_with = "reverse")]
+    pub rate: Option<u32>,
+    /// The secret shared with the server to tell its answers from the ones of a middlebox
+    #[clap(long, env = "PORTMOLE_SECRET", parse(try_from_str = parse_secret))]
+    pub secret: String,
+    /// Listen on the port range and ask the server to dial back to it (inbound reachability)
+    /// The server paces its dial backs, so `--concurrency` and `--rate` cannot be used with it
+    #[clap(long)]
+    pub reverse: bool,
+    /// The control port of the server, used in reverse mode
+    #[clap(long, default_value = "7777")]
+    pub control_port: u32,
+    #[clap(short, long, arg_enum, default_value = "text")]
+    pub format: OutputFormat,
+    /// Fail if one of these TCP ports is not open (E.g. `22,80,8000-8100`)
+    #[clap(long, value_name = "ports", parse(try_from_str = parse_port_list))]
+    pub expect_tcp: Option<PortList>,
+    /// Fail if one of these UDP ports is not acked (E.g. `53,51820`)
+    #[clap(long, value_name = "ports", parse(try_from_str = parse_port_list))]
+    pub expect_udp: Option<PortList>,
     #[clap(short, long, parse(from_occurrences))]
     pub verbose: usize,
 }
 
+/// How the client prints its results
+#[derive(clap::ArgEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    /// The ports grouped by status
+    Text,
+    Json,
+    Csv,
+}
+
+/// A list of ports, sorted and without duplicates
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortList(pub Vec<u32>);
+
+fn parse_port_list(i: &str) -> Result<PortList, &'static str> {
+    let mut ports = Vec::new();
+    for part in i.split(',').map(str::trim) {
+        if part.contains('-') {
+            let (port_min, port_max) = parse_port_range(part)?;
+            ports.extend(port_min..=port_max);
+        } else {
+            let port = part
+                .parse::<u32>()
+                .ok()
+                .ok_or("Please provide valid numbers in port list")?;
+            if !(MIN_PORT..=MAX_PORT).contains(&port) {
+                return Err("In port list ports must be between 1 and 65535");
+            }
+            ports.push(port);
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+
+    Ok(PortList(ports))
+}
+
+/// An empty secret would sign the probes with a key known to anyone
+fn parse_secret(i: &str) -> Result<String, &'static str> {
+    if i.is_empty() {
+        Err("The secret cannot be empty")
+    } else {
+        Ok(i.to_string())
+    }
+}
+
+fn parse_concurrency(i: &str) -> Result<usize, &'static str> {
+    match i.parse::<usize>() {
+        Ok(0) => Err("Concurrency must be at least 1"),
+        Ok(concurrency) => Ok(concurrency),
+        Err(_) => Err("Please provide a valid number for concurrency"),
+    }
+}
+
 fn parse_port_range(i: &str) -> Result<(u32, u32), &'static str> {
     if !i.contains('-') {
         panic!("Please use <-> as a separator");
@@ -71,3 +160,94 @@ fn parse_port_range(i: &str) -> Result<(u32, u32), &'static str> {
         Err(_) => Err("Please provide valid numbers in port range"),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn port_lists_are_sorted_and_deduplicated() {
+        assert_eq!(parse_port_list("80"), Ok(PortList(vec![80])));
+        assert_eq!(
+            parse_port_list("443, 22,80"),
+            Ok(PortList(vec![22, 80, 443]))
+        );
+        assert_eq!(parse_port_list("80,80,22"), Ok(PortList(vec![22, 80])));
+    }
+
+    #[test]
+    fn overlapping_and_adjacent_ranges_are_merged() {
+        assert_eq!(parse_port_list("1-5,3-8"), Ok(PortList((1..=8).collect())));
+        assert_eq!(parse_port_list("1-3,4-6"), Ok(PortList((1..=6).collect())));
+        assert_eq!(
+            parse_port_list("10-12,11,1-10"),
+            Ok(PortList((1..=12).collect()))
+        );
+    }
+
+    #[test]
+    fn ports_must_be_between_1_and_65535() {
+        assert_eq!(parse_port_list("1"), Ok(PortList(vec![1])));
+        assert_eq!(parse_port_list("65535"), Ok(PortList(vec![65535])));
+        assert_eq!(
+            parse_port_list("65534-65535"),
+            Ok(PortList(vec![65534, 65535]))
+        );
+        assert!(parse_port_list("0").is_err());
+        assert!(parse_port_list("65536").is_err());
+        assert!(parse_port_list("0-10").is_err());
+        assert!(parse_port_list("65530-65536").is_err());
+        assert!(parse_port_list("22,0").is_err());
+    }
+
+    #[test]
+    fn reversed_ranges_are_rejected() {
+        assert!(parse_port_list("10-5").is_err());
+        assert!(parse_port_list("22,8100-8000").is_err());
+        assert_eq!(parse_port_range("5-10"), Ok((5, 10)));
+        assert_eq!(parse_port_range("10-10"), Ok((10, 10)));
+        assert!(parse_port_range("10-5").is_err());
+    }
+
+    fn parse_client(args: &[&str]) -> Result<ClientArgs, clap::Error> {
+        let args = [
+            "portmole",
+            "client",
+            "-s",
+            "localhost",
+            "-p",
+            "1-2",
+            "--secret",
+            "s",
+        ]
+        .iter()
+        .chain(args);
+        match Commands::try_parse_from(args)? {
+            Commands::Client(args) => Ok(args),
+            Commands::Server(_) => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn reverse_mode_rejects_the_pacing_of_the_client() {
+        assert!(parse_client(&["--reverse"]).unwrap().reverse);
+        assert_eq!(
+            parse_client(&["--concurrency", "4"]).unwrap().concurrency,
+            4
+        );
+        assert_eq!(parse_client(&["--rate", "10"]).unwrap().rate, Some(10));
+
+        for args in [["--concurrency", "4"], ["--rate", "10"]] {
+            let err = parse_client(&[&["--reverse"], &args[..]].concat()).unwrap_err();
+            assert_eq!(err.kind(), clap::ErrorKind::ArgumentConflict);
+        }
+    }
+
+    #[test]
+    fn invalid_port_lists_are_rejected() {
+        assert!(parse_port_list("").is_err());
+        assert!(parse_port_list("22,").is_err());
+        assert!(parse_port_list("http").is_err());
+        assert!(parse_port_list("1-2-3").is_err());
+    }
+}
@@ -1,8 +1,14 @@
-use std::{net::IpAddr, time::Duration};
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
-use eyre::{Context, ContextCompat};
-use futures::future;
+use eyre::{bail, eyre, Context};
+use futures::{future, stream, StreamExt, TryStreamExt};
 use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpStream, UdpSocket},
     time,
 };
@@ -11,107 +17,305 @@ use trust_dns_resolver::{
     AsyncResolver,
 };
 
-use crate::{setup_logging, ClientArgs, EyreResult, ACK_MSG, HELLO_MSG, MSG_BUFFER_LENGTH};
+use crate::{
+    net,
+    pacer::Pacer,
+    protocol::{Session, FRAME_LENGTH},
+    report::{compress_ranges, PortResult, Probe, Report, Target, TcpStatus, UdpStatus},
+    reverse, setup_logging, ClientArgs, EyreResult, OutputFormat, PortList, MSG_BUFFER_LENGTH,
+};
 
 pub async fn handle_client(args: ClientArgs) -> EyreResult<()> {
     setup_logging(args.verbose)?;
 
+    let (port_min, port_max) = args.port_range;
+    for PortList(ports) in [&args.expect_tcp, &args.expect_udp].into_iter().flatten() {
+        if ports
+            .iter()
+            .any(|port| !(port_min..=port_max).contains(port))
+        {
+            bail!(
+                "Expected ports must be in the port range {}-{}",
+                port_min,
+                port_max
+            );
+        }
+    }
+
     let resolver = AsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default())?;
     let resp = resolver
         .lookup_ip(args.server.clone())
         .await
         .wrap_err("Cannot find this domain name")?;
-    let address = resp
-        .iter()
-        .next()
-        .wrap_err("Cannot resolve IP from this domain name")?;
+    // Every A and AAAA record, a server can be reachable over IPv4 but not over IPv6
+    let mut addresses = Vec::new();
+    for address in resp.iter() {
+        if !addresses.contains(&address) {
+            addresses.push(address);
+        }
+    }
+    if addresses.is_empty() {
+        bail!("Cannot resolve IP from this domain name");
+    }
 
-    log::debug!("Server IP resolved from {} to {}", args.server, address);
+    log::debug!(
+        "Server IPs resolved from {} to {:?}",
+        args.server,
+        addresses
+    );
 
-    future::join_all(
-        (args.port_range.0..=args.port_range.1).map(|port| {
-            spawn_tcp_udp_connection(address, port, Duration::from_millis(args.timeout))
-        }),
-    )
-    .await
-    .into_iter()
-    .try_for_each(|res| {
-        let res = res?;
-        let res_tcp = res.0;
-        let res_udp = res.1;
-        EyreResult::from_iter([res_tcp, res_udp])
-    })?;
-
-    Ok(())
+    let session = Arc::new(Session::new(&args.secret));
+
+    let targets = if args.reverse {
+        reverse::probe_inbound(&args, &addresses, session).await?
+    } else {
+        let pacer = Pacer::new(args.rate);
+        let mut targets = Vec::new();
+        for address in addresses {
+            let ports = probe_outbound(&args, &session, &pacer, address).await?;
+            targets.push(Target::new(address, None, ports));
+        }
+        targets
+    };
+    let report = Report {
+        server: args.server.clone(),
+        targets,
+    };
+    match args.format {
+        OutputFormat::Text => print!("{}", report.summary()),
+        OutputFormat::Json => println!("{}", report.to_json()?),
+        OutputFormat::Csv => print!("{}", report.to_csv()),
+    }
+
+    check_expected_ports(&report, &args)
+}
+
+/// Fail if some of the ports expected to be reachable are not
+fn check_expected_ports(report: &Report, args: &ClientArgs) -> EyreResult<()> {
+    let mut blocked = Vec::new();
+
+    for target in &report.targets {
+        if let Some(PortList(expected)) = &args.expect_tcp {
+            let open = target.ports_where(|res| res.tcp.status == TcpStatus::Open);
+            let missing = expected
+                .iter()
+                .filter(|port| !open.contains(port))
+                .copied()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                blocked.push(format!(
+                    "TCP {} on {}",
+                    compress_ranges(&missing),
+                    target.address
+                ));
+            }
+        }
+        if let Some(PortList(expected)) = &args.expect_udp {
+            let acked = target.ports_where(|res| res.udp.status == UdpStatus::Acked);
+            let missing = expected
+                .iter()
+                .filter(|port| !acked.contains(port))
+                .copied()
+                .collect::<Vec<_>>();
+            if !missing.is_empty() {
+                blocked.push(format!(
+                    "UDP {} on {}",
+                    compress_ranges(&missing),
+                    target.address
+                ));
+            }
+        }
+    }
+
+    if blocked.is_empty() {
+        Ok(())
+    } else {
+        Err(eyre!("Expected ports are blocked: {}", blocked.join(", ")))
+    }
+}
+
+/// Probe each port of the range on an address of the server, at most `concurrency` ports at a time
+async fn probe_outbound(
+    args: &ClientArgs,
+    session: &Session,
+    pacer: &Pacer,
+    address: IpAddr,
+) -> EyreResult<Vec<PortResult>> {
+    let (port_min, port_max) = args.port_range;
+    let timeout = Duration::from_millis(args.timeout);
+
+    stream::iter(port_min..=port_max)
+        .map(|port| spawn_tcp_udp_connection(session, pacer, address, port, timeout))
+        .buffered(args.concurrency)
+        .try_collect()
+        .await
 }
 
 async fn spawn_tcp_udp_connection(
+    session: &Session,
+    pacer: &Pacer,
     address: IpAddr,
     port: u32,
     timeout: Duration,
-) -> EyreResult<(EyreResult<()>, EyreResult<()>)> {
-    Ok(future::join(
-        spawn_tcp_connection(address, port, timeout),
-        spawn_udp_connection(address, port, timeout),
+) -> EyreResult<PortResult> {
+    let (tcp, udp) = future::join(
+        spawn_tcp_connection(session, pacer, address, port, timeout),
+        spawn_udp_connection(session, pacer, address, port, timeout),
     )
-    .await)
+    .await;
+
+    Ok(PortResult {
+        port,
+        tcp: tcp?,
+        udp: udp?,
+    })
 }
 
-async fn spawn_tcp_connection(address: IpAddr, port: u32, timeout: Duration) -> EyreResult<()> {
-    let sever_address = format!("{}:{}", address, port);
+async fn spawn_tcp_connection(
+    session: &Session,
+    pacer: &Pacer,
+    address: IpAddr,
+    port: u32,
+    timeout: Duration,
+) -> EyreResult<Probe<TcpStatus>> {
+    let sever_address = SocketAddr::new(address, port as u16);
+    pacer.wait().await;
+    let start = Instant::now();
 
-    match time::timeout(timeout, TcpStream::connect(&sever_address)).await {
-        Ok(tcp_stream) => {
-            match tcp_stream {
-                Ok(stream) => log::info!("TCP Connection succeed to {}", stream.peer_addr()?),
-                Err(err) => {
-                    log::trace!("TCP Cannot connect to {} because {}", sever_address, err);
-                    log::debug!("TCP Cannot connect to {}", sever_address);
+    let probe = match time::timeout(timeout, TcpStream::connect(sever_address)).await {
+        Ok(tcp_stream) => match tcp_stream {
+            Ok(mut stream) => {
+                log::debug!("TCP Connection accepted by {}", sever_address);
+                let acked = exchange_tcp_frames(session, &mut stream, port, timeout).await;
+                if acked {
+                    log::info!("TCP Connection succeed to {}", sever_address);
+                    Probe::new(TcpStatus::Open, Some(start.elapsed()))
+                } else {
+                    log::debug!(
+                        "TCP Connection accepted by {} but not by our server",
+                        sever_address
+                    );
+                    Probe::new(TcpStatus::Intercepted, Some(start.elapsed()))
+                }
+            }
+            Err(err) => {
+                log::trace!("TCP Cannot connect to {} because {}", sever_address, err);
+                log::debug!("TCP Cannot connect to {}", sever_address);
+                if err.kind() == io::ErrorKind::ConnectionRefused {
+                    Probe::new(TcpStatus::Closed, Some(start.elapsed()))
+                } else {
+                    Probe::new(TcpStatus::Filtered, None)
                 }
-            };
+            }
+        },
+        Err(_elapsed) => {
+            log::debug!(
+                "TCP Cannot connect to {}, timeout after {}ms",
+                sever_address,
+                timeout.as_millis()
+            );
+            Probe::new(TcpStatus::Timeout, None)
         }
-        Err(_elapsed) => log::debug!(
-            "TCP Cannot connect to {}, timeout after {}ms",
-            sever_address,
-            timeout.as_millis()
-        ),
-    }
+    };
 
-    Ok(())
+    Ok(probe)
 }
 
-async fn spawn_udp_connection(address: IpAddr, port: u32, timeout: Duration) -> EyreResult<()> {
-    let socket = UdpSocket::bind("127.0.0.1:0").await?;
-    let server_address = format!("{}:{}", address, port);
+/// Send a `Hello` on an open connection and check the server acknowledges it
+async fn exchange_tcp_frames(
+    session: &Session,
+    stream: &mut TcpStream,
+    port: u32,
+    timeout: Duration,
+) -> bool {
+    let port = port as u16;
+    let mut buf = [0; FRAME_LENGTH];
 
-    match socket.connect(&server_address).await {
+    let exchange = async {
+        stream.write_all(&session.hello(port)).await?;
+        stream.read_exact(&mut buf).await
+    };
+    match time::timeout(timeout, exchange).await {
+        Ok(Ok(_len)) => session.is_ack(port, &buf),
+        Ok(Err(err)) => {
+            log::trace!(
+                "TCP Cannot exchange frames on port {} because {}",
+                port,
+                err
+            );
+            false
+        }
+        Err(_elapsed) => false,
+    }
+}
+
+async fn spawn_udp_connection(
+    session: &Session,
+    pacer: &Pacer,
+    address: IpAddr,
+    port: u32,
+    timeout: Duration,
+) -> EyreResult<Probe<UdpStatus>> {
+    let server_address = SocketAddr::new(address, port as u16);
+    // Bound to the family of the server, a loopback address cannot reach it
+    let socket = match UdpSocket::bind(SocketAddr::new(net::unspecified_like(address), 0)).await {
+        Ok(socket) => socket,
+        Err(err) => {
+            // E.g. no IPv6 on this host, the other family and ports are still scanned
+            log::trace!(
+                "UDP Cannot bind a socket for {} because {}",
+                server_address,
+                err
+            );
+            log::debug!("UDP Cannot bind a socket for {}", server_address);
+            return Ok(Probe::new(UdpStatus::Silent, None));
+        }
+    };
+
+    let probe = match socket.connect(server_address).await {
         Ok(()) => {
             let mut buf = [0; MSG_BUFFER_LENGTH];
 
-            socket.send(HELLO_MSG).await?;
+            pacer.wait().await;
+            let start = Instant::now();
+            if let Err(err) = socket.send(&session.hello(port as u16)).await {
+                log::trace!("UDP Cannot send to {} because {}", server_address, err);
+                log::debug!("UDP Cannot send to {}", server_address);
+                return Ok(Probe::new(UdpStatus::Silent, None));
+            }
             match time::timeout(timeout, socket.recv(&mut buf)).await {
-                Ok(recv_result) => {
-                    let len = recv_result?;
-
-                    if &buf[..len] == ACK_MSG {
+                Ok(Ok(len)) => {
+                    if session.is_ack(port as u16, &buf[..len]) {
                         log::info!("UDP Connection succeed to {}", server_address);
+                        Probe::new(UdpStatus::Acked, Some(start.elapsed()))
                     } else {
-                        log::debug!("UDP Connection successful but ACK message is not good");
+                        log::debug!(
+                            "UDP Connection successful but the answer is not from our server"
+                        );
+                        Probe::new(UdpStatus::WrongAck, Some(start.elapsed()))
                     }
                 }
+                Ok(Err(err)) => {
+                    log::trace!("UDP Cannot receive from {} because {}", server_address, err);
+                    log::debug!("UDP Cannot receive from {}", server_address);
+                    Probe::new(UdpStatus::Silent, None)
+                }
                 Err(_elapsed) => {
                     log::debug!(
                         "UDP Cannot receive from {}, timeout after {}ms",
                         server_address,
                         timeout.as_millis()
                     );
+                    Probe::new(UdpStatus::Silent, None)
                 }
             }
         }
         Err(err) => {
             log::trace!("UDP Cannot connect to {} because {}", server_address, err);
             log::debug!("UDP Cannot connect to {}", server_address);
+            Probe::new(UdpStatus::Silent, None)
         }
-    }
-    Ok(())
+    };
+
+    Ok(probe)
 }
@@ -2,15 +2,18 @@ use clap::Parser;
 
 mod cli;
 mod client;
+mod net;
+mod pacer;
+mod protocol;
+mod report;
+mod reverse;
 mod server;
 use cli::*;
 
 pub type EyreResult<Output> = color_eyre::eyre::Result<Output>;
 
-pub const HELLO_MSG: &[u8] = b"PING";
-pub const EMPTY_MSG: &[u8] = b"";
-pub const ACK_MSG: &[u8] = b"PONG";
-pub const MSG_BUFFER_LENGTH: usize = 16;
+/// Large enough to notice an answer longer than a frame
+pub const MSG_BUFFER_LENGTH: usize = protocol::FRAME_LENGTH + 1;
 
 fn setup_logging(verbosity: usize) -> EyreResult<()> {
     if verbosity > 0 {
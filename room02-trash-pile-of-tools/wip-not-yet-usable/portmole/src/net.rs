@@ -0,0 +1,84 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+};
+
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::{TcpListener, UdpSocket};
+
+/// The addresses to listen on to accept both IPv4 and IPv6
+pub const ANY_ADDRESSES: [IpAddr; 2] = [
+    IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+    IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+];
+
+/// The unspecified address of the same family as an address (E.g. to bind a socket able to reach it)
+pub fn unspecified_like(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+        IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+    }
+}
+
+fn socket(address: SocketAddr, ty: Type, protocol: Protocol) -> io::Result<Socket> {
+    let socket = Socket::new(Domain::for_address(address), ty, Some(protocol))?;
+    // Leave IPv4 to the IPv4 sockets so that both families can listen on the same port
+    if address.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    socket.set_nonblocking(true)?;
+
+    Ok(socket)
+}
+
+pub fn bind_tcp(address: SocketAddr) -> io::Result<TcpListener> {
+    let socket = socket(address, Type::STREAM, Protocol::TCP)?;
+    socket.set_reuse_address(true)?;
+    socket.bind(&address.into())?;
+    socket.listen(1024)?;
+
+    TcpListener::from_std(socket.into())
+}
+
+pub fn bind_udp(address: SocketAddr) -> io::Result<UdpSocket> {
+    let socket = socket(address, Type::DGRAM, Protocol::UDP)?;
+    socket.bind(&address.into())?;
+
+    UdpSocket::from_std(socket.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unspecified_addresses_keep_the_family() {
+        let ipv4: IpAddr = "192.0.2.1".parse().unwrap();
+        let ipv6: IpAddr = "2001:db8::1".parse().unwrap();
+
+        assert_eq!(unspecified_like(ipv4), IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(unspecified_like(ipv6), IpAddr::V6(Ipv6Addr::UNSPECIFIED));
+        assert_eq!(
+            unspecified_like(Ipv4Addr::LOCALHOST.into()),
+            ANY_ADDRESSES[0]
+        );
+        assert_eq!(
+            unspecified_like(Ipv6Addr::LOCALHOST.into()),
+            ANY_ADDRESSES[1]
+        );
+    }
+
+    #[tokio::test]
+    async fn both_families_listen_on_the_same_port() {
+        let ipv4 = bind_tcp(SocketAddr::new(ANY_ADDRESSES[0], 0)).unwrap();
+        let port = ipv4.local_addr().unwrap().port();
+
+        let ipv6 = bind_tcp(SocketAddr::new(ANY_ADDRESSES[1], port)).unwrap();
+        assert_eq!(ipv6.local_addr().unwrap().port(), port);
+
+        let udp_ipv4 = bind_udp(SocketAddr::new(ANY_ADDRESSES[0], port)).unwrap();
+        let udp_ipv6 = bind_udp(SocketAddr::new(ANY_ADDRESSES[1], port)).unwrap();
+        assert!(udp_ipv4.local_addr().unwrap().is_ipv4());
+        assert!(udp_ipv6.local_addr().unwrap().is_ipv6());
+    }
+}
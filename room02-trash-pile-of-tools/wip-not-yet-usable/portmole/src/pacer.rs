@@ -0,0 +1,80 @@
+use std::time::Duration;
+
+use tokio::{
+    sync::Mutex,
+    time::{self, Interval, MissedTickBehavior},
+};
+
+/// Spread the packets sent by the client to stay under a rate
+pub struct Pacer {
+    interval: Option<Mutex<Interval>>,
+}
+
+impl Pacer {
+    /// `None` to send the packets as fast as possible
+    pub fn new(packets_per_second: Option<u32>) -> Self {
+        let interval = packets_per_second.filter(|pps| *pps > 0).map(|pps| {
+            // Zero above 1G packets per second, an interval cannot be empty
+            let period = (Duration::from_secs(1) / pps).max(Duration::from_nanos(1));
+            let mut interval = time::interval(period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            Mutex::new(interval)
+        });
+
+        Self { interval }
+    }
+
+    /// Wait for the turn of the next packet
+    pub async fn wait(&self) {
+        if let Some(interval) = &self.interval {
+            interval.lock().await.tick().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::Instant;
+
+    use super::*;
+
+    #[tokio::test(start_paused = true)]
+    async fn packets_are_spread_over_the_second() {
+        let pacer = Pacer::new(Some(10));
+        let start = Instant::now();
+
+        for _ in 0..11 {
+            pacer.wait().await;
+        }
+
+        // The first packet is sent right away
+        assert_eq!(start.elapsed(), Duration::from_secs(1));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn no_rate_does_not_wait() {
+        for pacer in [Pacer::new(None), Pacer::new(Some(0))] {
+            let start = Instant::now();
+
+            for _ in 0..100 {
+                pacer.wait().await;
+            }
+
+            assert_eq!(start.elapsed(), Duration::ZERO);
+        }
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn huge_rates_do_not_panic() {
+        // Above 1G packets per second the period would be zero
+        let pacer = Pacer::new(Some(u32::MAX));
+        let start = Instant::now();
+
+        for _ in 0..3 {
+            pacer.wait().await;
+        }
+
+        // NOTE: The timers of tokio have a resolution of 1ms
+        assert!(start.elapsed() <= Duration::from_millis(2));
+    }
+}
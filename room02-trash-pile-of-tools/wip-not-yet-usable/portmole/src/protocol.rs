@@ -0,0 +1,326 @@
+//! The frames exchanged by the client and the server over TCP and UDP
+//!
+//! A frame is `magic | version | kind | port | nonce | HMAC-SHA256(secret, everything before)`.
+//! The client sends a `Hello` with a nonce drawn for the whole run and the probed port, the server
+//! answers an `Ack` with the same nonce and the port it received the frame on. Something else
+//! answering (E.g. a captive portal or a transparent proxy) cannot produce a valid `Ack`.
+//!
+//! In reverse mode, the client sends its `Hello` on a control connection followed by a
+//! `ControlMessage::Request` (one JSON per line), then the server sends a `DialBack` frame to each
+//! port of the client.
+
+use std::{net::SocketAddr, time::Duration};
+
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::EyreResult;
+
+type HmacSha256 = Hmac<Sha256>;
+
+const MAGIC: &[u8; 4] = b"PMOL";
+const VERSION: u8 = 1;
+const NONCE_LENGTH: usize = 16;
+const MAC_LENGTH: usize = 32;
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 1 + 2 + NONCE_LENGTH;
+pub const FRAME_LENGTH: usize = HEADER_LENGTH + MAC_LENGTH;
+
+pub type Nonce = [u8; NONCE_LENGTH];
+
+/// How long the server waits for a TCP connection to the client in reverse mode
+pub const DIAL_BACK_TIMEOUT: Duration = Duration::from_secs(2);
+/// How many ports of a client the server dials back at the same time
+pub const DIAL_BACK_CONCURRENCY: usize = 256;
+
+/// The longest the server can take to dial back to a port range before sending `Done`
+pub fn dial_back_duration(port_min: u32, port_max: u32) -> Duration {
+    let ports = port_max.saturating_sub(port_min) as usize + 1;
+    DIAL_BACK_TIMEOUT * ports.div_ceil(DIAL_BACK_CONCURRENCY) as u32
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameKind {
+    Hello = 1,
+    Ack = 2,
+    DialBack = 3,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Frame {
+    pub kind: FrameKind,
+    pub port: u16,
+    pub nonce: Nonce,
+}
+
+fn hmac(secret: &[u8]) -> HmacSha256 {
+    HmacSha256::new_from_slice(secret).expect("HMAC accepts keys of any length")
+}
+
+impl Frame {
+    fn header(&self) -> [u8; HEADER_LENGTH] {
+        let mut header = [0; HEADER_LENGTH];
+        header[..4].copy_from_slice(MAGIC);
+        header[4] = VERSION;
+        header[5] = self.kind as u8;
+        header[6..8].copy_from_slice(&self.port.to_be_bytes());
+        header[8..].copy_from_slice(&self.nonce);
+        header
+    }
+
+    pub fn encode(&self, secret: &[u8]) -> [u8; FRAME_LENGTH] {
+        let header = self.header();
+        let mut mac = hmac(secret);
+        mac.update(&header);
+
+        let mut frame = [0; FRAME_LENGTH];
+        frame[..HEADER_LENGTH].copy_from_slice(&header);
+        frame[HEADER_LENGTH..].copy_from_slice(&mac.finalize().into_bytes());
+        frame
+    }
+
+    /// Decode a frame, `None` if it is malformed or was not signed with the same secret
+    pub fn decode(data: &[u8], secret: &[u8]) -> Option<Self> {
+        if data.len() != FRAME_LENGTH || &data[..4] != MAGIC || data[4] != VERSION {
+            return None;
+        }
+
+        let mut mac = hmac(secret);
+        mac.update(&data[..HEADER_LENGTH]);
+        mac.verify_slice(&data[HEADER_LENGTH..]).ok()?;
+
+        let kind = match data[5] {
+            1 => FrameKind::Hello,
+            2 => FrameKind::Ack,
+            3 => FrameKind::DialBack,
+            _ => return None,
+        };
+        let mut nonce = [0; NONCE_LENGTH];
+        nonce.copy_from_slice(&data[8..HEADER_LENGTH]);
+
+        Some(Self {
+            kind,
+            port: u16::from_be_bytes([data[6], data[7]]),
+            nonce,
+        })
+    }
+}
+
+/// The state of the client shared by all the probes of a run
+#[derive(Debug, Clone)]
+pub struct Session {
+    secret: Vec<u8>,
+    nonce: Nonce,
+}
+
+impl Session {
+    pub fn new(secret: &str) -> Self {
+        let mut nonce = [0; NONCE_LENGTH];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        Self {
+            secret: secret.as_bytes().to_vec(),
+            nonce,
+        }
+    }
+
+    pub fn hello(&self, port: u16) -> [u8; FRAME_LENGTH] {
+        Frame {
+            kind: FrameKind::Hello,
+            port,
+            nonce: self.nonce,
+        }
+        .encode(&self.secret)
+    }
+
+    /// Whether the data is the answer of our server to the `Hello` sent to a port
+    pub fn is_ack(&self, port: u16, data: &[u8]) -> bool {
+        self.is_frame(FrameKind::Ack, port, data)
+    }
+
+    /// Whether the data was sent by our server to a port in reverse mode
+    pub fn is_dial_back(&self, port: u16, data: &[u8]) -> bool {
+        self.is_frame(FrameKind::DialBack, port, data)
+    }
+
+    fn is_frame(&self, kind: FrameKind, port: u16, data: &[u8]) -> bool {
+        Frame::decode(data, &self.secret)
+            == Some(Frame {
+                kind,
+                port,
+                nonce: self.nonce,
+            })
+    }
+}
+
+/// Decode a `Hello`, `None` if it is not a valid one
+pub fn decode_hello(data: &[u8], secret: &[u8]) -> Option<Frame> {
+    Frame::decode(data, secret).filter(|frame| frame.kind == FrameKind::Hello)
+}
+
+/// The `Ack` the server must answer to a `Hello` received on a port, `None` if it is not a valid `Hello`
+pub fn answer(data: &[u8], secret: &[u8], port: u16) -> Option<[u8; FRAME_LENGTH]> {
+    let hello = decode_hello(data, secret)?;
+
+    Some(
+        Frame {
+            kind: FrameKind::Ack,
+            port,
+            nonce: hello.nonce,
+        }
+        .encode(secret),
+    )
+}
+
+/// The messages exchanged on the control connection after the `Hello`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ControlMessage {
+    /// The client asks the server to dial back to each port of a range
+    Request { port_min: u32, port_max: u32 },
+    /// The address of the client as seen by the server (after NAT)
+    Observed { address: SocketAddr },
+    /// The server dialed back to every port
+    Done,
+}
+
+/// Send a control message as one JSON line
+pub async fn write_message<W>(writer: &mut W, message: &ControlMessage) -> EyreResult<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut line = serde_json::to_vec(message)?;
+    line.push(b'\n');
+    writer.write_all(&line).await?;
+
+    Ok(())
+}
+
+/// Receive a control message, `None` once the connection is closed
+pub async fn read_message<R>(reader: &mut R) -> EyreResult<Option<ControlMessage>>
+where
+    R: AsyncBufRead + Unpin,
+{
+    let mut line = String::new();
+    if reader.read_line(&mut line).await? == 0 {
+        return Ok(None);
+    }
+
+    Ok(Some(serde_json::from_str(&line)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECRET: &[u8] = b"my-secret";
+
+    fn frame(kind: FrameKind) -> Frame {
+        Frame {
+            kind,
+            port: 8080,
+            nonce: [7; NONCE_LENGTH],
+        }
+    }
+
+    /// Sign a header as `Frame::encode` does, to forge frames it cannot produce
+    fn sign(header: &[u8]) -> Vec<u8> {
+        let mut mac = hmac(SECRET);
+        mac.update(header);
+        [header, &mac.finalize().into_bytes()].concat()
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        for kind in [FrameKind::Hello, FrameKind::Ack, FrameKind::DialBack] {
+            let encoded = frame(kind).encode(SECRET);
+
+            assert_eq!(&encoded[..4], MAGIC);
+            assert_eq!(encoded[4], VERSION);
+            assert_eq!(encoded[5], kind as u8);
+            assert_eq!(&encoded[6..8], &8080u16.to_be_bytes());
+            assert_eq!(Frame::decode(&encoded, SECRET), Some(frame(kind)));
+        }
+    }
+
+    #[test]
+    fn frames_signed_with_another_secret_are_rejected() {
+        let encoded = frame(FrameKind::Hello).encode(b"another-secret");
+
+        assert_eq!(Frame::decode(&encoded, SECRET), None);
+    }
+
+    #[test]
+    fn tampered_frames_are_rejected() {
+        let encoded = frame(FrameKind::Ack).encode(SECRET);
+
+        let mut port = encoded;
+        port[7] ^= 1;
+        assert_eq!(Frame::decode(&port, SECRET), None);
+
+        let mut nonce = encoded;
+        nonce[HEADER_LENGTH - 1] ^= 1;
+        assert_eq!(Frame::decode(&nonce, SECRET), None);
+
+        let mut mac = encoded;
+        mac[FRAME_LENGTH - 1] ^= 1;
+        assert_eq!(Frame::decode(&mac, SECRET), None);
+    }
+
+    #[test]
+    fn malformed_frames_are_rejected() {
+        let encoded = frame(FrameKind::Hello).encode(SECRET);
+        let header = &encoded[..HEADER_LENGTH];
+
+        assert_eq!(Frame::decode(b"", SECRET), None);
+        assert_eq!(Frame::decode(&encoded[..FRAME_LENGTH - 1], SECRET), None);
+        assert_eq!(Frame::decode(&[&encoded[..], &[0]].concat(), SECRET), None);
+
+        let mut magic = header.to_vec();
+        magic[0] = b'X';
+        assert_eq!(Frame::decode(&sign(&magic), SECRET), None);
+
+        let mut version = header.to_vec();
+        version[4] = VERSION + 1;
+        assert_eq!(Frame::decode(&sign(&version), SECRET), None);
+
+        let mut kind = header.to_vec();
+        kind[5] = 4;
+        assert_eq!(Frame::decode(&sign(&kind), SECRET), None);
+
+        // Sanity check of the forged frames
+        assert_eq!(
+            Frame::decode(&sign(header), SECRET),
+            Some(frame(FrameKind::Hello))
+        );
+    }
+
+    #[test]
+    fn the_server_acks_the_hello_of_the_session() {
+        let session = Session::new("my-secret");
+        let ack = answer(&session.hello(8080), SECRET, 8080).unwrap();
+
+        assert!(session.is_ack(8080, &ack));
+        assert!(!session.is_dial_back(8080, &ack));
+        // Answered on another port than the probed one (E.g. redirected by a NAT)
+        let redirected = answer(&session.hello(8080), SECRET, 8081).unwrap();
+        assert!(!session.is_ack(8080, &redirected));
+        // The ack of another run
+        assert!(!Session::new("my-secret").is_ack(8080, &ack));
+    }
+
+    #[test]
+    fn the_server_only_answers_signed_hellos() {
+        let session = Session::new("my-secret");
+
+        assert_eq!(answer(&session.hello(8080), b"another-secret", 8080), None);
+        assert_eq!(
+            answer(&frame(FrameKind::Ack).encode(SECRET), SECRET, 8080),
+            None
+        );
+        assert_eq!(answer(b"", SECRET, 8080), None);
+        assert_eq!(answer(&[0; FRAME_LENGTH], SECRET, 8080), None);
+    }
+}
@@ -0,0 +1,360 @@
+use std::{
+    fmt,
+    net::{IpAddr, SocketAddr},
+    time::Duration,
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::EyreResult;
+
+/// What happened when connecting to a TCP port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TcpStatus {
+    /// The connection was accepted and our server answered the probe
+    Open,
+    /// The connection was accepted, but not by our server (E.g. a transparent proxy)
+    Intercepted,
+    /// The connection was refused (something answered with a RST)
+    Closed,
+    /// The connection failed for another reason (E.g. an ICMP unreachable)
+    Filtered,
+    /// Nothing answered before the timeout
+    Timeout,
+}
+
+impl TcpStatus {
+    const ALL: [Self; 5] = [
+        Self::Open,
+        Self::Intercepted,
+        Self::Closed,
+        Self::Filtered,
+        Self::Timeout,
+    ];
+}
+
+impl fmt::Display for TcpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Open => "open",
+            Self::Intercepted => "intercepted",
+            Self::Closed => "closed",
+            Self::Filtered => "filtered",
+            Self::Timeout => "timeout",
+        })
+    }
+}
+
+/// What happened when sending a message to a UDP port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum UdpStatus {
+    /// The server answered with the expected acknowledgement
+    Acked,
+    /// Nothing answered before the timeout (or the message could not be sent)
+    Silent,
+    /// Something answered, but not with an acknowledgement signed by our server
+    WrongAck,
+}
+
+impl UdpStatus {
+    const ALL: [Self; 3] = [Self::Acked, Self::Silent, Self::WrongAck];
+}
+
+impl fmt::Display for UdpStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Acked => "acked",
+            Self::Silent => "silent",
+            Self::WrongAck => "wrong-ack",
+        })
+    }
+}
+
+/// The result of a probe with the time it took to get an answer
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct Probe<Status> {
+    pub status: Status,
+    #[serde(rename = "latency_ms", serialize_with = "serialize_latency")]
+    pub latency: Option<Duration>,
+}
+
+impl<Status> Probe<Status> {
+    pub fn new(status: Status, latency: Option<Duration>) -> Self {
+        Self { status, latency }
+    }
+}
+
+fn serialize_latency<S: Serializer>(
+    latency: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match latency {
+        Some(latency) => serializer.serialize_f64(latency_ms(*latency)),
+        None => serializer.serialize_none(),
+    }
+}
+
+fn latency_ms(latency: Duration) -> f64 {
+    latency.as_secs_f64() * 1000.
+}
+
+/// The TCP and UDP results of a port
+#[derive(Debug, Clone, Serialize)]
+pub struct PortResult {
+    pub port: u32,
+    pub tcp: Probe<TcpStatus>,
+    pub udp: Probe<UdpStatus>,
+}
+
+/// The family of the address of a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum AddressFamily {
+    Ipv4,
+    Ipv6,
+}
+
+impl From<IpAddr> for AddressFamily {
+    fn from(address: IpAddr) -> Self {
+        match address {
+            IpAddr::V4(_) => Self::Ipv4,
+            IpAddr::V6(_) => Self::Ipv6,
+        }
+    }
+}
+
+impl fmt::Display for AddressFamily {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Ipv4 => "IPv4",
+            Self::Ipv6 => "IPv6",
+        })
+    }
+}
+
+/// The results for one of the addresses of the server
+#[derive(Debug, Clone, Serialize)]
+pub struct Target {
+    pub address: IpAddr,
+    pub family: AddressFamily,
+    /// Our address as seen by the server in reverse mode
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub public_address: Option<SocketAddr>,
+    /// Sorted by port
+    pub ports: Vec<PortResult>,
+}
+
+impl Target {
+    pub fn new(
+        address: IpAddr,
+        public_address: Option<SocketAddr>,
+        ports: Vec<PortResult>,
+    ) -> Self {
+        Self {
+            address,
+            family: AddressFamily::from(address),
+            public_address,
+            ports,
+        }
+    }
+
+    /// One line per status with the ports in it (E.g. `TCP open: 1-1023, 8080`)
+    pub fn summary(&self) -> String {
+        let tcp = TcpStatus::ALL.iter().map(|status| {
+            (
+                format!("TCP {}", status),
+                self.ports_where(|res| res.tcp.status == *status),
+            )
+        });
+        let udp = UdpStatus::ALL.iter().map(|status| {
+            (
+                format!("UDP {}", status),
+                self.ports_where(|res| res.udp.status == *status),
+            )
+        });
+
+        let ports = tcp
+            .chain(udp)
+            .filter(|(_, ports)| !ports.is_empty())
+            .map(|(label, ports)| format!("{}: {}\n", label, compress_ranges(&ports)));
+
+        self.public_address
+            .map(|address| format!("Public address: {}\n", address))
+            .into_iter()
+            .chain(ports)
+            .collect()
+    }
+
+    pub fn ports_where<F>(&self, predicate: F) -> Vec<u32>
+    where
+        F: Fn(&PortResult) -> bool,
+    {
+        self.ports
+            .iter()
+            .filter(|res| predicate(res))
+            .map(|res| res.port)
+            .collect()
+    }
+}
+
+/// The results of a whole run of the client, one target per address of the server
+#[derive(Debug, Clone, Serialize)]
+pub struct Report {
+    pub server: String,
+    pub targets: Vec<Target>,
+}
+
+impl Report {
+    /// The summary of each target under its address (E.g. `IPv6 2001:db8::1`)
+    pub fn summary(&self) -> String {
+        self.targets
+            .iter()
+            .map(|target| {
+                let lines = target
+                    .summary()
+                    .lines()
+                    .map(|line| format!("  {}\n", line))
+                    .collect::<String>();
+                format!("{} {}\n{}", target.family, target.address, lines)
+            })
+            .collect()
+    }
+
+    pub fn to_json(&self) -> EyreResult<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    /// One row per address, port and protocol
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("address,family,port,protocol,status,latency_ms\n");
+        for target in &self.targets {
+            let family = match target.family {
+                AddressFamily::Ipv4 => "ipv4",
+                AddressFamily::Ipv6 => "ipv6",
+            };
+            for res in &target.ports {
+                let rows = [
+                    ("tcp", res.tcp.status.to_string(), res.tcp.latency),
+                    ("udp", res.udp.status.to_string(), res.udp.latency),
+                ];
+                for (protocol, status, latency) in rows {
+                    let latency = latency
+                        .map(|latency| format!("{:.3}", latency_ms(latency)))
+                        .unwrap_or_default();
+                    csv.push_str(&format!(
+                        "{},{},{},{},{},{}\n",
+                        target.address, family, res.port, protocol, status, latency
+                    ));
+                }
+            }
+        }
+        csv
+    }
+}
+
+/// Compress sorted ports into ranges (E.g. `[1, 2, 3, 8080]` gives `1-3, 8080`), duplicates are merged
+pub fn compress_ranges(ports: &[u32]) -> String {
+    let mut ranges: Vec<(u32, u32)> = Vec::new();
+    for port in ports {
+        match ranges.last_mut() {
+            Some((_, end)) if *port <= *end + 1 => *end = (*end).max(*port),
+            _ => ranges.push((*port, *port)),
+        }
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ports(tcp: TcpStatus, udp: UdpStatus) -> Vec<PortResult> {
+        (1..=3)
+            .map(|port| PortResult {
+                port,
+                tcp: Probe::new(tcp, None),
+                udp: Probe::new(udp, None),
+            })
+            .collect()
+    }
+
+    fn report() -> Report {
+        Report {
+            server: "my.server.example".to_string(),
+            targets: vec![
+                Target::new(
+                    "192.0.2.1".parse().unwrap(),
+                    None,
+                    ports(TcpStatus::Open, UdpStatus::Acked),
+                ),
+                Target::new(
+                    "2001:db8::1".parse().unwrap(),
+                    None,
+                    ports(TcpStatus::Timeout, UdpStatus::Silent),
+                ),
+            ],
+        }
+    }
+
+    #[test]
+    fn targets_are_split_by_family() {
+        let report = report();
+
+        assert_eq!(report.targets[0].family, AddressFamily::Ipv4);
+        assert_eq!(report.targets[1].family, AddressFamily::Ipv6);
+        assert_eq!(
+            report.summary(),
+            "IPv4 192.0.2.1\n  TCP open: 1-3\n  UDP acked: 1-3\n\
+             IPv6 2001:db8::1\n  TCP timeout: 1-3\n  UDP silent: 1-3\n"
+        );
+    }
+
+    #[test]
+    fn each_row_has_the_family_of_its_target() {
+        let csv = report().to_csv();
+        let rows = csv.lines().collect::<Vec<_>>();
+
+        assert_eq!(rows.len(), 1 + 2 * 3 * 2);
+        assert_eq!(rows[1], "192.0.2.1,ipv4,1,tcp,open,");
+        assert_eq!(rows[7], "2001:db8::1,ipv6,1,tcp,timeout,");
+        assert!(report().to_json().unwrap().contains("\"family\": \"ipv6\""));
+    }
+
+    #[test]
+    fn adjacent_ports_are_compressed() {
+        assert_eq!(compress_ranges(&[]), "");
+        assert_eq!(compress_ranges(&[22]), "22");
+        assert_eq!(compress_ranges(&[1, 2, 3, 8080]), "1-3, 8080");
+        assert_eq!(compress_ranges(&[1, 3, 5]), "1, 3, 5");
+        assert_eq!(compress_ranges(&[1, 2, 4, 5]), "1-2, 4-5");
+    }
+
+    #[test]
+    fn overlapping_ports_are_merged() {
+        assert_eq!(compress_ranges(&[1, 1, 2, 2, 3]), "1-3");
+        assert_eq!(compress_ranges(&[8080, 8080]), "8080");
+        assert_eq!(compress_ranges(&[1, 2, 2, 4]), "1-2, 4");
+    }
+
+    #[test]
+    fn the_bounds_of_the_port_range_are_kept() {
+        assert_eq!(compress_ranges(&[0]), "0");
+        assert_eq!(compress_ranges(&[0, 1, 2]), "0-2");
+        assert_eq!(compress_ranges(&[65535]), "65535");
+        assert_eq!(compress_ranges(&[65533, 65534, 65535]), "65533-65535");
+        assert_eq!(compress_ranges(&[1, 65535]), "1, 65535");
+    }
+}
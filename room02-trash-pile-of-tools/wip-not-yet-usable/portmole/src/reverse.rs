@@ -0,0 +1,366 @@
+use std::{
+    collections::HashSet,
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::{bail, Context, ContextCompat};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
+    sync::mpsc::{self, UnboundedSender},
+    task::JoinHandle,
+    time,
+};
+
+use crate::{
+    net,
+    protocol::{self, ControlMessage, Session, FRAME_LENGTH},
+    report::{PortResult, Probe, Target, TcpStatus, UdpStatus},
+    ClientArgs, EyreResult, MSG_BUFFER_LENGTH,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Transport {
+    Tcp,
+    Udp,
+}
+
+/// Listen on each port of the range and ask each address of the server to dial back to them
+/// The ports reached by the server are reported as TCP open and UDP acked
+pub async fn probe_inbound(
+    args: &ClientArgs,
+    addresses: &[IpAddr],
+    session: Arc<Session>,
+) -> EyreResult<Vec<Target>> {
+    let (port_min, port_max) = args.port_range;
+    let timeout = Duration::from_millis(args.timeout);
+    let (arrived_sender, mut arrived_receiver) = mpsc::unbounded_channel();
+
+    // Bind everything before the server starts dialing, on both families as the server can have both
+    let mut listeners = Vec::new();
+    for port in port_min..=port_max {
+        for address in net::ANY_ADDRESSES {
+            let listen_address = SocketAddr::new(address, port as u16);
+            listeners.extend(listen_tcp(
+                listen_address,
+                session.clone(),
+                arrived_sender.clone(),
+                timeout,
+            ));
+            listeners.extend(listen_udp(
+                listen_address,
+                session.clone(),
+                arrived_sender.clone(),
+            ));
+        }
+    }
+
+    // One server address at a time, so that the dial backs are counted for the right one
+    let mut targets = Vec::new();
+    let mut last_error = None;
+    for address in addresses.iter().copied() {
+        let public_address = request_dial_back(args, address, &session, timeout).await;
+
+        // Give the last dial backs some time to arrive
+        time::sleep(timeout).await;
+        let mut arrived = HashSet::new();
+        while let Ok(dial_back) = arrived_receiver.try_recv() {
+            arrived.insert(dial_back);
+        }
+
+        let public_address = match public_address {
+            Ok(public_address) => public_address,
+            Err(err) => {
+                log::error!("Control Cannot be dialed back by {} => {}", address, err);
+                last_error = Some(err);
+                continue;
+            }
+        };
+
+        let ports = (port_min..=port_max)
+            .map(|port| {
+                let tcp = if arrived.contains(&(Transport::Tcp, port)) {
+                    TcpStatus::Open
+                } else {
+                    TcpStatus::Timeout
+                };
+                let udp = if arrived.contains(&(Transport::Udp, port)) {
+                    UdpStatus::Acked
+                } else {
+                    UdpStatus::Silent
+                };
+
+                PortResult {
+                    port,
+                    tcp: Probe::new(tcp, None),
+                    udp: Probe::new(udp, None),
+                }
+            })
+            .collect();
+        targets.push(Target::new(address, Some(public_address), ports));
+    }
+
+    for listener in listeners {
+        listener.abort();
+    }
+    // Only fail when no address of the server could dial back (E.g. IPv6 can be broken on our side)
+    match last_error {
+        Some(err) if targets.is_empty() => Err(err),
+        _ => Ok(targets),
+    }
+}
+
+/// Ask the server to dial back to the port range, return our address as seen by the server
+async fn request_dial_back(
+    args: &ClientArgs,
+    address: IpAddr,
+    session: &Session,
+    timeout: Duration,
+) -> EyreResult<SocketAddr> {
+    let control_port = args.control_port as u16;
+    let control_address = SocketAddr::new(address, control_port);
+
+    let mut control = time::timeout(timeout, TcpStream::connect(control_address))
+        .await
+        .wrap_err("Timeout while connecting to the control port of the server")?
+        .wrap_err("Cannot connect to the control port of the server")?;
+    control.write_all(&session.hello(control_port)).await?;
+
+    let mut buf = [0; FRAME_LENGTH];
+    time::timeout(timeout, control.read_exact(&mut buf))
+        .await
+        .wrap_err("Timeout while waiting for the server to answer on the control port")??;
+    if !session.is_ack(control_port, &buf) {
+        bail!("The control port is not answered by our server");
+    }
+
+    let mut control = BufReader::new(control);
+    let (port_min, port_max) = args.port_range;
+    protocol::write_message(
+        &mut control,
+        &ControlMessage::Request { port_min, port_max },
+    )
+    .await?;
+
+    // A server that stops answering must not hang the client
+    let deadline = protocol::dial_back_duration(port_min, port_max) + timeout;
+    time::timeout(deadline, wait_dial_back(&mut control))
+        .await
+        .wrap_err("Timeout while waiting for the server to dial back")?
+}
+
+/// Read the control messages until the server is done dialing back, return our public address
+async fn wait_dial_back(control: &mut BufReader<TcpStream>) -> EyreResult<SocketAddr> {
+    let mut public_address = None;
+    loop {
+        match protocol::read_message(control).await? {
+            Some(ControlMessage::Observed { address }) => {
+                log::info!("Control The server sees us as {}", address);
+                public_address = Some(address);
+            }
+            Some(ControlMessage::Done) => break,
+            Some(message) => bail!("Unexpected control message {:?}", message),
+            None => bail!("The server closed the control connection"),
+        }
+    }
+
+    public_address.wrap_err("The server did not tell our public address")
+}
+
+fn listen_tcp(
+    listen_address: SocketAddr,
+    session: Arc<Session>,
+    arrived: UnboundedSender<(Transport, u32)>,
+    timeout: Duration,
+) -> Option<JoinHandle<()>> {
+    let port = u32::from(listen_address.port());
+    let listener = match net::bind_tcp(listen_address) {
+        Ok(listener) => listener,
+        Err(err) => {
+            log::error!("TCP Cannot listen on {} => {}", listen_address, err);
+            return None;
+        }
+    };
+
+    Some(tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((mut stream, src)) => {
+                    let mut buf = [0; FRAME_LENGTH];
+                    let read = time::timeout(timeout, stream.read_exact(&mut buf)).await;
+                    if matches!(read, Ok(Ok(_))) && session.is_dial_back(port as u16, &buf) {
+                        log::info!("TCP Dialed back from {} on port {}", src, port);
+                        let _ = arrived.send((Transport::Tcp, port));
+                    } else {
+                        log::debug!(
+                            "TCP Connection from {} on port {} is not our server",
+                            src,
+                            port
+                        );
+                    }
+                }
+                Err(e) => {
+                    log::error!("TCP Error on {} => {}", listen_address, e);
+                }
+            }
+        }
+    }))
+}
+
+fn listen_udp(
+    listen_address: SocketAddr,
+    session: Arc<Session>,
+    arrived: UnboundedSender<(Transport, u32)>,
+) -> Option<JoinHandle<()>> {
+    let port = u32::from(listen_address.port());
+    let socket = match net::bind_udp(listen_address) {
+        Ok(socket) => socket,
+        Err(err) => {
+            log::error!("UDP Cannot listen on {} => {}", listen_address, err);
+            return None;
+        }
+    };
+
+    Some(tokio::spawn(async move {
+        let mut buf = [0; MSG_BUFFER_LENGTH];
+        loop {
+            match socket.recv_from(&mut buf).await {
+                Ok((len, src)) => {
+                    if session.is_dial_back(port as u16, &buf[..len]) {
+                        log::info!("UDP Dialed back from {} on port {}", src, port);
+                        let _ = arrived.send((Transport::Udp, port));
+                    } else {
+                        log::debug!(
+                            "UDP Message from {} on port {} is not our server",
+                            src,
+                            port
+                        );
+                    }
+                }
+                Err(e) => {
+                    log::error!("UDP Error on {} => {}", listen_address, e);
+                }
+            }
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::{Ipv4Addr, UdpSocket as StdUdpSocket},
+        time::Instant,
+    };
+
+    use clap::Parser;
+
+    use super::*;
+    use crate::{server, Commands};
+
+    const SECRET: &str = "my-secret";
+
+    fn client_args(port: u16, control_port: u16) -> ClientArgs {
+        let port_range = format!("{}-{}", port, port);
+        let control_port = control_port.to_string();
+        let args = [
+            "portmole",
+            "client",
+            "-s",
+            "localhost",
+            "-p",
+            &port_range,
+            "--secret",
+            SECRET,
+            "--reverse",
+            "--control-port",
+            &control_port,
+            "--timeout",
+            "200",
+        ];
+        match Commands::parse_from(args) {
+            Commands::Client(args) => args,
+            Commands::Server(_) => unreachable!(),
+        }
+    }
+
+    /// A port free for TCP and UDP on both families, to listen on as a client
+    fn free_port() -> u16 {
+        loop {
+            let socket = StdUdpSocket::bind((Ipv4Addr::LOCALHOST, 0)).unwrap();
+            let port = socket.local_addr().unwrap().port();
+            drop(socket);
+            let free = net::ANY_ADDRESSES.iter().all(|address| {
+                let address = SocketAddr::new(*address, port);
+                net::bind_tcp(address).is_ok() && net::bind_udp(address).is_ok()
+            });
+            if free {
+                return port;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn the_server_dials_back_over_tcp_and_udp() {
+        let control = net::bind_tcp((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        let control_port = control.local_addr().unwrap().port();
+        tokio::spawn(async move {
+            let (stream, src) = control.accept().await.unwrap();
+            server::handle_control(stream, src, u32::from(control_port), SECRET.as_bytes())
+                .await
+                .unwrap();
+        });
+
+        let port = free_port();
+        let args = client_args(port, control_port);
+        let session = Arc::new(Session::new(SECRET));
+        let targets = probe_inbound(&args, &[Ipv4Addr::LOCALHOST.into()], session)
+            .await
+            .unwrap();
+
+        assert_eq!(targets.len(), 1);
+        let target = &targets[0];
+        assert_eq!(
+            target.public_address.map(|address| address.ip()),
+            Some(Ipv4Addr::LOCALHOST.into())
+        );
+        assert_eq!(target.ports.len(), 1);
+        assert_eq!(target.ports[0].port, u32::from(port));
+        assert_eq!(target.ports[0].tcp.status, TcpStatus::Open);
+        assert_eq!(target.ports[0].udp.status, UdpStatus::Acked);
+    }
+
+    #[tokio::test]
+    async fn a_silent_server_does_not_hang_the_client() {
+        let control = net::bind_tcp((Ipv4Addr::LOCALHOST, 0).into()).unwrap();
+        let control_port = control.local_addr().unwrap().port();
+        // Acknowledge the hello, then never send the control messages
+        tokio::spawn(async move {
+            let (mut stream, _src) = control.accept().await.unwrap();
+            let mut buf = [0; FRAME_LENGTH];
+            stream.read_exact(&mut buf).await.unwrap();
+            let ack = protocol::answer(&buf, SECRET.as_bytes(), control_port).unwrap();
+            stream.write_all(&ack).await.unwrap();
+            time::sleep(Duration::from_secs(60)).await;
+        });
+
+        let port = free_port();
+        let args = client_args(port, control_port);
+        let session = Session::new(SECRET);
+        let start = Instant::now();
+        let err = request_dial_back(
+            &args,
+            Ipv4Addr::LOCALHOST.into(),
+            &session,
+            Duration::from_millis(args.timeout),
+        )
+        .await
+        .unwrap_err();
+
+        assert!(err
+            .to_string()
+            .contains("Timeout while waiting for the server to dial back"));
+        assert!(start.elapsed() < protocol::dial_back_duration(1, 1) + Duration::from_secs(1));
+    }
+}
@@ -1,88 +1,256 @@
-use futures::future;
-use tokio::net::{TcpListener, UdpSocket};
+use std::{
+    net::{IpAddr, SocketAddr},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::{bail, ContextCompat};
+use futures::{future, stream, StreamExt};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufReader},
+    net::{TcpListener, TcpStream, UdpSocket},
+    time,
+};
 
 use crate::{
-    setup_logging, EyreResult, ServerArgs, ACK_MSG, EMPTY_MSG, HELLO_MSG, MSG_BUFFER_LENGTH,
+    net,
+    protocol::{
+        self, ControlMessage, Frame, FrameKind, Nonce, DIAL_BACK_CONCURRENCY, DIAL_BACK_TIMEOUT,
+        FRAME_LENGTH,
+    },
+    setup_logging, EyreResult, ServerArgs, MSG_BUFFER_LENGTH,
 };
 
+/// How long a client has to send its `Hello` once connected
+const HELLO_TIMEOUT: Duration = Duration::from_secs(5);
+
 pub async fn handle_server(args: ServerArgs) -> EyreResult<()> {
     setup_logging(args.verbose)?;
 
-    future::join_all(
-        (args.port_range.0..=args.port_range.1).map(|port| spawn_tcp_udp_listener("0.0.0.0", port)),
-    )
-    .await
-    .into_iter()
-    .try_for_each(|res| {
-        let res = res?;
-        let res_tcp = res.0;
-        let res_udp = res.1;
-        EyreResult::from_iter([res_tcp, res_udp])
-    })?;
+    let secret: Arc<[u8]> = Arc::from(args.secret.as_bytes());
+
+    // Everything is bound before accepting, the accept loops never end so a later error would never be seen
+    let mut listeners = Vec::new();
+    // Both families on every port, so that the clients can compare IPv4 and IPv6
+    for server_address in (args.port_range.0..=args.port_range.1)
+        .flat_map(|port| net::ANY_ADDRESSES.map(|address| SocketAddr::new(address, port as u16)))
+    {
+        match net::bind_tcp(server_address) {
+            Ok(listener) => listeners.push(tokio::spawn(spawn_tcp_listener(
+                listener,
+                server_address,
+                secret.clone(),
+            ))),
+            Err(err) => log::warn!("TCP Cannot listen on {} => {}", server_address, err),
+        }
+        match net::bind_udp(server_address) {
+            Ok(listener) => listeners.push(tokio::spawn(spawn_udp_listener(
+                listener,
+                server_address,
+                secret.clone(),
+            ))),
+            Err(err) => log::warn!("UDP Cannot listen on {} => {}", server_address, err),
+        }
+    }
+    if listeners.is_empty() {
+        bail!(
+            "Cannot listen on any port of the range {}-{}",
+            args.port_range.0,
+            args.port_range.1
+        );
+    }
+
+    if let Some(port) = args.control_port {
+        let mut control = Vec::new();
+        for address in net::ANY_ADDRESSES {
+            let server_address = SocketAddr::new(address, port as u16);
+            match net::bind_tcp(server_address) {
+                Ok(listener) => control.push(tokio::spawn(spawn_control_listener(
+                    listener,
+                    server_address,
+                    secret.clone(),
+                ))),
+                // E.g. IPv6 is disabled on this host, the other family can still be used
+                Err(err) => log::error!("Control Cannot listen on {} => {}", server_address, err),
+            }
+        }
+        if control.is_empty() {
+            bail!("Cannot listen on the control port {}", port);
+        }
+        listeners.extend(control);
+    }
+
+    future::join_all(listeners).await;
 
     Ok(())
 }
 
-async fn spawn_tcp_listener(address: &str, port: u32) -> EyreResult<()> {
-    let server_address = format!("{}:{}", address, port);
+/// Listen for the clients asking to be dialed back in reverse mode
+async fn spawn_control_listener(
+    listener: TcpListener,
+    server_address: SocketAddr,
+    secret: Arc<[u8]>,
+) {
+    let port = u32::from(server_address.port());
+    log::info!("Control Listener spawn on {}", server_address);
 
-    match TcpListener::bind(&server_address).await {
-        Ok(listener) => loop {
-            log::info!("TCP Listener spawn on {}", server_address);
-
-            match listener.accept().await {
-                Ok((_stream, src)) => {
-                    log::debug!("TCP Connection from {} to {}", src, server_address);
-                }
-                Err(e) => {
-                    log::error!("TCP Error on {} => {}", server_address, e);
-                }
-            };
-        },
-        Err(_err) => log::debug!("TCP address already used {}", server_address),
+    loop {
+        match listener.accept().await {
+            Ok((stream, src)) => {
+                log::debug!("Control Connection from {}", src);
+                let secret = secret.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = handle_control(stream, src, port, &secret).await {
+                        log::debug!("Control Error with {} => {}", src, err);
+                    }
+                });
+            }
+            Err(e) => {
+                log::error!("Control Error on {} => {}", server_address, e);
+            }
+        };
     }
+}
+
+/// Dial back to each port requested by a client
+/// NOTE: Only the address the control connection comes from is dialed, so the server cannot be used to reach a third party
+pub(crate) async fn handle_control(
+    mut stream: TcpStream,
+    src: SocketAddr,
+    control_port: u32,
+    secret: &[u8],
+) -> EyreResult<()> {
+    let mut buf = [0; FRAME_LENGTH];
+    time::timeout(HELLO_TIMEOUT, stream.read_exact(&mut buf)).await??;
+    let hello = protocol::decode_hello(&buf, secret).wrap_err("Invalid hello")?;
+    let ack = Frame {
+        kind: FrameKind::Ack,
+        port: control_port as u16,
+        nonce: hello.nonce,
+    };
+    stream.write_all(&ack.encode(secret)).await?;
+
+    let mut stream = BufReader::new(stream);
+    let (port_min, port_max) =
+        match time::timeout(HELLO_TIMEOUT, protocol::read_message(&mut stream)).await?? {
+            Some(ControlMessage::Request { port_min, port_max })
+                if (1..=port_max).contains(&port_min) && port_max <= 65535 =>
+            {
+                (port_min, port_max)
+            }
+            message => bail!("Unexpected control message {:?}", message),
+        };
+    protocol::write_message(&mut stream, &ControlMessage::Observed { address: src }).await?;
+
+    log::info!(
+        "Control Dialing back to {} on ports {}-{}",
+        src.ip(),
+        port_min,
+        port_max
+    );
+    stream::iter(port_min..=port_max)
+        .for_each_concurrent(DIAL_BACK_CONCURRENCY, |port| {
+            dial_back(src.ip(), port, hello.nonce, secret)
+        })
+        .await;
+    protocol::write_message(&mut stream, &ControlMessage::Done).await?;
 
     Ok(())
 }
 
-async fn spawn_udp_listener(address: &str, port: u32) -> EyreResult<()> {
-    let server_address = format!("{}:{}", address, port);
+/// Send a `DialBack` frame to a port of the client over TCP and UDP
+async fn dial_back(address: IpAddr, port: u32, nonce: Nonce, secret: &[u8]) {
+    let client_address = SocketAddr::new(address, port as u16);
+    let frame = Frame {
+        kind: FrameKind::DialBack,
+        port: port as u16,
+        nonce,
+    }
+    .encode(secret);
 
-    match UdpSocket::bind(&server_address).await {
-        Ok(listener) => {
-            log::info!("UDP Listener spawn on {}", server_address);
-            let mut buf = [0; MSG_BUFFER_LENGTH];
+    let tcp = async {
+        match time::timeout(DIAL_BACK_TIMEOUT, TcpStream::connect(client_address)).await {
+            Ok(Ok(mut stream)) => {
+                if let Err(err) = stream.write_all(&frame).await {
+                    log::debug!("TCP Cannot dial back to {} => {}", client_address, err);
+                }
+            }
+            Ok(Err(err)) => log::debug!("TCP Cannot dial back to {} => {}", client_address, err),
+            Err(_elapsed) => log::debug!("TCP Cannot dial back to {}, timeout", client_address),
+        }
+    };
+    let udp = async {
+        let bind_address = SocketAddr::new(net::unspecified_like(address), 0);
+        let sent = match UdpSocket::bind(bind_address).await {
+            Ok(socket) => socket.send_to(&frame, client_address).await,
+            Err(err) => Err(err),
+        };
+        if let Err(err) = sent {
+            log::debug!("UDP Cannot dial back to {} => {}", client_address, err);
+        }
+    };
 
-            loop {
-                match listener.recv_from(&mut buf).await {
-                    Ok((len, src)) => {
-                        log::debug!("UDP Connection from {} to {}", src, server_address);
+    future::join(tcp, udp).await;
+}
 
-                        if &buf[..len] == HELLO_MSG {
-                            listener.send_to(ACK_MSG, &src).await?;
-                        } else {
-                            listener.send_to(EMPTY_MSG, &src).await?;
-                        }
-                    }
-                    Err(e) => {
-                        log::error!("UDP Error on {} => {}", server_address, e);
-                    }
-                };
+async fn spawn_tcp_listener(listener: TcpListener, server_address: SocketAddr, secret: Arc<[u8]>) {
+    let port = u32::from(server_address.port());
+    log::info!("TCP Listener spawn on {}", server_address);
+
+    loop {
+        match listener.accept().await {
+            Ok((stream, src)) => {
+                log::debug!("TCP Connection from {} to {}", src, server_address);
+                tokio::spawn(answer_tcp_hello(stream, port, secret.clone()));
             }
-        }
-        Err(_err) => log::debug!("UDP address already used {}", server_address),
+            Err(e) => {
+                log::error!("TCP Error on {} => {}", server_address, e);
+            }
+        };
     }
+}
 
-    Ok(())
+/// Acknowledge the `Hello` sent by a client on a new connection
+async fn answer_tcp_hello(mut stream: TcpStream, port: u32, secret: Arc<[u8]>) {
+    let mut buf = [0; FRAME_LENGTH];
+
+    match time::timeout(HELLO_TIMEOUT, stream.read_exact(&mut buf)).await {
+        Ok(Ok(_len)) => {
+            if let Some(ack) = protocol::answer(&buf, &secret, port as u16) {
+                if let Err(err) = stream.write_all(&ack).await {
+                    log::debug!("TCP Cannot answer on port {} => {}", port, err);
+                }
+            } else {
+                log::debug!("TCP Invalid hello on port {}", port);
+            }
+        }
+        Ok(Err(err)) => log::debug!("TCP Cannot read hello on port {} => {}", port, err),
+        Err(_elapsed) => log::debug!("TCP No hello on port {}", port),
+    }
 }
 
-async fn spawn_tcp_udp_listener(
-    address: &str,
-    port: u32,
-) -> EyreResult<(EyreResult<()>, EyreResult<()>)> {
-    Ok(future::join(
-        spawn_tcp_listener(address, port),
-        spawn_udp_listener(address, port),
-    )
-    .await)
+async fn spawn_udp_listener(listener: UdpSocket, server_address: SocketAddr, secret: Arc<[u8]>) {
+    let port = u32::from(server_address.port());
+    log::info!("UDP Listener spawn on {}", server_address);
+    let mut buf = [0; MSG_BUFFER_LENGTH];
+
+    loop {
+        match listener.recv_from(&mut buf).await {
+            Ok((len, src)) => {
+                log::debug!("UDP Connection from {} to {}", src, server_address);
+
+                // NOTE: Invalid frames are dropped, answering them would make the server a reflector
+                if let Some(ack) = protocol::answer(&buf[..len], &secret, port as u16) {
+                    if let Err(err) = listener.send_to(&ack, &src).await {
+                        log::debug!("UDP Cannot answer to {} => {}", src, err);
+                    }
+                } else {
+                    log::debug!("UDP Invalid hello from {} on port {}", src, port);
+                }
+            }
+            Err(e) => {
+                log::error!("UDP Error on {} => {}", server_address, e);
+            }
+        };
+    }
 }
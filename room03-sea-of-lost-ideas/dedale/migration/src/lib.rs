@@ -6,6 +6,12 @@ mod m20220101_000001_users;
 
 mod m20240416_162051_apps;
 mod m20240416_162511_machines;
+mod m20240520_093012_machines_routing;
+mod m20240520_093548_domains;
+mod m20240527_141203_apps_scaling;
+mod m20240603_101524_machines_backend;
+mod m20240610_142233_secrets;
+mod m20240617_093015_certificates;
 pub struct Migrator;
 
 #[async_trait::async_trait]
@@ -15,6 +21,12 @@ impl MigratorTrait for Migrator {
             Box::new(m20220101_000001_users::Migration),
             Box::new(m20240416_162051_apps::Migration),
             Box::new(m20240416_162511_machines::Migration),
+            Box::new(m20240520_093012_machines_routing::Migration),
+            Box::new(m20240520_093548_domains::Migration),
+            Box::new(m20240527_141203_apps_scaling::Migration),
+            Box::new(m20240603_101524_machines_backend::Migration),
+            Box::new(m20240610_142233_secrets::Migration),
+            Box::new(m20240617_093015_certificates::Migration),
         ]
     }
 }
@@ -0,0 +1,47 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Domains::Table)
+                    .col(pk_auto(Domains::Id))
+                    .col(string_uniq(Domains::Hostname))
+                    .col(integer(Domains::AppId))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-domains-apps")
+                            .from(Domains::Table, Domains::AppId)
+                            .to(Apps::Table, Apps::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Domains::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Domains {
+    Table,
+    Id,
+    Hostname,
+    AppId,
+}
+
+#[derive(DeriveIden)]
+enum Apps {
+    Table,
+    Id,
+}
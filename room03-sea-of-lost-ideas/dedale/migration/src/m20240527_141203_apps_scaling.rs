@@ -0,0 +1,60 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// NOTE: one column per statement as sqlite does not support multiple alter options
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            boolean(Apps::AutoStopMachines).default(true).to_owned(),
+            integer(Apps::MinMachinesRunning).default(0).to_owned(),
+            integer(Apps::IdleTimeout).default(300).to_owned(),
+            string_null(Apps::HealthCheckPath),
+        ];
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Apps::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let columns = [
+            Apps::AutoStopMachines,
+            Apps::MinMachinesRunning,
+            Apps::IdleTimeout,
+            Apps::HealthCheckPath,
+        ];
+        for column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Apps::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(DeriveIden)]
+enum Apps {
+    Table,
+    AutoStopMachines,
+    MinMachinesRunning,
+    /// In seconds
+    IdleTimeout,
+    HealthCheckPath,
+}
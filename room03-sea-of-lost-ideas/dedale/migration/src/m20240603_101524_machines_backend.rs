@@ -0,0 +1,73 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+// NOTE: one column per statement as sqlite does not support multiple alter options
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Apps::Table)
+                    .add_column(string(Apps::Backend).default("docker"))
+                    .to_owned(),
+            )
+            .await?;
+
+        let columns = [
+            string_null(Machines::Image),
+            string_null(Machines::Command),
+            json_null(Machines::Env),
+        ];
+        for mut column in columns {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Machines::Table)
+                        .add_column(&mut column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        for column in [Machines::Image, Machines::Command, Machines::Env] {
+            manager
+                .alter_table(
+                    Table::alter()
+                        .table(Machines::Table)
+                        .drop_column(column)
+                        .to_owned(),
+                )
+                .await?;
+        }
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Apps::Table)
+                    .drop_column(Apps::Backend)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Apps {
+    Table,
+    Backend,
+}
+
+#[derive(DeriveIden)]
+enum Machines {
+    Table,
+    Image,
+    Command,
+    Env,
+}
@@ -0,0 +1,64 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Secrets::Table)
+                    .col(pk_auto(Secrets::Id))
+                    .col(integer(Secrets::AppId))
+                    .col(string(Secrets::Name))
+                    // sealed with the key of the server, never the plain value
+                    .col(text(Secrets::Value))
+                    .col(string(Secrets::Digest))
+                    .foreign_key(
+                        ForeignKey::create()
+                            .name("fk-secrets-apps")
+                            .from(Secrets::Table, Secrets::AppId)
+                            .to(Apps::Table, Apps::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx-secrets-app_id-name")
+                    .table(Secrets::Table)
+                    .col(Secrets::AppId)
+                    .col(Secrets::Name)
+                    .unique()
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Secrets::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Secrets {
+    Table,
+    Id,
+    AppId,
+    Name,
+    Value,
+    Digest,
+}
+
+#[derive(DeriveIden)]
+enum Apps {
+    Table,
+    Id,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::{prelude::*, schema::*};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                table_auto(Certificates::Table)
+                    .col(pk_auto(Certificates::Id))
+                    .col(string_uniq(Certificates::Hostname))
+                    // PEM, the certificate of the hostname first
+                    .col(text(Certificates::Chain))
+                    .col(text(Certificates::PrivateKey))
+                    .col(date_time(Certificates::NotAfter))
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                table_auto(AcmeAccounts::Table)
+                    .col(pk_auto(AcmeAccounts::Id))
+                    .col(string_uniq(AcmeAccounts::DirectoryUrl))
+                    .col(text(AcmeAccounts::Credentials))
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(AcmeAccounts::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Certificates::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Certificates {
+    Table,
+    Id,
+    Hostname,
+    Chain,
+    PrivateKey,
+    NotAfter,
+}
+
+#[derive(DeriveIden)]
+enum AcmeAccounts {
+    Table,
+    Id,
+    DirectoryUrl,
+    Credentials,
+}
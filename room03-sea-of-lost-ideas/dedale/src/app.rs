@@ -15,7 +15,10 @@ use migration::Migrator;
 use sea_orm::DatabaseConnection;
 
 use crate::{
-    controllers, initializers, models::_entities::users, tasks, workers::downloader::DownloadWorker,
+    controllers, initializers,
+    models::_entities::{acme_accounts, apps, certificates, domains, machines, secrets, users},
+    tasks,
+    workers::downloader::DownloadWorker,
 };
 
 pub struct App;
@@ -45,8 +48,10 @@ impl Hooks for App {
 
         let proxy_url = format!("{}:{}", server_config.binding, server_config.port);
         let server_url = format!("{}", listener.local_addr()?);
+        let routes = crate::proxy::routing::installed();
+        let tls = crate::proxy::tls::installed();
         std::thread::spawn(move || {
-            crate::proxy::Proxy::run(&proxy_url, Some(&server_url)).unwrap();
+            crate::proxy::Proxy::run(&proxy_url, Some(&server_url), routes, tls).unwrap();
         });
 
         axum::serve(listener, app).await?;
@@ -55,14 +60,20 @@ impl Hooks for App {
     }
 
     async fn initializers(_ctx: &AppContext) -> Result<Vec<Box<dyn Initializer>>> {
-        Ok(vec![Box::new(
-            initializers::view_engine::ViewEngineInitializer,
-        )])
+        Ok(vec![
+            Box::new(initializers::view_engine::ViewEngineInitializer),
+            Box::new(initializers::secrets::SecretsInitializer),
+            Box::new(initializers::proxy::ProxyInitializer),
+        ])
     }
 
     fn routes(_ctx: &AppContext) -> AppRoutes {
         AppRoutes::with_default_routes()
             .prefix("/api")
+            .add_route(controllers::apps::routes())
+            .add_route(controllers::machines::routes())
+            .add_route(controllers::secrets::routes())
+            .add_route(controllers::logs::routes())
             .add_route(controllers::auth::routes())
             .add_route(controllers::user::routes())
     }
@@ -76,6 +87,12 @@ impl Hooks for App {
     }
 
     async fn truncate(db: &DatabaseConnection) -> Result<()> {
+        truncate_table(db, certificates::Entity).await?;
+        truncate_table(db, acme_accounts::Entity).await?;
+        truncate_table(db, secrets::Entity).await?;
+        truncate_table(db, machines::Entity).await?;
+        truncate_table(db, domains::Entity).await?;
+        truncate_table(db, apps::Entity).await?;
         truncate_table(db, users::Entity).await?;
         Ok(())
     }
@@ -1,18 +1,80 @@
 use clap::Parser;
+use dedale::{controllers::apps::CreateParams, views::apps::AppResponse};
+
+use crate::{client::Client, config::Config, output};
 
 #[derive(Debug, Clone, Parser)]
-#[clap(
-    visible_alias = "app", 
-    long_about,
-    verbatim_doc_comment,
-)]
+#[clap(visible_alias = "app", long_about, verbatim_doc_comment)]
 /// The APPS commands focus on managing your Dedale applications. Start with the CREATE command to register your application.
 /// The LIST command will list all currently registered applications.
 pub enum Command {
     /// Create a new application
-    Create,
+    Create {
+        name: String,
+        /// Runs the machines: docker, podman, systemd-nspawn or process
+        #[clap(long)]
+        backend: Option<String>,
+    },
     /// Permanently destroys an app
-    Destroy,
+    Destroy {
+        name: String,
+        /// Required, the machines of the app are destroyed too
+        #[clap(short, long)]
+        yes: bool,
+    },
     /// List applications
     List,
-}
\ No newline at end of file
+}
+
+fn print(apps: &[AppResponse]) {
+    let rows = apps
+        .iter()
+        .map(|app| {
+            [
+                app.name.clone(),
+                app.backend.clone(),
+                app.machines.to_string(),
+                app.created_at.format("%Y-%m-%d %H:%M").to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    output::table(["name", "backend", "machines", "created"], &rows);
+}
+
+pub fn run(config: &Config, command: &Command, json: bool) -> eyre::Result<()> {
+    let client = Client::new(config);
+
+    match command {
+        Command::Create { name, backend } => {
+            let app = client.post::<_, AppResponse>(
+                "/apps",
+                &CreateParams {
+                    app_name: name.clone(),
+                    backend: backend.clone(),
+                },
+            )?;
+            if json {
+                output::json(&app)?;
+            } else {
+                println!("created app {}", app.name);
+            }
+        }
+        Command::Destroy { name, yes } => {
+            if !yes {
+                eyre::bail!("destroying {name} also destroys its machines, confirm with --yes");
+            }
+            client.delete(&format!("/apps/{name}"))?;
+            println!("destroyed app {name}");
+        }
+        Command::List => {
+            let apps = client.get::<Vec<AppResponse>>("/apps")?;
+            if json {
+                output::json(&apps)?;
+            } else {
+                print(&apps);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -1,4 +1,10 @@
+use std::io::{self, BufRead, Write};
+
 use clap::Parser;
+use dedale::views::{auth::LoginResponse, user::CurrentResponse};
+use serde_json::json;
+
+use crate::{client::Client, config::Config, output};
 
 #[derive(Debug, Clone, Parser)]
 #[clap(verbatim_doc_comment)]
@@ -7,13 +13,104 @@ use clap::Parser;
 /// If you do have an account, begin with the AUTH LOGIN subcommand.
 pub enum Command {
     /// Log in a user
-    Login,
+    Login(Credentials),
     /// Logs out the currently logged in user
     Logout,
     /// Create a new dedale account
-    Signup,
+    Signup {
+        /// Prompted when unset
+        #[clap(long)]
+        name: Option<String>,
+        #[clap(flatten)]
+        credentials: Credentials,
+    },
     /// Show the current auth token
     Token,
     /// Displays the users email address/service identity currently authenticated and in use.
-    Whoami
-}
\ No newline at end of file
+    Whoami,
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct Credentials {
+    /// Prompted when unset
+    #[clap(long)]
+    email: Option<String>,
+    /// Prompted when unset
+    #[clap(long, env = "DEDALE_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+    /// URL of the dedale server, saved in the config
+    #[clap(long)]
+    url: Option<String>,
+}
+
+fn prompt(label: &str, value: Option<String>) -> eyre::Result<String> {
+    if let Some(value) = value {
+        return Ok(value);
+    }
+
+    print!("{label}: ");
+    io::stdout().flush()?;
+    let mut value = String::new();
+    io::stdin().lock().read_line(&mut value)?;
+
+    Ok(value.trim().to_string())
+}
+
+pub fn run(config: &mut Config, command: &Command, json: bool) -> eyre::Result<()> {
+    match command {
+        Command::Login(credentials) => {
+            if let Some(url) = &credentials.url {
+                config.api_url.clone_from(url);
+            }
+            let email = prompt("Email", credentials.email.clone())?;
+            let password = prompt("Password", credentials.password.clone())?;
+
+            let login = Client::new(config).post::<_, LoginResponse>(
+                "/auth/login",
+                &json!({ "email": email, "password": password }),
+            )?;
+            config.access_token = login.token;
+            config.save()?;
+
+            println!("logged in as {}", login.name);
+        }
+        Command::Logout => {
+            config.access_token.clear();
+            config.save()?;
+
+            println!("logged out");
+        }
+        Command::Signup { name, credentials } => {
+            if let Some(url) = &credentials.url {
+                config.api_url.clone_from(url);
+                config.save()?;
+            }
+            let name = prompt("Name", name.clone())?;
+            let email = prompt("Email", credentials.email.clone())?;
+            let password = prompt("Password", credentials.password.clone())?;
+
+            Client::new(config).post::<_, ()>(
+                "/auth/register",
+                &json!({ "name": name, "email": email, "password": password }),
+            )?;
+
+            println!("check your emails to verify your account, then log in");
+        }
+        Command::Token => {
+            if config.access_token.is_empty() {
+                eyre::bail!("not logged in");
+            }
+            println!("{}", config.access_token);
+        }
+        Command::Whoami => {
+            let user = Client::new(config).get::<CurrentResponse>("/user/current")?;
+            if json {
+                output::json(&user)?;
+            } else {
+                println!("{} <{}>", user.name, user.email);
+            }
+        }
+    }
+
+    Ok(())
+}
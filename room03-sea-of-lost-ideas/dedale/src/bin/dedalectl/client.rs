@@ -0,0 +1,74 @@
+use eyre::{bail, WrapErr};
+use reqwest::{
+    blocking::{RequestBuilder, Response},
+    StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::config::Config;
+
+/// Calls the REST API of the dedale server, authenticated with the token of
+/// the config
+pub struct Client<'a> {
+    config: &'a Config,
+    http: reqwest::blocking::Client,
+}
+
+impl<'a> Client<'a> {
+    pub fn new(config: &'a Config) -> Self {
+        Self {
+            config,
+            http: reqwest::blocking::Client::new(),
+        }
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> eyre::Result<T> {
+        Ok(self.send(self.http.get(self.url(path)))?.json()?)
+    }
+
+    pub fn post<B: Serialize, T: DeserializeOwned>(&self, path: &str, body: &B) -> eyre::Result<T> {
+        Ok(self
+            .send(self.http.post(self.url(path)).json(body))?
+            .json()?)
+    }
+
+    /// The response is read as it arrives, without timeout
+    pub fn stream(&self, path: &str) -> eyre::Result<Response> {
+        let http = reqwest::blocking::Client::builder().timeout(None).build()?;
+        self.send(http.get(self.url(path)))
+    }
+
+    pub fn delete(&self, path: &str) -> eyre::Result<()> {
+        self.send(self.http.delete(self.url(path)))?;
+        Ok(())
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api{path}", self.config.api_url.trim_end_matches('/'))
+    }
+
+    fn send(&self, mut request: RequestBuilder) -> eyre::Result<Response> {
+        if !self.config.access_token.is_empty() {
+            request = request.bearer_auth(&self.config.access_token);
+        }
+        let response = request.send().wrap_err_with(|| {
+            format!(
+                "could not reach the dedale server at {}",
+                self.config.api_url
+            )
+        })?;
+
+        let status = response.status();
+        if status == StatusCode::UNAUTHORIZED {
+            bail!("unauthorized, log in with `dedalectl auth login`");
+        }
+        if !status.is_success() {
+            bail!(
+                "request failed ({status}): {}",
+                response.text().unwrap_or_default()
+            );
+        }
+
+        Ok(response)
+    }
+}
@@ -1,22 +1,36 @@
-use std::{env, path::PathBuf};
+use std::{env, fs, path::PathBuf};
 
+use eyre::WrapErr;
 use serde::{Deserialize, Serialize};
 
+const DEFAULT_API_URL: &str = "http://localhost:3000";
+
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 struct OptionalConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
     access_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    api_url: Option<String>,
 }
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 #[non_exhaustive]
 pub struct Config {
-    access_token: String,
+    pub access_token: String,
+    /// Where the dedale server is reachable, e.g. `https://dedale.example.com`
+    pub api_url: String,
 }
 
 impl Config {
+    /// `~/.dedale/config.toml`, like `~/.fly/config.yml` for flyctl
+    fn path() -> PathBuf {
+        PathBuf::from(env::var("HOME").expect("env var HOME is not set"))
+            .join(".dedale")
+            .join("config.toml")
+    }
+
     pub fn parse() -> Self {
-        let config_path =
-            PathBuf::from(env::var("HOME").expect("env var HOME is not set")).join("config.toml");
-        
+        let config_path = Self::path();
+
         let optional_config = if config_path.exists() {
             let file = std::fs::read_to_string(&config_path).unwrap();
             toml::from_str::<OptionalConfig>(&file).unwrap()
@@ -25,8 +39,24 @@ impl Config {
         };
         Self {
             access_token: optional_config.access_token.unwrap_or_default(),
-            ..Default::default()
+            api_url: env::var("DEDALE_API_URL")
+                .ok()
+                .or(optional_config.api_url)
+                .unwrap_or_else(|| DEFAULT_API_URL.to_string()),
         }
     }
-}
 
+    pub fn save(&self) -> eyre::Result<()> {
+        let config_path = Self::path();
+        let optional_config = OptionalConfig {
+            access_token: Some(self.access_token.clone()).filter(|token| !token.is_empty()),
+            api_url: Some(self.api_url.clone()).filter(|url| url != DEFAULT_API_URL),
+        };
+
+        if let Some(dir) = config_path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&config_path, toml::to_string(&optional_config)?)
+            .wrap_err_with(|| format!("could not write {}", config_path.display()))
+    }
+}
@@ -0,0 +1,55 @@
+use std::{fs, path::PathBuf};
+
+use clap::Parser;
+use dedale::{controllers::apps::DeployParams, fly_toml::FlyToml, views::apps::DeployResponse};
+use eyre::{bail, WrapErr};
+
+use crate::{client::Client, config::Config, output};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(verbatim_doc_comment)]
+/// Deploy Dedale applications from a fly.toml.
+/// The app and its machines are created on the first deploy, and updated by the next ones.
+/// Keys of the fly.toml not supported by Dedale are listed.
+pub struct Args {
+    /// Path to the app configuration
+    #[clap(short, long, default_value = "fly.toml")]
+    config: PathBuf,
+    /// Image reference to run, overrides build.image
+    #[clap(short, long)]
+    image: Option<String>,
+}
+
+pub fn run(config: &Config, args: &Args, json: bool) -> eyre::Result<()> {
+    if config.access_token.is_empty() {
+        bail!("not logged in, log in with `dedalectl auth login`");
+    }
+
+    let fly_toml = fs::read_to_string(&args.config)
+        .wrap_err_with(|| format!("could not read {}", args.config.display()))?;
+    // fail before reaching the server when the config cannot be deployed
+    FlyToml::parse(&fly_toml)
+        .wrap_err_with(|| format!("invalid {}", args.config.display()))?
+        .plan(args.image.as_deref())?;
+
+    let deployed = Client::new(config).post::<_, DeployResponse>(
+        "/apps/deploy",
+        &DeployParams {
+            config: fly_toml,
+            image: args.image.clone(),
+        },
+    )?;
+
+    if json {
+        return output::json(&deployed);
+    }
+    for unsupported in &deployed.unsupported {
+        eprintln!("warning: unsupported {unsupported}");
+    }
+    println!("deployed {}", deployed.app);
+    for machine in &deployed.machines {
+        println!("  machine {machine}");
+    }
+
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use std::io::{BufRead, BufReader};
+
+use clap::Parser;
+use dedale::proxy::logs::LogLine;
+use eyre::WrapErr;
+
+use crate::{client::Client, config::Config, machine::AppArg};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(verbatim_doc_comment)]
+/// Print the logs of an app: the output of its machines and the requests proxied to them.
+/// The dedale server only keeps the last lines of each app, in memory.
+pub struct Args {
+    #[clap(flatten)]
+    app: AppArg,
+    /// Keep printing the lines as they are written
+    #[clap(short, long)]
+    follow: bool,
+    /// How many of the last lines to print first
+    #[clap(short = 'n', long, default_value_t = 100)]
+    lines: usize,
+}
+
+fn print(line: &LogLine) {
+    println!(
+        "{} {} [{}] {}",
+        line.timestamp.format("%Y-%m-%dT%H:%M:%S%.3fZ"),
+        line.machine.as_deref().unwrap_or("-"),
+        line.source,
+        line.message
+    );
+}
+
+pub fn run(config: &Config, args: &Args, json: bool) -> eyre::Result<()> {
+    let response = Client::new(config).stream(&format!(
+        "/apps/{}/logs?follow={}&lines={}",
+        args.app.name()?,
+        args.follow,
+        args.lines
+    ))?;
+
+    for line in BufReader::new(response).lines() {
+        let line = line.wrap_err("lost the connection to the dedale server")?;
+        // already one JSON document per line
+        if json {
+            println!("{line}");
+        } else {
+            print(&serde_json::from_str(&line).wrap_err("invalid log line")?);
+        }
+    }
+
+    Ok(())
+}
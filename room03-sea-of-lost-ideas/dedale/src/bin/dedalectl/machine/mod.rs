@@ -1,21 +1,178 @@
+use std::{collections::BTreeMap, fs};
+
 use clap::Parser;
+use dedale::{
+    controllers::machines::{CreateParams, InitConfig, MachineConfig, ServiceConfig},
+    fly_toml::FlyToml,
+    views::machines::MachineResponse,
+};
+use eyre::{eyre, WrapErr};
+use serde_json::json;
+
+use crate::{client::Client, config::Config, output};
 
 #[derive(Debug, Clone, Parser)]
 #[clap(
     visible_aliases = ["machines", "m"], 
     verbatim_doc_comment,
 )]
-/// Manage Dedale Machines. 
+/// Manage Dedale Machines.
 /// Dedale Machines are super-slow, or at least not lighting fast VMs that can be created, and then "quickly" started and stopped as needed with dedalectl commands or with the Machines REST dedale.
 pub enum Command {
     /// Create, but don't start, a machine
-    Create,
+    Create {
+        #[clap(flatten)]
+        app: AppArg,
+        /// Generated from the app name when unset
+        #[clap(long)]
+        name: Option<String>,
+        #[clap(short, long)]
+        image: String,
+        /// Port the machine listens on, given to it as PORT
+        #[clap(short, long, default_value_t = 8080)]
+        port: u16,
+        /// Environment of the machine, as KEY=VALUE
+        #[clap(short, long = "env", value_parser = parse_env)]
+        env: Vec<(String, String)>,
+        /// Overrides the command of the image
+        #[clap(last = true)]
+        command: Vec<String>,
+    },
     /// List Dedale machines
-    List,
+    List {
+        #[clap(flatten)]
+        app: AppArg,
+    },
     /// Destroy Dedale machines
-    Destroy,
+    Destroy {
+        #[clap(flatten)]
+        app: AppArg,
+        #[clap(required = true)]
+        machines: Vec<String>,
+    },
     /// Start one or more Dedale machines
-    Start,
+    Start {
+        #[clap(flatten)]
+        app: AppArg,
+        #[clap(required = true)]
+        machines: Vec<String>,
+    },
     /// Stop one or more Dedale machines
-    Stop,
-}
\ No newline at end of file
+    Stop {
+        #[clap(flatten)]
+        app: AppArg,
+        #[clap(required = true)]
+        machines: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, Parser)]
+pub struct AppArg {
+    /// Read from ./fly.toml when unset
+    #[clap(short, long)]
+    app: Option<String>,
+}
+
+impl AppArg {
+    pub fn name(&self) -> eyre::Result<String> {
+        if let Some(app) = &self.app {
+            return Ok(app.clone());
+        }
+
+        let config =
+            fs::read_to_string("fly.toml").wrap_err("no --app and could not read ./fly.toml")?;
+        Ok(FlyToml::parse(&config)?.app)
+    }
+}
+
+pub fn parse_env(env: &str) -> Result<(String, String), String> {
+    env.split_once('=')
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .ok_or_else(|| format!("{env} is not KEY=VALUE"))
+}
+
+fn print(machines: &[MachineResponse]) {
+    let rows = machines
+        .iter()
+        .map(|machine| {
+            [
+                machine.id.clone(),
+                format!("{:?}", machine.state).to_lowercase(),
+                machine.image.clone().unwrap_or_default(),
+                format!("{}:{}", machine.address, machine.internal_port),
+                machine.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    output::table(["id", "state", "image", "address", "updated"], &rows);
+}
+
+pub fn run(config: &Config, command: &Command, json: bool) -> eyre::Result<()> {
+    let client = Client::new(config);
+
+    let machines = match command {
+        Command::Create {
+            app,
+            name,
+            image,
+            port,
+            env,
+            command,
+        } => {
+            let machine = client.post::<_, MachineResponse>(
+                &format!("/apps/{}/machines", app.name()?),
+                &CreateParams {
+                    name: name.clone(),
+                    config: MachineConfig {
+                        image: Some(image.clone()),
+                        env: env.iter().cloned().collect::<BTreeMap<_, _>>(),
+                        init: InitConfig {
+                            cmd: command.clone(),
+                        },
+                        services: vec![ServiceConfig {
+                            internal_port: *port,
+                        }],
+                    },
+                },
+            )?;
+            vec![machine]
+        }
+        Command::List { app } => {
+            client.get::<Vec<MachineResponse>>(&format!("/apps/{}/machines", app.name()?))?
+        }
+        Command::Destroy { app, machines } => {
+            let app = app.name()?;
+            for machine in machines {
+                client.delete(&format!("/apps/{app}/machines/{machine}"))?;
+                println!("destroyed machine {machine}");
+            }
+            return Ok(());
+        }
+        Command::Start { app, machines } | Command::Stop { app, machines } => {
+            let app = app.name()?;
+            let action = if matches!(command, Command::Start { .. }) {
+                "start"
+            } else {
+                "stop"
+            };
+            machines
+                .iter()
+                .map(|machine| {
+                    client
+                        .post::<_, MachineResponse>(
+                            &format!("/apps/{app}/machines/{machine}/{action}"),
+                            &json!({}),
+                        )
+                        .map_err(|e| eyre!("could not {action} {machine}: {e}"))
+                })
+                .collect::<eyre::Result<Vec<_>>>()?
+        }
+    };
+
+    if json {
+        output::json(&machines)
+    } else {
+        print(&machines);
+        Ok(())
+    }
+}
@@ -2,13 +2,21 @@ use clap::Parser;
 
 mod apps;
 mod auth;
+mod client;
 mod config;
+mod deploy;
+mod logs;
 mod machine;
+mod output;
+mod secrets;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about, long_about = None)]
 /// This is dedalectl, the Fly.. **cough** poor man command line interface to reach the sun.
 struct App {
+    /// Print JSON instead of tables
+    #[clap(long, global = true)]
+    json: bool,
     #[clap(subcommand)]
     command: Command,
 }
@@ -21,9 +29,24 @@ enum Command {
     Apps(apps::Command),
     #[clap(subcommand, about = "Manage machines")]
     Machine(machine::Command),
+    #[clap(subcommand, about = "Manage the secrets of an app")]
+    Secrets(secrets::Command),
+    #[clap(about = "Deploy an app from its fly.toml")]
+    Deploy(deploy::Args),
+    #[clap(about = "Print the logs of an app")]
+    Logs(logs::Args),
 }
 
-fn main() {
-    let _config = config::Config::parse();
-    let _app = App::parse();
+fn main() -> eyre::Result<()> {
+    let mut config = config::Config::parse();
+    let app = App::parse();
+
+    match &app.command {
+        Command::Auth(command) => auth::run(&mut config, command, app.json),
+        Command::Apps(command) => apps::run(&config, command, app.json),
+        Command::Machine(command) => machine::run(&config, command, app.json),
+        Command::Secrets(command) => secrets::run(&config, command, app.json),
+        Command::Deploy(args) => deploy::run(&config, args, app.json),
+        Command::Logs(args) => logs::run(&config, args, app.json),
+    }
 }
@@ -0,0 +1,36 @@
+use serde::Serialize;
+
+/// Pretty printed JSON, for scripts
+pub fn json<T: Serialize>(value: &T) -> eyre::Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Columns aligned on their widest cell
+pub fn table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) {
+    let mut widths = headers.map(str::len);
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let line = |cells: [&str; N]| {
+        let line = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect::<Vec<_>>()
+            .join("  ");
+        println!("{}", line.trim_end());
+    };
+    line(
+        headers
+            .map(str::to_uppercase)
+            .each_ref()
+            .map(String::as_str),
+    );
+    for row in rows {
+        line(row.each_ref().map(String::as_str));
+    }
+}
@@ -0,0 +1,118 @@
+use std::{
+    collections::BTreeMap,
+    io::{self, Read},
+};
+
+use clap::Parser;
+use dedale::{controllers::secrets::SetParams, views::secrets::SecretResponse};
+use eyre::{bail, WrapErr};
+
+use crate::{
+    client::Client,
+    config::Config,
+    machine::{parse_env, AppArg},
+    output,
+};
+
+#[derive(Debug, Clone, Parser)]
+#[clap(visible_alias = "secret", long_about, verbatim_doc_comment)]
+/// Secrets are provided to the machines of an app as environment variables.
+/// They are encrypted by the dedale server and their values are never shown, machines see them when they are next started.
+pub enum Command {
+    /// List the names and digests of the secrets of an app
+    List {
+        #[clap(flatten)]
+        app: AppArg,
+    },
+    /// Set one or more secrets, as NAME=VALUE
+    Set {
+        #[clap(flatten)]
+        app: AppArg,
+        #[clap(required = true, value_parser = parse_env)]
+        secrets: Vec<(String, String)>,
+    },
+    /// Remove one or more secrets
+    Unset {
+        #[clap(flatten)]
+        app: AppArg,
+        #[clap(required = true)]
+        names: Vec<String>,
+    },
+    /// Set secrets read from stdin, as NAME=VALUE lines
+    Import {
+        #[clap(flatten)]
+        app: AppArg,
+    },
+}
+
+/// `NAME=VALUE` lines, blank lines and `#` comments are skipped
+fn parse_lines(input: &str) -> eyre::Result<BTreeMap<String, String>> {
+    input
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| parse_env(line).map_err(|e| eyre::eyre!(e)))
+        .collect()
+}
+
+fn print(secrets: &[SecretResponse]) {
+    let rows = secrets
+        .iter()
+        .map(|secret| {
+            [
+                secret.name.clone(),
+                secret.digest.clone(),
+                secret.updated_at.format("%Y-%m-%d %H:%M").to_string(),
+            ]
+        })
+        .collect::<Vec<_>>();
+    output::table(["name", "digest", "updated"], &rows);
+}
+
+fn set(
+    client: &Client,
+    app: &AppArg,
+    secrets: BTreeMap<String, String>,
+) -> eyre::Result<Vec<SecretResponse>> {
+    if secrets.is_empty() {
+        bail!("no secrets to set");
+    }
+
+    client.post::<_, Vec<SecretResponse>>(
+        &format!("/apps/{}/secrets", app.name()?),
+        &SetParams { secrets },
+    )
+}
+
+pub fn run(config: &Config, command: &Command, json: bool) -> eyre::Result<()> {
+    let client = Client::new(config);
+
+    let secrets = match command {
+        Command::List { app } => {
+            client.get::<Vec<SecretResponse>>(&format!("/apps/{}/secrets", app.name()?))?
+        }
+        Command::Set { app, secrets } => set(&client, app, secrets.iter().cloned().collect())?,
+        Command::Import { app } => {
+            let mut input = String::new();
+            io::stdin()
+                .read_to_string(&mut input)
+                .wrap_err("could not read the secrets from stdin")?;
+            set(&client, app, parse_lines(&input)?)?
+        }
+        Command::Unset { app, names } => {
+            let app = app.name()?;
+            for name in names {
+                client.delete(&format!("/apps/{app}/secrets/{name}"))?;
+                println!("unset secret {name}");
+            }
+            return Ok(());
+        }
+    };
+
+    if json {
+        output::json(&secrets)
+    } else {
+        print(&secrets);
+        Ok(())
+    }
+}
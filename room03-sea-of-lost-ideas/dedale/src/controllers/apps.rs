@@ -0,0 +1,219 @@
+use loco_rs::prelude::*;
+use sea_orm::ModelTrait;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fly_toml::{self, FlyToml},
+    models::{_entities::users, apps, machines},
+    proxy::{
+        activity,
+        backend::{AnyServiceBackend, BackendKind, BackendState},
+        logs,
+    },
+    views::apps::{AppResponse, DeployResponse},
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateParams {
+    pub app_name: String,
+    /// `docker` when unset
+    pub backend: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeployParams {
+    /// Content of the `fly.toml`
+    pub config: String,
+    /// Overrides `build.image`
+    pub image: Option<String>,
+}
+
+/// The backend running the machines of an app
+pub(super) async fn backend(app: &apps::Model) -> Result<AnyServiceBackend> {
+    let kind = app
+        .backend
+        .parse::<BackendKind>()
+        .map_err(Error::BadRequest)?;
+    AnyServiceBackend::new_backend(kind)
+        .await
+        .map_err(|e| Error::string(&e.to_string()))
+}
+
+/// Stops a machine if started, and tells the proxy it is stopping then
+/// stopped
+pub(super) async fn stop_machine(backend: &mut AnyServiceBackend, name: &str) -> Result<()> {
+    let activity = activity::shared();
+    activity.write().await.machine_stopping(name);
+    let stopped = if backend.status(name).await.ok() == Some(BackendState::Started) {
+        backend.stop(name).await.map(|_| ())
+    } else {
+        Ok(())
+    };
+    activity.write().await.machine_stopped(name);
+
+    stopped.map_err(|e| Error::string(&e.to_string()))
+}
+
+/// Stops a machine then removes it from its backend, e.g. its container
+pub(super) async fn destroy_machine(backend: &mut AnyServiceBackend, name: &str) -> Result<()> {
+    stop_machine(backend, name).await?;
+    backend
+        .destroy(name)
+        .await
+        .map_err(|e| Error::string(&e.to_string()))?;
+
+    Ok(())
+}
+
+/// Lists the apps of the current user
+async fn list(auth: auth::JWT, State(ctx): State<AppContext>) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+
+    let mut response = Vec::new();
+    for app in apps::Model::list_owned(&ctx.db, user.id).await? {
+        let machines = machines::Model::list_by_app(&ctx.db, app.id).await?;
+        response.push(AppResponse::new(&app, machines.len()));
+    }
+
+    format::json(response)
+}
+
+/// Creates an app without machines
+async fn create(
+    auth: auth::JWT,
+    State(ctx): State<AppContext>,
+    Json(params): Json<CreateParams>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+
+    if !fly_toml::is_valid_app_name(&params.app_name) {
+        return Err(Error::BadRequest(format!(
+            "invalid app name {:?}",
+            params.app_name
+        )));
+    }
+    if let Some(backend) = &params.backend {
+        backend.parse::<BackendKind>().map_err(Error::BadRequest)?;
+    }
+
+    let app = match apps::Model::create(
+        &ctx.db,
+        user.id,
+        &params.app_name,
+        params.backend.as_deref(),
+    )
+    .await
+    {
+        Ok(app) => app,
+        Err(ModelError::EntityAlreadyExists {}) => {
+            return Err(Error::BadRequest(format!(
+                "app {} already exists",
+                params.app_name
+            )));
+        }
+        Err(e) => return Err(e.into()),
+    };
+    tracing::info!(app = app.name, "app created");
+
+    format::json(AppResponse::new(&app, 0))
+}
+
+async fn get_one(
+    auth: auth::JWT,
+    Path(name): Path<String>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+    let app = apps::Model::find_owned(&ctx.db, user.id, &name).await?;
+    let machines = machines::Model::list_by_app(&ctx.db, app.id).await?;
+
+    format::json(AppResponse::new(&app, machines.len()))
+}
+
+/// Destroys the machines of an app then deletes it with its machines
+async fn destroy(
+    auth: auth::JWT,
+    Path(name): Path<String>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+    let app = apps::Model::find_owned(&ctx.db, user.id, &name).await?;
+
+    let machines = machines::Model::list_by_app(&ctx.db, app.id).await?;
+    if !machines.is_empty() {
+        let mut backend = backend(&app).await?;
+        for machine in &machines {
+            destroy_machine(&mut backend, &machine.name).await?;
+        }
+    }
+    for machine in machines {
+        machine.delete(&ctx.db).await?;
+    }
+    app.delete(&ctx.db).await?;
+    logs::shared().forget(&name);
+    tracing::info!(app = name, "app destroyed");
+
+    format::empty()
+}
+
+/// Creates or updates the app described by a `fly.toml` and its machines, the
+/// containers of the machines already deployed are destroyed once it is saved,
+/// so that they start again from the new config
+async fn deploy(
+    auth: auth::JWT,
+    State(ctx): State<AppContext>,
+    Json(params): Json<DeployParams>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+
+    let config = FlyToml::parse(&params.config)
+        .map_err(|e| Error::BadRequest(format!("invalid fly.toml: {e}")))?;
+    let deployment = config
+        .plan(params.image.as_deref())
+        .map_err(|e| Error::BadRequest(e.to_string()))?;
+    if let Some(machine) = deployment
+        .machines
+        .iter()
+        .find(|machine| !machines::is_valid_name(&machine.name))
+    {
+        return Err(Error::BadRequest(format!(
+            "invalid machine name {:?}",
+            machine.name
+        )));
+    }
+
+    let previous = match apps::Model::find_owned(&ctx.db, user.id, &deployment.app.name).await {
+        Ok(app) => machines::Model::list_by_app(&ctx.db, app.id).await?,
+        Err(_) => Vec::new(),
+    };
+
+    let (app, machines) = match apps::Model::deploy(&ctx.db, user.id, &deployment).await {
+        Ok(deployed) => deployed,
+        Err(ModelError::EntityAlreadyExists {}) => {
+            return unauthorized("app owned by another user");
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    // the containers of the previous deployment are only removed once the new
+    // one is saved, they are started again from the new machines when needed
+    if !previous.is_empty() {
+        let mut backend = backend(&app).await?;
+        for machine in &previous {
+            destroy_machine(&mut backend, &machine.name).await?;
+        }
+    }
+    tracing::info!(app = app.name, machines = machines.len(), "app deployed");
+
+    format::json(DeployResponse::new(&app, &machines, deployment.unsupported))
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("apps")
+        .add("/", get(list))
+        .add("/", post(create))
+        .add("/deploy", post(deploy))
+        .add("/:app", get(get_one))
+        .add("/:app", delete(destroy))
+}
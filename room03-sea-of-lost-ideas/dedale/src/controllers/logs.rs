@@ -0,0 +1,77 @@
+use std::convert::Infallible;
+
+use axum::{
+    body::Body,
+    extract::Query,
+    http::{header, StatusCode},
+    response::IntoResponse,
+};
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+use tokio_stream::StreamExt;
+
+use crate::{
+    models::{_entities::users, apps},
+    proxy::logs::{self, LogLine},
+};
+
+/// Lines sent when `lines` is not given
+const DEFAULT_LINES: usize = 100;
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct LogsParams {
+    /// Keep the response open and send the lines as they are written
+    #[serde(default)]
+    pub follow: bool,
+    /// How many of the last lines to send first
+    pub lines: Option<usize>,
+}
+
+/// One JSON document per line
+fn ndjson(line: &LogLine) -> String {
+    // strings and a date always serialize
+    let mut json = serde_json::to_string(line).unwrap_or_default();
+    json.push('\n');
+    json
+}
+
+/// Sends the last lines of an app as JSON lines, then the new ones while
+/// following
+async fn stream(
+    auth: auth::JWT,
+    Path(app): Path<String>,
+    Query(params): Query<LogsParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+    let app = apps::Model::find_owned(&ctx.db, user.id, &app).await?;
+
+    let limit = params.lines.unwrap_or(DEFAULT_LINES);
+    let logs = logs::shared();
+    let body = if params.follow {
+        let (recent, followed) = logs.follow(&app.name, limit);
+        Body::from_stream(
+            tokio_stream::iter(recent)
+                .chain(followed)
+                .map(|line| Ok::<_, Infallible>(ndjson(&line))),
+        )
+    } else {
+        Body::from(
+            logs.recent(&app.name, limit)
+                .iter()
+                .map(ndjson)
+                .collect::<String>(),
+        )
+    };
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        body,
+    )
+        .into_response())
+}
+
+pub fn routes() -> Routes {
+    Routes::new().prefix("apps/:app/logs").add("/", get(stream))
+}
@@ -0,0 +1,291 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use axum::{extract::Query, http::StatusCode, response::IntoResponse};
+use loco_rs::prelude::*;
+use sea_orm::ModelTrait;
+use serde::{Deserialize, Serialize};
+use tokio::time::{sleep, Instant};
+use uuid::Uuid;
+
+use super::apps::{backend, destroy_machine, stop_machine};
+use crate::{
+    models::{_entities::users, apps, machines, secrets},
+    proxy::backend::{AnyServiceBackend, MachineSpec},
+    vault,
+    views::machines::{MachineResponse, MachineState},
+};
+
+const WAIT_INTERVAL: Duration = Duration::from_millis(200);
+const DEFAULT_WAIT_TIMEOUT: u64 = 60;
+const MAX_WAIT_TIMEOUT: u64 = 300;
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InitConfig {
+    /// Overrides the command of the image
+    #[serde(default)]
+    pub cmd: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ServiceConfig {
+    pub internal_port: u16,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MachineConfig {
+    pub image: Option<String>,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    #[serde(default)]
+    pub init: InitConfig,
+    /// Only the first service is routed
+    #[serde(default)]
+    pub services: Vec<ServiceConfig>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CreateParams {
+    /// Generated from the app name when unset
+    pub name: Option<String>,
+    pub config: MachineConfig,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct WaitParams {
+    #[serde(default = "default_wait_state")]
+    pub state: MachineState,
+    /// In seconds
+    pub timeout: Option<u64>,
+}
+
+const fn default_wait_state() -> MachineState {
+    MachineState::Started
+}
+
+/// Quote the arguments of `init.cmd` for `sh -c`
+fn shell_join(args: &[String]) -> String {
+    args.iter()
+        .map(|arg| {
+            if !arg.is_empty()
+                && arg
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "-_./=:,@%+".contains(c))
+            {
+                arg.clone()
+            } else {
+                format!("'{}'", arg.replace('\'', r"'\''"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn load(
+    ctx: &AppContext,
+    auth: &auth::JWT,
+    app: &str,
+) -> Result<(apps::Model, AnyServiceBackend)> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+    let app = apps::Model::find_owned(&ctx.db, user.id, app).await?;
+    let backend = backend(&app).await?;
+
+    Ok((app, backend))
+}
+
+/// The spec a machine is created or started with, with the secrets of its app
+async fn runtime_spec(
+    ctx: &AppContext,
+    app: &apps::Model,
+    machine: &machines::Model,
+) -> Result<MachineSpec> {
+    let mut spec = MachineSpec::from(machine);
+    spec.app.clone_from(&app.name);
+    if let Some(vault) = vault::installed() {
+        spec.env
+            .extend(secrets::Model::env_of(&ctx.db, &vault, app.id).await?);
+    }
+
+    Ok(spec)
+}
+
+async fn state(backend: &mut AnyServiceBackend, machine: &str) -> Result<MachineState> {
+    backend
+        .status(machine)
+        .await
+        .map(MachineState::from)
+        .map_err(|e| Error::string(&e.to_string()))
+}
+
+async fn list(
+    auth: auth::JWT,
+    Path(app): Path<String>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+
+    let mut response = Vec::new();
+    for machine in machines::Model::list_by_app(&ctx.db, app.id).await? {
+        let state = state(&mut backend, &machine.name).await?;
+        response.push(MachineResponse::new(&app, &machine, state));
+    }
+
+    format::json(response)
+}
+
+/// Creates a machine and its container, it is started by the first request or
+/// by `start`
+async fn create(
+    auth: auth::JWT,
+    Path(app): Path<String>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<CreateParams>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+
+    let name = params
+        .name
+        .unwrap_or_else(|| format!("{}-{}", app.name, &Uuid::new_v4().simple().to_string()[..8]));
+    if !machines::is_valid_name(&name) {
+        return Err(Error::BadRequest(format!("invalid machine name {name:?}")));
+    }
+    let spec = MachineSpec {
+        app: app.name.clone(),
+        image: params.config.image,
+        command: (!params.config.init.cmd.is_empty()).then(|| shell_join(&params.config.init.cmd)),
+        env: params.config.env,
+        internal_port: params
+            .config
+            .services
+            .first()
+            .map_or(8080, |service| service.internal_port),
+    };
+
+    let machine = match machines::Model::create(&ctx.db, app.id, &name, &spec).await {
+        Ok(machine) => machine,
+        Err(ModelError::EntityAlreadyExists {}) => {
+            return Err(Error::BadRequest(format!("machine {name} already exists")));
+        }
+        Err(e) => return Err(e.into()),
+    };
+
+    let spec = runtime_spec(&ctx, &app, &machine).await?;
+    if let Err(e) = backend.create(&machine.name, &spec).await {
+        machine.delete(&ctx.db).await?;
+        return Err(Error::BadRequest(format!(
+            "could not create machine {name}: {e}"
+        )));
+    }
+    tracing::info!(app = app.name, machine = machine.name, "machine created");
+
+    let state = state(&mut backend, &machine.name).await?;
+    format::json(MachineResponse::new(&app, &machine, state))
+}
+
+async fn get_one(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+    let machine = machines::Model::find_by_app_and_name(&ctx.db, app.id, &name).await?;
+
+    let state = state(&mut backend, &machine.name).await?;
+    format::json(MachineResponse::new(&app, &machine, state))
+}
+
+async fn start(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+    let machine = machines::Model::find_by_app_and_name(&ctx.db, app.id, &name).await?;
+
+    let spec = runtime_spec(&ctx, &app, &machine).await?;
+    backend
+        .start(&machine.name, &spec)
+        .await
+        .map_err(|e| Error::string(&e.to_string()))?;
+    tracing::info!(app = app.name, machine = machine.name, "machine started");
+
+    let state = state(&mut backend, &machine.name).await?;
+    format::json(MachineResponse::new(&app, &machine, state))
+}
+
+async fn stop(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+    let machine = machines::Model::find_by_app_and_name(&ctx.db, app.id, &name).await?;
+
+    stop_machine(&mut backend, &machine.name).await?;
+    tracing::info!(app = app.name, machine = machine.name, "machine stopped");
+
+    let state = state(&mut backend, &machine.name).await?;
+    format::json(MachineResponse::new(&app, &machine, state))
+}
+
+/// Destroys a machine then deletes it
+async fn destroy(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+    let machine = machines::Model::find_by_app_and_name(&ctx.db, app.id, &name).await?;
+
+    destroy_machine(&mut backend, &machine.name).await?;
+    machine.delete(&ctx.db).await?;
+    tracing::info!(app = app.name, machine = name, "machine destroyed");
+
+    format::empty()
+}
+
+/// Waits for a machine to reach a state, answers 408 when it does not in time
+async fn wait(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    Query(params): Query<WaitParams>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let (app, mut backend) = load(&ctx, &auth, &app).await?;
+    let machine = machines::Model::find_by_app_and_name(&ctx.db, app.id, &name).await?;
+
+    let timeout = Duration::from_secs(
+        params
+            .timeout
+            .unwrap_or(DEFAULT_WAIT_TIMEOUT)
+            .min(MAX_WAIT_TIMEOUT),
+    );
+    let deadline = Instant::now() + timeout;
+    loop {
+        let state = state(&mut backend, &machine.name).await?;
+        if state == params.state {
+            return format::json(MachineResponse::new(&app, &machine, state));
+        }
+        if Instant::now() >= deadline {
+            return Ok((
+                StatusCode::REQUEST_TIMEOUT,
+                Json(serde_json::json!({
+                    "error": format!("machine {name} not {:?} after {timeout:?}", params.state),
+                })),
+            )
+                .into_response());
+        }
+        sleep(WAIT_INTERVAL).await;
+    }
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("apps/:app/machines")
+        .add("/", get(list))
+        .add("/", post(create))
+        .add("/:machine", get(get_one))
+        .add("/:machine", delete(destroy))
+        .add("/:machine/start", post(start))
+        .add("/:machine/stop", post(stop))
+        .add("/:machine/wait", get(wait))
+}
@@ -1,2 +1,6 @@
+pub mod apps;
 pub mod auth;
+pub mod logs;
+pub mod machines;
+pub mod secrets;
 pub mod user;
@@ -0,0 +1,89 @@
+use std::collections::BTreeMap;
+
+use loco_rs::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::{_entities::users, apps, secrets},
+    vault,
+    views::secrets::SecretResponse,
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SetParams {
+    /// Values by name
+    pub secrets: BTreeMap<String, String>,
+}
+
+async fn load(ctx: &AppContext, auth: &auth::JWT, app: &str) -> Result<apps::Model> {
+    let user = users::Model::find_by_pid(&ctx.db, &auth.claims.pid).await?;
+    Ok(apps::Model::find_owned(&ctx.db, user.id, app).await?)
+}
+
+/// Lists the secrets of an app, without their values
+async fn list(
+    auth: auth::JWT,
+    Path(app): Path<String>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let app = load(&ctx, &auth, &app).await?;
+
+    let secrets = secrets::Model::list_by_app(&ctx.db, app.id).await?;
+    format::json(secrets.iter().map(SecretResponse::new).collect::<Vec<_>>())
+}
+
+/// Creates or replaces secrets, machines see them when they are next started
+async fn set(
+    auth: auth::JWT,
+    Path(app): Path<String>,
+    State(ctx): State<AppContext>,
+    Json(params): Json<SetParams>,
+) -> Result<Response> {
+    let app = load(&ctx, &auth, &app).await?;
+
+    let Some(vault) = vault::installed() else {
+        return Err(Error::string(
+            "secrets are disabled, the server has no secrets key",
+        ));
+    };
+    if params.secrets.is_empty() {
+        return Err(Error::BadRequest("no secrets to set".to_string()));
+    }
+    if let Some(name) = params
+        .secrets
+        .keys()
+        .find(|name| !secrets::is_valid_name(name))
+    {
+        return Err(Error::BadRequest(format!("invalid secret name {name:?}")));
+    }
+
+    let secrets = secrets::Model::set_many(&ctx.db, &vault, app.id, &params.secrets).await?;
+    tracing::info!(
+        app = app.name,
+        secrets = ?params.secrets.keys().collect::<Vec<_>>(),
+        "secrets set"
+    );
+
+    format::json(secrets.iter().map(SecretResponse::new).collect::<Vec<_>>())
+}
+
+async fn unset(
+    auth: auth::JWT,
+    Path((app, name)): Path<(String, String)>,
+    State(ctx): State<AppContext>,
+) -> Result<Response> {
+    let app = load(&ctx, &auth, &app).await?;
+
+    secrets::Model::unset(&ctx.db, app.id, &name).await?;
+    tracing::info!(app = app.name, secret = name, "secret unset");
+
+    format::empty()
+}
+
+pub fn routes() -> Routes {
+    Routes::new()
+        .prefix("apps/:app/secrets")
+        .add("/", get(list))
+        .add("/", post(set))
+        .add("/:name", delete(unset))
+}
@@ -0,0 +1,462 @@
+//! `fly.toml` app configuration, see <https://fly.io/docs/reference/configuration/>
+//!
+//! Every key of the Fly schema is parsed, the ones dedale cannot honour yet are
+//! reported by [`FlyToml::plan`] instead of being silently dropped.
+
+use std::{collections::BTreeMap, fmt};
+
+use serde::{Deserialize, Serialize};
+
+/// The process group of the apps without `[processes]`
+pub const DEFAULT_PROCESS: &str = "app";
+
+/// App names are used as hostnames, e.g. `<app>.dedale.localhost`
+#[must_use]
+pub fn is_valid_app_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// Keys dedale does not know, by name
+type Other = BTreeMap<String, toml::Value>;
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct FlyToml {
+    pub app: String,
+    pub primary_region: Option<String>,
+    pub kill_signal: Option<String>,
+    pub kill_timeout: Option<toml::Value>,
+    #[serde(default)]
+    pub build: Build,
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    pub http_service: Option<HttpService>,
+    #[serde(default)]
+    pub services: Vec<Service>,
+    #[serde(default)]
+    pub checks: BTreeMap<String, Check>,
+    #[serde(default, deserialize_with = "one_or_many")]
+    pub mounts: Vec<Mount>,
+    /// Command of each process group
+    #[serde(default)]
+    pub processes: BTreeMap<String, String>,
+    #[serde(default)]
+    pub vm: Vec<toml::Value>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Build {
+    pub image: Option<String>,
+    pub dockerfile: Option<String>,
+    pub builder: Option<String>,
+    #[serde(default)]
+    pub buildpacks: Vec<String>,
+    #[serde(default)]
+    pub args: BTreeMap<String, String>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+/// `auto_stop_machines` is either a boolean or one of `off`, `stop` and
+/// `suspend`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum AutoStop {
+    Enabled(bool),
+    Mode(String),
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct HttpService {
+    pub internal_port: u16,
+    #[serde(default)]
+    pub force_https: bool,
+    pub auto_stop_machines: Option<AutoStop>,
+    pub auto_start_machines: Option<bool>,
+    pub min_machines_running: Option<u32>,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    pub concurrency: Option<toml::Value>,
+    #[serde(default)]
+    pub checks: Vec<HttpCheck>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Service {
+    pub internal_port: u16,
+    #[serde(default = "default_protocol")]
+    pub protocol: String,
+    pub auto_stop_machines: Option<AutoStop>,
+    pub auto_start_machines: Option<bool>,
+    pub min_machines_running: Option<u32>,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(default)]
+    pub ports: Vec<Port>,
+    pub concurrency: Option<toml::Value>,
+    #[serde(default)]
+    pub tcp_checks: Vec<toml::Value>,
+    #[serde(default)]
+    pub http_checks: Vec<HttpCheck>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+fn default_protocol() -> String {
+    "tcp".to_string()
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Port {
+    pub port: Option<u16>,
+    pub start_port: Option<u16>,
+    pub end_port: Option<u16>,
+    #[serde(default)]
+    pub handlers: Vec<String>,
+    #[serde(default)]
+    pub force_https: bool,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct HttpCheck {
+    pub path: Option<String>,
+    pub method: Option<String>,
+    pub interval: Option<String>,
+    pub timeout: Option<String>,
+    pub grace_period: Option<String>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+/// A top level `[checks.<name>]`
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Check {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub port: Option<u16>,
+    pub path: Option<String>,
+    pub method: Option<String>,
+    pub interval: Option<String>,
+    pub timeout: Option<String>,
+    pub grace_period: Option<String>,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct Mount {
+    pub source: String,
+    pub destination: String,
+    #[serde(default)]
+    pub processes: Vec<String>,
+    #[serde(flatten)]
+    pub other: Other,
+}
+
+/// `[mounts]` and `[[mounts]]` are both accepted
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<Mount>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum OneOrMany {
+        One(Mount),
+        Many(Vec<Mount>),
+    }
+
+    Ok(match OneOrMany::deserialize(deserializer)? {
+        OneOrMany::One(mount) => vec![mount],
+        OneOrMany::Many(mounts) => mounts,
+    })
+}
+
+/// A key of the configuration dedale ignores
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Unsupported {
+    /// Dotted path of the key, e.g. `http_service.force_https`
+    pub key: String,
+    pub reason: String,
+}
+
+impl fmt::Display for Unsupported {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.key, self.reason)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlanError {
+    InvalidAppName(String),
+    /// Neither `--image` nor `build.image`
+    MissingImage,
+    /// Neither `[http_service]` nor `[[services]]`
+    MissingService,
+    UnknownProcess(String),
+}
+
+impl fmt::Display for PlanError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidAppName(name) => write!(
+                f,
+                "invalid app name {name:?}, only lowercase letters, digits and dashes are allowed"
+            ),
+            Self::MissingImage => f.write_str("no image, set build.image or pass one"),
+            Self::MissingService => f.write_str("no [http_service] nor [[services]] to route to"),
+            Self::UnknownProcess(process) => {
+                write!(f, "process group {process:?} is not in [processes]")
+            }
+        }
+    }
+}
+
+impl std::error::Error for PlanError {}
+
+/// The app record a configuration produces
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AppPlan {
+    pub name: String,
+    pub auto_stop_machines: bool,
+    pub min_machines_running: u32,
+    pub health_check_path: Option<String>,
+}
+
+/// A machine record a configuration produces, one per routed process group
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MachinePlan {
+    pub name: String,
+    pub process: String,
+    pub image: String,
+    /// `None` runs the command of the image
+    pub command: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub internal_port: u16,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Deployment {
+    pub app: AppPlan,
+    pub machines: Vec<MachinePlan>,
+    pub unsupported: Vec<Unsupported>,
+}
+
+/// The routed service, `[http_service]` first then the first `[[services]]`
+struct Routed<'a> {
+    internal_port: u16,
+    auto_stop_machines: Option<&'a AutoStop>,
+    auto_start_machines: Option<bool>,
+    min_machines_running: Option<u32>,
+    processes: &'a [String],
+    checks: &'a [HttpCheck],
+}
+
+impl FlyToml {
+    /// # Errors
+    ///
+    /// When the configuration is not valid TOML or does not follow the schema
+    pub fn parse(config: &str) -> Result<Self, toml::de::Error> {
+        toml::from_str(config)
+    }
+
+    /// The records to create for this configuration, `image` overrides
+    /// `build.image`
+    ///
+    /// # Errors
+    ///
+    /// When the configuration cannot be deployed at all
+    pub fn plan(&self, image: Option<&str>) -> Result<Deployment, PlanError> {
+        if !is_valid_app_name(&self.app) {
+            return Err(PlanError::InvalidAppName(self.app.clone()));
+        }
+        let image = image
+            .or(self.build.image.as_deref())
+            .ok_or(PlanError::MissingImage)?;
+        let mut unsupported = self.unsupported();
+
+        let routed = self.routed().ok_or(PlanError::MissingService)?;
+        let auto_stop_machines = match routed.auto_stop_machines {
+            None => true,
+            Some(AutoStop::Enabled(enabled)) => *enabled,
+            Some(AutoStop::Mode(mode)) => match mode.as_str() {
+                "off" => false,
+                "stop" => true,
+                _ => {
+                    unsupported.push(Unsupported {
+                        key: "auto_stop_machines".to_string(),
+                        reason: format!("{mode:?} is not supported, machines are stopped"),
+                    });
+                    true
+                }
+            },
+        };
+        if routed.auto_start_machines == Some(false) {
+            unsupported.push(Unsupported {
+                key: "auto_start_machines".to_string(),
+                reason: "machines are always started by requests".to_string(),
+            });
+        }
+
+        // like Fly, a service without processes runs on every process group
+        let processes = if !routed.processes.is_empty() {
+            routed.processes.to_vec()
+        } else if self.processes.is_empty() {
+            vec![DEFAULT_PROCESS.to_string()]
+        } else {
+            self.processes.keys().cloned().collect()
+        };
+        let machines = processes
+            .into_iter()
+            .map(|process| {
+                let command = self.processes.get(&process).cloned();
+                if command.is_none() && !(self.processes.is_empty() && process == DEFAULT_PROCESS) {
+                    return Err(PlanError::UnknownProcess(process));
+                }
+
+                Ok(MachinePlan {
+                    name: format!("{}-{process}", self.app),
+                    process,
+                    image: image.to_string(),
+                    command,
+                    env: self.env.clone(),
+                    internal_port: routed.internal_port,
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Deployment {
+            app: AppPlan {
+                name: self.app.clone(),
+                auto_stop_machines,
+                min_machines_running: routed.min_machines_running.unwrap_or_default(),
+                health_check_path: self.health_check_path(&routed),
+            },
+            machines,
+            unsupported,
+        })
+    }
+
+    fn routed(&self) -> Option<Routed<'_>> {
+        if let Some(service) = &self.http_service {
+            return Some(Routed {
+                internal_port: service.internal_port,
+                auto_stop_machines: service.auto_stop_machines.as_ref(),
+                auto_start_machines: service.auto_start_machines,
+                min_machines_running: service.min_machines_running,
+                processes: &service.processes,
+                checks: &service.checks,
+            });
+        }
+
+        self.services.first().map(|service| Routed {
+            internal_port: service.internal_port,
+            auto_stop_machines: service.auto_stop_machines.as_ref(),
+            auto_start_machines: service.auto_start_machines,
+            min_machines_running: service.min_machines_running,
+            processes: &service.processes,
+            checks: &service.http_checks,
+        })
+    }
+
+    /// The first HTTP check of the routed service, or of the top level checks
+    fn health_check_path(&self, routed: &Routed<'_>) -> Option<String> {
+        routed
+            .checks
+            .iter()
+            .find_map(|check| check.path.clone())
+            .or_else(|| {
+                self.checks
+                    .values()
+                    .filter(|check| check.kind == "http")
+                    .find_map(|check| check.path.clone())
+            })
+    }
+
+    /// The keys parsed but ignored by dedale, and the unknown ones
+    fn unsupported(&self) -> Vec<Unsupported> {
+        let mut unsupported = Vec::new();
+        let mut push = |key: &str, reason: &str| {
+            unsupported.push(Unsupported {
+                key: key.to_string(),
+                reason: reason.to_string(),
+            });
+        };
+
+        if self.primary_region.is_some() {
+            push("primary_region", "dedale runs in a single region");
+        }
+        if self.kill_signal.is_some() || self.kill_timeout.is_some() {
+            push("kill_signal", "machines are stopped by their backend");
+        }
+        if self.build.dockerfile.is_some()
+            || self.build.builder.is_some()
+            || !self.build.buildpacks.is_empty()
+            || !self.build.args.is_empty()
+        {
+            push("build", "images are not built by dedale, set build.image");
+        }
+        if let Some(service) = &self.http_service {
+            if service.force_https {
+                push("http_service.force_https", "HTTPS is not redirected");
+            }
+            if service.concurrency.is_some() {
+                push("http_service.concurrency", "requests are not limited");
+            }
+        }
+        let services = if self.http_service.is_some() {
+            &self.services[..]
+        } else {
+            self.services.get(1..).unwrap_or_default()
+        };
+        if !services.is_empty() {
+            push(
+                "services",
+                "only one service is routed, [http_service] or the first [[services]]",
+            );
+        }
+        if !self.mounts.is_empty() {
+            push("mounts", "volumes are not supported");
+        }
+        if !self.vm.is_empty() {
+            push("vm", "machines are not sized");
+        }
+
+        let mut unknown = |prefix: &str, other: &Other| {
+            for key in other.keys() {
+                unsupported.push(Unsupported {
+                    key: format!("{prefix}{key}"),
+                    reason: "unknown key".to_string(),
+                });
+            }
+        };
+        unknown("", &self.other);
+        unknown("build.", &self.build.other);
+        if let Some(service) = &self.http_service {
+            unknown("http_service.", &service.other);
+            for check in &service.checks {
+                unknown("http_service.checks.", &check.other);
+            }
+        }
+        for (i, service) in self.services.iter().enumerate() {
+            unknown(&format!("services.{i}."), &service.other);
+        }
+        for (name, check) in &self.checks {
+            unknown(&format!("checks.{name}."), &check.other);
+        }
+        for (i, mount) in self.mounts.iter().enumerate() {
+            unknown(&format!("mounts.{i}."), &mount.other);
+        }
+
+        unsupported
+    }
+}
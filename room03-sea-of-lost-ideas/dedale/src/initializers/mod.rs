@@ -1,2 +1,4 @@
 #![allow(clippy::module_name_repetitions)]
+pub mod proxy;
+pub mod secrets;
 pub mod view_engine;
@@ -0,0 +1,75 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use loco_rs::{
+    app::{AppContext, Initializer},
+    Error, Result,
+};
+use serde::Deserialize;
+use tracing::info;
+
+use crate::{
+    proxy::{
+        routing::{self, RoutingTable},
+        tls::{self, Tls, TlsConfig},
+    },
+    vault,
+};
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct ProxySettings {
+    /// Apps are reachable on `<app>.<domain>`
+    domain: String,
+    /// Plain TCP only when unset
+    tls: Option<TlsConfig>,
+}
+
+impl Default for ProxySettings {
+    fn default() -> Self {
+        Self {
+            domain: "dedale.localhost".to_string(),
+            tls: None,
+        }
+    }
+}
+
+/// Load the routes of the apps and their certificates before the proxy is
+/// started
+pub struct ProxyInitializer;
+#[async_trait]
+impl Initializer for ProxyInitializer {
+    fn name(&self) -> String {
+        "proxy".to_string()
+    }
+
+    async fn before_run(&self, ctx: &AppContext) -> Result<()> {
+        let settings = ctx
+            .config
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.get("proxy"))
+            .map(|proxy| serde_json::from_value::<ProxySettings>(proxy.clone()))
+            .transpose()
+            .map_err(|e| Error::string(&format!("invalid proxy settings: {e}")))?
+            .unwrap_or_default();
+
+        let table = RoutingTable::load(ctx.db.clone(), &settings.domain).await?;
+        routing::install(Arc::new(table));
+        info!("apps routed on *.{}", settings.domain);
+
+        if let Some(config) = settings.tls {
+            // the private keys of the certificates are stored sealed
+            let vault = vault::installed().ok_or_else(|| {
+                Error::string("proxy.tls needs a secrets key (secrets.key) to store private keys")
+            })?;
+            let tls = Tls::load(ctx.db.clone(), vault, config).await?;
+            if tls.config.acme.is_none() {
+                info!("no ACME directory, only the stored certificates are served");
+            }
+            tls::install(Arc::new(tls));
+        }
+
+        Ok(())
+    }
+}
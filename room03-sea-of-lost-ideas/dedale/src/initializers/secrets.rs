@@ -0,0 +1,60 @@
+use std::sync::Arc;
+
+use axum::async_trait;
+use loco_rs::{
+    app::{AppContext, Initializer},
+    Error, Result,
+};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{
+    models::secrets,
+    vault::{self, Vault},
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct SecretsSettings {
+    /// 32 bytes encoded in base64
+    key: Option<String>,
+}
+
+/// Install the key of the secrets of the apps, before the proxy loads the
+/// routes which need them
+pub struct SecretsInitializer;
+#[async_trait]
+impl Initializer for SecretsInitializer {
+    fn name(&self) -> String {
+        "secrets".to_string()
+    }
+
+    async fn before_run(&self, ctx: &AppContext) -> Result<()> {
+        let settings = ctx
+            .config
+            .settings
+            .as_ref()
+            .and_then(|settings| settings.get("secrets"))
+            .map(|secrets| serde_json::from_value::<SecretsSettings>(secrets.clone()))
+            .transpose()
+            .map_err(|e| Error::string(&format!("invalid secrets settings: {e}")))?
+            .unwrap_or_default();
+
+        let Some(key) = settings.key.filter(|key| !key.is_empty()) else {
+            warn!("no secrets key, the secrets of the apps are disabled");
+            return Ok(());
+        };
+        let vault = Vault::from_base64(&key)
+            .map_err(|e| Error::string(&format!("invalid secrets settings: {e}")))?;
+        let refreshed = secrets::Model::refresh_digests(&ctx.db, &vault)
+            .await
+            .map_err(|e| Error::string(&format!("cannot refresh the secrets digests: {e}")))?;
+        if refreshed > 0 {
+            info!("refreshed the digests of {refreshed} secrets");
+        }
+        vault::install(Arc::new(vault));
+        info!("secrets of the apps enabled");
+
+        Ok(())
+    }
+}
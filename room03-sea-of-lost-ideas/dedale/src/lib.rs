@@ -1,9 +1,11 @@
 pub mod app;
 pub mod controllers;
+pub mod fly_toml;
 pub mod initializers;
 pub mod mailers;
 pub mod models;
 pub mod proxy;
 pub mod tasks;
+pub mod vault;
 pub mod views;
 pub mod workers;
@@ -13,12 +13,21 @@ pub struct Model {
     #[sea_orm(unique)]
     pub name: String,
     pub user_id: i32,
+    pub auto_stop_machines: bool,
+    pub min_machines_running: i32,
+    pub idle_timeout: i32,
+    pub health_check_path: Option<String>,
+    pub backend: String,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::domains::Entity")]
+    Domains,
     #[sea_orm(has_many = "super::machines::Entity")]
     Machines,
+    #[sea_orm(has_many = "super::secrets::Entity")]
+    Secrets,
     #[sea_orm(
         belongs_to = "super::users::Entity",
         from = "Column::UserId",
@@ -29,12 +38,24 @@ pub enum Relation {
     Users,
 }
 
+impl Related<super::domains::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Domains.def()
+    }
+}
+
 impl Related<super::machines::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Machines.def()
     }
 }
 
+impl Related<super::secrets::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Secrets.def()
+    }
+}
+
 impl Related<super::users::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Users.def()
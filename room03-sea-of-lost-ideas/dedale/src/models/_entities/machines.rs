@@ -13,6 +13,11 @@ pub struct Model {
     #[sea_orm(unique)]
     pub name: String,
     pub app_id: i32,
+    pub address: String,
+    pub internal_port: i32,
+    pub image: Option<String>,
+    pub command: Option<String>,
+    pub env: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
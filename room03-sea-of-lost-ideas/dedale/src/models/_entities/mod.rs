@@ -2,6 +2,10 @@
 
 pub mod prelude;
 
+pub mod acme_accounts;
 pub mod apps;
+pub mod certificates;
+pub mod domains;
 pub mod machines;
+pub mod secrets;
 pub mod users;
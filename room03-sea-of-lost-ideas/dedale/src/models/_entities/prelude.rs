@@ -1,3 +1,7 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 1.0.0-rc.1
 
-pub use super::{apps::Entity as Apps, machines::Entity as Machines, users::Entity as Users};
+pub use super::{
+    acme_accounts::Entity as AcmeAccounts, apps::Entity as Apps,
+    certificates::Entity as Certificates, domains::Entity as Domains, machines::Entity as Machines,
+    secrets::Entity as Secrets, users::Entity as Users,
+};
@@ -0,0 +1,62 @@
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{entity::prelude::*, ActiveValue};
+
+pub use super::_entities::acme_accounts::{self, ActiveModel, Entity, Model};
+use crate::vault::{Vault, VaultError};
+
+/// The credentials of an account are sealed for its directory
+fn sealed_for(directory_url: &str) -> String {
+    format!("acme-account/{directory_url}")
+}
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+impl Model {
+    /// The credentials of the account in clear
+    ///
+    /// # Errors
+    ///
+    /// When the credentials were not sealed by this vault
+    pub fn reveal_credentials(&self, vault: &Vault) -> Result<String, VaultError> {
+        vault.open_for(&sealed_for(&self.directory_url), &self.credentials)
+    }
+
+    /// finds the account registered on an ACME directory
+    ///
+    /// # Errors
+    ///
+    /// When could not find the account or DB query error
+    pub async fn find_by_directory_url(
+        db: &DatabaseConnection,
+        directory_url: &str,
+    ) -> ModelResult<Self> {
+        let account = acme_accounts::Entity::find()
+            .filter(acme_accounts::Column::DirectoryUrl.eq(directory_url))
+            .one(db)
+            .await?;
+        account.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// Saves the credentials of an account registered on an ACME directory,
+    /// sealed
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn create(
+        db: &DatabaseConnection,
+        vault: &Vault,
+        directory_url: &str,
+        credentials: &str,
+    ) -> ModelResult<Self> {
+        Ok(ActiveModel {
+            directory_url: ActiveValue::set(directory_url.to_string()),
+            credentials: ActiveValue::set(vault.seal_for(&sealed_for(directory_url), credentials)),
+            ..Default::default()
+        }
+        .insert(db)
+        .await?)
+    }
+}
@@ -1,7 +1,183 @@
-use sea_orm::entity::prelude::*;
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, IntoActiveModel, QueryOrder, TransactionTrait, TryIntoModel,
+};
 
-use super::_entities::apps::ActiveModel;
+pub use super::_entities::apps::{self, ActiveModel, Entity, Model};
+use super::{_entities::machines, machines::backend_name};
+use crate::{fly_toml::Deployment, proxy::routing};
 
+#[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     // extend activemodel below (keep comment for generators)
+
+    async fn after_save<C>(model: Model, _db: &C, _insert: bool) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(model)
+    }
+
+    async fn after_delete<C>(self, _db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(self)
+    }
+}
+
+impl Model {
+    /// finds an app by its name
+    ///
+    /// # Errors
+    ///
+    /// When could not find the app or DB query error
+    pub async fn find_by_name(db: &DatabaseConnection, name: &str) -> ModelResult<Self> {
+        let app = apps::Entity::find()
+            .filter(apps::Column::Name.eq(name))
+            .one(db)
+            .await?;
+        app.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// finds an app of a user by its name, the apps of other users are not
+    /// found
+    ///
+    /// # Errors
+    ///
+    /// When could not find the app or DB query error
+    pub async fn find_owned(
+        db: &DatabaseConnection,
+        user_id: i32,
+        name: &str,
+    ) -> ModelResult<Self> {
+        let app = apps::Entity::find()
+            .filter(apps::Column::Name.eq(name))
+            .filter(apps::Column::UserId.eq(user_id))
+            .one(db)
+            .await?;
+        app.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// lists the apps of a user
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_owned(db: &DatabaseConnection, user_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(apps::Entity::find()
+            .filter(apps::Column::UserId.eq(user_id))
+            .order_by_asc(apps::Column::Name)
+            .all(db)
+            .await?)
+    }
+
+    /// Creates an empty app
+    ///
+    /// # Errors
+    ///
+    /// When an app with the same name exists or DB query error
+    pub async fn create(
+        db: &DatabaseConnection,
+        user_id: i32,
+        name: &str,
+        backend: Option<&str>,
+    ) -> ModelResult<Self> {
+        let txn = db.begin().await?;
+
+        if apps::Entity::find()
+            .filter(apps::Column::Name.eq(name))
+            .one(&txn)
+            .await?
+            .is_some()
+        {
+            return Err(ModelError::EntityAlreadyExists {});
+        }
+
+        let mut app = ActiveModel {
+            name: ActiveValue::set(name.to_string()),
+            user_id: ActiveValue::set(user_id),
+            ..Default::default()
+        };
+        if let Some(backend) = backend {
+            app.backend = ActiveValue::set(backend.to_string());
+        }
+        let app = app.insert(&txn).await?;
+
+        txn.commit().await?;
+
+        Ok(app)
+    }
+
+    /// Creates or updates an app and its machines from a deployment, the
+    /// machines not in the deployment anymore are deleted
+    ///
+    /// The machines must have been destroyed on their backend first
+    ///
+    /// # Errors
+    ///
+    /// When the app belongs to someone else or DB query error
+    pub async fn deploy(
+        db: &DatabaseConnection,
+        user_id: i32,
+        deployment: &Deployment,
+    ) -> ModelResult<(Self, Vec<machines::Model>)> {
+        let txn = db.begin().await?;
+        let plan = &deployment.app;
+
+        let existing = apps::Entity::find()
+            .filter(apps::Column::Name.eq(&plan.name))
+            .one(&txn)
+            .await?;
+        let mut app = match existing {
+            Some(app) if app.user_id != user_id => return Err(ModelError::EntityAlreadyExists {}),
+            Some(app) => app.into_active_model(),
+            None => ActiveModel {
+                name: ActiveValue::set(plan.name.clone()),
+                user_id: ActiveValue::set(user_id),
+                ..Default::default()
+            },
+        };
+        app.auto_stop_machines = ActiveValue::set(plan.auto_stop_machines);
+        app.min_machines_running =
+            ActiveValue::set(i32::try_from(plan.min_machines_running).unwrap_or(i32::MAX));
+        app.health_check_path = ActiveValue::set(plan.health_check_path.clone());
+        let app = app.save(&txn).await?.try_into_model()?;
+
+        let mut existing = machines::Entity::find()
+            .filter(machines::Column::AppId.eq(app.id))
+            .all(&txn)
+            .await?;
+        let mut deployed = Vec::with_capacity(deployment.machines.len());
+        for plan in &deployment.machines {
+            let name = backend_name(app.id, &plan.name);
+            let mut machine = match existing.iter().position(|m| m.name == name) {
+                Some(i) => existing.swap_remove(i).into_active_model(),
+                None => machines::ActiveModel {
+                    name: ActiveValue::set(name),
+                    app_id: ActiveValue::set(app.id),
+                    ..Default::default()
+                },
+            };
+            machine.internal_port = ActiveValue::set(i32::from(plan.internal_port));
+            machine.image = ActiveValue::set(Some(plan.image.clone()));
+            machine.command = ActiveValue::set(plan.command.clone());
+            machine.env = ActiveValue::set(Some(serde_json::json!(plan.env)));
+            deployed.push(machine.save(&txn).await?.try_into_model()?);
+        }
+
+        machines::Entity::delete_many()
+            .filter(machines::Column::AppId.eq(app.id))
+            .filter(machines::Column::Id.is_not_in(deployed.iter().map(|m| m.id)))
+            .exec(&txn)
+            .await?;
+
+        txn.commit().await?;
+        // the machines deleted above skip `after_delete`
+        routing::notify_changed();
+
+        Ok((app, deployed))
+    }
 }
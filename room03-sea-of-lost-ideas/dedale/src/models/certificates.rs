@@ -0,0 +1,66 @@
+use loco_rs::model::ModelResult;
+use sea_orm::{entity::prelude::*, ActiveValue, IntoActiveModel, TransactionTrait, TryIntoModel};
+
+pub use super::_entities::certificates::{self, ActiveModel, Entity, Model};
+use crate::vault::{Vault, VaultError};
+
+/// The private key of a certificate is sealed for its hostname
+fn sealed_for(hostname: &str) -> String {
+    format!("certificate/{hostname}")
+}
+
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+}
+
+impl Model {
+    /// The private key of the certificate in clear
+    ///
+    /// # Errors
+    ///
+    /// When the key was not sealed by this vault
+    pub fn reveal_private_key(&self, vault: &Vault) -> Result<String, VaultError> {
+        vault.open_for(&sealed_for(&self.hostname), &self.private_key)
+    }
+
+    /// Stores the certificate of a hostname, replacing the previous one, its
+    /// private key is sealed
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn upsert(
+        db: &DatabaseConnection,
+        vault: &Vault,
+        hostname: &str,
+        chain: &str,
+        private_key: &str,
+        not_after: DateTime,
+    ) -> ModelResult<Self> {
+        let txn = db.begin().await?;
+
+        let existing = certificates::Entity::find()
+            .filter(certificates::Column::Hostname.eq(hostname))
+            .one(&txn)
+            .await?;
+        let mut certificate = existing.map_or_else(
+            || ActiveModel {
+                hostname: ActiveValue::set(hostname.to_string()),
+                ..Default::default()
+            },
+            |certificate| ActiveModel {
+                updated_at: ActiveValue::set(chrono::Utc::now().naive_utc()),
+                ..certificate.into_active_model()
+            },
+        );
+        certificate.chain = ActiveValue::set(chain.to_string());
+        certificate.private_key =
+            ActiveValue::set(vault.seal_for(&sealed_for(hostname), private_key));
+        certificate.not_after = ActiveValue::set(not_after);
+        let certificate = certificate.save(&txn).await?.try_into_model()?;
+
+        txn.commit().await?;
+
+        Ok(certificate)
+    }
+}
@@ -0,0 +1,25 @@
+use sea_orm::entity::prelude::*;
+
+use super::_entities::domains::{ActiveModel, Model};
+use crate::proxy::routing;
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+
+    async fn after_save<C>(model: Model, _db: &C, _insert: bool) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(model)
+    }
+
+    async fn after_delete<C>(self, _db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(self)
+    }
+}
@@ -1,7 +1,127 @@
-use sea_orm::entity::prelude::*;
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{entity::prelude::*, ActiveValue, QueryOrder, TransactionTrait};
 
-use super::_entities::machines::ActiveModel;
+pub use super::_entities::machines::{self, ActiveModel, Entity, Model};
+use crate::proxy::{backend::MachineSpec, routing};
 
+/// Machine names are given to their backend, e.g. as the name of a container
+#[must_use]
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_lowercase() || c.is_ascii_digit())
+        && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+}
+
+/// The name of a machine on its backend, it is stored as the name of the
+/// machine
+///
+/// The id of the app keeps machines of different apps apart, and away from
+/// the containers not managed by dedale
+#[must_use]
+pub fn backend_name(app_id: i32, name: &str) -> String {
+    format!("dedale-{app_id}-{name}")
+}
+
+#[async_trait::async_trait]
 impl ActiveModelBehavior for ActiveModel {
     // extend activemodel below (keep comment for generators)
+
+    async fn after_save<C>(model: Model, _db: &C, _insert: bool) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(model)
+    }
+
+    async fn after_delete<C>(self, _db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(self)
+    }
+}
+
+impl Model {
+    /// The name given to the machine, without the prefix of its backend name
+    #[must_use]
+    pub fn short_name(&self) -> &str {
+        self.name
+            .strip_prefix(&backend_name(self.app_id, ""))
+            .unwrap_or(&self.name)
+    }
+
+    /// finds a machine of an app by its name
+    ///
+    /// # Errors
+    ///
+    /// When could not find the machine or DB query error
+    pub async fn find_by_app_and_name(
+        db: &DatabaseConnection,
+        app_id: i32,
+        name: &str,
+    ) -> ModelResult<Self> {
+        let machine = machines::Entity::find()
+            .filter(machines::Column::AppId.eq(app_id))
+            .filter(machines::Column::Name.eq(backend_name(app_id, name)))
+            .one(db)
+            .await?;
+        machine.ok_or_else(|| ModelError::EntityNotFound)
+    }
+
+    /// Creates a machine of an app, it is not started
+    ///
+    /// # Errors
+    ///
+    /// When the app has a machine with the same name or DB query error
+    pub async fn create(
+        db: &DatabaseConnection,
+        app_id: i32,
+        name: &str,
+        spec: &MachineSpec,
+    ) -> ModelResult<Self> {
+        let txn = db.begin().await?;
+        let name = backend_name(app_id, name);
+
+        if machines::Entity::find()
+            .filter(machines::Column::Name.eq(&name))
+            .one(&txn)
+            .await?
+            .is_some()
+        {
+            return Err(ModelError::EntityAlreadyExists {});
+        }
+
+        let machine = ActiveModel {
+            name: ActiveValue::set(name),
+            app_id: ActiveValue::set(app_id),
+            internal_port: ActiveValue::set(i32::from(spec.internal_port)),
+            image: ActiveValue::set(spec.image.clone()),
+            command: ActiveValue::set(spec.command.clone()),
+            env: ActiveValue::set(Some(serde_json::json!(spec.env))),
+            ..Default::default()
+        }
+        .insert(&txn)
+        .await?;
+
+        txn.commit().await?;
+
+        Ok(machine)
+    }
+
+    /// lists the machines of an app
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_app(db: &DatabaseConnection, app_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(machines::Entity::find()
+            .filter(machines::Column::AppId.eq(app_id))
+            .order_by_asc(machines::Column::Name)
+            .all(db)
+            .await?)
+    }
 }
@@ -1,4 +1,8 @@
 pub mod _entities;
+pub mod acme_accounts;
 pub mod apps;
+pub mod certificates;
+pub mod domains;
 pub mod machines;
+pub mod secrets;
 pub mod users;
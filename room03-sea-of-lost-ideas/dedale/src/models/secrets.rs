@@ -0,0 +1,176 @@
+use std::collections::BTreeMap;
+
+use loco_rs::model::{ModelError, ModelResult};
+use sea_orm::{
+    entity::prelude::*, ActiveValue, IntoActiveModel, QueryOrder, TransactionTrait, TryIntoModel,
+};
+use tracing::warn;
+
+pub use super::_entities::secrets::{self, ActiveModel, Entity, Model};
+use crate::{
+    proxy::routing,
+    vault::{Vault, VaultError},
+};
+
+/// Secrets are environment variables, `[A-Za-z_][A-Za-z0-9_]*`
+#[must_use]
+pub fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+#[async_trait::async_trait]
+impl ActiveModelBehavior for ActiveModel {
+    // extend activemodel below (keep comment for generators)
+
+    async fn after_save<C>(model: Model, _db: &C, _insert: bool) -> Result<Model, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(model)
+    }
+
+    async fn after_delete<C>(self, _db: &C) -> Result<Self, DbErr>
+    where
+        C: ConnectionTrait,
+    {
+        routing::notify_changed();
+        Ok(self)
+    }
+}
+
+impl Model {
+    /// The value of the secret in clear
+    ///
+    /// # Errors
+    ///
+    /// When the value was not sealed by this vault
+    pub fn reveal(&self, vault: &Vault) -> Result<String, VaultError> {
+        vault.open(self.app_id, &self.name, &self.value)
+    }
+
+    /// lists the secrets of an app, values are sealed
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn list_by_app(db: &DatabaseConnection, app_id: i32) -> ModelResult<Vec<Self>> {
+        Ok(secrets::Entity::find()
+            .filter(secrets::Column::AppId.eq(app_id))
+            .order_by_asc(secrets::Column::Name)
+            .all(db)
+            .await?)
+    }
+
+    /// The secrets of an app as environment variables, those which cannot be
+    /// decrypted are skipped
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn env_of(
+        db: &DatabaseConnection,
+        vault: &Vault,
+        app_id: i32,
+    ) -> ModelResult<BTreeMap<String, String>> {
+        Ok(Self::list_by_app(db, app_id)
+            .await?
+            .iter()
+            .filter_map(|secret| match secret.reveal(vault) {
+                Ok(value) => Some((secret.name.clone(), value)),
+                Err(e) => {
+                    warn!("secret {} of app {}: {e}", secret.name, secret.app_id);
+                    None
+                }
+            })
+            .collect())
+    }
+
+    /// Sets secrets of an app, creating or replacing them
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn set_many(
+        db: &DatabaseConnection,
+        vault: &Vault,
+        app_id: i32,
+        values: &BTreeMap<String, String>,
+    ) -> ModelResult<Vec<Self>> {
+        let txn = db.begin().await?;
+
+        let mut secrets = Vec::new();
+        for (name, value) in values {
+            let existing = secrets::Entity::find()
+                .filter(secrets::Column::AppId.eq(app_id))
+                .filter(secrets::Column::Name.eq(name))
+                .one(&txn)
+                .await?;
+
+            let mut secret = existing.map_or_else(
+                || ActiveModel {
+                    app_id: ActiveValue::set(app_id),
+                    name: ActiveValue::set(name.clone()),
+                    ..Default::default()
+                },
+                |secret| ActiveModel {
+                    updated_at: ActiveValue::set(chrono::Utc::now().naive_utc()),
+                    ..secret.into_active_model()
+                },
+            );
+            secret.value = ActiveValue::set(vault.seal(app_id, name, value));
+            secret.digest = ActiveValue::set(vault.digest(app_id, name, value));
+            secrets.push(secret.save(&txn).await?.try_into_model()?);
+        }
+
+        txn.commit().await?;
+
+        Ok(secrets)
+    }
+
+    /// Computes again the digests which were not made by this vault (E.g. those
+    /// from before the digests were keyed), the secrets which cannot be
+    /// decrypted are left as is
+    ///
+    /// # Errors
+    ///
+    /// When DB query error
+    pub async fn refresh_digests(db: &DatabaseConnection, vault: &Vault) -> ModelResult<usize> {
+        let mut refreshed = 0;
+        for secret in secrets::Entity::find().all(db).await? {
+            let Ok(value) = secret.reveal(vault) else {
+                continue;
+            };
+            let digest = vault.digest(secret.app_id, &secret.name, &value);
+            if digest != secret.digest {
+                let mut secret = secret.into_active_model();
+                secret.digest = ActiveValue::set(digest);
+                secret.update(db).await?;
+                refreshed += 1;
+            }
+        }
+
+        Ok(refreshed)
+    }
+
+    /// Removes a secret of an app
+    ///
+    /// # Errors
+    ///
+    /// When could not find the secret or DB query error
+    pub async fn unset(db: &DatabaseConnection, app_id: i32, name: &str) -> ModelResult<()> {
+        let secret = secrets::Entity::find()
+            .filter(secrets::Column::AppId.eq(app_id))
+            .filter(secrets::Column::Name.eq(name))
+            .one(db)
+            .await?
+            .ok_or_else(|| ModelError::EntityNotFound)?;
+        secret.delete(db).await?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+//! Certificates of the apps issued with ACME, the HTTP-01 challenges are
+//! answered by the proxy itself
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock, RwLock},
+    time::Duration,
+};
+
+use chrono::Utc;
+use instant_acme::{
+    Account, AccountCredentials, AuthorizationStatus, ChallengeType, Identifier, NewAccount,
+    NewOrder, Order, OrderStatus,
+};
+use loco_rs::model::ModelError;
+use openssl::{
+    ec::{EcGroup, EcKey},
+    error::ErrorStack,
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    stack::Stack,
+    x509::{extension::SubjectAlternativeName, X509ReqBuilder},
+};
+use pingora::{server::ShutdownWatch, services::background::BackgroundService};
+use serde::Deserialize;
+use tokio::time::{sleep, Instant};
+use tracing::{info, warn};
+
+use super::{
+    routing::RoutingTable,
+    tls::{Certificate, CertificateError, Tls},
+};
+use crate::{
+    models::{acme_accounts, certificates},
+    vault::VaultError,
+};
+
+/// Where the ACME server fetches the key authorization of a token
+pub const CHALLENGE_PATH: &str = "/.well-known/acme-challenge/";
+
+/// Look for hostnames without a valid certificate this often
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+/// A hostname whose certificate could not be issued waits this long before
+/// another attempt
+const RETRY_AFTER_FAILURE: Duration = Duration::from_secs(3600);
+const ORDER_POLL_INTERVAL: Duration = Duration::from_secs(1);
+const ORDER_POLL_ATTEMPTS: u32 = 60;
+
+static CHALLENGES: OnceLock<Arc<Challenges>> = OnceLock::new();
+
+/// The pending challenges, shared by the proxy and the issuer
+pub fn challenges() -> Arc<Challenges> {
+    CHALLENGES.get_or_init(Arc::default).clone()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AcmeConfig {
+    /// e.g. `https://acme-v02.api.letsencrypt.org/directory`
+    pub directory_url: String,
+    /// Emails the ACME server may write to about the certificates
+    #[serde(default)]
+    pub contact: Vec<String>,
+    /// Certificates are renewed this many days before they expire
+    #[serde(default = "default_renew_before_days")]
+    pub renew_before_days: u32,
+}
+
+const fn default_renew_before_days() -> u32 {
+    30
+}
+
+/// The key authorizations of the HTTP-01 challenges, by token
+#[derive(Debug, Default)]
+pub struct Challenges {
+    by_token: RwLock<HashMap<String, String>>,
+}
+
+impl Challenges {
+    pub fn insert(&self, token: &str, key_authorization: &str) {
+        self.by_token
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(token.to_string(), key_authorization.to_string());
+    }
+
+    pub fn remove(&self, token: &str) {
+        self.by_token
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .remove(token);
+    }
+
+    #[must_use]
+    pub fn get(&self, token: &str) -> Option<String> {
+        self.by_token
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(token)
+            .cloned()
+    }
+}
+
+#[derive(Debug)]
+pub enum AcmeError {
+    Acme(instant_acme::Error),
+    /// The order did not complete
+    Order(String),
+    Certificate(CertificateError),
+    Credentials(serde_json::Error),
+    /// The stored credentials cannot be opened
+    Vault(VaultError),
+    Model(ModelError),
+}
+
+impl fmt::Display for AcmeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Acme(e) => write!(f, "ACME error: {e}"),
+            Self::Order(e) => write!(f, "order failed: {e}"),
+            Self::Certificate(e) => e.fmt(f),
+            Self::Credentials(e) => write!(f, "invalid ACME account credentials: {e}"),
+            Self::Vault(e) => write!(f, "ACME account credentials: {e}"),
+            Self::Model(e) => e.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for AcmeError {}
+
+impl From<instant_acme::Error> for AcmeError {
+    fn from(e: instant_acme::Error) -> Self {
+        Self::Acme(e)
+    }
+}
+
+impl From<CertificateError> for AcmeError {
+    fn from(e: CertificateError) -> Self {
+        Self::Certificate(e)
+    }
+}
+
+impl From<ErrorStack> for AcmeError {
+    fn from(e: ErrorStack) -> Self {
+        Self::Certificate(e.into())
+    }
+}
+
+impl From<serde_json::Error> for AcmeError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Credentials(e)
+    }
+}
+
+impl From<VaultError> for AcmeError {
+    fn from(e: VaultError) -> Self {
+        Self::Vault(e)
+    }
+}
+
+impl From<ModelError> for AcmeError {
+    fn from(e: ModelError) -> Self {
+        Self::Model(e)
+    }
+}
+
+/// PEM encoded, as stored in the database
+#[derive(Debug)]
+pub struct IssuedCertificate {
+    pub chain: String,
+    pub private_key: String,
+}
+
+/// An account on an ACME directory
+pub struct AcmeClient {
+    account: Account,
+    challenges: Arc<Challenges>,
+}
+
+impl fmt::Debug for AcmeClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AcmeClient")
+    }
+}
+
+impl AcmeClient {
+    /// Registers a new account, its credentials are needed to use it again
+    ///
+    /// # Errors
+    ///
+    /// When the directory cannot be reached or refuses the account
+    pub async fn register(
+        config: &AcmeConfig,
+        challenges: Arc<Challenges>,
+    ) -> Result<(Self, AccountCredentials), AcmeError> {
+        let contact = config
+            .contact
+            .iter()
+            .map(|email| format!("mailto:{email}"))
+            .collect::<Vec<_>>();
+        let (account, credentials) = Account::create(
+            &NewAccount {
+                contact: &contact.iter().map(String::as_str).collect::<Vec<_>>(),
+                terms_of_service_agreed: true,
+                only_return_existing: false,
+            },
+            &config.directory_url,
+            None,
+        )
+        .await?;
+
+        Ok((
+            Self {
+                account,
+                challenges,
+            },
+            credentials,
+        ))
+    }
+
+    /// # Errors
+    ///
+    /// When the directory cannot be reached
+    pub async fn from_credentials(
+        credentials: AccountCredentials,
+        challenges: Arc<Challenges>,
+    ) -> Result<Self, AcmeError> {
+        Ok(Self {
+            account: Account::from_credentials(credentials).await?,
+            challenges,
+        })
+    }
+
+    /// Orders a certificate for a hostname, which must reach the proxy for
+    /// the HTTP-01 challenge
+    ///
+    /// # Errors
+    ///
+    /// When the order is refused, or not completed in time
+    pub async fn issue(&self, hostname: &str) -> Result<IssuedCertificate, AcmeError> {
+        let mut order = self
+            .account
+            .new_order(&NewOrder {
+                identifiers: &[Identifier::Dns(hostname.to_string())],
+            })
+            .await?;
+
+        let mut tokens = Vec::new();
+        let authorized = self.authorize(&mut order, &mut tokens).await;
+        for token in &tokens {
+            self.challenges.remove(token);
+        }
+        authorized?;
+
+        let (private_key, request) = certificate_request(hostname)?;
+        order.finalize(&request).await?;
+        for _ in 0..ORDER_POLL_ATTEMPTS {
+            if let Some(chain) = order.certificate().await? {
+                return Ok(IssuedCertificate { chain, private_key });
+            }
+            sleep(ORDER_POLL_INTERVAL).await;
+        }
+
+        Err(AcmeError::Order(
+            "certificate not issued in time".to_string(),
+        ))
+    }
+
+    /// Answer the challenges of the order until it is ready to be finalized
+    async fn authorize(
+        &self,
+        order: &mut Order,
+        tokens: &mut Vec<String>,
+    ) -> Result<(), AcmeError> {
+        for authorization in order.authorizations().await? {
+            match authorization.status {
+                AuthorizationStatus::Valid => continue,
+                AuthorizationStatus::Pending => {}
+                status => {
+                    return Err(AcmeError::Order(format!("authorization is {status:?}")));
+                }
+            }
+
+            let challenge = authorization
+                .challenges
+                .iter()
+                .find(|challenge| challenge.r#type == ChallengeType::Http01)
+                .ok_or_else(|| AcmeError::Order("no HTTP-01 challenge".to_string()))?;
+            self.challenges.insert(
+                &challenge.token,
+                order.key_authorization(challenge).as_str(),
+            );
+            tokens.push(challenge.token.clone());
+            order.set_challenge_ready(&challenge.url).await?;
+        }
+
+        for _ in 0..ORDER_POLL_ATTEMPTS {
+            let state = order.refresh().await?;
+            match state.status {
+                OrderStatus::Ready => return Ok(()),
+                OrderStatus::Invalid => {
+                    return Err(AcmeError::Order(format!(
+                        "order is invalid: {:?}",
+                        state.error
+                    )));
+                }
+                _ => sleep(ORDER_POLL_INTERVAL).await,
+            }
+        }
+
+        Err(AcmeError::Order(
+            "challenges not validated in time".to_string(),
+        ))
+    }
+}
+
+/// A new private key and the DER encoded request of a certificate for a
+/// hostname
+fn certificate_request(hostname: &str) -> Result<(String, Vec<u8>), ErrorStack> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    let key = PKey::from_ec_key(EcKey::generate(&group)?)?;
+
+    let mut request = X509ReqBuilder::new()?;
+    request.set_pubkey(&key)?;
+    let mut names = SubjectAlternativeName::new();
+    names.dns(hostname);
+    let mut extensions = Stack::new()?;
+    extensions.push(names.build(&request.x509v3_context(None))?)?;
+    request.add_extensions(&extensions)?;
+    request.sign(&key, MessageDigest::sha256())?;
+
+    Ok((
+        String::from_utf8_lossy(&key.private_key_to_pem_pkcs8()?).into_owned(),
+        request.build().to_der()?,
+    ))
+}
+
+/// Issue the certificates of the hostnames of the apps, and renew them before
+/// they expire
+#[derive(Debug)]
+pub(super) struct CertificateIssuer {
+    pub(super) tls: Arc<Tls>,
+    pub(super) config: AcmeConfig,
+    pub(super) routes: Arc<RoutingTable>,
+    pub(super) challenges: Arc<Challenges>,
+}
+
+impl CertificateIssuer {
+    /// The account of the directory, registered on first use
+    async fn client(&self) -> Result<AcmeClient, AcmeError> {
+        let directory_url = &self.config.directory_url;
+        match acme_accounts::Model::find_by_directory_url(&self.tls.db, directory_url).await {
+            Ok(account) => {
+                AcmeClient::from_credentials(
+                    serde_json::from_str(&account.reveal_credentials(&self.tls.vault)?)?,
+                    self.challenges.clone(),
+                )
+                .await
+            }
+            Err(ModelError::EntityNotFound) => {
+                let (client, credentials) =
+                    AcmeClient::register(&self.config, self.challenges.clone()).await?;
+                acme_accounts::Model::create(
+                    &self.tls.db,
+                    &self.tls.vault,
+                    directory_url,
+                    &serde_json::to_string(&credentials)?,
+                )
+                .await?;
+                info!("ACME account registered on {directory_url}");
+
+                Ok(client)
+            }
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    async fn issue(&self, client: &AcmeClient, hostname: &str) -> Result<(), AcmeError> {
+        let issued = client.issue(hostname).await?;
+        let certificate = Certificate::from_pem(&issued.chain, &issued.private_key)?;
+        certificates::Model::upsert(
+            &self.tls.db,
+            &self.tls.vault,
+            hostname,
+            &issued.chain,
+            &issued.private_key,
+            certificate.not_after,
+        )
+        .await?;
+        self.tls.certificates.insert(hostname, certificate);
+
+        Ok(())
+    }
+
+    async fn issue_missing(
+        &self,
+        client: &mut Option<AcmeClient>,
+        failures: &mut HashMap<String, Instant>,
+    ) {
+        let renew_at = Utc::now().naive_utc()
+            + chrono::Duration::days(i64::from(self.config.renew_before_days));
+        let hostnames = self
+            .tls
+            .certificates
+            .to_renew(&self.routes.hostnames(), renew_at);
+
+        for hostname in hostnames {
+            if failures
+                .get(&hostname)
+                .is_some_and(|failed_at| failed_at.elapsed() < RETRY_AFTER_FAILURE)
+            {
+                continue;
+            }
+
+            if client.is_none() {
+                match self.client().await {
+                    Ok(connected) => *client = Some(connected),
+                    Err(e) => {
+                        warn!("could not use the ACME directory: {e}");
+                        return;
+                    }
+                }
+            }
+            let Some(connected) = client.as_ref() else {
+                return;
+            };
+
+            match self.issue(connected, &hostname).await {
+                Ok(()) => {
+                    failures.remove(&hostname);
+                    info!("certificate issued for {hostname}");
+                }
+                Err(e) => {
+                    warn!("could not issue a certificate for {hostname}: {e}");
+                    failures.insert(hostname, Instant::now());
+                }
+            }
+        }
+    }
+}
+
+#[async_trait::async_trait]
+impl BackgroundService for CertificateIssuer {
+    async fn start(&self, mut shutdown: ShutdownWatch) {
+        info!("certificate issuer starting");
+
+        let mut client = None;
+        let mut failures = HashMap::new();
+        while !*shutdown.borrow() {
+            self.issue_missing(&mut client, &mut failures).await;
+
+            // Either the server is shutting down or the check interval elapsed
+            let _ = tokio::time::timeout(CHECK_INTERVAL, shutdown.changed()).await;
+        }
+
+        info!("certificate issuer shutting down");
+    }
+}
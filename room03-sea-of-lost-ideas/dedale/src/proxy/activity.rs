@@ -0,0 +1,112 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, OnceLock},
+};
+
+use tokio::{sync::RwLock, time::Instant};
+
+use super::routing::AppRoute;
+
+static ACTIVITY: OnceLock<Arc<RwLock<Activity>>> = OnceLock::new();
+
+/// The activity seen by the proxy, shared with the machines API so that
+/// machines stopped through it are started again by the next request
+pub fn shared() -> Arc<RwLock<Activity>> {
+    ACTIVITY.get_or_init(Arc::default).clone()
+}
+
+/// The traffic of an app as seen by the proxy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AppActivity {
+    /// When the last request started or finished
+    pub last_request: Instant,
+    /// Requests not finished yet
+    pub in_flight: u64,
+}
+
+/// What the proxy knows about the traffic of the apps and their started
+/// machines
+#[derive(Debug, Default)]
+pub struct Activity {
+    started: HashSet<String>,
+    /// Machines asked to stop, not stopped yet by their backend
+    stopping: HashSet<String>,
+    apps: HashMap<String, AppActivity>,
+}
+
+impl Activity {
+    #[must_use]
+    pub const fn started(&self) -> &HashSet<String> {
+        &self.started
+    }
+
+    #[must_use]
+    pub fn app(&self, app: &str) -> Option<AppActivity> {
+        self.apps.get(app).copied()
+    }
+
+    /// The apps that received requests since dedale started
+    pub fn apps(&self) -> impl Iterator<Item = &str> {
+        self.apps.keys().map(String::as_str)
+    }
+
+    #[must_use]
+    pub fn is_stopping(&self, machine: &str) -> bool {
+        self.stopping.contains(machine)
+    }
+
+    pub fn machine_started(&mut self, machine: &str) {
+        self.started.insert(machine.to_string());
+    }
+
+    /// The machine does not get new requests, it is started again once
+    /// stopped
+    pub fn machine_stopping(&mut self, machine: &str) {
+        self.started.remove(machine);
+        self.stopping.insert(machine.to_string());
+    }
+
+    /// The backend stopped the machine
+    pub fn machine_stopped(&mut self, machine: &str) {
+        self.started.remove(machine);
+        self.stopping.remove(machine);
+    }
+
+    pub fn request_started(&mut self, app: &str, now: Instant) {
+        let activity = self.apps.entry(app.to_string()).or_insert(AppActivity {
+            last_request: now,
+            in_flight: 0,
+        });
+        activity.last_request = now;
+        activity.in_flight += 1;
+    }
+
+    pub fn request_finished(&mut self, app: &str, now: Instant) {
+        if let Some(activity) = self.apps.get_mut(app) {
+            activity.last_request = now;
+            activity.in_flight = activity.in_flight.saturating_sub(1);
+        }
+    }
+
+    /// The started machines of an app that can be stopped, the app has no
+    /// request in flight and has been idle for its idle timeout
+    #[must_use]
+    pub fn idle_machines(&self, route: &AppRoute, now: Instant) -> Vec<String> {
+        let scaling = &route.scaling;
+        let idle = self.apps.get(&route.app).is_some_and(|activity| {
+            activity.in_flight == 0
+                && now.saturating_duration_since(activity.last_request) >= scaling.idle_timeout
+        });
+        if !scaling.auto_stop_machines || !idle {
+            return Vec::new();
+        }
+
+        route
+            .upstreams
+            .iter()
+            .filter(|upstream| self.started.contains(&upstream.machine))
+            .skip(scaling.min_machines_running)
+            .map(|upstream| upstream.machine.clone())
+            .collect()
+    }
+}
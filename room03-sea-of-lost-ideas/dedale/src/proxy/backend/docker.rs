@@ -1,11 +1,21 @@
+use std::{collections::HashMap, pin::pin};
+
 use bollard::{
-    container::{StartContainerOptions, StopContainerOptions},
-    secret::ContainerStateStatusEnum,
+    container::{
+        Config, CreateContainerOptions, LogOutput, LogsOptions, RemoveContainerOptions,
+        StartContainerOptions, StopContainerOptions,
+    },
+    image::CreateImageOptions,
+    secret::{ContainerStateStatusEnum, HostConfig, PortBinding},
     Docker,
 };
+use chrono::Utc;
 use pingora::ErrorType::InternalError;
+use tokio_stream::StreamExt;
+use tracing::warn;
 
-use super::{BackendState, ProxyServiceBackend};
+use super::{BackendState, MachineSpec, ProxyServiceBackend};
+use crate::proxy::logs::{self, LogLine, LogSource};
 
 pub struct DockerServiceBackend {
     docker: Docker,
@@ -42,11 +52,90 @@ impl ProxyServiceBackend for DockerServiceBackend {
             ))
     }
 
-    async fn start(&mut self, service: &str) -> pingora::Result<String> {
+    /// Create the container from the image of the machine when it does not
+    /// exist yet, the image is pulled first when missing
+    async fn create(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        if self.status(service).await? != BackendState::NotFound {
+            return Ok(service.to_string());
+        }
+        let Some(image) = &spec.image else {
+            return Err(pingora::Error::explain(
+                InternalError,
+                format!("machine {service} has no image"),
+            ));
+        };
+
+        if self.docker.inspect_image(image).await.is_err() {
+            let mut pull = pin!(self.docker.create_image(
+                Some(CreateImageOptions {
+                    from_image: image.as_str(),
+                    ..CreateImageOptions::default()
+                }),
+                None,
+                None,
+            ));
+            while let Some(progress) = pull.next().await {
+                progress.map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?;
+            }
+        }
+
+        let port = format!("{}/tcp", spec.internal_port);
+        let env = spec
+            .env
+            .iter()
+            .chain([(&"PORT".to_string(), &spec.internal_port.to_string())])
+            .map(|(key, value)| format!("{key}={value}"))
+            .collect();
+        let config = Config {
+            image: Some(image.clone()),
+            env: Some(env),
+            exposed_ports: Some(HashMap::from([(port.clone(), HashMap::new())])),
+            host_config: Some(HostConfig {
+                port_bindings: Some(HashMap::from([(
+                    port,
+                    Some(vec![PortBinding {
+                        host_ip: Some("127.0.0.1".to_string()),
+                        host_port: Some(spec.internal_port.to_string()),
+                    }]),
+                )])),
+                ..HostConfig::default()
+            }),
+            ..Config::default()
+        };
+        self.docker
+            .create_container(
+                Some(CreateContainerOptions {
+                    name: service,
+                    platform: None,
+                }),
+                config,
+            )
+            .await
+            .map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?;
+
+        Ok(service.to_string())
+    }
+
+    /// A stopped container is recreated, so that it runs with the current
+    /// environment and secrets of the machine
+    async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        match self.status(service).await? {
+            BackendState::Started => return Ok(service.to_string()),
+            BackendState::Stopped => self
+                .docker
+                .remove_container(service, None::<RemoveContainerOptions>)
+                .await
+                .map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?,
+            BackendState::NotFound => {}
+        }
+
+        let since = Utc::now().timestamp();
+        self.create(service, spec).await?;
         self.docker
             .start_container(service, None::<StartContainerOptions<String>>)
             .await
             .map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?;
+        self.follow_logs(service, &spec.app, since);
         Ok(service.to_string())
     }
 
@@ -55,6 +144,55 @@ impl ProxyServiceBackend for DockerServiceBackend {
             .stop_container(service, None::<StopContainerOptions>)
             .await
             .map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?;
+        logs::unfollow_machine(service);
+        Ok(service.to_string())
+    }
+
+    async fn destroy(&mut self, service: &str) -> pingora::Result<String> {
+        if self.status(service).await? != BackendState::NotFound {
+            self.docker
+                .remove_container(service, None::<RemoveContainerOptions>)
+                .await
+                .map_err(|e| pingora::Error::explain(InternalError, e.to_string()))?;
+        }
         Ok(service.to_string())
     }
 }
+
+impl DockerServiceBackend {
+    /// Log what the container writes from `since` (unix time) under its app
+    fn follow_logs(&self, service: &str, app: &str, since: i64) {
+        let docker = self.docker.clone();
+        let (service, app) = (service.to_string(), app.to_string());
+        logs::follow_machine(&service.clone(), async move {
+            let logs = logs::shared();
+            let mut output = pin!(docker.logs(
+                &service,
+                Some(LogsOptions::<String> {
+                    follow: true,
+                    stdout: true,
+                    stderr: true,
+                    since,
+                    ..LogsOptions::default()
+                }),
+            ));
+
+            while let Some(output) = output.next().await {
+                let (source, message) = match output {
+                    Ok(LogOutput::StdOut { message } | LogOutput::Console { message }) => {
+                        (LogSource::Stdout, message)
+                    }
+                    Ok(LogOutput::StdErr { message }) => (LogSource::Stderr, message),
+                    Ok(LogOutput::StdIn { .. }) => continue,
+                    Err(e) => {
+                        warn!("could not follow the logs of {service}: {e}");
+                        return;
+                    }
+                };
+                for line in String::from_utf8_lossy(&message).lines() {
+                    logs.push(LogLine::new(&app, Some(&service), source, line.to_string()));
+                }
+            }
+        });
+    }
+}
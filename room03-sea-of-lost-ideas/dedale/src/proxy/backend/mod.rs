@@ -1,17 +1,86 @@
 #[cfg(feature = "backend_docker")]
 mod docker;
-#[cfg(feature = "backend_docker")]
-pub(super) use docker::*;
+#[cfg(feature = "backend_nspawn")]
+mod nspawn;
+#[cfg(feature = "backend_podman")]
+mod podman;
+#[cfg(feature = "backend_process")]
+mod process;
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+use pingora::ErrorType::InternalError;
 use tracing::warn;
 
+#[cfg(feature = "backend_docker")]
+pub use self::docker::*;
+#[cfg(feature = "backend_nspawn")]
+pub use self::nspawn::*;
+#[cfg(feature = "backend_podman")]
+pub use self::podman::*;
+#[cfg(feature = "backend_process")]
+pub use self::process::*;
+
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
-pub(super) enum BackendState {
+pub enum BackendState {
     Started,
     Stopped,
     NotFound,
 }
 
-pub(super) trait ProxyServiceBackend {
+/// Where the machines of an app run, selected per app
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum BackendKind {
+    #[default]
+    Docker,
+    /// A supervised local process
+    Process,
+    Podman,
+    /// A container managed by `machinectl`
+    Nspawn,
+}
+
+impl fmt::Display for BackendKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Docker => "docker",
+            Self::Process => "process",
+            Self::Podman => "podman",
+            Self::Nspawn => "systemd-nspawn",
+        })
+    }
+}
+
+impl FromStr for BackendKind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "docker" => Ok(Self::Docker),
+            "process" => Ok(Self::Process),
+            "podman" => Ok(Self::Podman),
+            "systemd-nspawn" | "nspawn" => Ok(Self::Nspawn),
+            _ => Err(format!("unknown backend {s}")),
+        }
+    }
+}
+
+/// What a backend needs to run a machine, besides its name
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MachineSpec {
+    /// The app of the machine, its output is logged under it
+    pub app: String,
+    /// The image of the container (unused by the process backend)
+    pub image: Option<String>,
+    /// The command run by the process backend, through `sh -c`
+    pub command: Option<String>,
+    pub env: BTreeMap<String, String>,
+    /// Given to the process backend as `PORT`
+    pub internal_port: u16,
+}
+
+// only used through `AnyServiceBackend`, the futures are known to be `Send`
+#[allow(async_fn_in_trait)]
+pub trait ProxyServiceBackend {
     const IDENT: &'static str;
 
     async fn new_backend() -> pingora::Result<Self>
@@ -27,16 +96,219 @@ pub(super) trait ProxyServiceBackend {
         );
         Ok(BackendState::NotFound)
     }
-    /// Should start the service
+    /// Should prepare the service so that it can be started, e.g. create its
+    /// container
     /// must be callable multiple times without error
     #[allow(clippy::unused_async)]
-    async fn start(&mut self, service: &str) -> pingora::Result<String> {
-        todo!("start {service} of {} is not implemented", Self::IDENT);
+    async fn create(&mut self, service: &str, _spec: &MachineSpec) -> pingora::Result<String> {
+        Ok(service.to_string())
     }
+    /// Should start the service
+    /// must be callable multiple times without error
+    async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String>;
     /// Should stop the service
     /// must be callable multiple times without error
+    async fn stop(&mut self, service: &str) -> pingora::Result<String>;
+    /// Should remove what `create` prepared, the service is stopped first
+    /// must be callable multiple times without error
     #[allow(clippy::unused_async)]
-    async fn stop(&mut self, service: &str) -> pingora::Result<String> {
-        todo!("stop {service} of {} is not implemented", Self::IDENT);
+    async fn destroy(&mut self, service: &str) -> pingora::Result<String> {
+        Ok(service.to_string())
+    }
+}
+
+/// One of the backends compiled in
+pub enum AnyServiceBackend {
+    #[cfg(feature = "backend_docker")]
+    Docker(DockerServiceBackend),
+    #[cfg(feature = "backend_process")]
+    Process(ProcessServiceBackend),
+    #[cfg(feature = "backend_podman")]
+    Podman(PodmanServiceBackend),
+    #[cfg(feature = "backend_nspawn")]
+    Nspawn(NspawnServiceBackend),
+}
+
+impl AnyServiceBackend {
+    /// # Errors
+    ///
+    /// When the backend is not compiled in or cannot be reached
+    pub async fn new_backend(kind: BackendKind) -> pingora::Result<Self> {
+        match kind {
+            #[cfg(feature = "backend_docker")]
+            BackendKind::Docker => Ok(Self::Docker(DockerServiceBackend::new_backend().await?)),
+            #[cfg(feature = "backend_process")]
+            BackendKind::Process => Ok(Self::Process(ProcessServiceBackend::new_backend().await?)),
+            #[cfg(feature = "backend_podman")]
+            BackendKind::Podman => Ok(Self::Podman(PodmanServiceBackend::new_backend().await?)),
+            #[cfg(feature = "backend_nspawn")]
+            BackendKind::Nspawn => Ok(Self::Nspawn(NspawnServiceBackend::new_backend().await?)),
+            #[allow(unreachable_patterns)]
+            kind => Err(pingora::Error::explain(
+                InternalError,
+                format!("backend {kind} is not enabled"),
+            )),
+        }
+    }
+
+    /// # Errors
+    ///
+    /// When the backend cannot be reached
+    pub async fn status(&mut self, service: &str) -> pingora::Result<BackendState> {
+        match self {
+            #[cfg(feature = "backend_docker")]
+            Self::Docker(backend) => backend.status(service).await,
+            #[cfg(feature = "backend_process")]
+            Self::Process(backend) => backend.status(service).await,
+            #[cfg(feature = "backend_podman")]
+            Self::Podman(backend) => backend.status(service).await,
+            #[cfg(feature = "backend_nspawn")]
+            Self::Nspawn(backend) => backend.status(service).await,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// When the machine cannot be created
+    pub async fn create(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        match self {
+            #[cfg(feature = "backend_docker")]
+            Self::Docker(backend) => backend.create(service, spec).await,
+            #[cfg(feature = "backend_process")]
+            Self::Process(backend) => backend.create(service, spec).await,
+            #[cfg(feature = "backend_podman")]
+            Self::Podman(backend) => backend.create(service, spec).await,
+            #[cfg(feature = "backend_nspawn")]
+            Self::Nspawn(backend) => backend.create(service, spec).await,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// When the machine cannot be started
+    pub async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        match self {
+            #[cfg(feature = "backend_docker")]
+            Self::Docker(backend) => backend.start(service, spec).await,
+            #[cfg(feature = "backend_process")]
+            Self::Process(backend) => backend.start(service, spec).await,
+            #[cfg(feature = "backend_podman")]
+            Self::Podman(backend) => backend.start(service, spec).await,
+            #[cfg(feature = "backend_nspawn")]
+            Self::Nspawn(backend) => backend.start(service, spec).await,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// When the machine cannot be stopped
+    pub async fn stop(&mut self, service: &str) -> pingora::Result<String> {
+        match self {
+            #[cfg(feature = "backend_docker")]
+            Self::Docker(backend) => backend.stop(service).await,
+            #[cfg(feature = "backend_process")]
+            Self::Process(backend) => backend.stop(service).await,
+            #[cfg(feature = "backend_podman")]
+            Self::Podman(backend) => backend.stop(service).await,
+            #[cfg(feature = "backend_nspawn")]
+            Self::Nspawn(backend) => backend.stop(service).await,
+        }
+    }
+
+    /// # Errors
+    ///
+    /// When the machine cannot be destroyed
+    pub async fn destroy(&mut self, service: &str) -> pingora::Result<String> {
+        match self {
+            #[cfg(feature = "backend_docker")]
+            Self::Docker(backend) => backend.destroy(service).await,
+            #[cfg(feature = "backend_process")]
+            Self::Process(backend) => backend.destroy(service).await,
+            #[cfg(feature = "backend_podman")]
+            Self::Podman(backend) => backend.destroy(service).await,
+            #[cfg(feature = "backend_nspawn")]
+            Self::Nspawn(backend) => backend.destroy(service).await,
+        }
+    }
+}
+
+/// Log the output of a process under the app of its machine
+#[cfg(any(
+    feature = "backend_process",
+    feature = "backend_podman",
+    feature = "backend_nspawn"
+))]
+fn capture_output(child: &mut tokio::process::Child, service: &str, app: &str) {
+    use super::logs::{self, LogSource};
+
+    let logs = logs::shared();
+    if let Some(stdout) = child.stdout.take() {
+        logs::capture(
+            logs.clone(),
+            app.to_string(),
+            service.to_string(),
+            LogSource::Stdout,
+            stdout,
+        );
+    }
+    if let Some(stderr) = child.stderr.take() {
+        logs::capture(
+            logs,
+            app.to_string(),
+            service.to_string(),
+            LogSource::Stderr,
+            stderr,
+        );
+    }
+}
+
+/// Follow the logs of a machine with a command of its container manager,
+/// until it is stopped
+#[cfg(any(feature = "backend_podman", feature = "backend_nspawn"))]
+fn follow_output(program: &'static str, args: Vec<String>, service: &str, app: &str) {
+    use std::process::Stdio;
+
+    let (service, app) = (service.to_string(), app.to_string());
+    super::logs::follow_machine(&service.clone(), async move {
+        let child = tokio::process::Command::new(program)
+            .args(&args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            // aborted when the machine is stopped
+            .kill_on_drop(true)
+            .spawn();
+        match child {
+            Ok(mut child) => {
+                capture_output(&mut child, &service, &app);
+                if let Err(e) = child.wait().await {
+                    warn!("{program} failed to follow the logs of {service}: {e}");
+                }
+            }
+            Err(e) => warn!("could not follow the logs of {service}: {program}: {e}"),
+        }
+    });
+}
+
+/// Run a command of a container manager, its trimmed stdout on success
+#[cfg(any(feature = "backend_podman", feature = "backend_nspawn"))]
+async fn run(program: &str, args: &[&str]) -> pingora::Result<String> {
+    let output = tokio::process::Command::new(program)
+        .args(args)
+        .output()
+        .await
+        .map_err(|e| pingora::Error::explain(InternalError, format!("{program}: {e}")))?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    } else {
+        Err(pingora::Error::explain(
+            InternalError,
+            format!(
+                "{program} {}: {}",
+                args.join(" "),
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ))
     }
 }
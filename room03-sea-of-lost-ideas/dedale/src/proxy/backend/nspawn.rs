@@ -0,0 +1,97 @@
+use chrono::Utc;
+use pingora::ErrorType::InternalError;
+
+use super::{follow_output, run, BackendState, MachineSpec, ProxyServiceBackend};
+use crate::proxy::logs;
+
+/// Containers run by `systemd-nspawn` through `machinectl`, the image of a
+/// machine is `/var/lib/machines/<machine>`
+pub struct NspawnServiceBackend;
+
+impl ProxyServiceBackend for NspawnServiceBackend {
+    const IDENT: &'static str = "systemd-nspawn";
+
+    async fn new_backend() -> pingora::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn status(&mut self, service: &str) -> pingora::Result<BackendState> {
+        // machined only knows the running machines
+        if run(
+            "machinectl",
+            &["show", service, "--property=State", "--value"],
+        )
+        .await
+        .is_ok_and(|state| state == "running")
+        {
+            return Ok(BackendState::Started);
+        }
+
+        Ok(
+            if run("machinectl", &["image-status", service]).await.is_ok() {
+                BackendState::Stopped
+            } else {
+                BackendState::NotFound
+            },
+        )
+    }
+
+    /// The image of the machine is not created by dedale, it must exist
+    async fn create(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        no_environment(service, spec)?;
+        if self.status(service).await? == BackendState::NotFound {
+            return Err(pingora::Error::explain(
+                InternalError,
+                format!("no image /var/lib/machines/{service}"),
+            ));
+        }
+        Ok(service.to_string())
+    }
+
+    /// Its logs are read from its journal, which must be linked to the one of
+    /// the host
+    async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        no_environment(service, spec)?;
+        let since = Utc::now();
+        if self.status(service).await? != BackendState::Started {
+            run("machinectl", &["start", service]).await?;
+        }
+        follow_output(
+            "journalctl",
+            vec![
+                "--machine".to_string(),
+                service.to_string(),
+                "--follow".to_string(),
+                "--output".to_string(),
+                "cat".to_string(),
+                "--since".to_string(),
+                format!("@{}", since.timestamp()),
+            ],
+            service,
+            &spec.app,
+        );
+        Ok(service.to_string())
+    }
+
+    async fn stop(&mut self, service: &str) -> pingora::Result<String> {
+        if self.status(service).await? == BackendState::Started {
+            run("machinectl", &["poweroff", service]).await?;
+        }
+        logs::unfollow_machine(service);
+        Ok(service.to_string())
+    }
+}
+
+/// The environment of the machine is set in its image, `machinectl` cannot
+/// pass it, refuse the machines which would run without their variables or
+/// secrets
+fn no_environment(service: &str, spec: &MachineSpec) -> pingora::Result<()> {
+    if spec.env.is_empty() {
+        return Ok(());
+    }
+    let names = spec.env.keys().cloned().collect::<Vec<_>>().join(", ");
+    Err(pingora::Error::explain(
+        InternalError,
+        format!("machine {service} cannot be given {names}, set them in its image"),
+    ))
+}
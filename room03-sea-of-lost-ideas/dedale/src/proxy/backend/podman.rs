@@ -0,0 +1,164 @@
+use std::{
+    fs::{self, OpenOptions},
+    io::Write,
+    os::unix::fs::OpenOptionsExt,
+    path::PathBuf,
+};
+
+use chrono::Utc;
+use pingora::ErrorType::InternalError;
+use uuid::Uuid;
+
+use super::{follow_output, run, BackendState, MachineSpec, ProxyServiceBackend};
+use crate::proxy::logs;
+
+/// Containers run by podman, without a daemon
+pub struct PodmanServiceBackend;
+
+impl ProxyServiceBackend for PodmanServiceBackend {
+    const IDENT: &'static str = "podman";
+
+    async fn new_backend() -> pingora::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn status(&mut self, service: &str) -> pingora::Result<BackendState> {
+        Ok(
+            match run(
+                "podman",
+                &["inspect", "--format", "{{.State.Status}}", service],
+            )
+            .await
+            .as_deref()
+            {
+                Ok("running") => BackendState::Started,
+                Ok("created" | "configured" | "exited" | "paused" | "stopped") => {
+                    BackendState::Stopped
+                }
+                _ => BackendState::NotFound,
+            },
+        )
+    }
+
+    /// Create the container from the image of the machine when it does not
+    /// exist yet
+    async fn create(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        if self.status(service).await? != BackendState::NotFound {
+            return Ok(service.to_string());
+        }
+        let Some(image) = &spec.image else {
+            return Err(pingora::Error::explain(
+                InternalError,
+                format!("machine {service} has no image"),
+            ));
+        };
+
+        let publish = format!("127.0.0.1:{0}:{0}", spec.internal_port);
+        let env_file = EnvFile::write(spec)?;
+        let env_file_path = env_file.0.to_string_lossy();
+        // the image is never taken for an option
+        let args = [
+            "create",
+            "--name",
+            service,
+            "--publish",
+            &publish,
+            "--env-file",
+            &env_file_path,
+            "--",
+            image,
+        ];
+        run("podman", &args).await?;
+
+        Ok(service.to_string())
+    }
+
+    /// A stopped container is recreated, so that it runs with the current
+    /// environment and secrets of the machine
+    async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        match self.status(service).await? {
+            BackendState::Started => return Ok(service.to_string()),
+            BackendState::Stopped => {
+                run("podman", &["rm", service]).await?;
+            }
+            BackendState::NotFound => {}
+        }
+
+        let since = Utc::now();
+        self.create(service, spec).await?;
+        run("podman", &["start", service]).await?;
+        follow_output(
+            "podman",
+            vec![
+                "logs".to_string(),
+                "--follow".to_string(),
+                "--since".to_string(),
+                since.to_rfc3339(),
+                service.to_string(),
+            ],
+            service,
+            &spec.app,
+        );
+
+        Ok(service.to_string())
+    }
+
+    async fn stop(&mut self, service: &str) -> pingora::Result<String> {
+        run("podman", &["stop", "--ignore", service]).await?;
+        logs::unfollow_machine(service);
+        Ok(service.to_string())
+    }
+
+    async fn destroy(&mut self, service: &str) -> pingora::Result<String> {
+        run("podman", &["rm", "--ignore", service]).await?;
+        Ok(service.to_string())
+    }
+}
+
+/// The environment of a machine given to `podman create`, in a file only
+/// readable by dedale so that its secrets stay out of the arguments of podman,
+/// removed when dropped
+struct EnvFile(PathBuf);
+
+impl EnvFile {
+    fn write(spec: &MachineSpec) -> pingora::Result<Self> {
+        // podman reads one variable per line, without quoting
+        if let Some(key) = spec.env.iter().find_map(|(key, value)| {
+            (key.contains(['=', '\n']) || value.contains('\n')).then_some(key)
+        }) {
+            return Err(pingora::Error::explain(
+                InternalError,
+                format!("{key} cannot be given to podman: multiline value or invalid name"),
+            ));
+        }
+        let content = spec
+            .env
+            .iter()
+            .chain([(&"PORT".to_string(), &spec.internal_port.to_string())])
+            .map(|(key, value)| format!("{key}={value}\n"))
+            .collect::<Vec<_>>()
+            .concat();
+
+        let env_file = Self(std::env::temp_dir().join(format!("dedale-{}.env", Uuid::new_v4())));
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .mode(0o600)
+            .open(&env_file.0)
+            .and_then(|mut file| file.write_all(content.as_bytes()))
+            .map_err(|e| {
+                pingora::Error::explain(
+                    InternalError,
+                    format!("cannot write {}: {e}", env_file.0.display()),
+                )
+            })?;
+
+        Ok(env_file)
+    }
+}
+
+impl Drop for EnvFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.0).ok();
+    }
+}
@@ -0,0 +1,174 @@
+use std::{
+    collections::HashMap,
+    process::Stdio,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, OnceLock,
+    },
+    time::Duration,
+};
+
+use pingora::ErrorType::InternalError;
+use tokio::{process::Command, sync::oneshot, task::JoinHandle, time::sleep};
+use tracing::{debug, info, warn};
+
+use super::{capture_output, BackendState, MachineSpec, ProxyServiceBackend};
+
+/// How long a process that exited waits before being restarted
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+/// The only variable given to processes besides their own, the environment of
+/// dedale holds its keys
+const PATH: &str = "/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin";
+
+/// The processes started by dedale, by machine
+static SUPERVISORS: OnceLock<Mutex<HashMap<String, Supervisor>>> = OnceLock::new();
+
+fn supervisors() -> &'static Mutex<HashMap<String, Supervisor>> {
+    SUPERVISORS.get_or_init(Mutex::default)
+}
+
+/// The task restarting the process of a machine until it is stopped
+struct Supervisor {
+    running: Arc<AtomicBool>,
+    stop: oneshot::Sender<()>,
+    task: JoinHandle<()>,
+}
+
+/// Machines run as local processes supervised by dedale, they are killed
+/// with it
+pub struct ProcessServiceBackend;
+
+impl ProxyServiceBackend for ProcessServiceBackend {
+    const IDENT: &'static str = "process";
+
+    async fn new_backend() -> pingora::Result<Self> {
+        Ok(Self)
+    }
+
+    async fn status(&mut self, service: &str) -> pingora::Result<BackendState> {
+        Ok(
+            match supervisors()
+                .lock()
+                .expect("process supervisors poisoned")
+                .get(service)
+            {
+                Some(supervisor) if supervisor.running.load(Ordering::SeqCst) => {
+                    BackendState::Started
+                }
+                _ => BackendState::Stopped,
+            },
+        )
+    }
+
+    async fn start(&mut self, service: &str, spec: &MachineSpec) -> pingora::Result<String> {
+        let Some(command) = &spec.command else {
+            return Err(pingora::Error::explain(
+                InternalError,
+                format!("machine {service} has no command"),
+            ));
+        };
+
+        let mut supervisors = supervisors().lock().expect("process supervisors poisoned");
+        if supervisors
+            .get(service)
+            .is_some_and(|supervisor| !supervisor.task.is_finished())
+        {
+            debug!("process of {service} already supervised");
+            return Ok(service.to_string());
+        }
+
+        let running = Arc::new(AtomicBool::new(false));
+        let (stop, stopped) = oneshot::channel();
+        let task = tokio::spawn(supervise(
+            service.to_string(),
+            command.clone(),
+            spec.clone(),
+            running.clone(),
+            stopped,
+        ));
+        supervisors.insert(
+            service.to_string(),
+            Supervisor {
+                running,
+                stop,
+                task,
+            },
+        );
+        drop(supervisors);
+
+        Ok(service.to_string())
+    }
+
+    async fn stop(&mut self, service: &str) -> pingora::Result<String> {
+        let supervisor = supervisors()
+            .lock()
+            .expect("process supervisors poisoned")
+            .remove(service);
+
+        if let Some(supervisor) = supervisor {
+            // the task may already be gone
+            supervisor.stop.send(()).ok();
+            if let Err(e) = supervisor.task.await {
+                warn!("supervisor of {service} failed: {e}");
+            }
+        }
+
+        Ok(service.to_string())
+    }
+}
+
+/// Run the command of a machine, restarting it when it exits, until stopped
+async fn supervise(
+    service: String,
+    command: String,
+    spec: MachineSpec,
+    running: Arc<AtomicBool>,
+    mut stopped: oneshot::Receiver<()>,
+) {
+    loop {
+        let child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec {command}"))
+            .env_clear()
+            .env("PATH", PATH)
+            .envs(&spec.env)
+            .env("PORT", spec.internal_port.to_string())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .kill_on_drop(true)
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                warn!("could not run process of {service}: {e}");
+                return;
+            }
+        };
+        capture_output(&mut child, &service, &spec.app);
+        running.store(true, Ordering::SeqCst);
+        info!("process of {service} started");
+
+        tokio::select! {
+            status = child.wait() => {
+                running.store(false, Ordering::SeqCst);
+                match status {
+                    Ok(status) => warn!("process of {service} exited with {status}"),
+                    Err(e) => warn!("process of {service} failed: {e}"),
+                }
+            }
+            _ = &mut stopped => {
+                if let Err(e) = child.kill().await {
+                    warn!("could not kill process of {service}: {e}");
+                }
+                running.store(false, Ordering::SeqCst);
+                info!("process of {service} stopped");
+                return;
+            }
+        }
+
+        tokio::select! {
+            () = sleep(RESTART_DELAY) => debug!("restarting process of {service}"),
+            _ = &mut stopped => return,
+        }
+    }
+}
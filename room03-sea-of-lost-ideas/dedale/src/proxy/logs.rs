@@ -0,0 +1,257 @@
+//! Recent output of the machines of the apps and the requests proxied to
+//! them, kept in memory
+
+use std::{
+    collections::{HashMap, VecDeque},
+    fmt,
+    future::Future,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tokio::{
+    io::{AsyncBufReadExt, AsyncRead, BufReader},
+    sync::broadcast,
+    task::JoinHandle,
+};
+use tokio_stream::{
+    wrappers::{errors::BroadcastStreamRecvError, BroadcastStream},
+    Stream, StreamExt,
+};
+use tracing::warn;
+
+/// Lines kept per app, the oldest ones are dropped first
+pub const MAX_LINES_PER_APP: usize = 1000;
+/// Lines a follower can lag behind before missing some
+pub const FOLLOW_BUFFER: usize = 1024;
+
+static LOGS: OnceLock<Arc<Logs>> = OnceLock::new();
+
+/// The logs written by the backends and the proxy, read by the logs API
+pub fn shared() -> Arc<Logs> {
+    LOGS.get_or_init(|| Arc::new(Logs::new(MAX_LINES_PER_APP)))
+        .clone()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogSource {
+    Stdout,
+    Stderr,
+    /// The access log of the proxy
+    Proxy,
+    /// Written by dedale itself, e.g. when a follower missed lines
+    Dedale,
+}
+
+impl fmt::Display for LogSource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+            Self::Proxy => "proxy",
+            Self::Dedale => "dedale",
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LogLine {
+    pub timestamp: DateTime<Utc>,
+    pub app: String,
+    /// `None` for the requests answered before reaching a machine
+    pub machine: Option<String>,
+    pub source: LogSource,
+    pub message: String,
+}
+
+impl LogLine {
+    #[must_use]
+    pub fn new(app: &str, machine: Option<&str>, source: LogSource, message: String) -> Self {
+        Self {
+            timestamp: Utc::now(),
+            app: app.to_string(),
+            machine: machine.map(str::to_string),
+            source,
+            message,
+        }
+    }
+}
+
+/// The last lines of an app, and its followers
+#[derive(Debug)]
+struct AppLogs {
+    lines: VecDeque<LogLine>,
+    /// One channel per app, so that a chatty app does not make the followers
+    /// of the others lag
+    followers: broadcast::Sender<LogLine>,
+}
+
+impl AppLogs {
+    fn new() -> Self {
+        Self {
+            lines: VecDeque::new(),
+            followers: broadcast::channel(FOLLOW_BUFFER).0,
+        }
+    }
+}
+
+/// The last lines of every app, and the lines pushed since for those following
+/// them
+#[derive(Debug)]
+pub struct Logs {
+    capacity: usize,
+    by_app: Mutex<HashMap<String, AppLogs>>,
+}
+
+impl Logs {
+    /// Keeps up to `capacity` lines per app
+    #[must_use]
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            by_app: Mutex::default(),
+        }
+    }
+
+    pub fn push(&self, line: LogLine) {
+        let mut by_app = self.by_app.lock().unwrap_or_else(PoisonError::into_inner);
+        let logs = by_app.entry(line.app.clone()).or_insert_with(AppLogs::new);
+        logs.lines.push_back(line.clone());
+        if logs.lines.len() > self.capacity {
+            logs.lines.pop_front();
+        }
+
+        // sent under the lock for `follow`, nobody may be following
+        logs.followers.send(line).ok();
+        drop(by_app);
+    }
+
+    /// The last `limit` lines of an app, oldest first
+    #[must_use]
+    pub fn recent(&self, app: &str, limit: usize) -> Vec<LogLine> {
+        self.by_app
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .get(app)
+            .map(|logs| last(&logs.lines, limit))
+            .unwrap_or_default()
+    }
+
+    /// The last `limit` lines of an app, and the lines pushed after them until
+    /// the app is forgotten
+    ///
+    /// A follower too slow to keep up misses lines, they are replaced by a
+    /// `[N lines dropped]` line
+    pub fn follow(
+        &self,
+        app: &str,
+        limit: usize,
+    ) -> (Vec<LogLine>, impl Stream<Item = LogLine> + Send + 'static) {
+        // subscribed under the lock so that no line is missed nor repeated
+        let mut by_app = self.by_app.lock().unwrap_or_else(PoisonError::into_inner);
+        let logs = by_app.entry(app.to_string()).or_insert_with(AppLogs::new);
+        let followed = logs.followers.subscribe();
+        let recent = last(&logs.lines, limit);
+        drop(by_app);
+
+        let app = app.to_string();
+        let followed = BroadcastStream::new(followed).map(move |line| {
+            line.unwrap_or_else(|BroadcastStreamRecvError::Lagged(missed)| {
+                LogLine::new(
+                    &app,
+                    None,
+                    LogSource::Dedale,
+                    format!("[{missed} lines dropped]"),
+                )
+            })
+        });
+
+        (recent, followed)
+    }
+
+    /// Drop the lines of a destroyed app, its followers are done
+    pub fn forget(&self, app: &str) {
+        self.by_app
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(app);
+    }
+}
+
+fn last(lines: &VecDeque<LogLine>, limit: usize) -> Vec<LogLine> {
+    lines
+        .iter()
+        .skip(lines.len().saturating_sub(limit))
+        .cloned()
+        .collect()
+}
+
+/// Push the lines written by a machine until its output is closed
+pub fn capture<R>(
+    logs: Arc<Logs>,
+    app: String,
+    machine: String,
+    source: LogSource,
+    output: R,
+) -> JoinHandle<()>
+where
+    R: AsyncRead + Unpin + Send + 'static,
+{
+    tokio::spawn(async move {
+        let mut output = BufReader::new(output);
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            match output.read_until(b'\n', &mut line).await {
+                Ok(0) => return,
+                Ok(_) => {
+                    // machines are not required to write UTF-8
+                    let message = String::from_utf8_lossy(&line);
+                    let message = message.trim_end_matches(['\n', '\r']).to_string();
+                    logs.push(LogLine::new(&app, Some(&machine), source, message));
+                }
+                Err(e) => {
+                    warn!("could not read the {source} of {machine}: {e}");
+                    return;
+                }
+            }
+        }
+    })
+}
+
+/// The tasks following the logs of machines run by a container engine, by
+/// machine
+static FOLLOWERS: OnceLock<Mutex<HashMap<String, JoinHandle<()>>>> = OnceLock::new();
+
+fn followers() -> &'static Mutex<HashMap<String, JoinHandle<()>>> {
+    FOLLOWERS.get_or_init(Mutex::default)
+}
+
+/// Run `task` to follow the logs of a machine, unless they already are
+pub fn follow_machine<F>(machine: &str, task: F)
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let mut followers = followers().lock().unwrap_or_else(PoisonError::into_inner);
+    if followers
+        .get(machine)
+        .is_some_and(|follower| !follower.is_finished())
+    {
+        return;
+    }
+    followers.insert(machine.to_string(), tokio::spawn(task));
+}
+
+/// Stop following the logs of a stopped machine
+pub fn unfollow_machine(machine: &str) {
+    let follower = followers()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(machine);
+
+    if let Some(follower) = follower {
+        follower.abort();
+    }
+}
@@ -1,5 +1,4 @@
 use std::{
-    collections::HashMap,
     net::ToSocketAddrs,
     sync::{
         atomic::{AtomicU64, Ordering},
@@ -8,79 +7,209 @@ use std::{
     time::Duration,
 };
 
+use acme::{CertificateIssuer, Challenges, CHALLENGE_PATH};
+use activity::Activity;
+use bytes::Bytes;
+use logs::{LogLine, LogSource, Logs};
 use pingora::{
     self,
+    http::ResponseHeader,
+    listeners::TlsSettings,
     proxy::{http_proxy_service, ProxyHttp, Session},
     server::Server,
     services::background::background_service,
     upstreams::peer::HttpPeer,
-    ErrorType::InvalidHTTPHeader,
+    ErrorSource,
+    ErrorType::{ConnectionClosed, HTTPStatus, InvalidHTTPHeader, ReadError, WriteError},
 };
+use routing::{Resolved, RoutesRefresher, RoutingTable, Upstream};
 use service_starter::ServiceStarter;
 use service_stopper::ServiceStopper;
+use tls::{SniResolver, Tls};
 use tokio::{
     sync::{
         mpsc::{channel, Sender},
         oneshot, RwLock,
     },
-    time::sleep,
+    time::{sleep, timeout, Instant},
 };
 use tracing::{debug, info, warn};
 
+pub mod acme;
+pub mod activity;
 pub mod backend;
+pub mod logs;
+pub mod routing;
 mod service_starter;
 mod service_stopper;
+pub mod tls;
 
 const MAX_RETRY_COUNT: u16 = 3;
+/// How long a request is held while its machine starts and becomes ready
+const START_TIMEOUT: Duration = Duration::from_secs(60);
+
+const NOT_FOUND_PAGE: &str = include_str!("../../assets/proxy/404.html");
+const UNAVAILABLE_PAGE: &str = include_str!("../../assets/proxy/503.html");
 
 #[derive(Debug)]
 pub(super) struct Proxy {
-    services_starter: Sender<(String, oneshot::Sender<String>)>,
+    services_starter: Sender<(Upstream, oneshot::Sender<String>)>,
+    activity: Arc<RwLock<Activity>>,
+    routes: Option<Arc<RoutingTable>>,
+    challenges: Arc<Challenges>,
+    logs: Arc<Logs>,
     concurrent_req_count: AtomicU64,
     default_service: Option<String>,
 }
 
 impl Proxy {
-    pub fn run(listen_url: &str, default_service: Option<&str>) -> pingora::Result<()> {
+    /// Without routes every request goes to the default service, without TLS
+    /// only `listen_url` is listened on
+    pub fn run(
+        listen_url: &str,
+        default_service: Option<&str>,
+        routes: Option<Arc<RoutingTable>>,
+        tls: Option<Arc<Tls>>,
+    ) -> pingora::Result<()> {
         let mut server = Server::new(None)?;
         server.bootstrap();
 
         let (tx_need_service, rx_need_service) = channel(1024);
-        let services_state = Arc::new(RwLock::new(HashMap::new()));
+        let activity = activity::shared();
+        let challenges = acme::challenges();
 
         let mut proxy = http_proxy_service(
             &server.configuration,
             Self {
                 services_starter: tx_need_service,
+                activity: activity.clone(),
+                routes: routes.clone(),
+                challenges: challenges.clone(),
+                logs: logs::shared(),
                 concurrent_req_count: AtomicU64::new(0),
                 default_service: default_service.map(str::to_string),
             },
         );
         proxy.add_tcp(listen_url);
         info!("listening on {listen_url}");
+        if let Some(tls) = &tls {
+            let mut settings = TlsSettings::with_callbacks(Box::new(SniResolver {
+                certificates: tls.certificates.clone(),
+            }))?;
+            settings.enable_h2();
+            proxy.add_tls_with_settings(&tls.config.listen, None, settings);
+            info!("listening with TLS on {}", tls.config.listen);
+        }
 
         let start_service_handler = background_service(
             "service-starter",
             ServiceStarter {
                 services_starter: RwLock::new(rx_need_service),
-                services_state: services_state.clone(),
+                activity: activity.clone(),
+                starting: Arc::default(),
+            },
+        );
+        let stop_service_handler = background_service(
+            "service-stopper",
+            ServiceStopper {
+                activity,
+                routes: routes.clone(),
             },
         );
-        let stop_service_handler =
-            background_service("service-stopper", ServiceStopper { services_state });
 
         server.add_service(proxy);
         server.add_service(start_service_handler);
         server.add_service(stop_service_handler);
-        
+        if let Some(table) = routes.clone() {
+            server.add_service(background_service(
+                "routes-refresher",
+                RoutesRefresher { table },
+            ));
+        } else {
+            warn!("no routing table, every request goes to the default service");
+        }
+        match (tls, routes) {
+            (Some(tls), Some(routes)) => {
+                if let Some(config) = tls.config.acme.clone() {
+                    server.add_service(background_service(
+                        "certificate-issuer",
+                        CertificateIssuer {
+                            tls,
+                            config,
+                            routes,
+                            challenges,
+                        },
+                    ));
+                }
+            }
+            (Some(_), None) => warn!("no routing table, no certificate is issued"),
+            _ => {}
+        }
+
         server.run_forever();
     }
 }
 
+/// Write a small HTML page as the whole response
+async fn respond_page(
+    session: &mut Session,
+    status: u16,
+    page: &'static str,
+) -> pingora::Result<()> {
+    respond(
+        session,
+        status,
+        "text/html; charset=utf-8",
+        Bytes::from_static(page.as_bytes()),
+    )
+    .await
+}
+
+async fn respond(
+    session: &mut Session,
+    status: u16,
+    content_type: &str,
+    body: Bytes,
+) -> pingora::Result<()> {
+    let mut header = ResponseHeader::build(status, Some(2))?;
+    header.insert_header("Content-Type", content_type)?;
+    header.insert_header("Content-Length", body.len().to_string())?;
+
+    let session = session.as_mut();
+    session.write_response_header(Box::new(header)).await?;
+    session.write_response_body(body).await?;
+
+    Ok(())
+}
+
+/// e.g. `GET /index.html 200 12ms`, the status is `-` when nothing was
+/// answered
+fn access_log(session: &Session, duration: Duration) -> String {
+    let request = session.req_header();
+    let status = session.response_written().map_or_else(
+        || "-".to_string(),
+        |response| response.status.as_str().to_string(),
+    );
+
+    format!(
+        "{} {} {status} {}ms",
+        request.method,
+        request
+            .uri
+            .path_and_query()
+            .map_or("/", |path| path.as_str()),
+        duration.as_millis()
+    )
+}
+
 #[derive(Debug)]
 pub(super) struct ProxyCtx {
+    /// The app the request is routed to, `None` for the default service
+    app: Option<String>,
+    /// The machine picked for the request, for the access log
+    machine: Option<String>,
     host: Option<String>,
-    tx_service_started: Option<oneshot::Sender<String>>,
+    started_at: Instant,
     rx_service_started: Option<oneshot::Receiver<String>>,
     retry_count: u16,
 }
@@ -90,11 +219,12 @@ impl ProxyHttp for Proxy {
     type CTX = ProxyCtx;
 
     fn new_ctx(&self) -> Self::CTX {
-        let (tx_service_started, rx_service_started) = oneshot::channel();
         Self::CTX {
+            app: None,
+            machine: None,
             host: None,
-            tx_service_started: Some(tx_service_started),
-            rx_service_started: Some(rx_service_started),
+            started_at: Instant::now(),
+            rx_service_started: None,
             retry_count: 0,
         }
     }
@@ -110,19 +240,71 @@ impl ProxyHttp for Proxy {
             self.concurrent_req_count.fetch_add(1, Ordering::SeqCst)
         );
 
-        if let Some(host) = session.get_header("host").and_then(|h| {
-            h.to_str()
-                .ok()
-                .and_then(|h| if false { None } else { Some(h) })
-        }) {
-            let host = host.to_string();
-            ctx.host = Some(host.clone());
-            // this function will run only once after initialization
-            // so we can safely take and unwrap the sender
+        // the challenges of the certificates being issued, on every hostname
+        if let Some(key_authorization) = session
+            .req_header()
+            .uri
+            .path()
+            .strip_prefix(CHALLENGE_PATH)
+            .and_then(|token| self.challenges.get(token))
+        {
+            respond(
+                session,
+                200,
+                "application/octet-stream",
+                Bytes::from(key_authorization),
+            )
+            .await?;
+            return Ok(true);
+        }
+
+        let Some(routes) = &self.routes else {
+            return Ok(false);
+        };
+        let Some(host) = session
+            .get_header("host")
+            .and_then(|h| h.to_str().ok())
+            .map(str::to_string)
+        else {
+            return Ok(false);
+        };
+
+        let route = match routes.resolve(&host) {
+            Resolved::App(route) => route,
+            Resolved::UnknownApp => {
+                debug!("no app for {host}");
+                respond_page(session, 404, NOT_FOUND_PAGE).await?;
+                return Ok(true);
+            }
+            Resolved::NotManaged => return Ok(false),
+        };
+        ctx.app = Some(route.app.clone());
+
+        let (upstream, started) = {
+            let mut activity = self.activity.write().await;
+            activity.request_started(&route.app, Instant::now());
+            let upstream = route.pick(activity.started()).cloned();
+            let started = upstream
+                .as_ref()
+                .is_some_and(|upstream| activity.started().contains(&upstream.machine));
+            (upstream, started)
+        };
+        let Some(upstream) = upstream else {
+            warn!("app {} has no machine", route.app);
+            respond_page(session, 503, UNAVAILABLE_PAGE).await?;
+            return Ok(true);
+        };
+
+        ctx.machine = Some(upstream.machine.clone());
+        if started {
+            ctx.host = Some(upstream.address);
+        } else {
+            let (tx_service_started, rx_service_started) = oneshot::channel();
+            ctx.rx_service_started = Some(rx_service_started);
             self.services_starter
-                .send((host, ctx.tx_service_started.take().unwrap()))
+                .send((upstream, tx_service_started))
                 .await
-                .unwrap();
+                .map_err(|e| pingora::Error::explain(HTTPStatus(503), e.to_string()))?;
         }
 
         Ok(false)
@@ -168,18 +350,32 @@ impl ProxyHttp for Proxy {
             sleep(Duration::from_millis(10)).await;
             debug!("retrying with same host");
             ctx.host.clone().unwrap()
-        } else if let Some(ref host) = ctx.host {
-            debug!("waiting for {host} to be ready");
-            // this function will run only once after initialization
-            // so we can safely take and unwrap the receiver
-            // TODO: Handle timeout
-            let host = ctx.rx_service_started.take().unwrap().await.unwrap();
+        } else if let Some(rx_service_started) = ctx.rx_service_started.take() {
+            debug!("waiting for {:?} to be ready", ctx.app);
+            let host = match timeout(START_TIMEOUT, rx_service_started).await {
+                Ok(Ok(host)) => host,
+                // the starter drops the sender when the machine cannot be started
+                Ok(Err(_)) => {
+                    return Err(pingora::Error::explain(
+                        HTTPStatus(503),
+                        "machine could not be started",
+                    ))
+                }
+                Err(_) => {
+                    return Err(pingora::Error::explain(
+                        HTTPStatus(504),
+                        "machine not ready in time",
+                    ))
+                }
+            };
             debug!("done waiting for {host} to be ready");
 
             // Update target host in ctx
             ctx.host = Some(host.clone());
 
             host
+        } else if let Some(ref host) = ctx.host {
+            host.clone()
         } else if let Some(ref host) = self.default_service {
             // Update target host in ctx
             ctx.host = Some(host.clone());
@@ -197,7 +393,9 @@ impl ProxyHttp for Proxy {
                 .map(|mut s| s.next())
                 .ok()
                 .flatten()
-                .ok_or_else(|| pingora::Error::explain(InvalidHTTPHeader, "invalid host"))?,
+                .ok_or_else(|| {
+                    pingora::Error::explain(HTTPStatus(503), "invalid machine address")
+                })?,
             false,
             host,
         ));
@@ -206,14 +404,61 @@ impl ProxyHttp for Proxy {
     }
 
     #[tracing::instrument(skip_all)]
-    async fn logging(
+    async fn fail_to_proxy(
         &self,
-        _session: &mut Session,
-        _e: Option<&pingora::Error>,
-        _ctx: &mut Self::CTX,
-    ) where
+        session: &mut Session,
+        e: &pingora::Error,
+        ctx: &mut Self::CTX,
+    ) -> u16
+    where
+        Self::CTX: Send + Sync,
+    {
+        let code = match e.etype() {
+            HTTPStatus(code) => *code,
+            // a failing machine of an app
+            _ if ctx.app.is_some() && e.esource() == &ErrorSource::Upstream => 503,
+            _ => match e.esource() {
+                ErrorSource::Upstream => 502,
+                // the client is gone, nothing to respond
+                ErrorSource::Downstream => match e.etype() {
+                    WriteError | ReadError | ConnectionClosed => 0,
+                    _ => 400,
+                },
+                ErrorSource::Internal | ErrorSource::Unset => 500,
+            },
+        };
+
+        let responded = match code {
+            0 => Ok(()),
+            503 => respond_page(session, code, UNAVAILABLE_PAGE).await,
+            _ => {
+                session.as_mut().respond_error(code).await;
+                Ok(())
+            }
+        };
+        if let Err(e) = responded {
+            warn!("could not respond {code}: {e}");
+        }
+
+        code
+    }
+
+    #[tracing::instrument(skip_all)]
+    async fn logging(&self, session: &mut Session, _e: Option<&pingora::Error>, ctx: &mut Self::CTX)
+    where
         Self::CTX: Send + Sync,
     {
+        if let Some(app) = &ctx.app {
+            let now = Instant::now();
+            self.activity.write().await.request_finished(app, now);
+            self.logs.push(LogLine::new(
+                app,
+                ctx.machine.as_deref(),
+                LogSource::Proxy,
+                access_log(session, now.duration_since(ctx.started_at)),
+            ));
+        }
+
         debug!(
             "end of request {}",
             self.concurrent_req_count.fetch_sub(1, Ordering::SeqCst)
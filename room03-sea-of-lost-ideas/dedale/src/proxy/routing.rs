@@ -0,0 +1,431 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, OnceLock, RwLock,
+    },
+    time::Duration,
+};
+
+use pingora::{server::ShutdownWatch, services::background::BackgroundService};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+use tokio::sync::Notify;
+use tracing::{debug, info, warn};
+
+use super::backend::{BackendKind, MachineSpec};
+use crate::{
+    models::{
+        _entities::{apps, domains, machines},
+        secrets,
+    },
+    vault,
+};
+
+/// Reload the routes at least this often, to catch changes made outside of dedale
+const REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+static ROUTING_TABLE: OnceLock<Arc<RoutingTable>> = OnceLock::new();
+
+/// Register the routing table used by the proxy, only the first one is kept
+pub fn install(table: Arc<RoutingTable>) {
+    if ROUTING_TABLE.set(table).is_err() {
+        warn!("routing table already installed");
+    }
+}
+
+#[must_use]
+pub fn installed() -> Option<Arc<RoutingTable>> {
+    ROUTING_TABLE.get().cloned()
+}
+
+/// Ask the proxy to reload its routes, called when apps, machines or domains
+/// change
+pub fn notify_changed() {
+    if let Some(table) = ROUTING_TABLE.get() {
+        table.changed.notify_one();
+    }
+}
+
+/// How to tell a machine is ready to receive the requests once started
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Readiness {
+    /// The internal port accepts connections
+    Tcp,
+    /// The health path answers with a 2xx or 3xx status
+    Http { path: String },
+}
+
+/// A machine of an app, reachable on `address`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upstream {
+    pub app: String,
+    pub machine: String,
+    /// `host:port` of the internal port of the machine
+    pub address: String,
+    pub readiness: Readiness,
+    pub backend: BackendKind,
+    pub spec: MachineSpec,
+}
+
+impl From<&machines::Model> for MachineSpec {
+    fn from(machine: &machines::Model) -> Self {
+        let env = machine
+            .env
+            .as_ref()
+            .and_then(|env| env.as_object())
+            .map(|env| {
+                env.iter()
+                    .map(|(key, value)| {
+                        let value = value
+                            .as_str()
+                            .map_or_else(|| value.to_string(), str::to_string);
+                        (key.clone(), value)
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        Self {
+            // only the id of the app is known here
+            app: String::new(),
+            image: machine.image.clone(),
+            command: machine.command.clone(),
+            env,
+            internal_port: u16::try_from(machine.internal_port).unwrap_or_default(),
+        }
+    }
+}
+
+/// When the machines of an app are stopped
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Scaling {
+    pub auto_stop_machines: bool,
+    /// Started machines kept running when the app is idle
+    pub min_machines_running: usize,
+    /// Without requests for this long the app is idle
+    pub idle_timeout: Duration,
+}
+
+impl Default for Scaling {
+    fn default() -> Self {
+        Self {
+            auto_stop_machines: true,
+            min_machines_running: 0,
+            idle_timeout: Duration::from_secs(300),
+        }
+    }
+}
+
+impl From<&apps::Model> for Scaling {
+    fn from(app: &apps::Model) -> Self {
+        Self {
+            auto_stop_machines: app.auto_stop_machines,
+            min_machines_running: usize::try_from(app.min_machines_running).unwrap_or_default(),
+            idle_timeout: Duration::from_secs(u64::try_from(app.idle_timeout).unwrap_or_default()),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct AppRoute {
+    pub app: String,
+    pub upstreams: Vec<Upstream>,
+    pub scaling: Scaling,
+    next: AtomicUsize,
+}
+
+impl AppRoute {
+    #[must_use]
+    pub const fn new(app: String, upstreams: Vec<Upstream>, scaling: Scaling) -> Self {
+        Self {
+            app,
+            upstreams,
+            scaling,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    /// Round robin across the started machines, or across every machine when
+    /// none is started (one of them will be started)
+    pub fn pick(&self, started: &HashSet<String>) -> Option<&Upstream> {
+        let started_upstreams = self
+            .upstreams
+            .iter()
+            .filter(|upstream| started.contains(&upstream.machine))
+            .collect::<Vec<_>>();
+        let candidates = if started_upstreams.is_empty() {
+            self.upstreams.iter().collect()
+        } else {
+            started_upstreams
+        };
+
+        if candidates.is_empty() {
+            return None;
+        }
+        let index = self.next.fetch_add(1, Ordering::Relaxed) % candidates.len();
+
+        Some(candidates[index])
+    }
+}
+
+#[derive(Debug)]
+pub enum Resolved {
+    App(Arc<AppRoute>),
+    /// Looks like `<app>.<domain>` but there is no such app
+    UnknownApp,
+    /// Not a hostname managed by dedale (e.g. the dashboard)
+    NotManaged,
+}
+
+/// The hostnames known at the last refresh
+#[derive(Debug, Default)]
+pub struct Routes {
+    by_app: HashMap<String, Arc<AppRoute>>,
+    by_domain: HashMap<String, Arc<AppRoute>>,
+}
+
+impl Routes {
+    /// The secrets of an app, by app id, are added to the environment of its
+    /// machines and take precedence over it
+    #[must_use]
+    pub fn new(
+        apps: Vec<(apps::Model, Vec<machines::Model>)>,
+        domains: Vec<domains::Model>,
+        secrets: &HashMap<i32, BTreeMap<String, String>>,
+    ) -> Self {
+        let by_id = apps
+            .into_iter()
+            .map(|(app, machines)| {
+                let readiness = app
+                    .health_check_path
+                    .clone()
+                    .map_or(Readiness::Tcp, |path| Readiness::Http { path });
+                let backend = app.backend.parse().unwrap_or_else(|e| {
+                    warn!(
+                        "app {}: {e}, using the {} backend",
+                        app.name,
+                        BackendKind::default()
+                    );
+                    BackendKind::default()
+                });
+                let secrets = secrets.get(&app.id);
+                let upstreams = machines
+                    .into_iter()
+                    .map(|machine| {
+                        let mut spec = MachineSpec::from(&machine);
+                        spec.app.clone_from(&app.name);
+                        if let Some(secrets) = secrets {
+                            spec.env.extend(secrets.clone());
+                        }
+                        Upstream {
+                            app: app.name.clone(),
+                            address: format!("{}:{}", machine.address, machine.internal_port),
+                            spec,
+                            machine: machine.name,
+                            readiness: readiness.clone(),
+                            backend,
+                        }
+                    })
+                    .collect();
+                let route = AppRoute::new(app.name.clone(), upstreams, Scaling::from(&app));
+                (app.id, Arc::new(route))
+            })
+            .collect::<HashMap<_, _>>();
+
+        let by_domain = domains
+            .into_iter()
+            .filter_map(|domain| {
+                by_id
+                    .get(&domain.app_id)
+                    .map(|route| (normalize(&domain.hostname), route.clone()))
+            })
+            .collect();
+        let by_app = by_id
+            .into_values()
+            .map(|route| (route.app.to_ascii_lowercase(), route))
+            .collect();
+
+        Self { by_app, by_domain }
+    }
+
+    /// Find the app behind a `Host` header, custom domains first then
+    /// `<app>.<domain>`
+    #[must_use]
+    pub fn resolve(&self, host: &str, domain: &str) -> Resolved {
+        let hostname = normalize(host);
+        if let Some(route) = self.by_domain.get(&hostname) {
+            return Resolved::App(route.clone());
+        }
+
+        match hostname
+            .strip_suffix(&normalize(domain))
+            .and_then(|app| app.strip_suffix('.'))
+        {
+            Some(app) if !app.is_empty() && !app.contains('.') => self
+                .by_app
+                .get(app)
+                .map_or(Resolved::UnknownApp, |route| Resolved::App(route.clone())),
+            Some(_) => Resolved::UnknownApp,
+            None => Resolved::NotManaged,
+        }
+    }
+
+    /// Every hostname routed to an app, `<app>.<domain>` and custom domains
+    #[must_use]
+    pub fn hostnames(&self, domain: &str) -> Vec<String> {
+        let domain = normalize(domain);
+        let mut hostnames = self
+            .by_app
+            .keys()
+            .map(|app| format!("{app}.{domain}"))
+            .chain(self.by_domain.keys().cloned())
+            .collect::<Vec<_>>();
+        hostnames.sort();
+
+        hostnames
+    }
+}
+
+/// Lowercase hostname without the port nor the trailing dot
+fn normalize(host: &str) -> String {
+    let hostname = match host.rsplit_once(':') {
+        Some((hostname, port))
+            if !hostname.contains(':') && port.bytes().all(|b| b.is_ascii_digit()) =>
+        {
+            hostname
+        }
+        _ => host,
+    };
+
+    hostname.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// Map the hostnames to the machines of the apps, from the database
+#[derive(Debug)]
+pub struct RoutingTable {
+    db: DatabaseConnection,
+    domain: String,
+    routes: RwLock<Arc<Routes>>,
+    changed: Notify,
+}
+
+impl RoutingTable {
+    /// Load the routes of every app, apps are reachable on `<app>.<domain>`
+    ///
+    /// # Errors
+    ///
+    /// When the apps, machines or domains cannot be queried
+    pub async fn load(db: DatabaseConnection, domain: &str) -> Result<Self, DbErr> {
+        let table = Self {
+            db,
+            domain: domain.to_string(),
+            routes: RwLock::new(Arc::default()),
+            changed: Notify::new(),
+        };
+        table.refresh().await?;
+
+        Ok(table)
+    }
+
+    /// Reload the routes from the database
+    ///
+    /// # Errors
+    ///
+    /// When the apps, machines or domains cannot be queried, the previous
+    /// routes are kept
+    pub async fn refresh(&self) -> Result<(), DbErr> {
+        let apps = apps::Entity::find()
+            .find_with_related(machines::Entity)
+            .all(&self.db)
+            .await?;
+        let domains = domains::Entity::find().all(&self.db).await?;
+        let secrets = self.secrets().await?;
+
+        let routes = Arc::new(Routes::new(apps, domains, &secrets));
+        debug!(
+            "loaded routes for {} apps and {} domains",
+            routes.by_app.len(),
+            routes.by_domain.len()
+        );
+        *self
+            .routes
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner) = routes;
+
+        Ok(())
+    }
+
+    /// The secrets of every app in clear, none without a vault
+    async fn secrets(&self) -> Result<HashMap<i32, BTreeMap<String, String>>, DbErr> {
+        let Some(vault) = vault::installed() else {
+            return Ok(HashMap::new());
+        };
+
+        let mut by_app = HashMap::<_, BTreeMap<_, _>>::new();
+        for secret in secrets::Entity::find().all(&self.db).await? {
+            match secret.reveal(&vault) {
+                Ok(value) => {
+                    by_app
+                        .entry(secret.app_id)
+                        .or_default()
+                        .insert(secret.name, value);
+                }
+                Err(e) => warn!("secret {} of app {}: {e}", secret.name, secret.app_id),
+            }
+        }
+
+        Ok(by_app)
+    }
+
+    #[must_use]
+    pub fn resolve(&self, host: &str) -> Resolved {
+        self.routes
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .resolve(host, &self.domain)
+    }
+
+    /// Every hostname routed to an app
+    #[must_use]
+    pub fn hostnames(&self) -> Vec<String> {
+        self.routes
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .hostnames(&self.domain)
+    }
+
+    /// The route of an app by name
+    #[must_use]
+    pub fn app(&self, app: &str) -> Option<Arc<AppRoute>> {
+        self.routes
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .by_app
+            .get(&app.to_ascii_lowercase())
+            .cloned()
+    }
+}
+
+/// Reload the routing table when notified of a change and periodically
+#[derive(Debug)]
+pub(super) struct RoutesRefresher {
+    pub(super) table: Arc<RoutingTable>,
+}
+
+#[async_trait::async_trait]
+impl BackgroundService for RoutesRefresher {
+    async fn start(&self, shutdown: ShutdownWatch) {
+        info!("routes refresher starting");
+
+        while !*shutdown.borrow() {
+            // Either a change was notified or the refresh interval elapsed
+            let _ = tokio::time::timeout(REFRESH_INTERVAL, self.table.changed.notified()).await;
+
+            if let Err(e) = self.table.refresh().await {
+                warn!("could not refresh routes: {e}");
+            }
+        }
+
+        info!("routes refresher shutting down");
+    }
+}
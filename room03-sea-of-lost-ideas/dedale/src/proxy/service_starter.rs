@@ -1,18 +1,40 @@
-use std::{collections::HashMap, sync::Arc};
+use std::{
+    collections::HashMap,
+    io,
+    sync::{Arc, Mutex, PoisonError},
+    time::Duration,
+};
 
 use pingora::{server::ShutdownWatch, services::background::BackgroundService};
 use tokio::{
+    io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+    net::TcpStream,
     sync::{mpsc::Receiver, oneshot, RwLock},
-    time::Instant,
+    time::{sleep, timeout},
+};
+use tracing::{debug, info, warn};
+
+use super::{
+    activity::Activity,
+    backend::{AnyServiceBackend, BackendState},
+    routing::{Readiness, Upstream},
+    START_TIMEOUT,
 };
-use tracing::{debug, info};
 
-use super::backend::{BackendState, DockerServiceBackend, ProxyServiceBackend};
+const READINESS_INTERVAL: Duration = Duration::from_millis(200);
 
+/// The requests waiting for a machine, by machine being started
+type Starting = Arc<Mutex<HashMap<String, Vec<oneshot::Sender<String>>>>>;
+
+/// Start the machines required by the requests, then answer their address
+/// once they are ready
+/// The sender is dropped when the machine cannot be started in time
 #[derive(Debug)]
 pub(super) struct ServiceStarter {
-    pub(super) services_starter: RwLock<Receiver<(String, oneshot::Sender<String>)>>,
-    pub(super) services_state: Arc<RwLock<HashMap<String, Instant>>>,
+    pub(super) services_starter: RwLock<Receiver<(Upstream, oneshot::Sender<String>)>>,
+    pub(super) activity: Arc<RwLock<Activity>>,
+    /// One start per machine, shared by the requests arriving meanwhile
+    pub(super) starting: Starting,
 }
 
 #[async_trait::async_trait]
@@ -21,36 +43,135 @@ impl BackgroundService for ServiceStarter {
         info!("service starter starting");
 
         while !*shutdown.borrow() {
-            while let Some((required_service, started)) =
-                self.services_starter.write().await.recv().await
-            {
-                debug!("got request to start service {required_service}");
-                // TODO: get info + backend in DB
-                let mut backend = DockerServiceBackend::new_backend().await.unwrap();
-
-                if backend
-                    .status("nginx")
-                    .await
-                    .is_ok_and(|s| s == BackendState::Started)
-                {
-                    debug!("service {required_service} already started");
-                } else {
-                    debug!("starting service {required_service}");
-                    if backend.start("nginx").await.is_err() {
-                        continue;
-                    }
+            while let Some((upstream, started)) = self.services_starter.write().await.recv().await {
+                debug!("got request to start machine {}", upstream.machine);
+                let mut starting = self.starting.lock().unwrap_or_else(PoisonError::into_inner);
+                if let Some(waiting) = starting.get_mut(&upstream.machine) {
+                    debug!("machine {} already starting", upstream.machine);
+                    waiting.push(started);
+                    continue;
                 }
+                starting.insert(upstream.machine.clone(), vec![started]);
+                drop(starting);
 
-                self.services_state
-                    .write()
-                    .await
-                    .insert(required_service.clone(), Instant::now());
-
-                // this should never panic because we just inserted the sender
-                started.send("localhost:8080".to_string()).unwrap();
+                // one task per machine so that a slow machine does not hold the others
+                tokio::spawn(start_machine(
+                    upstream,
+                    self.starting.clone(),
+                    self.activity.clone(),
+                ));
             }
         }
 
         info!("service starter shutting down");
     }
 }
+
+/// Answer the address of the machine to the requests waiting for it once
+/// started, or drop them
+async fn start_machine(upstream: Upstream, starting: Starting, activity: Arc<RwLock<Activity>>) {
+    let ready = start_and_wait(&upstream, &activity).await;
+
+    let waiting = starting
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .remove(&upstream.machine)
+        .unwrap_or_default();
+    if ready {
+        for started in waiting {
+            // the request may have been cancelled meanwhile
+            started.send(upstream.address.clone()).ok();
+        }
+    }
+}
+
+/// Whether the machine was started and is ready
+async fn start_and_wait(upstream: &Upstream, activity: &RwLock<Activity>) -> bool {
+    let machine = &upstream.machine;
+    let mut backend = match AnyServiceBackend::new_backend(upstream.backend).await {
+        Ok(backend) => backend,
+        Err(e) => {
+            warn!("could not start machine {machine}: {e}");
+            return false;
+        }
+    };
+
+    // started again once the stop in progress is done
+    if timeout(START_TIMEOUT, wait_stopped(machine, activity))
+        .await
+        .is_err()
+    {
+        warn!("machine {machine} not stopped after {START_TIMEOUT:?}");
+        return false;
+    }
+
+    if backend
+        .status(machine)
+        .await
+        .is_ok_and(|s| s == BackendState::Started)
+    {
+        debug!("machine {machine} already started");
+    } else {
+        debug!("starting machine {machine}");
+        if let Err(e) = backend.start(machine, &upstream.spec).await {
+            warn!("could not start machine {machine}: {e}");
+            return false;
+        }
+    }
+
+    if timeout(START_TIMEOUT, wait_ready(upstream)).await.is_err() {
+        warn!("machine {machine} not ready after {START_TIMEOUT:?}");
+        return false;
+    }
+    activity.write().await.machine_started(machine);
+
+    true
+}
+
+/// Wait for the backend to stop a machine being stopped
+async fn wait_stopped(machine: &str, activity: &RwLock<Activity>) {
+    while activity.read().await.is_stopping(machine) {
+        debug!("machine {machine} is stopping");
+        sleep(READINESS_INTERVAL).await;
+    }
+}
+
+/// Wait for a started machine to pass its readiness check
+async fn wait_ready(upstream: &Upstream) {
+    loop {
+        match is_ready(upstream).await {
+            Ok(true) => {
+                debug!("machine {} is ready", upstream.machine);
+                return;
+            }
+            Ok(false) => debug!("machine {} is not ready yet", upstream.machine),
+            Err(e) => debug!("machine {} is not ready yet: {e}", upstream.machine),
+        }
+        sleep(READINESS_INTERVAL).await;
+    }
+}
+
+async fn is_ready(upstream: &Upstream) -> io::Result<bool> {
+    let mut stream = TcpStream::connect(&upstream.address).await?;
+
+    match &upstream.readiness {
+        Readiness::Tcp => Ok(true),
+        Readiness::Http { path } => {
+            let request = format!(
+                "GET {path} HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+                upstream.address
+            );
+            stream.write_all(request.as_bytes()).await?;
+
+            // e.g. `HTTP/1.1 200 OK`
+            let mut status_line = String::new();
+            BufReader::new(stream).read_line(&mut status_line).await?;
+            let status = status_line
+                .split_whitespace()
+                .nth(1)
+                .and_then(|status| status.parse::<u16>().ok());
+
+            Ok(status.is_some_and(|status| (200..400).contains(&status)))
+        }
+    }
+}
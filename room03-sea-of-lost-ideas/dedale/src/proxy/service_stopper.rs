@@ -1,52 +1,96 @@
-use std::{collections::HashMap, sync::Arc, time::Duration};
+use std::{sync::Arc, time::Duration};
 
 use pingora::{server::ShutdownWatch, services::background::BackgroundService};
 use tokio::{
     sync::RwLock,
     time::{sleep, Instant},
 };
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use super::backend::{BackendState, DockerServiceBackend, ProxyServiceBackend};
+use super::{
+    activity::Activity,
+    backend::{AnyServiceBackend, BackendState},
+    routing::{RoutingTable, Upstream},
+};
+
+const CHECK_INTERVAL: Duration = Duration::from_secs(10);
 
+/// Stop the machines of the apps idle for longer than their idle timeout
 #[derive(Debug)]
 pub(super) struct ServiceStopper {
-    pub(super) services_state: Arc<RwLock<HashMap<String, Instant>>>,
+    pub(super) activity: Arc<RwLock<Activity>>,
+    pub(super) routes: Option<Arc<RoutingTable>>,
+}
+
+impl ServiceStopper {
+    /// Mark the idle machines as stopping, so that new requests start them
+    /// again once stopped
+    async fn take_idle_machines(&self, routes: &RoutingTable) -> Vec<Upstream> {
+        let mut activity = self.activity.write().await;
+        let now = Instant::now();
+
+        let machines = activity
+            .apps()
+            .filter_map(|app| routes.app(app))
+            .flat_map(|route| {
+                let idle = activity.idle_machines(&route, now);
+                route
+                    .upstreams
+                    .iter()
+                    .filter(|upstream| idle.contains(&upstream.machine))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        for upstream in &machines {
+            activity.machine_stopping(&upstream.machine);
+        }
+
+        machines
+    }
 }
+
+async fn stop_machine(upstream: &Upstream) {
+    let machine = &upstream.machine;
+    let mut backend = match AnyServiceBackend::new_backend(upstream.backend).await {
+        Ok(backend) => backend,
+        Err(e) => {
+            warn!("could not stop machine {machine}: {e}");
+            return;
+        }
+    };
+    if backend
+        .status(machine)
+        .await
+        .is_ok_and(|s| s == BackendState::Started)
+    {
+        if let Err(e) = backend.stop(machine).await {
+            warn!("could not stop machine {machine}: {e}");
+        }
+    }
+}
+
 #[async_trait::async_trait]
 impl BackgroundService for ServiceStopper {
     async fn start(&self, shutdown: ShutdownWatch) {
         info!("service stopper starting");
 
+        let Some(routes) = &self.routes else {
+            warn!("no routing table, machines are never stopped");
+            return;
+        };
+
         while !*shutdown.borrow() {
-            let mut services_to_stop: Vec<String> = Vec::new();
-            self.services_state
-                .write()
-                .await
-                .retain(|service, instant| {
-                    let now = Instant::now();
-                    if now - *instant > Duration::from_secs(30) {
-                        services_to_stop.push(service.clone());
-
-                        false
-                    } else {
-                        true
-                    }
-                });
-
-            let mut backend = DockerServiceBackend::new_backend().await.unwrap();
-            for service in services_to_stop {
-                debug!("stopping service {service}");
-                if backend
-                    .status("nginx")
-                    .await
-                    .is_ok_and(|s| s == BackendState::Started)
-                {
-                    backend.stop("nginx").await.ok();
-                }
+            let machines_to_stop = self.take_idle_machines(routes).await;
+
+            for upstream in machines_to_stop {
+                let machine = &upstream.machine;
+                debug!("stopping idle machine {machine}");
+                stop_machine(&upstream).await;
+                self.activity.write().await.machine_stopped(machine);
             }
 
-            sleep(Duration::from_secs(10)).await;
+            sleep(CHECK_INTERVAL).await;
         }
 
         info!("service stopper shutting down");
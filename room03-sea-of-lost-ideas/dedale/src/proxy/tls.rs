@@ -0,0 +1,240 @@
+//! TLS listener of the proxy, the certificate of a connection is picked from
+//! the hostname it asks for (SNI)
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use chrono::{DateTime, NaiveDateTime};
+use openssl::{
+    asn1::Asn1Time,
+    error::ErrorStack,
+    pkey::{PKey, Private},
+    ssl::{NameType, SslRef},
+    x509::{X509Ref, X509},
+};
+use pingora::{listeners::TlsAccept, tls::ext};
+use sea_orm::{DatabaseConnection, DbErr, EntityTrait};
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use super::acme::AcmeConfig;
+use crate::{models::certificates, vault::Vault};
+
+static TLS: OnceLock<Arc<Tls>> = OnceLock::new();
+
+/// Register the TLS settings used by the proxy, only the first one is kept
+pub fn install(tls: Arc<Tls>) {
+    if TLS.set(tls).is_err() {
+        warn!("TLS already installed");
+    }
+}
+
+/// `None` when the proxy only listens on plain TCP
+#[must_use]
+pub fn installed() -> Option<Arc<Tls>> {
+    TLS.get().cloned()
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    /// e.g. `0.0.0.0:443`
+    pub listen: String,
+    /// Without it no certificate is issued, only those in the database are
+    /// served
+    pub acme: Option<AcmeConfig>,
+}
+
+#[derive(Debug)]
+pub enum CertificateError {
+    /// No certificate in the PEM chain
+    Empty,
+    Invalid(ErrorStack),
+}
+
+impl fmt::Display for CertificateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Empty => f.write_str("no certificate in the chain"),
+            Self::Invalid(e) => write!(f, "invalid certificate: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for CertificateError {}
+
+impl From<ErrorStack> for CertificateError {
+    fn from(e: ErrorStack) -> Self {
+        Self::Invalid(e)
+    }
+}
+
+/// A certificate chain and its private key, ready to be served
+#[derive(Debug)]
+pub struct Certificate {
+    /// The certificate of the hostname first, never empty
+    chain: Vec<X509>,
+    key: PKey<Private>,
+    pub not_after: NaiveDateTime,
+}
+
+impl Certificate {
+    /// # Errors
+    ///
+    /// When the chain or the key cannot be parsed
+    pub fn from_pem(chain: &str, private_key: &str) -> Result<Self, CertificateError> {
+        let chain = X509::stack_from_pem(chain.as_bytes())?;
+        let key = PKey::private_key_from_pem(private_key.as_bytes())?;
+        let not_after = not_after(chain.first().ok_or(CertificateError::Empty)?)?;
+
+        Ok(Self {
+            chain,
+            key,
+            not_after,
+        })
+    }
+
+    fn use_in(&self, ssl: &mut SslRef) -> Result<(), ErrorStack> {
+        let (certificate, intermediates) = self
+            .chain
+            .split_first()
+            .expect("certificate chains are not empty");
+        ext::ssl_use_certificate(ssl, certificate)?;
+        ext::ssl_use_private_key(ssl, &self.key)?;
+        for intermediate in intermediates {
+            ext::ssl_add_chain_cert(ssl, intermediate)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn not_after(certificate: &X509Ref) -> Result<NaiveDateTime, ErrorStack> {
+    let since_epoch = Asn1Time::from_unix(0)?.diff(certificate.not_after())?;
+    let timestamp = i64::from(since_epoch.days) * 86_400 + i64::from(since_epoch.secs);
+
+    Ok(DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .naive_utc())
+}
+
+/// The certificates served, by hostname
+#[derive(Debug, Default)]
+pub struct CertificateStore {
+    by_hostname: RwLock<HashMap<String, Arc<Certificate>>>,
+}
+
+impl CertificateStore {
+    pub fn insert(&self, hostname: &str, certificate: Certificate) {
+        self.by_hostname
+            .write()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .insert(normalize(hostname), Arc::new(certificate));
+    }
+
+    #[must_use]
+    pub fn get(&self, hostname: &str) -> Option<Arc<Certificate>> {
+        self.by_hostname
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .get(&normalize(hostname))
+            .cloned()
+    }
+
+    /// The hostnames without a certificate, or with one expiring before
+    /// `renew_at`
+    #[must_use]
+    pub fn to_renew(&self, hostnames: &[String], renew_at: NaiveDateTime) -> Vec<String> {
+        let by_hostname = self
+            .by_hostname
+            .read()
+            .unwrap_or_else(std::sync::PoisonError::into_inner);
+
+        hostnames
+            .iter()
+            .map(|hostname| normalize(hostname))
+            .filter(|hostname| {
+                by_hostname
+                    .get(hostname)
+                    .map_or(true, |certificate| certificate.not_after < renew_at)
+            })
+            .collect()
+    }
+}
+
+fn normalize(hostname: &str) -> String {
+    hostname.trim_end_matches('.').to_ascii_lowercase()
+}
+
+/// The TLS listener and its certificates, loaded from the database
+#[derive(Debug)]
+pub struct Tls {
+    pub config: TlsConfig,
+    pub certificates: Arc<CertificateStore>,
+    pub(super) db: DatabaseConnection,
+    /// Seals the private keys stored in the database
+    pub(super) vault: Arc<Vault>,
+}
+
+impl Tls {
+    /// # Errors
+    ///
+    /// When the certificates cannot be queried, those which cannot be opened
+    /// or parsed are skipped
+    pub async fn load(
+        db: DatabaseConnection,
+        vault: Arc<Vault>,
+        config: TlsConfig,
+    ) -> Result<Self, DbErr> {
+        let store = CertificateStore::default();
+        let stored = certificates::Entity::find().all(&db).await?;
+        for certificate in &stored {
+            let parsed = certificate
+                .reveal_private_key(&vault)
+                .map_err(|e| e.to_string())
+                .and_then(|private_key| {
+                    Certificate::from_pem(&certificate.chain, &private_key)
+                        .map_err(|e| e.to_string())
+                });
+            match parsed {
+                Ok(parsed) => store.insert(&certificate.hostname, parsed),
+                Err(e) => warn!("certificate of {}: {e}", certificate.hostname),
+            }
+        }
+        debug!("loaded {} certificates", stored.len());
+
+        Ok(Self {
+            config,
+            certificates: Arc::new(store),
+            db,
+            vault,
+        })
+    }
+}
+
+/// Serve the certificate of the hostname asked by the client, the handshake
+/// fails without one
+#[derive(Debug)]
+pub(super) struct SniResolver {
+    pub(super) certificates: Arc<CertificateStore>,
+}
+
+#[async_trait::async_trait]
+impl TlsAccept for SniResolver {
+    async fn certificate_callback(&self, ssl: &mut SslRef) {
+        let Some(hostname) = ssl.servername(NameType::HOST_NAME).map(str::to_string) else {
+            debug!("TLS handshake without SNI");
+            return;
+        };
+        let Some(certificate) = self.certificates.get(&hostname) else {
+            debug!("no certificate for {hostname}");
+            return;
+        };
+
+        if let Err(e) = certificate.use_in(ssl) {
+            warn!("could not use the certificate of {hostname}: {e}");
+        }
+    }
+}
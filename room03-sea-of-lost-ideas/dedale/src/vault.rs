@@ -0,0 +1,184 @@
+//! Encryption of the secrets of the apps at rest
+//!
+//! Values are sealed with ChaCha20-Poly1305 under the key of the server
+//! (`settings.secrets.key`), bound to the app and name they belong to so that
+//! a sealed value cannot be moved to another secret.
+//!
+//! The private keys of the proxy (TLS certificates, ACME accounts) are sealed
+//! the same way, bound to what they are the key of.
+//!
+//! The digests of the values are keyed too, so that a listed digest cannot be
+//! checked against guessed values without the key.
+
+use std::{
+    fmt::{self, Write},
+    sync::{Arc, OnceLock},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::{
+    aead::{Aead, AeadCore, KeyInit, OsRng, Payload},
+    ChaCha20Poly1305, Key, Nonce,
+};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use tracing::warn;
+
+const NONCE_LEN: usize = 12;
+/// Derives the key of the digests from the key of the server
+const DIGEST_KEY_LABEL: &[u8] = b"dedale secrets digest";
+
+static VAULT: OnceLock<Arc<Vault>> = OnceLock::new();
+
+/// Register the vault used for the secrets, only the first one is kept
+pub fn install(vault: Arc<Vault>) {
+    if VAULT.set(vault).is_err() {
+        warn!("vault already installed");
+    }
+}
+
+/// `None` when the server has no secrets key
+#[must_use]
+pub fn installed() -> Option<Arc<Vault>> {
+    VAULT.get().cloned()
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VaultError {
+    /// The key is not 32 bytes encoded in base64
+    InvalidKey,
+    /// Not sealed by this key, or for another secret
+    Corrupted,
+}
+
+impl fmt::Display for VaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidKey => f.write_str("secrets key must be 32 bytes encoded in base64"),
+            Self::Corrupted => f.write_str("secret cannot be decrypted with this key"),
+        }
+    }
+}
+
+impl std::error::Error for VaultError {}
+
+pub struct Vault {
+    cipher: ChaCha20Poly1305,
+    digest: Hmac<Sha256>,
+}
+
+impl fmt::Debug for Vault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Vault")
+    }
+}
+
+impl Vault {
+    /// # Errors
+    ///
+    /// When the key is not 32 bytes encoded in base64
+    pub fn from_base64(key: &str) -> Result<Self, VaultError> {
+        let key = STANDARD
+            .decode(key.trim())
+            .map_err(|_| VaultError::InvalidKey)?;
+        if key.len() != 32 {
+            return Err(VaultError::InvalidKey);
+        }
+
+        let digest_key = <Hmac<Sha256> as Mac>::new_from_slice(&key)
+            .map_err(|_| VaultError::InvalidKey)?
+            .chain_update(DIGEST_KEY_LABEL)
+            .finalize()
+            .into_bytes();
+
+        Ok(Self {
+            cipher: ChaCha20Poly1305::new(Key::from_slice(&key)),
+            digest: <Hmac<Sha256> as Mac>::new_from_slice(&digest_key)
+                .map_err(|_| VaultError::InvalidKey)?,
+        })
+    }
+
+    /// The sealed value, base64 of the nonce followed by the ciphertext
+    #[must_use]
+    pub fn seal(&self, app_id: i32, name: &str, value: &str) -> String {
+        self.seal_for(&associated_data(app_id, name), value)
+    }
+
+    /// # Errors
+    ///
+    /// When the value was not sealed by this key for this secret
+    pub fn open(&self, app_id: i32, name: &str, sealed: &str) -> Result<String, VaultError> {
+        self.open_for(&associated_data(app_id, name), sealed)
+    }
+
+    /// Short fingerprint of the value of a secret to tell when it changed, like
+    /// `fly secrets list`
+    #[must_use]
+    pub fn digest(&self, app_id: i32, name: &str, value: &str) -> String {
+        self.digest
+            .clone()
+            .chain_update(associated_data(app_id, name))
+            .chain_update([0_u8])
+            .chain_update(value)
+            .finalize()
+            .into_bytes()[..8]
+            .iter()
+            .fold(String::new(), |mut hex, byte| {
+                // writing to a string cannot fail
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+
+    /// A value sealed like a secret, bound to `aad` instead of an app
+    ///
+    /// # Panics
+    ///
+    /// Never, encrypting in memory cannot fail
+    #[must_use]
+    pub fn seal_for(&self, aad: &str, value: &str) -> String {
+        let nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: value.as_bytes(),
+                    aad: aad.as_bytes(),
+                },
+            )
+            .expect("in memory encryption");
+
+        let mut sealed = nonce.to_vec();
+        sealed.extend(ciphertext);
+        STANDARD.encode(sealed)
+    }
+
+    /// # Errors
+    ///
+    /// When the value was not sealed by this key for `aad`
+    pub fn open_for(&self, aad: &str, sealed: &str) -> Result<String, VaultError> {
+        let sealed = STANDARD.decode(sealed).map_err(|_| VaultError::Corrupted)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(VaultError::Corrupted);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+
+        let value = self
+            .cipher
+            .decrypt(
+                Nonce::from_slice(nonce),
+                Payload {
+                    msg: ciphertext,
+                    aad: aad.as_bytes(),
+                },
+            )
+            .map_err(|_| VaultError::Corrupted)?;
+
+        String::from_utf8(value).map_err(|_| VaultError::Corrupted)
+    }
+}
+
+fn associated_data(app_id: i32, name: &str) -> String {
+    format!("{app_id}/{name}")
+}
@@ -0,0 +1,57 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    fly_toml::Unsupported,
+    models::_entities::{apps, machines},
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct AppResponse {
+    pub name: String,
+    pub backend: String,
+    pub machines: usize,
+    pub auto_stop_machines: bool,
+    pub min_machines_running: i32,
+    pub created_at: NaiveDateTime,
+}
+
+impl AppResponse {
+    #[must_use]
+    pub fn new(app: &apps::Model, machines: usize) -> Self {
+        Self {
+            name: app.name.clone(),
+            backend: app.backend.clone(),
+            machines,
+            auto_stop_machines: app.auto_stop_machines,
+            min_machines_running: app.min_machines_running,
+            created_at: app.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeployResponse {
+    pub app: String,
+    pub machines: Vec<String>,
+    /// Keys of the `fly.toml` ignored by dedale
+    pub unsupported: Vec<Unsupported>,
+}
+
+impl DeployResponse {
+    #[must_use]
+    pub fn new(
+        app: &apps::Model,
+        machines: &[machines::Model],
+        unsupported: Vec<Unsupported>,
+    ) -> Self {
+        Self {
+            app: app.name.clone(),
+            machines: machines
+                .iter()
+                .map(|m| m.short_name().to_string())
+                .collect(),
+            unsupported,
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use std::collections::BTreeMap;
+
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    models::_entities::{apps, machines},
+    proxy::backend::{BackendState, MachineSpec},
+};
+
+/// The state of a machine as reported by its backend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MachineState {
+    /// Never started by its backend
+    Created,
+    Started,
+    Stopped,
+}
+
+impl From<BackendState> for MachineState {
+    fn from(state: BackendState) -> Self {
+        match state {
+            BackendState::Started => Self::Started,
+            BackendState::Stopped => Self::Stopped,
+            BackendState::NotFound => Self::Created,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct MachineResponse {
+    /// Machines are identified by their name
+    pub id: String,
+    pub name: String,
+    pub app: String,
+    pub state: MachineState,
+    pub image: Option<String>,
+    pub command: Option<String>,
+    pub env: BTreeMap<String, String>,
+    pub internal_port: u16,
+    pub address: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl MachineResponse {
+    #[must_use]
+    pub fn new(app: &apps::Model, machine: &machines::Model, state: MachineState) -> Self {
+        let spec = MachineSpec::from(machine);
+        Self {
+            id: machine.short_name().to_string(),
+            name: machine.short_name().to_string(),
+            app: app.name.clone(),
+            state,
+            image: spec.image,
+            command: spec.command,
+            env: spec.env,
+            internal_port: spec.internal_port,
+            address: machine.address.clone(),
+            created_at: machine.created_at,
+            updated_at: machine.updated_at,
+        }
+    }
+}
@@ -1,2 +1,5 @@
+pub mod apps;
 pub mod auth;
+pub mod machines;
+pub mod secrets;
 pub mod user;
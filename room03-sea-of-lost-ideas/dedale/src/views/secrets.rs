@@ -0,0 +1,26 @@
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+
+use crate::models::_entities::secrets;
+
+/// A secret without its value, which is never sent back
+#[derive(Debug, Deserialize, Serialize)]
+pub struct SecretResponse {
+    pub name: String,
+    /// Tells when the value changed
+    pub digest: String,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
+impl SecretResponse {
+    #[must_use]
+    pub fn new(secret: &secrets::Model) -> Self {
+        Self {
+            name: secret.name.clone(),
+            digest: secret.digest.clone(),
+            created_at: secret.created_at,
+            updated_at: secret.updated_at,
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use dedale::fly_toml::{FlyToml, MachinePlan, PlanError};
+
+/// Generated by `fly launch`
+const HELLO_FLY: &str = r"
+app = 'hello-fly'
+primary_region = 'cdg'
+
+[build]
+
+[http_service]
+  internal_port = 8080
+  force_https = true
+  auto_stop_machines = true
+  auto_start_machines = true
+  min_machines_running = 0
+  processes = ['app']
+
+[[vm]]
+  size = 'shared-cpu-1x'
+";
+
+const WORKERS: &str = r#"
+app = "workers"
+
+[build]
+  image = "registry.example.com/workers:1"
+
+[env]
+  LOG_LEVEL = "debug"
+
+[processes]
+  web = "bin/server --port 8080"
+  worker = "bin/worker"
+
+[[services]]
+  internal_port = 8080
+  protocol = "tcp"
+  auto_stop_machines = "off"
+  min_machines_running = 1
+  processes = ["web"]
+
+  [[services.ports]]
+    port = 80
+    handlers = ["http"]
+
+  [[services.http_checks]]
+    path = "/health"
+    interval = "10s"
+
+[[services]]
+  internal_port = 9000
+  processes = ["worker"]
+
+[checks.alive]
+  type = "tcp"
+  port = 8080
+
+[mounts]
+  source = "data"
+  destination = "/data"
+
+[deploy]
+  release_command = "bin/migrate"
+"#;
+
+#[test]
+fn plans_generated_configs() {
+    let deployment = FlyToml::parse(HELLO_FLY)
+        .unwrap()
+        .plan(Some("hello-fly:latest"))
+        .unwrap();
+
+    assert_eq!(deployment.app.name, "hello-fly");
+    assert!(deployment.app.auto_stop_machines);
+    assert_eq!(deployment.app.min_machines_running, 0);
+    assert_eq!(deployment.app.health_check_path, None);
+    assert_eq!(
+        deployment.machines,
+        [MachinePlan {
+            name: "hello-fly-app".to_string(),
+            process: "app".to_string(),
+            image: "hello-fly:latest".to_string(),
+            command: None,
+            env: BTreeMap::new(),
+            internal_port: 8080,
+        }]
+    );
+
+    let unsupported = deployment
+        .unsupported
+        .iter()
+        .map(|unsupported| unsupported.key.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        unsupported,
+        ["primary_region", "http_service.force_https", "vm"]
+    );
+}
+
+#[test]
+fn plans_process_groups_and_services() {
+    let deployment = FlyToml::parse(WORKERS).unwrap().plan(None).unwrap();
+
+    assert!(!deployment.app.auto_stop_machines);
+    assert_eq!(deployment.app.min_machines_running, 1);
+    assert_eq!(deployment.app.health_check_path.as_deref(), Some("/health"));
+    assert_eq!(
+        deployment.machines,
+        [MachinePlan {
+            name: "workers-web".to_string(),
+            process: "web".to_string(),
+            image: "registry.example.com/workers:1".to_string(),
+            command: Some("bin/server --port 8080".to_string()),
+            env: BTreeMap::from([("LOG_LEVEL".to_string(), "debug".to_string())]),
+            internal_port: 8080,
+        }]
+    );
+
+    let unsupported = deployment
+        .unsupported
+        .iter()
+        .map(|unsupported| unsupported.key.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(unsupported, ["services", "mounts", "deploy"]);
+}
+
+#[test]
+fn rejects_configs_that_cannot_be_deployed() {
+    assert_eq!(
+        FlyToml::parse(HELLO_FLY).unwrap().plan(None),
+        Err(PlanError::MissingImage)
+    );
+    assert_eq!(
+        FlyToml::parse("app = 'Hello_Fly'")
+            .unwrap()
+            .plan(Some("hello")),
+        Err(PlanError::InvalidAppName("Hello_Fly".to_string()))
+    );
+    assert_eq!(
+        FlyToml::parse("app = 'hello'").unwrap().plan(Some("hello")),
+        Err(PlanError::MissingService)
+    );
+    assert_eq!(
+        FlyToml::parse(
+            "app = 'hello'\n[processes]\nweb = 'server'\n[http_service]\ninternal_port = 80\nprocesses = ['api']"
+        )
+        .unwrap()
+        .plan(Some("hello")),
+        Err(PlanError::UnknownProcess("api".to_string()))
+    );
+    assert!(FlyToml::parse("app = 'hello'\n[http_service]\ninternal_port = 'http'").is_err());
+}
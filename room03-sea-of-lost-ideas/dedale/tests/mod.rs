@@ -1,3 +1,5 @@
+mod fly_toml;
 mod models;
+mod proxy;
 mod requests;
 mod tasks;
@@ -1,4 +1,4 @@
-use dedale::app::App;
+use dedale::{app::App, models::machines};
 use loco_rs::testing;
 use serial_test::serial;
 
@@ -29,3 +29,21 @@ async fn test_model() {
     // snapshot the result:
     // assert_debug_snapshot!(item);
 }
+
+#[test]
+fn machine_names_are_lowercase_words() {
+    assert!(machines::is_valid_name("hello-web"));
+    assert!(machines::is_valid_name("1st"));
+    assert!(!machines::is_valid_name(""));
+    assert!(!machines::is_valid_name("-web"));
+    assert!(!machines::is_valid_name("Web"));
+    assert!(!machines::is_valid_name("web/../1"));
+}
+
+#[test]
+fn backend_names_are_unique_per_app() {
+    assert_ne!(
+        machines::backend_name(1, "hello-web"),
+        machines::backend_name(2, "hello-web")
+    );
+}
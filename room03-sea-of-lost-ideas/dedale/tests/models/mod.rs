@@ -3,3 +3,5 @@ mod users;
 mod apps;
 
 mod machines;
+
+mod certificates;
@@ -0,0 +1,55 @@
+use std::sync::Arc;
+
+use chrono::Utc;
+use dedale::proxy::{
+    acme::{AcmeClient, AcmeConfig, Challenges},
+    tls::{Certificate, CertificateStore},
+};
+
+#[test]
+fn keeps_the_key_authorizations_of_pending_challenges() {
+    let challenges = Challenges::default();
+    challenges.insert("token", "token.thumbprint");
+
+    assert_eq!(challenges.get("token").as_deref(), Some("token.thumbprint"));
+    assert_eq!(challenges.get("other"), None);
+
+    challenges.remove("token");
+    assert_eq!(challenges.get("token"), None);
+}
+
+/// Needs a Pebble server skipping the validation of the challenges, e.g.
+///
+/// ```sh
+/// docker run -e PEBBLE_VA_ALWAYS_VALID=1 -p 14000:14000 ghcr.io/letsencrypt/pebble
+/// curl -o pebble.minica.pem https://raw.githubusercontent.com/letsencrypt/pebble/main/test/certs/pebble.minica.pem
+/// SSL_CERT_FILE=pebble.minica.pem cargo test -- --ignored issues_certificates_with_pebble
+/// ```
+#[tokio::test]
+#[ignore = "needs a Pebble ACME server"]
+async fn issues_certificates_with_pebble() {
+    let config = AcmeConfig {
+        directory_url: std::env::var("PEBBLE_DIRECTORY_URL")
+            .unwrap_or_else(|_| "https://localhost:14000/dir".to_string()),
+        contact: vec!["admin@example.com".to_string()],
+        renew_before_days: 30,
+    };
+    let challenges = Arc::new(Challenges::default());
+
+    let (client, credentials) = AcmeClient::register(&config, challenges.clone())
+        .await
+        .unwrap();
+    let issued = client.issue("blog.example.com").await.unwrap();
+    let certificate = Certificate::from_pem(&issued.chain, &issued.private_key).unwrap();
+    assert!(certificate.not_after > Utc::now().naive_utc());
+
+    let store = CertificateStore::default();
+    store.insert("blog.example.com", certificate);
+    assert!(store.get("blog.example.com").is_some());
+
+    // the account can be used again from its credentials
+    let client = AcmeClient::from_credentials(credentials, challenges.clone())
+        .await
+        .unwrap();
+    client.issue("shop.example.com").await.unwrap();
+}
@@ -0,0 +1,128 @@
+use std::time::Duration;
+
+use dedale::proxy::{
+    activity::Activity,
+    backend::{BackendKind, MachineSpec},
+    routing::{AppRoute, Readiness, Scaling, Upstream},
+};
+use tokio::time::Instant;
+
+fn route(scaling: Scaling) -> AppRoute {
+    let upstreams = (1..=3)
+        .map(|i| Upstream {
+            app: "blog".to_string(),
+            machine: format!("blog-{i}"),
+            address: format!("127.0.0.1:808{i}"),
+            readiness: Readiness::Tcp,
+            backend: BackendKind::Process,
+            spec: MachineSpec::default(),
+        })
+        .collect();
+
+    AppRoute::new("blog".to_string(), upstreams, scaling)
+}
+
+fn started_activity(now: Instant) -> Activity {
+    let mut activity = Activity::default();
+    for machine in ["blog-1", "blog-2", "blog-3"] {
+        activity.machine_started(machine);
+    }
+    activity.request_started("blog", now);
+    activity.request_finished("blog", now);
+
+    activity
+}
+
+#[test]
+fn tracks_requests_in_flight() {
+    let now = Instant::now();
+    let mut activity = Activity::default();
+
+    activity.request_started("blog", now);
+    activity.request_started("blog", now);
+    assert_eq!(activity.app("blog").unwrap().in_flight, 2);
+
+    let later = now + Duration::from_secs(1);
+    activity.request_finished("blog", later);
+    let blog = activity.app("blog").unwrap();
+    assert_eq!(blog.in_flight, 1);
+    assert_eq!(blog.last_request, later);
+
+    // unknown apps are ignored
+    activity.request_finished("nope", later);
+    assert!(activity.app("nope").is_none());
+}
+
+#[test]
+fn stops_idle_machines_only() {
+    let now = Instant::now();
+    let activity = started_activity(now);
+    let route = route(Scaling::default());
+
+    assert!(activity.idle_machines(&route, now).is_empty());
+    assert_eq!(
+        activity.idle_machines(&route, now + Duration::from_secs(300)),
+        ["blog-1", "blog-2", "blog-3"]
+    );
+}
+
+#[test]
+fn keeps_machines_with_requests_in_flight() {
+    let now = Instant::now();
+    let mut activity = started_activity(now);
+    activity.request_started("blog", now);
+
+    let route = route(Scaling::default());
+    assert!(activity
+        .idle_machines(&route, now + Duration::from_secs(3600))
+        .is_empty());
+}
+
+#[test]
+fn keeps_min_machines_running() {
+    let now = Instant::now();
+    let mut activity = started_activity(now);
+    activity.machine_stopped("blog-1");
+
+    let route = route(Scaling {
+        min_machines_running: 1,
+        idle_timeout: Duration::from_secs(10),
+        ..Scaling::default()
+    });
+    assert_eq!(
+        activity.idle_machines(&route, now + Duration::from_secs(10)),
+        ["blog-3"]
+    );
+}
+
+#[test]
+fn never_stops_without_auto_stop() {
+    let now = Instant::now();
+    let activity = started_activity(now);
+
+    let route = route(Scaling {
+        auto_stop_machines: false,
+        ..Scaling::default()
+    });
+    assert!(activity
+        .idle_machines(&route, now + Duration::from_secs(3600))
+        .is_empty());
+}
+
+#[test]
+fn stopping_machines_are_not_started_until_stopped() {
+    let now = Instant::now();
+    let mut activity = started_activity(now);
+
+    activity.machine_stopping("blog-1");
+    assert!(!activity.started().contains("blog-1"));
+    assert!(activity.is_stopping("blog-1"));
+    // a stopping machine is not stopped again
+    assert_eq!(
+        activity.idle_machines(&route(Scaling::default()), now + Duration::from_secs(300)),
+        ["blog-2", "blog-3"]
+    );
+
+    activity.machine_stopped("blog-1");
+    assert!(!activity.is_stopping("blog-1"));
+}
@@ -0,0 +1,113 @@
+use std::time::Duration;
+
+use dedale::proxy::{
+    backend::{BackendState, MachineSpec, ProcessServiceBackend, ProxyServiceBackend},
+    logs::{self, LogSource},
+};
+use tokio::time::sleep;
+
+fn spec(command: &str) -> MachineSpec {
+    MachineSpec {
+        command: Some(command.to_string()),
+        env: [("GREETING".to_string(), "hello".to_string())].into(),
+        internal_port: 8123,
+        ..MachineSpec::default()
+    }
+}
+
+#[tokio::test]
+async fn process_backend_starts_and_stops_machines() {
+    let mut backend = ProcessServiceBackend::new_backend().await.unwrap();
+    // exits right away unless the environment of the machine is set
+    let spec = spec(r#"sh -c 'test "$PORT" = 8123 && test "$GREETING" = hello && sleep 30'"#);
+
+    assert_eq!(
+        backend.status("process-1").await.unwrap(),
+        BackendState::Stopped
+    );
+
+    backend.start("process-1", &spec).await.unwrap();
+    // starting twice keeps the same process
+    backend.start("process-1", &spec).await.unwrap();
+    sleep(Duration::from_millis(500)).await;
+    assert_eq!(
+        backend.status("process-1").await.unwrap(),
+        BackendState::Started
+    );
+
+    backend.stop("process-1").await.unwrap();
+    assert_eq!(
+        backend.status("process-1").await.unwrap(),
+        BackendState::Stopped
+    );
+    // stopping twice is fine
+    backend.stop("process-1").await.unwrap();
+}
+
+#[tokio::test]
+async fn process_backend_requires_a_command() {
+    let mut backend = ProcessServiceBackend::new_backend().await.unwrap();
+
+    assert!(backend
+        .start("process-2", &MachineSpec::default())
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn process_backend_logs_the_output_of_machines() {
+    let mut backend = ProcessServiceBackend::new_backend().await.unwrap();
+    let spec = MachineSpec {
+        app: "talkative".to_string(),
+        ..spec(r#"sh -c 'echo "$GREETING"; echo oops >&2; sleep 30'"#)
+    };
+
+    backend.start("process-3", &spec).await.unwrap();
+    sleep(Duration::from_millis(500)).await;
+    backend.stop("process-3").await.unwrap();
+
+    let lines = logs::shared().recent("talkative", 10);
+    assert!(lines.iter().any(|line| line.source == LogSource::Stdout
+        && line.message == "hello"
+        && line.machine.as_deref() == Some("process-3")));
+    assert!(lines
+        .iter()
+        .any(|line| line.source == LogSource::Stderr && line.message == "oops"));
+}
+
+#[tokio::test]
+async fn process_backend_does_not_share_the_environment_of_dedale() {
+    std::env::set_var("DEDALE_SECRETS_KEY", "not for the machines");
+    let mut backend = ProcessServiceBackend::new_backend().await.unwrap();
+    let spec = MachineSpec {
+        app: "isolated".to_string(),
+        ..spec(r#"sh -c 'echo "key=$DEDALE_SECRETS_KEY greeting=$GREETING"; sleep 30'"#)
+    };
+
+    backend.start("process-4", &spec).await.unwrap();
+    sleep(Duration::from_millis(500)).await;
+    backend.stop("process-4").await.unwrap();
+
+    let lines = logs::shared().recent("isolated", 10);
+    assert_eq!(
+        lines
+            .iter()
+            .map(|line| line.message.as_str())
+            .collect::<Vec<_>>(),
+        ["key= greeting=hello"]
+    );
+}
+
+#[cfg(feature = "backend_nspawn")]
+#[tokio::test]
+async fn nspawn_backend_refuses_machines_with_an_environment() {
+    use dedale::proxy::backend::NspawnServiceBackend;
+
+    let mut backend = NspawnServiceBackend::new_backend().await.unwrap();
+    let spec = spec("sleep 30");
+
+    let error = backend.create("nspawn-1", &spec).await.unwrap_err();
+    assert!(error.to_string().contains("GREETING"), "{error}");
+    let error = backend.start("nspawn-1", &spec).await.unwrap_err();
+    assert!(error.to_string().contains("GREETING"), "{error}");
+}
@@ -0,0 +1,108 @@
+use std::sync::Arc;
+
+use dedale::proxy::logs::{capture, LogLine, LogSource, Logs, FOLLOW_BUFFER};
+use tokio_stream::StreamExt;
+
+fn line(app: &str, message: &str) -> LogLine {
+    LogLine::new(
+        app,
+        Some("machine-1"),
+        LogSource::Stdout,
+        message.to_string(),
+    )
+}
+
+fn messages(lines: &[LogLine]) -> Vec<&str> {
+    lines.iter().map(|line| line.message.as_str()).collect()
+}
+
+#[test]
+fn keeps_the_last_lines_of_each_app() {
+    let logs = Logs::new(3);
+    for message in ["one", "two", "three", "four"] {
+        logs.push(line("chatty", message));
+    }
+    logs.push(line("quiet", "hello"));
+
+    assert_eq!(
+        messages(&logs.recent("chatty", 10)),
+        ["two", "three", "four"]
+    );
+    assert_eq!(messages(&logs.recent("chatty", 2)), ["three", "four"]);
+    assert_eq!(messages(&logs.recent("quiet", 10)), ["hello"]);
+    assert!(logs.recent("unknown", 10).is_empty());
+
+    logs.forget("chatty");
+    assert!(logs.recent("chatty", 10).is_empty());
+}
+
+#[tokio::test]
+async fn followers_get_the_lines_pushed_after_the_recent_ones() {
+    let logs = Logs::new(10);
+    logs.push(line("chatty", "before"));
+
+    let (recent, mut followed) = logs.follow("chatty", 10);
+    logs.push(line("chatty", "after"));
+
+    assert_eq!(messages(&recent), ["before"]);
+    assert_eq!(followed.next().await.unwrap().message, "after");
+}
+
+#[tokio::test]
+async fn followers_only_get_the_lines_of_their_app() {
+    let logs = Logs::new(10);
+
+    let (_, mut followed) = logs.follow("quiet", 10);
+    // more than a follower can lag behind
+    for _ in 0..=FOLLOW_BUFFER {
+        logs.push(line("chatty", "noise"));
+    }
+    logs.push(line("quiet", "hello"));
+
+    assert_eq!(followed.next().await.unwrap().message, "hello");
+}
+
+#[tokio::test]
+async fn followers_are_told_how_many_lines_they_missed() {
+    let logs = Logs::new(10);
+
+    let (_, mut followed) = logs.follow("chatty", 10);
+    for _ in 0..FOLLOW_BUFFER + 5 {
+        logs.push(line("chatty", "noise"));
+    }
+
+    let dropped = followed.next().await.unwrap();
+    assert_eq!(dropped.message, "[5 lines dropped]");
+    assert_eq!(dropped.source, LogSource::Dedale);
+    assert_eq!(followed.next().await.unwrap().message, "noise");
+}
+
+#[tokio::test]
+async fn captures_the_output_of_machines_line_by_line() {
+    let logs = Arc::new(Logs::new(10));
+    let output: &[u8] = b"listening on 8080\r\ninvalid \xff utf-8\n\nno newline";
+
+    capture(
+        logs.clone(),
+        "chatty".to_string(),
+        "chatty-1".to_string(),
+        LogSource::Stderr,
+        output,
+    )
+    .await
+    .unwrap();
+
+    let lines = logs.recent("chatty", 10);
+    assert_eq!(
+        messages(&lines),
+        [
+            "listening on 8080",
+            "invalid \u{fffd} utf-8",
+            "",
+            "no newline"
+        ]
+    );
+    assert!(lines.iter().all(
+        |line| line.machine.as_deref() == Some("chatty-1") && line.source == LogSource::Stderr
+    ));
+}
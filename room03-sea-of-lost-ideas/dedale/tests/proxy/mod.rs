@@ -0,0 +1,7 @@
+mod acme;
+mod activity;
+#[cfg(feature = "backend_process")]
+mod backend;
+mod logs;
+mod routing;
+mod tls;
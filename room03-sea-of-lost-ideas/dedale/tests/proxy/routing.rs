@@ -0,0 +1,257 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::NaiveDateTime;
+use dedale::{
+    models::_entities::{apps, domains, machines},
+    proxy::{
+        backend::{BackendKind, MachineSpec},
+        routing::{Readiness, Resolved, Routes},
+    },
+};
+use serde_json::json;
+
+const DOMAIN: &str = "dedale.localhost";
+
+fn app(id: i32, name: &str) -> apps::Model {
+    apps::Model {
+        created_at: NaiveDateTime::default(),
+        updated_at: NaiveDateTime::default(),
+        id,
+        name: name.to_string(),
+        user_id: 1,
+        auto_stop_machines: true,
+        min_machines_running: 0,
+        idle_timeout: 300,
+        health_check_path: (name == "blog").then(|| "/health".to_string()),
+        backend: "docker".to_string(),
+    }
+}
+
+fn machine(id: i32, name: &str, app_id: i32, internal_port: i32) -> machines::Model {
+    machines::Model {
+        created_at: NaiveDateTime::default(),
+        updated_at: NaiveDateTime::default(),
+        id,
+        name: name.to_string(),
+        app_id,
+        address: "127.0.0.1".to_string(),
+        internal_port,
+        image: None,
+        command: None,
+        env: None,
+    }
+}
+
+fn routes() -> Routes {
+    Routes::new(
+        vec![
+            (
+                app(1, "blog"),
+                vec![machine(1, "blog-1", 1, 8080), machine(2, "blog-2", 1, 8081)],
+            ),
+            (app(2, "empty"), vec![]),
+        ],
+        vec![domains::Model {
+            created_at: NaiveDateTime::default(),
+            updated_at: NaiveDateTime::default(),
+            id: 1,
+            hostname: "Blog.Example.com".to_string(),
+            app_id: 1,
+        }],
+        &HashMap::new(),
+    )
+}
+
+fn resolved_app(resolved: Resolved) -> Option<String> {
+    match resolved {
+        Resolved::App(route) => Some(route.app.clone()),
+        _ => None,
+    }
+}
+
+#[test]
+fn resolves_app_subdomains() {
+    let routes = routes();
+
+    assert_eq!(
+        resolved_app(routes.resolve("blog.dedale.localhost", DOMAIN)),
+        Some("blog".to_string())
+    );
+    assert_eq!(
+        resolved_app(routes.resolve("BLOG.dedale.localhost.:3000", DOMAIN)),
+        Some("blog".to_string())
+    );
+}
+
+#[test]
+fn resolves_custom_domains() {
+    let routes = routes();
+
+    assert_eq!(
+        resolved_app(routes.resolve("blog.example.com", DOMAIN)),
+        Some("blog".to_string())
+    );
+}
+
+#[test]
+fn rejects_unknown_apps() {
+    let routes = routes();
+
+    assert!(matches!(
+        routes.resolve("nope.dedale.localhost", DOMAIN),
+        Resolved::UnknownApp
+    ));
+    assert!(matches!(
+        routes.resolve("a.blog.dedale.localhost", DOMAIN),
+        Resolved::UnknownApp
+    ));
+    assert!(matches!(
+        routes.resolve("localhost:3000", DOMAIN),
+        Resolved::NotManaged
+    ));
+    assert!(matches!(
+        routes.resolve("dedale.localhost", DOMAIN),
+        Resolved::NotManaged
+    ));
+}
+
+#[test]
+fn balances_across_started_machines() {
+    let Resolved::App(route) = routes().resolve("blog.dedale.localhost", DOMAIN) else {
+        panic!("blog should be routed");
+    };
+
+    // none started, every machine is a candidate
+    let started = HashSet::new();
+    let picked = (0..4)
+        .map(|_| route.pick(&started).unwrap().machine.clone())
+        .collect::<Vec<_>>();
+    assert_eq!(picked, ["blog-1", "blog-2", "blog-1", "blog-2"]);
+
+    // only the started machine is used
+    let started = HashSet::from(["blog-2".to_string()]);
+    for _ in 0..3 {
+        let upstream = route.pick(&started).unwrap();
+        assert_eq!(upstream.machine, "blog-2");
+        assert_eq!(upstream.address, "127.0.0.1:8081");
+        assert_eq!(
+            upstream.readiness,
+            Readiness::Http {
+                path: "/health".to_string()
+            }
+        );
+    }
+}
+
+#[test]
+fn lists_the_hostnames_of_apps() {
+    assert_eq!(
+        routes().hostnames("Dedale.Localhost."),
+        [
+            "blog.dedale.localhost",
+            "blog.example.com",
+            "empty.dedale.localhost",
+        ]
+    );
+}
+
+#[test]
+fn apps_without_machines_have_no_upstream() {
+    let Resolved::App(route) = routes().resolve("empty.dedale.localhost", DOMAIN) else {
+        panic!("empty should be routed");
+    };
+
+    assert!(route.pick(&HashSet::new()).is_none());
+}
+
+#[test]
+fn reads_the_backend_and_spec_of_machines() {
+    let mut worker = machine(4, "worker-1", 3, 9000);
+    worker.command = Some("./worker".to_string());
+    worker.env = Some(json!({ "MODE": "fast", "THREADS": 4 }));
+    let routes = Routes::new(
+        vec![
+            (
+                apps::Model {
+                    backend: "process".to_string(),
+                    ..app(3, "worker")
+                },
+                vec![worker],
+            ),
+            (
+                apps::Model {
+                    backend: "vm".to_string(),
+                    ..app(4, "legacy")
+                },
+                vec![machine(5, "legacy-1", 4, 8080)],
+            ),
+        ],
+        vec![],
+        &HashMap::new(),
+    );
+
+    let Resolved::App(route) = routes.resolve("worker.dedale.localhost", DOMAIN) else {
+        panic!("worker should be routed");
+    };
+    let upstream = route.pick(&HashSet::new()).unwrap();
+    assert_eq!(upstream.backend, BackendKind::Process);
+    assert_eq!(
+        upstream.spec,
+        MachineSpec {
+            app: "worker".to_string(),
+            image: None,
+            command: Some("./worker".to_string()),
+            env: BTreeMap::from([
+                ("MODE".to_string(), "fast".to_string()),
+                ("THREADS".to_string(), "4".to_string()),
+            ]),
+            internal_port: 9000,
+        }
+    );
+
+    // an unknown backend falls back to the default one
+    let Resolved::App(route) = routes.resolve("legacy.dedale.localhost", DOMAIN) else {
+        panic!("legacy should be routed");
+    };
+    assert_eq!(
+        route.pick(&HashSet::new()).unwrap().backend,
+        BackendKind::Docker
+    );
+}
+
+#[test]
+fn injects_the_secrets_of_apps_in_their_machines() {
+    let mut worker = machine(4, "worker-1", 3, 9000);
+    worker.env = Some(json!({ "MODE": "fast", "DATABASE_URL": "sqlite::memory:" }));
+    let routes = Routes::new(
+        vec![
+            (app(3, "worker"), vec![worker]),
+            (app(4, "other"), vec![machine(5, "other-1", 4, 8080)]),
+        ],
+        vec![],
+        &HashMap::from([(
+            3,
+            BTreeMap::from([
+                ("DATABASE_URL".to_string(), "postgres://db".to_string()),
+                ("API_KEY".to_string(), "hunter2".to_string()),
+            ]),
+        )]),
+    );
+
+    let Resolved::App(route) = routes.resolve("worker.dedale.localhost", DOMAIN) else {
+        panic!("worker should be routed");
+    };
+    assert_eq!(
+        route.pick(&HashSet::new()).unwrap().spec.env,
+        BTreeMap::from([
+            ("API_KEY".to_string(), "hunter2".to_string()),
+            ("DATABASE_URL".to_string(), "postgres://db".to_string()),
+            ("MODE".to_string(), "fast".to_string()),
+        ])
+    );
+
+    let Resolved::App(route) = routes.resolve("other.dedale.localhost", DOMAIN) else {
+        panic!("other should be routed");
+    };
+    assert!(route.pick(&HashSet::new()).unwrap().spec.env.is_empty());
+}
@@ -0,0 +1,103 @@
+use chrono::{DateTime, NaiveDateTime};
+use dedale::proxy::tls::{Certificate, CertificateStore};
+use openssl::{
+    asn1::Asn1Time,
+    bn::BigNum,
+    ec::{EcGroup, EcKey},
+    hash::MessageDigest,
+    nid::Nid,
+    pkey::PKey,
+    x509::{X509NameBuilder, X509},
+};
+
+/// A self-signed certificate and its key, PEM encoded
+fn self_signed(hostname: &str, not_after: i64) -> (String, String) {
+    let key = PKey::from_ec_key(
+        EcKey::generate(&EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap()).unwrap(),
+    )
+    .unwrap();
+    let mut name = X509NameBuilder::new().unwrap();
+    name.append_entry_by_nid(Nid::COMMONNAME, hostname).unwrap();
+    let name = name.build();
+
+    let mut certificate = X509::builder().unwrap();
+    certificate.set_version(2).unwrap();
+    certificate
+        .set_serial_number(&BigNum::from_u32(1).unwrap().to_asn1_integer().unwrap())
+        .unwrap();
+    certificate.set_subject_name(&name).unwrap();
+    certificate.set_issuer_name(&name).unwrap();
+    certificate.set_pubkey(&key).unwrap();
+    certificate
+        .set_not_before(&Asn1Time::from_unix(0).unwrap())
+        .unwrap();
+    certificate
+        .set_not_after(&Asn1Time::from_unix(not_after).unwrap())
+        .unwrap();
+    certificate.sign(&key, MessageDigest::sha256()).unwrap();
+
+    (
+        String::from_utf8(certificate.build().to_pem().unwrap()).unwrap(),
+        String::from_utf8(key.private_key_to_pem_pkcs8().unwrap()).unwrap(),
+    )
+}
+
+fn at(timestamp: i64) -> NaiveDateTime {
+    DateTime::from_timestamp(timestamp, 0).unwrap().naive_utc()
+}
+
+#[test]
+fn reads_the_expiry_of_certificates() {
+    let (chain, key) = self_signed("blog.example.com", 1_900_000_000);
+    let certificate = Certificate::from_pem(&chain, &key).unwrap();
+
+    assert_eq!(certificate.not_after, at(1_900_000_000));
+}
+
+#[test]
+fn rejects_invalid_certificates() {
+    let (_, key) = self_signed("blog.example.com", 1_900_000_000);
+
+    assert!(Certificate::from_pem("", &key).is_err());
+    assert!(Certificate::from_pem("not a certificate", &key).is_err());
+}
+
+#[test]
+fn finds_certificates_by_hostname() {
+    let store = CertificateStore::default();
+    let (chain, key) = self_signed("blog.example.com", 1_900_000_000);
+    store.insert(
+        "Blog.Example.com",
+        Certificate::from_pem(&chain, &key).unwrap(),
+    );
+
+    assert!(store.get("blog.example.com").is_some());
+    assert!(store.get("BLOG.example.com.").is_some());
+    assert!(store.get("example.com").is_none());
+}
+
+#[test]
+fn renews_missing_and_expiring_certificates() {
+    let store = CertificateStore::default();
+    for (hostname, not_after) in [
+        ("valid.example.com", 1_900_000_000),
+        ("old.example.com", 1_700_000_000),
+    ] {
+        let (chain, key) = self_signed(hostname, not_after);
+        store.insert(hostname, Certificate::from_pem(&chain, &key).unwrap());
+    }
+
+    let hostnames = [
+        "valid.example.com".to_string(),
+        "old.example.com".to_string(),
+        "New.Example.com".to_string(),
+    ];
+    assert_eq!(
+        store.to_renew(&hostnames, at(1_800_000_000)),
+        ["old.example.com", "new.example.com"]
+    );
+    assert_eq!(
+        store.to_renew(&hostnames, at(2_000_000_000)),
+        ["valid.example.com", "old.example.com", "new.example.com"]
+    );
+}
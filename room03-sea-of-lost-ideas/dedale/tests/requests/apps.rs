@@ -0,0 +1,148 @@
+use dedale::{
+    app::App,
+    models::{_entities::machines, apps},
+    views::apps::DeployResponse,
+};
+use loco_rs::testing;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+use serial_test::serial;
+
+use super::prepare_data;
+
+const CONFIG: &str = r#"
+app = "hello"
+
+[processes]
+  web = "bin/server"
+  worker = "bin/worker"
+
+[http_service]
+  internal_port = 8080
+  force_https = true
+  auto_stop_machines = true
+  min_machines_running = 1
+
+  [[http_service.checks]]
+    path = "/health"
+"#;
+
+#[tokio::test]
+#[serial]
+async fn can_deploy_an_app() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        // a redeploy destroys the machines on their backend
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "hello", "backend": "process" }))
+            .await;
+
+        let response = request
+            .post("/api/apps/deploy")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "config": CONFIG,
+                "image": "hello:1",
+            }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let deployed: DeployResponse = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(deployed.app, "hello");
+        assert_eq!(deployed.machines, ["hello-web", "hello-worker"]);
+        assert_eq!(deployed.unsupported[0].key, "http_service.force_https");
+
+        let app = apps::Model::find_by_name(&ctx.db, "hello").await.unwrap();
+        assert_eq!(app.user_id, user.user.id);
+        assert_eq!(app.min_machines_running, 1);
+        assert_eq!(app.health_check_path.as_deref(), Some("/health"));
+
+        // a redeploy updates the machines and drops the removed process groups
+        let response = request
+            .post("/api/apps/deploy")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({
+                "config": CONFIG.replace("worker = \"bin/worker\"", ""),
+                "image": "hello:2",
+            }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        let deployed = machines::Entity::find()
+            .filter(machines::Column::AppId.eq(app.id))
+            .order_by_asc(machines::Column::Name)
+            .all(&ctx.db)
+            .await
+            .unwrap();
+        assert_eq!(deployed.len(), 1);
+        assert_eq!(deployed[0].short_name(), "hello-web");
+        assert_eq!(deployed[0].image.as_deref(), Some("hello:2"));
+        assert_eq!(deployed[0].command.as_deref(), Some("bin/server"));
+        assert_eq!(deployed[0].internal_port, 8080);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn machines_of_different_apps_do_not_collide() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        // both machines would be `hello-web-api`
+        for (app, process) in [("hello", "web-api"), ("hello-web", "api")] {
+            let response = request
+                .post("/api/apps/deploy")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({
+                    "config": format!(
+                        "app = \"{app}\"\n\
+                         [processes]\n  {process} = \"bin/server\"\n\
+                         [http_service]\n  internal_port = 8080\n"
+                    ),
+                    "image": "hello:1",
+                }))
+                .await;
+            assert_eq!(response.status_code(), 200, "{app}");
+        }
+
+        let deployed = machines::Entity::find().all(&ctx.db).await.unwrap();
+        assert_eq!(deployed.len(), 2);
+        assert_ne!(deployed[0].name, deployed[1].name);
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn rejects_invalid_configs() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let response = request
+            .post("/api/apps/deploy")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "config": CONFIG }))
+            .await;
+        assert_eq!(response.status_code(), 400);
+        assert!(apps::Model::find_by_name(&ctx.db, "hello").await.is_err());
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn requires_a_user() {
+    testing::request::<App, _, _>(|request, _ctx| async move {
+        let response = request
+            .post("/api/apps/deploy")
+            .json(&serde_json::json!({ "config": CONFIG, "image": "hello:1" }))
+            .await;
+        assert_eq!(response.status_code(), 401);
+    })
+    .await;
+}
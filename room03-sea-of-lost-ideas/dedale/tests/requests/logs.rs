@@ -0,0 +1,63 @@
+use dedale::{
+    app::App,
+    proxy::logs::{self, LogLine, LogSource},
+};
+use loco_rs::testing;
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn can_read_the_last_lines_of_an_app() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "chatty" }))
+            .await;
+        for message in ["one", "two", "three"] {
+            logs::shared().push(LogLine::new(
+                "chatty",
+                Some("chatty-1"),
+                LogSource::Stdout,
+                message.to_string(),
+            ));
+        }
+        logs::shared().push(LogLine::new(
+            "other",
+            None,
+            LogSource::Proxy,
+            "GET / 200 1ms".to_string(),
+        ));
+
+        let response = request
+            .get("/api/apps/chatty/logs?lines=2")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let lines = response
+            .text()
+            .lines()
+            .map(|line| serde_json::from_str::<LogLine>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.message.as_str())
+                .collect::<Vec<_>>(),
+            ["two", "three"]
+        );
+
+        // nor the logs of the apps of other users
+        let response = request
+            .get("/api/apps/other/logs")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
@@ -0,0 +1,161 @@
+use dedale::{
+    app::App,
+    views::{
+        apps::AppResponse,
+        machines::{MachineResponse, MachineState},
+    },
+};
+use loco_rs::testing;
+use serial_test::serial;
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn can_manage_apps() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        let response = request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "hello", "backend": "process" }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        let response = request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "hello" }))
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        let response = request
+            .get("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let apps: Vec<AppResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "hello");
+        assert_eq!(apps[0].backend, "process");
+
+        let response = request
+            .delete("/api/apps/hello")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        let response = request
+            .get("/api/apps/hello")
+            .add_header(auth_key, auth_value)
+            .await;
+        assert_eq!(response.status_code(), 404);
+    })
+    .await;
+}
+
+#[cfg(feature = "backend_process")]
+#[tokio::test]
+#[serial]
+async fn can_start_and_stop_machines() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "sleeper", "backend": "process" }))
+            .await;
+
+        let response = request
+            .post("/api/apps/sleeper/machines")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "name": "sleeper-1",
+                "config": {
+                    "init": { "cmd": ["sleep", "30"] },
+                    "services": [{ "internal_port": 8123 }],
+                },
+            }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let machine: MachineResponse = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(machine.id, "sleeper-1");
+        assert_eq!(machine.command.as_deref(), Some("sleep 30"));
+        assert_eq!(machine.internal_port, 8123);
+        assert_ne!(machine.state, MachineState::Started);
+
+        request
+            .post("/api/apps/sleeper/machines/sleeper-1/start")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let response = request
+            .get("/api/apps/sleeper/machines/sleeper-1/wait?state=started&timeout=5")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        request
+            .post("/api/apps/sleeper/machines/sleeper-1/stop")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let response = request
+            .get("/api/apps/sleeper/machines/sleeper-1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let machine: MachineResponse = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(machine.state, MachineState::Stopped);
+
+        let response = request
+            .delete("/api/apps/sleeper/machines/sleeper-1")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        let response = request
+            .get("/api/apps/sleeper/machines")
+            .add_header(auth_key, auth_value)
+            .await;
+        let machines: Vec<MachineResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert!(machines.is_empty());
+    })
+    .await;
+}
+
+#[cfg(feature = "backend_process")]
+#[tokio::test]
+#[serial]
+async fn can_not_create_machines_with_invalid_names() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "sleeper", "backend": "process" }))
+            .await;
+
+        for name in ["-sleeper", "Sleeper", "sleeper_1", "../sleeper", ""] {
+            let response = request
+                .post("/api/apps/sleeper/machines")
+                .add_header(auth_key.clone(), auth_value.clone())
+                .json(&serde_json::json!({
+                    "name": name,
+                    "config": { "init": { "cmd": ["sleep", "30"] } },
+                }))
+                .await;
+            assert_eq!(response.status_code(), 400, "{name:?}");
+        }
+
+        let response = request
+            .get("/api/apps/sleeper/machines")
+            .add_header(auth_key, auth_value)
+            .await;
+        let machines: Vec<MachineResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert!(machines.is_empty());
+    })
+    .await;
+}
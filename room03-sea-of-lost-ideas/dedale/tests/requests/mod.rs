@@ -1,3 +1,7 @@
+mod apps;
 mod auth;
+mod logs;
+mod machines;
 mod prepare_data;
+mod secrets;
 mod user;
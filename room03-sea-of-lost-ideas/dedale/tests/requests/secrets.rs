@@ -0,0 +1,148 @@
+use dedale::{
+    app::App,
+    initializers::secrets::SecretsInitializer,
+    models::{apps, secrets},
+    vault,
+    views::secrets::SecretResponse,
+};
+use loco_rs::{app::Initializer, testing};
+use sea_orm::{ActiveModelTrait, ActiveValue, IntoActiveModel};
+use serial_test::serial;
+use sha2::{Digest, Sha256};
+
+use super::prepare_data;
+
+#[tokio::test]
+#[serial]
+async fn can_set_list_and_unset_secrets() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        SecretsInitializer.before_run(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "vaulted" }))
+            .await;
+
+        let response = request
+            .post("/api/apps/vaulted/secrets")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({
+                "secrets": { "API_KEY": "hunter2", "DATABASE_URL": "postgres://db" },
+            }))
+            .await;
+        assert_eq!(response.status_code(), 200);
+
+        let response = request
+            .post("/api/apps/vaulted/secrets")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "secrets": { "NOT-AN-ENV": "value" } }))
+            .await;
+        assert_eq!(response.status_code(), 400);
+
+        // values are never sent back, nor stored in clear
+        let response = request
+            .get("/api/apps/vaulted/secrets")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+        assert!(!response.text().contains("hunter2"));
+        let listed: Vec<SecretResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(
+            listed.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(),
+            ["API_KEY", "DATABASE_URL"]
+        );
+
+        let app = apps::Model::find_by_name(&ctx.db, "vaulted").await.unwrap();
+        let stored = secrets::Model::list_by_app(&ctx.db, app.id).await.unwrap();
+        assert!(stored.iter().all(|s| !s.value.contains("hunter2")));
+
+        // replacing a value changes its digest
+        request
+            .post("/api/apps/vaulted/secrets")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "secrets": { "API_KEY": "correct horse" } }))
+            .await;
+        let response = request
+            .get("/api/apps/vaulted/secrets")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        let replaced: Vec<SecretResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(replaced.len(), 2);
+        assert_ne!(replaced[0].digest, listed[0].digest);
+        assert_eq!(replaced[1].digest, listed[1].digest);
+
+        let response = request
+            .delete("/api/apps/vaulted/secrets/API_KEY")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 200);
+        let response = request
+            .delete("/api/apps/vaulted/secrets/API_KEY")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .await;
+        assert_eq!(response.status_code(), 404);
+
+        let response = request
+            .get("/api/apps/vaulted/secrets")
+            .add_header(auth_key, auth_value)
+            .await;
+        let remaining: Vec<SecretResponse> = serde_json::from_str(&response.text()).unwrap();
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].name, "DATABASE_URL");
+    })
+    .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn digests_are_keyed_and_refreshed() {
+    testing::request::<App, _, _>(|request, ctx| async move {
+        SecretsInitializer.before_run(&ctx).await.unwrap();
+        let user = prepare_data::init_user_login(&request, &ctx).await;
+        let (auth_key, auth_value) = prepare_data::auth_header(&user.token);
+
+        request
+            .post("/api/apps")
+            .add_header(auth_key.clone(), auth_value.clone())
+            .json(&serde_json::json!({ "app_name": "vaulted" }))
+            .await;
+        request
+            .post("/api/apps/vaulted/secrets")
+            .add_header(auth_key, auth_value)
+            .json(&serde_json::json!({ "secrets": { "API_KEY": "hunter2" } }))
+            .await;
+
+        // a digest of the value alone could be checked against guessed values
+        let unkeyed = Sha256::digest("hunter2")[..8]
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>();
+        let app = apps::Model::find_by_name(&ctx.db, "vaulted").await.unwrap();
+        let stored = secrets::Model::list_by_app(&ctx.db, app.id).await.unwrap();
+        assert_ne!(stored[0].digest, unkeyed);
+
+        // the digests stored before they were keyed are computed again
+        let mut legacy = stored[0].clone().into_active_model();
+        legacy.digest = ActiveValue::set(unkeyed);
+        legacy.update(&ctx.db).await.unwrap();
+        let vault = vault::installed().unwrap();
+        assert_eq!(
+            secrets::Model::refresh_digests(&ctx.db, &vault)
+                .await
+                .unwrap(),
+            1
+        );
+        assert_eq!(
+            secrets::Model::refresh_digests(&ctx.db, &vault)
+                .await
+                .unwrap(),
+            0
+        );
+        let refreshed = secrets::Model::list_by_app(&ctx.db, app.id).await.unwrap();
+        assert_eq!(refreshed[0].digest, stored[0].digest);
+    })
+    .await;
+}